@@ -0,0 +1,175 @@
+use super::{Action, ActionError, GestureContext};
+use crate::common::OnError;
+
+use std::io;
+use std::sync::mpsc::{self, Sender};
+
+enum Call {
+    Execute,
+    Begin,
+    Update(f64),
+    Motion(f64, f64),
+    End,
+    Context(GestureContext),
+}
+
+/// Runs the wrapped action on a thread of its own, so that an action taking a
+/// while, like a query to an unresponsive compositor, doesn't hold up the
+/// gestures. Calls run in the order they're made, and their failures are dealt
+/// with on the thread as `on_error` says, since the calls return before them
+pub struct BackgroundAction {
+    description: String,
+    calls: Sender<Call>,
+}
+
+impl BackgroundAction {
+    /// Fails when the thread can't be started, like at the limit of threads
+    pub fn new(mut action: Box<dyn Action + Send>, on_error: OnError) -> io::Result<Self> {
+        let description = action.describe();
+        let (calls, received) = mpsc::channel();
+        // The thread ends once the action is dropped and the calls run out
+        std::thread::Builder::new()
+            .name("action".to_string())
+            .spawn(move || {
+                for call in received {
+                    let retry = matches!(call, Call::Execute);
+                    let result = match call {
+                        Call::Execute => action.execute(),
+                        Call::Begin => action.begin(),
+                        Call::Update(delta) => action.update(delta),
+                        Call::Motion(dx, dy) => action.motion(dx, dy),
+                        Call::End => action.end(),
+                        Call::Context(context) => {
+                            action.context(&context);
+                            Ok(())
+                        }
+                    };
+                    if let Err(ActionError(msg)) = result {
+                        super::recover(action.as_mut(), on_error, retry, msg);
+                    }
+                }
+            })?;
+        Ok(BackgroundAction { description, calls })
+    }
+
+    fn call(&self, call: Call) -> Result<(), ActionError> {
+        self.calls
+            .send(call)
+            .map_err(|_| ActionError(format!("Thread to {} has ended", self.description)))
+    }
+}
+
+impl Action for BackgroundAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        self.call(Call::Execute)
+    }
+
+    fn begin(&mut self) -> Result<(), ActionError> {
+        self.call(Call::Begin)
+    }
+
+    fn update(&mut self, delta: f64) -> Result<(), ActionError> {
+        self.call(Call::Update(delta))
+    }
+
+    fn motion(&mut self, dx: f64, dy: f64) -> Result<(), ActionError> {
+        self.call(Call::Motion(dx, dy))
+    }
+
+    fn end(&mut self) -> Result<(), ActionError> {
+        self.call(Call::End)
+    }
+
+    fn context(&mut self, context: &GestureContext) {
+        if let Err(ActionError(msg)) = self.call(Call::Context(context.clone())) {
+            log::error!("{}", msg);
+        }
+    }
+
+    fn describe(&self) -> String {
+        self.description.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BackgroundAction;
+    use crate::action::{Action, ActionError, ExecuteCommandAction};
+    use crate::common::OnError;
+
+    use std::collections::HashMap;
+    use std::sync::mpsc::{self, Sender};
+    use std::time::{Duration, Instant};
+
+    struct Slow(Sender<&'static str>);
+
+    impl Action for Slow {
+        fn execute(&mut self) -> Result<(), ActionError> {
+            std::thread::sleep(Duration::from_millis(50));
+            self.0.send("execute").unwrap();
+            Ok(())
+        }
+
+        fn end(&mut self) -> Result<(), ActionError> {
+            self.0.send("end").unwrap();
+            Ok(())
+        }
+
+        fn describe(&self) -> String {
+            "take a while".to_string()
+        }
+    }
+
+    #[test]
+    fn runs_in_order_without_blocking() {
+        let (tx, rx) = mpsc::channel();
+        let mut action = BackgroundAction::new(Box::new(Slow(tx)), OnError::Log).unwrap();
+        let start = Instant::now();
+        action.execute().unwrap();
+        action.execute().unwrap();
+        action.end().unwrap();
+        assert!(start.elapsed() < Duration::from_millis(50));
+        assert_eq!(action.describe(), "take a while");
+        drop(action);
+        assert_eq!(rx.iter().collect::<Vec<_>>(), ["execute", "execute", "end"]);
+    }
+
+    /// Sends whether each execute of the command worked
+    struct Tried(Sender<bool>, ExecuteCommandAction);
+
+    impl Action for Tried {
+        fn execute(&mut self) -> Result<(), ActionError> {
+            let result = self.1.execute();
+            self.0.send(result.is_ok()).unwrap();
+            result
+        }
+
+        fn describe(&self) -> String {
+            self.1.describe()
+        }
+    }
+
+    #[test]
+    fn failures_on_the_thread() {
+        let tries = |on_error| {
+            let (tx, rx) = mpsc::channel();
+            let command = ExecuteCommandAction {
+                path: "/nonexistent/wzmach-test".to_string(),
+                args: Vec::new(),
+                cwd: None,
+                env: HashMap::new(),
+                timeout: None,
+                gesture_env: false,
+                gesture: None,
+            };
+            let mut action = BackgroundAction::new(Box::new(Tried(tx, command)), on_error).unwrap();
+            // Queued, so the failure comes later
+            action.execute().unwrap();
+            drop(action);
+            rx.iter().collect::<Vec<_>>()
+        };
+        assert_eq!(tries(OnError::Log), [false]);
+        assert_eq!(tries(OnError::Ignore), [false]);
+        assert_eq!(tries(OnError::Retry), [false, false]);
+    }
+}
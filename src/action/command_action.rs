@@ -1,36 +1,160 @@
+use std::collections::HashMap;
+use std::io::Write;
 use std::os::unix::prelude::CommandExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
 
-use super::{Action, ActionError};
+use nix::sys::signal::Signal;
+
+use super::{Action, ActionError, GestureContext};
 
 pub struct ExecuteCommandAction {
     pub path: String,
     pub args: Vec<String>,
+    /// Directory to run in, instead of the daemon's
+    pub cwd: Option<PathBuf>,
+    /// Variables to add to the daemon's environment
+    pub env: HashMap<String, String>,
+    /// Kill the command when it runs for longer
+    pub timeout: Option<Duration>,
+    /// Pass the gesture to the command in `WZMACH_*` variables
+    pub gesture_env: bool,
+    /// The last gesture given
+    pub gesture: Option<GestureContext>,
 }
 
 pub struct InlineScriptAction {
     pub command: String,
+    /// Shell kept running to run the script in each time, so that it starts
+    /// faster and keeps its variables. `None` starts a shell each time
+    pub shell: Option<PersistentShell>,
+}
+
+/// `/bin/sh` reading scripts from a pipe, one after another. Started again
+/// when it exits
+#[derive(Default)]
+pub struct PersistentShell {
+    child: Option<Child>,
+}
+
+impl PersistentShell {
+    /// Send the script to the shell, starting one if it's not running
+    fn run(&mut self, script: &str) -> std::io::Result<()> {
+        // Quoted for eval, so that a syntax error can't leave the shell reading
+        // the next scripts as part of this one, and behind `command` so that
+        // the error doesn't end the shell. Scripts reading their input get
+        // none, instead of taking the next scripts from the pipe
+        let script = format!(
+            "command eval '{}' </dev/null\n",
+            script.replace('\'', "'\\''")
+        );
+        if let Some(child) = &mut self.child {
+            match child.try_wait()? {
+                None => {
+                    let stdin = child.stdin.as_mut().expect("stdin is piped");
+                    match stdin.write_all(script.as_bytes()) {
+                        Ok(()) => return Ok(()),
+                        // Exited just now
+                        Err(e) => log::warn!("Can't write to the shell, starting another: {}", e),
+                    }
+                }
+                Some(status) => log::warn!("Shell exited with {}, starting another", status),
+            }
+        }
+        let mut command = Command::new("/bin/sh");
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit());
+        // Safety: only changes the signal mask of the child before exec
+        unsafe {
+            command.pre_exec(|| {
+                nix::sys::signal::SigSet::empty().thread_set_mask()?;
+                Ok(())
+            });
+        }
+        let mut child = command.spawn()?;
+        log::debug!("Started shell {} for scripts", child.id());
+        let written = child
+            .stdin
+            .as_mut()
+            .expect("stdin is piped")
+            .write_all(script.as_bytes());
+        self.child = Some(child);
+        written
+    }
+}
+
+impl Drop for PersistentShell {
+    /// The shell exits once it runs out of input. Wait for it elsewhere, as it
+    /// may still be running a script
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            drop(child.stdin.take());
+            let waited = std::thread::Builder::new()
+                .name("shell exit".to_string())
+                .spawn(move || child.wait());
+            if let Err(e) = waited {
+                log::error!("Can't wait for the shell to exit: {}", e);
+            }
+        }
+    }
+}
+
+/// Run a command line in the user's shell
+pub struct ShellCommandAction {
+    pub shell: String,
+    pub command: String,
+    pub timeout: Option<Duration>,
+    /// Like for `ExecuteCommandAction`
+    pub gesture_env: bool,
+    pub gesture: Option<GestureContext>,
 }
 
 impl Action for ExecuteCommandAction {
     fn execute(&mut self) -> Result<(), ActionError> {
         log::debug!("Execute command {} {:?}", self.path, self.args);
 
-        std::process::Command::new(&self.path)
-            .args(self.args.iter())
+        let mut command = Command::new(&self.path);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        command.args(self.args.iter()).envs(self.env.iter());
+        if let Some(gesture) = self.gesture.as_ref().filter(|_| self.gesture_env) {
+            command.envs(gesture.vars());
+        }
+        command
             .stdin(std::process::Stdio::null())
             .stdout(std::process::Stdio::inherit())
             .stderr(std::process::Stdio::inherit())
-            .detach()?;
+            .run(self.timeout)?;
         log::trace!("Spawned the command");
 
         Ok(())
     }
+
+    fn context(&mut self, context: &GestureContext) {
+        self.gesture = Some(context.clone());
+    }
+
+    fn describe(&self) -> String {
+        match &self.cwd {
+            Some(cwd) => format!("execute {} {:?} in {}", self.path, self.args, cwd.display()),
+            None => format!("execute {} {:?}", self.path, self.args),
+        }
+    }
 }
 
 impl Action for InlineScriptAction {
     fn execute(&mut self) -> Result<(), ActionError> {
         log::debug!("Execute command {:?}", self.command);
 
+        if let Some(shell) = &mut self.shell {
+            shell.run(&self.command)?;
+            log::trace!("Sent the command to the shell");
+            return Ok(());
+        }
         std::process::Command::new("/bin/sh")
             .arg("-c")
             .arg(&self.command)
@@ -41,20 +165,80 @@ impl Action for InlineScriptAction {
 
         Ok(())
     }
+
+    fn describe(&self) -> String {
+        format!("run script {:?}", self.command)
+    }
+}
+
+impl Action for ShellCommandAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        log::debug!("Execute in {}: {:?}", self.shell, self.command);
+
+        let mut command = Command::new(&self.shell);
+        command.arg("-c").arg(&self.command);
+        if let Some(gesture) = self.gesture.as_ref().filter(|_| self.gesture_env) {
+            command.envs(gesture.vars());
+        }
+        command
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::inherit())
+            .stderr(std::process::Stdio::inherit())
+            .run(self.timeout)?;
+        log::trace!("Spawned the command");
+
+        Ok(())
+    }
+
+    fn context(&mut self, context: &GestureContext) {
+        self.gesture = Some(context.clone());
+    }
+
+    fn describe(&self) -> String {
+        format!("run {:?} in {}", self.command, self.shell)
+    }
 }
 
 /// Extension to be able to detach child processes without creating zombies
-trait DetachExt {
+pub(super) trait DetachExt {
     fn detach(&mut self) -> std::io::Result<()>;
+
+    /// Detach without a timeout. With one, start the command in a process
+    /// group of its own, and have a thread terminate the group when the
+    /// command runs for longer
+    fn run(&mut self, timeout: Option<Duration>) -> std::io::Result<()>;
 }
-impl DetachExt for std::process::Command {
+impl DetachExt for Command {
+    fn run(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        let timeout = match timeout {
+            Some(t) => t,
+            None => return self.detach(),
+        };
+        // Safety: only changes the signal mask of the child before exec
+        unsafe {
+            self.pre_exec(|| {
+                nix::sys::signal::SigSet::empty().thread_set_mask()?;
+                Ok(())
+            });
+        }
+        let child = self.process_group(0).spawn()?;
+        let name = self.get_program().to_string_lossy().into_owned();
+        std::thread::Builder::new()
+            .name("command timeout".to_string())
+            .spawn(move || reap(child, name, timeout))?;
+        Ok(())
+    }
+
     fn detach(&mut self) -> std::io::Result<()> {
         // Safety: usual daemonization stuff. Parent exits immediately before
         // exec, child goes on to exec what it wants
         use nix::unistd::ForkResult;
         unsafe {
             self.pre_exec(|| {
-                match nix::unistd::fork().unwrap() {
+                // The daemon blocks signals it reads from a signalfd, and the
+                // mask is inherited, so the command would ignore them too
+                nix::sys::signal::SigSet::empty().thread_set_mask()?;
+                match nix::unistd::fork()? {
                     ForkResult::Parent { child: _ } => std::process::exit(0),
                     ForkResult::Child => (),
                 }
@@ -66,8 +250,232 @@ impl DetachExt for std::process::Command {
     }
 }
 
+/// How long a command gets to exit after SIGTERM, before SIGKILL
+const KILL_GRACE: Duration = Duration::from_secs(2);
+
+/// Wait for the command to exit, and terminate it and whatever it started when
+/// it takes longer than the timeout
+fn reap(mut child: Child, name: String, timeout: Duration) {
+    let start = Instant::now();
+    let group = nix::unistd::Pid::from_raw(-(child.id() as i32));
+    let mut signal = Signal::SIGTERM;
+    let mut deadline = timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => return,
+            Ok(None) => (),
+            Err(e) => {
+                log::error!("Can't wait for {}: {}", name, e);
+                return;
+            }
+        }
+        if start.elapsed() >= deadline {
+            log::warn!(
+                "{} still runs after {} ms, sending {}",
+                name,
+                start.elapsed().as_millis(),
+                signal
+            );
+            if let Err(e) = nix::sys::signal::kill(group, signal) {
+                log::error!("Can't stop {}: {}", name, e);
+            }
+            if signal == Signal::SIGKILL {
+                let _ = child.wait();
+                return;
+            }
+            signal = Signal::SIGKILL;
+            deadline += KILL_GRACE;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
 impl From<std::io::Error> for ActionError {
     fn from(err: std::io::Error) -> ActionError {
         ActionError(format!("{}", err))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Action, ExecuteCommandAction, InlineScriptAction, ShellCommandAction};
+    use crate::action::GestureContext;
+
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn missing_program() {
+        let mut action = ExecuteCommandAction {
+            path: "/nonexistent/wzmach-test".to_string(),
+            args: Vec::new(),
+            cwd: None,
+            env: HashMap::new(),
+            timeout: None,
+            gesture_env: false,
+            gesture: None,
+        };
+        assert!(action.execute().is_err());
+    }
+
+    #[test]
+    fn cwd_and_env() {
+        let dir = std::env::temp_dir().join(format!("wzmach-cwd-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut action = ExecuteCommandAction {
+            path: "sh".to_string(),
+            args: vec!["-c".to_string(), "echo \"$GREETING\" > out".to_string()],
+            cwd: Some(dir.clone()),
+            env: HashMap::from([("GREETING".to_string(), "hello".to_string())]),
+            timeout: None,
+            gesture_env: false,
+            gesture: None,
+        };
+        action.execute().unwrap();
+
+        // The command is detached, so wait for it to write
+        let out = dir.join("out");
+        let start = Instant::now();
+        while std::fs::read_to_string(&out).map_or(true, |s| !s.ends_with('\n')) {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "Command didn't run"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "hello\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        action.cwd = Some("/nonexistent/wzmach-test".into());
+        assert!(action.execute().is_err());
+    }
+
+    #[test]
+    fn gesture_env() {
+        let out = std::env::temp_dir().join(format!("wzmach-gesture-{}", std::process::id()));
+        let _ = std::fs::remove_file(&out);
+        let mut action = ShellCommandAction {
+            shell: "sh".to_string(),
+            command: format!(
+                "echo \"$WZMACH_GESTURE $WZMACH_FINGERS $WZMACH_DIRECTION $WZMACH_SCALE\" > {}",
+                out.display()
+            ),
+            timeout: None,
+            gesture_env: true,
+            gesture: None,
+        };
+        action.context(&GestureContext {
+            gesture: "Pinch",
+            fingers: 2,
+            direction: Some("In".to_string()),
+            scale: Some(0.5),
+        });
+        action.execute().unwrap();
+
+        let start = Instant::now();
+        while std::fs::read_to_string(&out).map_or(true, |s| !s.ends_with('\n')) {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "Command didn't run"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "Pinch 2 In 0.500\n");
+        std::fs::remove_file(&out).unwrap();
+    }
+
+    #[test]
+    fn timeout() {
+        let pid_file = std::env::temp_dir().join(format!("wzmach-timeout-{}", std::process::id()));
+        let _ = std::fs::remove_file(&pid_file);
+        let mut action = ShellCommandAction {
+            shell: "sh".to_string(),
+            command: format!(
+                "sleep 10 & echo $! > {}; trap '' TERM; wait",
+                pid_file.display()
+            ),
+            timeout: Some(Duration::from_millis(100)),
+            gesture_env: false,
+            gesture: None,
+        };
+        action.execute().unwrap();
+
+        // The sleep started by the shell goes away with it, even though the
+        // shell ignores SIGTERM
+        let start = Instant::now();
+        let pid = loop {
+            if let Some(pid) = std::fs::read_to_string(&pid_file)
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+            {
+                break nix::unistd::Pid::from_raw(pid);
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "Command didn't run"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        while nix::sys::signal::kill(pid, None).is_ok() {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "Command wasn't killed"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        std::fs::remove_file(&pid_file).unwrap();
+    }
+
+    #[test]
+    fn persistent_shell() {
+        let out = std::env::temp_dir().join(format!("wzmach-shell-{}", std::process::id()));
+        let _ = std::fs::remove_file(&out);
+        let script = |command: &str| InlineScriptAction {
+            command: command.to_string(),
+            shell: Some(Default::default()),
+        };
+        let wait_for = |expected: &str| {
+            let start = Instant::now();
+            while std::fs::read_to_string(&out).map_or(true, |s| s != expected) {
+                assert!(
+                    start.elapsed() < Duration::from_secs(5),
+                    "Script didn't run"
+                );
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+
+        let mut count = script(&format!(
+            "n=$((${{n:-0}} + 1))\necho $n > {}",
+            out.display()
+        ));
+        count.execute().unwrap();
+        wait_for("1\n");
+        count.execute().unwrap();
+        wait_for("2\n");
+
+        // Started again after the script ends the shell, without the state
+        count.command.push_str("\nexit 3");
+        count.execute().unwrap();
+        wait_for("3\n");
+        let shell = count.shell.as_mut().unwrap().child.as_mut().unwrap();
+        while shell.try_wait().unwrap().is_none() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        count.execute().unwrap();
+        wait_for("1\n");
+
+        // Neither reading the input nor a syntax error takes the next scripts
+        let mut broken = script("read line; echo \"it's $line\"");
+        broken.execute().unwrap();
+        broken.command.push_str("\necho \"unterminated");
+        broken.execute().unwrap();
+        broken.command = format!("echo 'ran' > {}", out.display());
+        broken.execute().unwrap();
+        wait_for("ran\n");
+        let shell = broken.shell.as_mut().unwrap().child.as_mut().unwrap();
+        assert!(shell.try_wait().unwrap().is_none());
+
+        std::fs::remove_file(&out).unwrap();
+    }
+}
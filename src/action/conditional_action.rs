@@ -0,0 +1,122 @@
+use super::{Action, ActionError, GestureContext};
+use crate::window::Windows;
+
+/// Executes one action when the focused window has the class, and the other
+/// one otherwise, also when the window can't be determined
+pub struct ConditionalAction {
+    pub window_class: String,
+    pub then: Box<dyn Action>,
+    pub otherwise: Box<dyn Action>,
+    pub windows: Option<Windows>,
+    /// Branch chosen when a continuous trigger began, which follows the rest
+    /// of the gesture even if the focus changes
+    pub taken: bool,
+}
+
+impl ConditionalAction {
+    fn matches(&self) -> bool {
+        let window = self
+            .windows
+            .as_ref()
+            .and_then(|w| w.borrow_mut().active_window());
+        window.is_some_and(|w| w.class.contains(&self.window_class))
+    }
+
+    fn chosen(&mut self) -> &mut dyn Action {
+        if self.taken {
+            self.then.as_mut()
+        } else {
+            self.otherwise.as_mut()
+        }
+    }
+}
+
+impl Action for ConditionalAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        self.taken = self.matches();
+        self.chosen().execute()
+    }
+
+    fn begin(&mut self) -> Result<(), ActionError> {
+        self.taken = self.matches();
+        self.chosen().begin()
+    }
+
+    fn update(&mut self, delta: f64) -> Result<(), ActionError> {
+        self.chosen().update(delta)
+    }
+
+    fn motion(&mut self, dx: f64, dy: f64) -> Result<(), ActionError> {
+        self.chosen().motion(dx, dy)
+    }
+
+    fn end(&mut self) -> Result<(), ActionError> {
+        self.chosen().end()
+    }
+
+    fn context(&mut self, context: &GestureContext) {
+        self.then.context(context);
+        self.otherwise.context(context);
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "in {:?} windows {}, elsewhere {}",
+            self.window_class,
+            self.then.describe(),
+            self.otherwise.describe()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConditionalAction;
+    use crate::action::{Action, ActionError};
+    use crate::window::{ActiveWindow, WindowSource, Windows};
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Record(Rc<RefCell<Vec<usize>>>, usize);
+    impl Action for Record {
+        fn execute(&mut self) -> Result<(), ActionError> {
+            self.0.borrow_mut().push(self.1);
+            Ok(())
+        }
+
+        fn describe(&self) -> String {
+            format!("record {}", self.1)
+        }
+    }
+
+    struct Focus(Rc<RefCell<Option<&'static str>>>);
+    impl WindowSource for Focus {
+        fn active_window(&mut self) -> Option<ActiveWindow> {
+            self.0.borrow().map(|c| ActiveWindow {
+                class: vec![c.to_string()],
+                fullscreen: false,
+            })
+        }
+    }
+
+    #[test]
+    fn follows_focus() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let focus = Rc::new(RefCell::new(Some("firefox")));
+        let windows: Windows = Rc::new(RefCell::new(Focus(focus.clone())));
+        let mut action = ConditionalAction {
+            window_class: "firefox".to_string(),
+            then: Box::new(Record(log.clone(), 0)),
+            otherwise: Box::new(Record(log.clone(), 1)),
+            windows: Some(windows),
+            taken: false,
+        };
+        action.execute().unwrap();
+        *focus.borrow_mut() = Some("Alacritty");
+        action.execute().unwrap();
+        *focus.borrow_mut() = None;
+        action.execute().unwrap();
+        assert_eq!(*log.borrow(), vec![0, 1, 1]);
+    }
+}
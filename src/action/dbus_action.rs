@@ -0,0 +1,143 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use zbus::blocking::{connection, Connection};
+use zbus::message::{Flags, Message};
+use zbus::zvariant::{StructureBuilder, Value};
+
+use super::{Action, ActionError};
+
+/// Typed argument of a DBus method call
+#[derive(PartialEq, Debug, Clone, Deserialize)]
+pub enum DBusArg {
+    String(String),
+    Int32(i32),
+    Int64(i64),
+    UInt32(u32),
+    Double(f64),
+    Bool(bool),
+}
+
+impl DBusArg {
+    fn value(&self) -> Value<'_> {
+        match self {
+            DBusArg::String(s) => Value::from(s.as_str()),
+            DBusArg::Int32(i) => Value::from(*i),
+            DBusArg::Int64(i) => Value::from(*i),
+            DBusArg::UInt32(u) => Value::from(*u),
+            DBusArg::Double(d) => Value::from(*d),
+            DBusArg::Bool(b) => Value::from(*b),
+        }
+    }
+}
+
+thread_local! {
+    /// Connections to the buses that were called on, by the name given
+    static BUSES: RefCell<HashMap<String, Connection>> = RefCell::new(HashMap::new());
+}
+
+fn connect(bus: &str) -> zbus::Result<Connection> {
+    match bus {
+        "session" => Connection::session(),
+        "system" => Connection::system(),
+        address => connection::Builder::address(address)?.build(),
+    }
+}
+
+/// Send a message on the bus, `session`, `system` or the address of one. The
+/// connection is kept for the next messages, and made again once when it broke
+pub(super) fn send(bus: &str, message: &Message) -> zbus::Result<()> {
+    BUSES.with(|buses| {
+        let mut buses = buses.borrow_mut();
+        if let Some(conn) = buses.get(bus) {
+            match conn.send(message) {
+                Ok(()) => return Ok(()),
+                Err(e) => log::warn!("Lost the {} bus, connecting again: {}", bus, e),
+            }
+        }
+        let conn = connect(bus)?;
+        conn.send(message)?;
+        buses.insert(bus.to_string(), conn);
+        Ok(())
+    })
+}
+
+/// Call a DBus method without waiting for the reply, so a slow or missing
+/// service can't hold up the gestures
+pub struct DBusCallAction {
+    /// `session`, `system`, or the address of a bus
+    pub bus: String,
+    pub destination: String,
+    pub path: String,
+    pub interface: String,
+    pub method: String,
+    pub args: Vec<DBusArg>,
+}
+
+impl DBusCallAction {
+    fn message(&self) -> zbus::Result<Message> {
+        let message = Message::method_call(self.path.as_str(), self.method.as_str())?
+            .destination(self.destination.as_str())?
+            .interface(self.interface.as_str())?
+            .with_flags(Flags::NoReplyExpected)?;
+        if self.args.is_empty() {
+            return message.build(&());
+        }
+        let args = self
+            .args
+            .iter()
+            .fold(StructureBuilder::new(), |args, arg| {
+                args.append_field(arg.value())
+            })
+            .build()?;
+        message.build(&args)
+    }
+}
+
+impl Action for DBusCallAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        log::debug!("Execute DBus call {}", self.describe());
+
+        self.message()
+            .and_then(|message| send(&self.bus, &message))
+            .map_err(|e| ActionError(format!("DBus call failed: {}", e)))
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "call {}.{} on {} {} with {:?}",
+            self.interface, self.method, self.destination, self.path, self.args
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DBusArg, DBusCallAction};
+
+    #[test]
+    fn message() {
+        let mut action = DBusCallAction {
+            bus: "session".to_string(),
+            destination: "org.kde.KWin".to_string(),
+            path: "/KWin".to_string(),
+            interface: "org.kde.KWin".to_string(),
+            method: "setCurrentDesktop".to_string(),
+            args: vec![DBusArg::Int32(2), DBusArg::Bool(true)],
+        };
+        let message = action.message().unwrap();
+        let header = message.header();
+        assert_eq!(header.destination().unwrap().as_str(), "org.kde.KWin");
+        assert_eq!(header.path().unwrap().as_str(), "/KWin");
+        assert_eq!(header.interface().unwrap().as_str(), "org.kde.KWin");
+        assert_eq!(header.member().unwrap().as_str(), "setCurrentDesktop");
+        // Sent as the arguments, not as a struct of them
+        let body = message.body();
+        assert_eq!(body.signature().to_string_no_parens(), "ib");
+        assert_eq!(body.deserialize::<(i32, bool)>().unwrap(), (2, true));
+
+        action.args.clear();
+        assert_eq!(action.message().unwrap().body().signature().to_string(), "");
+    }
+}
@@ -0,0 +1,40 @@
+use super::{Action, ActionError};
+
+/// Logs what the wrapped action would do instead of doing it, to try configs
+/// out safely
+pub struct DryRunAction {
+    /// The trigger of the action, as it was configured
+    pub trigger: String,
+    pub action: Box<dyn Action>,
+}
+
+impl Action for DryRunAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        log::info!("{} fired, would {}", self.trigger, self.action.describe());
+        Ok(())
+    }
+
+    fn begin(&mut self) -> Result<(), ActionError> {
+        log::info!("{} began", self.trigger);
+        Ok(())
+    }
+
+    fn update(&mut self, delta: f64) -> Result<(), ActionError> {
+        log::info!("{} progressed by {:.3}", self.trigger, delta);
+        Ok(())
+    }
+
+    fn motion(&mut self, dx: f64, dy: f64) -> Result<(), ActionError> {
+        log::debug!("{} moved by {:.3}, {:.3}", self.trigger, dx, dy);
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), ActionError> {
+        log::info!("{} ended", self.trigger);
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        self.action.describe()
+    }
+}
@@ -0,0 +1,57 @@
+use super::{Action, ActionError};
+
+/// Run a Hyprland dispatcher, like `workspace` with `2`, over Hyprland's IPC
+/// socket, the same as `hyprctl dispatch` but without starting it
+pub struct HyprlandDispatchAction {
+    pub dispatcher: String,
+    pub args: String,
+}
+
+impl HyprlandDispatchAction {
+    fn message(&self) -> String {
+        format!("dispatch {} {}", self.dispatcher, self.args)
+            .trim_end()
+            .to_string()
+    }
+}
+
+impl Action for HyprlandDispatchAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        log::debug!("Execute {}", self.describe());
+
+        // The same config may run outside of Hyprland, where this only logs
+        let reply = crate::window::hyprland_request(&self.message())
+            .map_err(|e| ActionError(format!("Can't reach Hyprland: {}", e)))?;
+        log::debug!("Hyprland replied {:?}", reply);
+        if reply.trim() != "ok" {
+            return Err(ActionError(format!(
+                "Hyprland failed to dispatch {}: {}",
+                self.dispatcher,
+                reply.trim()
+            )));
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("Hyprland dispatch {:?}", self.message())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HyprlandDispatchAction;
+
+    #[test]
+    fn dispatch_message() {
+        let action = |dispatcher: &str, args: &str| HyprlandDispatchAction {
+            dispatcher: dispatcher.to_string(),
+            args: args.to_string(),
+        };
+        assert_eq!(
+            action("workspace", "e+1").message(),
+            "dispatch workspace e+1"
+        );
+        assert_eq!(action("killactive", "").message(), "dispatch killactive");
+    }
+}
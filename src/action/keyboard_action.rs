@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+use super::{Action, ActionError, InputDevice};
+use crate::common::KeyMode;
+use crate::config::Key;
+
+pub struct KeyboardInputAction {
+    pub device: InputDevice,
+    pub modifiers: Vec<Key>,
+    pub sequence: Vec<Key>,
+    /// Wait between the key events. This blocks the main loop, so the config
+    /// keeps the waits of all the events to a second
+    pub key_delay: Duration,
+    /// Times to press the sequence while the modifiers are held
+    pub repeat: u32,
+    /// Press all the sequence keys down before releasing any, instead of
+    /// pressing and releasing each in turn
+    pub chord: bool,
+    /// With `Hold`, the modifiers and the sequence go down once and stay down
+    /// until the next gesture, without repeating
+    pub mode: KeyMode,
+}
+
+/// Press keys and keep them down for a while, like a long press. The keys are
+/// released by the main loop, which keeps handling gestures meanwhile
+pub struct KeyHoldAction {
+    pub device: InputDevice,
+    /// Pressed before the key, and released after it
+    pub keys: Vec<Key>,
+    pub hold: Duration,
+}
+
+impl KeyboardInputAction {
+    /// The keys in the order they go, and whether each goes down
+    pub(super) fn events(&self) -> Vec<(&Key, bool)> {
+        let mut events: Vec<_> = self.modifiers.iter().map(|k| (k, true)).collect();
+        for _ in 0..self.repeat {
+            if self.chord {
+                events.extend(self.sequence.iter().map(|k| (k, true)));
+                events.extend(self.sequence.iter().rev().map(|k| (k, false)));
+            } else {
+                events.extend(self.sequence.iter().flat_map(|k| [(k, true), (k, false)]));
+            }
+        }
+        events.extend(self.modifiers.iter().rev().map(|k| (k, false)));
+        events
+    }
+}
+
+impl Action for KeyboardInputAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        let mut device = self.device.borrow_mut();
+        log::debug!("Execute action {:?} + {:?}", self.modifiers, self.sequence);
+        if self.mode == KeyMode::Hold {
+            let keys: Vec<_> = self
+                .modifiers
+                .iter()
+                .chain(&self.sequence)
+                .copied()
+                .collect();
+            device.latch(&keys)?;
+            return Ok(());
+        }
+        // The modifiers go down in one report, and every other key event in
+        // its own after them, so that no application sees a key before the
+        // modifiers or two events of a key at once
+        for (n, (key, down)) in self.events().into_iter().enumerate() {
+            if n >= self.modifiers.len().max(1) {
+                device.synchronize()?;
+                if !self.key_delay.is_zero() {
+                    std::thread::sleep(self.key_delay);
+                }
+            }
+            if down {
+                device.press(key)?;
+            } else {
+                device.release(key)?;
+            }
+        }
+        device.synchronize()?;
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        if self.mode == KeyMode::Hold {
+            return format!(
+                "hold {:?} + {:?} until the next gesture",
+                self.modifiers, self.sequence
+            );
+        }
+        let together = if self.chord { " together" } else { "" };
+        match self.repeat {
+            1 => format!(
+                "press {:?} + {:?}{}",
+                self.modifiers, self.sequence, together
+            ),
+            n => format!(
+                "press {:?} + {:?}{} {} times",
+                self.modifiers, self.sequence, together, n
+            ),
+        }
+    }
+}
+
+impl Action for KeyHoldAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        log::debug!("Execute {}", self.describe());
+        let until = Instant::now() + self.hold;
+        self.device.borrow_mut().hold(&self.keys, until)?;
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("hold {:?} for {:?}", self.keys, self.hold)
+    }
+}
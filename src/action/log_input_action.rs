@@ -0,0 +1,19 @@
+use super::{Action, ActionError};
+
+/// Stands in for keyboard and mouse actions when wzmach is built without
+/// uinput. Only logs the input it would have sent
+pub struct LogInputAction {
+    /// Description of the input, like the other actions describe themselves
+    pub input: String,
+}
+
+impl Action for LogInputAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        log::info!("Built without uinput, would {}", self.input);
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        self.input.clone()
+    }
+}
@@ -0,0 +1,241 @@
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use mlua::{HookTriggers, Lua, Value};
+
+use super::command_action::DetachExt;
+use super::{Action, ActionError, GestureContext};
+use crate::window::Windows;
+
+/// Presses the keys of a chord like `"ctrl+alt+t"`, failing with why not
+pub type EmitKeys = Box<dyn FnMut(&str) -> Result<(), String>>;
+
+/// How long a script can run before it's stopped, since it runs on the main
+/// loop and gestures wait for it
+pub const LUA_TIME_LIMIT: Duration = Duration::from_secs(1);
+
+/// How many Lua instructions run between checks of the time limit
+const CHECK_EVERY: u32 = 1000;
+
+/// Runs a Lua script in a state that's kept between gestures. The script sees
+/// the gesture in the `gesture` table, and can call `window()`,
+/// `emit_keys(chord)` and `run_command(command)`
+pub struct LuaScriptAction {
+    pub lua: Lua,
+    pub code: String,
+    pub emit_keys: EmitKeys,
+    pub windows: Option<Windows>,
+    /// The last gesture given
+    pub gesture: Option<GestureContext>,
+    /// Run time after which the script is stopped with an error
+    pub time_limit: Duration,
+}
+
+impl LuaScriptAction {
+    pub fn new(code: String, emit_keys: EmitKeys, windows: Option<Windows>) -> Self {
+        Self {
+            lua: Lua::new(),
+            code,
+            emit_keys,
+            windows,
+            gesture: None,
+            time_limit: LUA_TIME_LIMIT,
+        }
+    }
+
+    /// Whether the code compiles, with the error otherwise
+    pub fn check(code: &str) -> Result<(), String> {
+        Lua::new()
+            .load(code)
+            .set_name("LuaScript")
+            .into_function()
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl Action for LuaScriptAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        log::debug!("Run Lua script: {:?}", self.code);
+
+        let Self {
+            lua,
+            code,
+            emit_keys,
+            windows,
+            gesture,
+            time_limit,
+        } = self;
+        let globals = lua.globals();
+        let (started, time_limit) = (Instant::now(), *time_limit);
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(CHECK_EVERY),
+            move |_, _| match started.elapsed() > time_limit {
+                true => Err(mlua::Error::RuntimeError(format!(
+                    "Stopped after running for longer than {:?}",
+                    time_limit
+                ))),
+                false => Ok(()),
+            },
+        );
+        let result = lua.scope(|scope| {
+            let table = match gesture {
+                Some(gesture) => {
+                    let table = lua.create_table()?;
+                    table.set("kind", gesture.gesture)?;
+                    table.set("fingers", gesture.fingers)?;
+                    table.set("direction", gesture.direction.clone())?;
+                    table.set("scale", gesture.scale)?;
+                    Value::Table(table)
+                }
+                None => Value::Nil,
+            };
+            globals.set("gesture", table)?;
+
+            let windows = windows.clone();
+            let window = lua.create_function(move |lua, ()| {
+                let window = windows
+                    .as_ref()
+                    .and_then(|w| w.borrow_mut().active_window());
+                match window {
+                    Some(window) => {
+                        let table = lua.create_table()?;
+                        table.set("class", window.class)?;
+                        table.set("fullscreen", window.fullscreen)?;
+                        Ok(Value::Table(table))
+                    }
+                    None => Ok(Value::Nil),
+                }
+            })?;
+            globals.set("window", window)?;
+
+            let emit = scope.create_function_mut(|_, chord: String| {
+                emit_keys(&chord).map_err(mlua::Error::RuntimeError)
+            })?;
+            globals.set("emit_keys", emit)?;
+
+            let run = lua.create_function(|_, command: String| {
+                log::debug!("Execute from Lua: {:?}", command);
+                Command::new("sh")
+                    .arg("-c")
+                    .arg(&command)
+                    .stdin(Stdio::null())
+                    .detach()
+                    .map_err(mlua::Error::external)
+            })?;
+            globals.set("run_command", run)?;
+
+            lua.load(code.as_str()).set_name("LuaScript").exec()
+        });
+        lua.remove_hook();
+        result.map_err(|e| ActionError(e.to_string()))
+    }
+
+    fn context(&mut self, context: &GestureContext) {
+        self.gesture = Some(context.clone());
+    }
+
+    fn describe(&self) -> String {
+        format!("run Lua script {:?}", self.code)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LuaScriptAction;
+    use crate::action::{Action, GestureContext};
+    use crate::window::{ActiveWindow, WindowSource};
+    use std::time::Duration;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Focus(&'static str);
+    impl WindowSource for Focus {
+        fn active_window(&mut self) -> Option<ActiveWindow> {
+            Some(ActiveWindow {
+                class: vec![self.0.to_string()],
+                fullscreen: true,
+            })
+        }
+    }
+
+    /// Action that emits the keys into `emitted`
+    fn script(code: &str, emitted: &Rc<RefCell<Vec<String>>>) -> LuaScriptAction {
+        let emitted = emitted.clone();
+        LuaScriptAction::new(
+            code.to_string(),
+            Box::new(move |chord| match chord {
+                "nope" => Err("No key named nope".to_string()),
+                chord => {
+                    emitted.borrow_mut().push(chord.to_string());
+                    Ok(())
+                }
+            }),
+            Some(Rc::new(RefCell::new(Focus("firefox")))),
+        )
+    }
+
+    #[test]
+    fn api() {
+        let emitted = Rc::new(RefCell::new(Vec::new()));
+        let mut action = script(
+            r#"
+            local w = window()
+            if gesture.kind == "Swipe" and w.class[1] == "firefox" and w.fullscreen then
+                emit_keys(gesture.fingers .. "+" .. gesture.direction)
+            end
+            if gesture.scale == nil then emit_keys("no scale") end
+            "#,
+            &emitted,
+        );
+        action.context(&GestureContext {
+            gesture: "Swipe",
+            fingers: 3,
+            direction: Some("Left".to_string()),
+            scale: None,
+        });
+        action.execute().unwrap();
+        assert_eq!(*emitted.borrow(), ["3+Left", "no scale"]);
+    }
+
+    #[test]
+    fn keeps_state() {
+        let emitted = Rc::new(RefCell::new(Vec::new()));
+        let mut action = script(
+            "count = (count or 0) + 1; emit_keys(tostring(count))",
+            &emitted,
+        );
+        action.execute().unwrap();
+        action.execute().unwrap();
+        assert_eq!(*emitted.borrow(), ["1", "2"]);
+    }
+
+    #[test]
+    fn errors() {
+        let emitted = Rc::new(RefCell::new(Vec::new()));
+        let error = script("emit_keys('nope')", &emitted).execute().unwrap_err();
+        assert!(error.0.contains("No key named nope"), "{}", error.0);
+        let error = script("error('broken')", &emitted).execute().unwrap_err();
+        assert!(error.0.contains("broken"), "{}", error.0);
+        // Without a gesture given, the table is nil
+        assert!(script("emit_keys(gesture.kind)", &emitted)
+            .execute()
+            .is_err());
+        assert!(emitted.borrow().is_empty());
+
+        // Scripts that don't end are stopped, and the state still works after
+        let mut action = script(
+            "if stuck == nil then stuck = true; while true do end end; emit_keys('a')",
+            &emitted,
+        );
+        action.time_limit = Duration::from_millis(50);
+        let error = action.execute().unwrap_err();
+        assert!(error.0.contains("longer than"), "{}", error.0);
+        action.execute().unwrap();
+        assert_eq!(*emitted.borrow(), ["a"]);
+
+        assert!(LuaScriptAction::check("emit_keys('a')").is_ok());
+        assert!(LuaScriptAction::check("if then").is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use super::{Action, ActionError};
+
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+/// What a mock action was asked to do
+#[derive(PartialEq, Debug, Clone)]
+pub enum MockCall {
+    Execute,
+    Begin,
+    Update(f64),
+    Motion(f64, f64),
+    End,
+}
+
+/// Calls of mock actions in the order they came, each with what the action
+/// stands in for
+pub type MockCalls<T> = Rc<RefCell<Vec<(T, MockCall)>>>;
+
+/// Stands in for an action, to test which actions gestures lead to. Keeps its
+/// calls instead of doing anything
+pub struct MockAction<T> {
+    pub action: T,
+    pub calls: MockCalls<T>,
+}
+
+impl<T: Clone> MockAction<T> {
+    fn call(&self, call: MockCall) -> Result<(), ActionError> {
+        self.calls.borrow_mut().push((self.action.clone(), call));
+        Ok(())
+    }
+}
+
+impl<T: Clone + Debug> Action for MockAction<T> {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        self.call(MockCall::Execute)
+    }
+
+    fn begin(&mut self) -> Result<(), ActionError> {
+        self.call(MockCall::Begin)
+    }
+
+    fn update(&mut self, delta: f64) -> Result<(), ActionError> {
+        self.call(MockCall::Update(delta))
+    }
+
+    fn motion(&mut self, dx: f64, dy: f64) -> Result<(), ActionError> {
+        self.call(MockCall::Motion(dx, dy))
+    }
+
+    fn end(&mut self) -> Result<(), ActionError> {
+        self.call(MockCall::End)
+    }
+
+    fn describe(&self) -> String {
+        format!("mock of {:?}", self.action)
+    }
+}
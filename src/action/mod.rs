@@ -1,5 +1,28 @@
+mod background_action;
 mod command_action;
+mod conditional_action;
+mod dbus_action;
+mod dry_run_action;
+mod hyprland_action;
+mod keyboard_action;
+mod log_input_action;
+#[cfg(feature = "lua")]
+mod lua_action;
+mod mock_action;
+mod notify_action;
+mod progressive_action;
+mod sequence_action;
+mod sway_action;
+mod toggle_action;
+mod type_text_action;
+#[cfg(feature = "uinput")]
 mod uinput_action;
+mod virtual_device;
+mod virtual_keyboard;
+mod window_keys_action;
+mod workspace_action;
+
+use crate::common::OnError;
 
 use thiserror::Error;
 
@@ -9,7 +32,127 @@ pub struct ActionError(pub String);
 
 pub trait Action {
     fn execute(&mut self) -> Result<(), ActionError>;
+
+    /// What executing does, in words, like "press [LeftControl] + [T]"
+    fn describe(&self) -> String;
+
+    /// Continuous trigger reached its threshold and follows the gesture now
+    fn begin(&mut self) -> Result<(), ActionError> {
+        Ok(())
+    }
+
+    /// Continuous trigger followed the gesture by `delta`. Actions that don't
+    /// care about progress ignore it
+    fn update(&mut self, _delta: f64) -> Result<(), ActionError> {
+        Ok(())
+    }
+
+    /// Fingers of the gesture followed by a continuous trigger moved by this
+    /// much, in any direction
+    fn motion(&mut self, _dx: f64, _dy: f64) -> Result<(), ActionError> {
+        Ok(())
+    }
+
+    /// Gesture followed by a continuous trigger has ended
+    fn end(&mut self) -> Result<(), ActionError> {
+        Ok(())
+    }
+
+    /// The gesture that the next `execute` or `begin` comes from. Actions
+    /// that don't pass it on ignore it
+    fn context(&mut self, _context: &GestureContext) {}
 }
 
-pub use command_action::{ExecuteCommandAction, InlineScriptAction};
-pub use uinput_action::KeyboardInputAction;
+/// What the trigger of an action matched, for commands that handle several
+/// gestures
+#[derive(PartialEq, Debug, Clone)]
+pub struct GestureContext {
+    /// Kind of the trigger, like "Swipe"
+    pub gesture: &'static str,
+    pub fingers: i32,
+    /// Direction of the trigger, like "Up". `None` for holds and sequences
+    pub direction: Option<String>,
+    /// Scale of the fingers so far, in gestures of two fingers or more that
+    /// pinch, rotate or shear. `None` in swipes and holds
+    pub scale: Option<f64>,
+}
+
+impl GestureContext {
+    /// As environment variables, like `WZMACH_DIRECTION=Up`. Those without a
+    /// value are left out
+    pub fn vars(&self) -> Vec<(&'static str, String)> {
+        let mut vars = vec![
+            ("WZMACH_GESTURE", self.gesture.to_string()),
+            ("WZMACH_FINGERS", self.fingers.to_string()),
+        ];
+        if let Some(direction) = &self.direction {
+            vars.push(("WZMACH_DIRECTION", direction.clone()));
+        }
+        if let Some(scale) = self.scale {
+            vars.push(("WZMACH_SCALE", format!("{:.3}", scale)));
+        }
+        vars
+    }
+}
+
+/// Deal with the action failing with `msg` as `on_error` says. `Retry`
+/// executes it once more only when `retry` allows it, for what failed was
+/// executing and not a step of a continuous trigger, which would go twice as
+/// far
+pub fn recover(action: &mut dyn Action, on_error: OnError, retry: bool, msg: String) {
+    match on_error {
+        OnError::Log => log::error!("{}", msg),
+        OnError::Ignore => log::debug!("Ignoring: {}", msg),
+        OnError::Retry if retry => {
+            log::warn!("{}, retrying once", msg);
+            match action.execute() {
+                Ok(()) => log::info!("Retrying {} worked", action.describe()),
+                Err(ActionError(msg)) => log::error!("{}, again", msg),
+            }
+        }
+        OnError::Retry => log::error!("{}", msg),
+        OnError::Notify => {
+            log::error!("{}", msg);
+            let mut notify = NotifyAction {
+                summary: format!("Can't {}", action.describe()),
+                body: Some(msg),
+                icon: Some("dialog-error".to_string()),
+            };
+            if let Err(ActionError(e)) = notify.execute() {
+                log::warn!("Can't notify of the failure: {}", e);
+            }
+        }
+    }
+}
+
+/// Device that keyboard and mouse actions send their input to
+pub type InputDevice = std::rc::Rc<std::cell::RefCell<VirtualDevice>>;
+
+/// Name of the uinput device, by which its own key presses are told apart
+pub const VIRTUAL_DEVICE_NAME: &str = "wzmach_virtual";
+
+pub use background_action::BackgroundAction;
+pub use command_action::{
+    ExecuteCommandAction, InlineScriptAction, PersistentShell, ShellCommandAction,
+};
+pub use conditional_action::ConditionalAction;
+pub use dbus_action::{DBusArg, DBusCallAction};
+pub use dry_run_action::DryRunAction;
+pub use hyprland_action::HyprlandDispatchAction;
+pub use keyboard_action::{KeyHoldAction, KeyboardInputAction};
+pub use log_input_action::LogInputAction;
+#[cfg(feature = "lua")]
+pub use lua_action::{EmitKeys, LuaScriptAction};
+pub use mock_action::{MockAction, MockCall, MockCalls};
+pub use notify_action::NotifyAction;
+pub use progressive_action::ProgressiveAction;
+pub use sequence_action::SequenceAction;
+pub use sway_action::SwayCommandAction;
+pub use toggle_action::ToggleAction;
+pub use type_text_action::TypeTextAction;
+#[cfg(feature = "uinput")]
+pub use uinput_action::{MouseButtonAction, MouseMoveAction, MouseScrollAction};
+pub use virtual_device::{DeviceError, MockKeys, VirtualDevice};
+pub use virtual_keyboard::WaylandKeyboard;
+pub use window_keys_action::WindowKeysAction;
+pub use workspace_action::WorkspaceSwitchAction;
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use zbus::message::{Flags, Message};
+use zbus::zvariant::Value;
+
+use super::dbus_action;
+use super::{Action, ActionError};
+
+/// Show a desktop notification through `org.freedesktop.Notifications`. Like
+/// DBus calls, the reply with the id of the notification isn't waited for
+pub struct NotifyAction {
+    pub summary: String,
+    pub body: Option<String>,
+    /// Name of an icon from the icon theme, or a path
+    pub icon: Option<String>,
+}
+
+impl NotifyAction {
+    fn message(&self) -> zbus::Result<Message> {
+        Message::method_call("/org/freedesktop/Notifications", "Notify")?
+            .destination("org.freedesktop.Notifications")?
+            .interface("org.freedesktop.Notifications")?
+            .with_flags(Flags::NoReplyExpected)?
+            .build(&(
+                "wzmach",
+                // Replaces no notification
+                0u32,
+                self.icon.as_deref().unwrap_or(""),
+                &self.summary,
+                self.body.as_deref().unwrap_or(""),
+                Vec::<&str>::new(),
+                HashMap::<&str, Value>::new(),
+                // Expires when the server decides
+                -1i32,
+            ))
+    }
+}
+
+impl Action for NotifyAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        log::debug!("Execute {}", self.describe());
+
+        self.message()
+            .and_then(|message| dbus_action::send("session", &message))
+            .map_err(|e| ActionError(format!("Can't notify: {}", e)))
+    }
+
+    fn describe(&self) -> String {
+        match &self.body {
+            Some(body) => format!("notify {:?}: {:?}", self.summary, body),
+            None => format!("notify {:?}", self.summary),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::NotifyAction;
+    use std::collections::HashMap;
+    use zbus::zvariant::Value;
+
+    #[test]
+    fn message() {
+        let action = NotifyAction {
+            summary: "Switched to Tom's desk".to_string(),
+            body: None,
+            icon: Some("desktop".to_string()),
+        };
+        let message = action.message().unwrap();
+        assert_eq!(message.header().member().unwrap().as_str(), "Notify");
+        let body = message.body();
+        assert_eq!(body.signature().to_string_no_parens(), "susssasa{sv}i");
+        let (app, id, icon, summary, text, actions, hints, timeout) = body
+            .deserialize::<(
+                &str,
+                u32,
+                &str,
+                &str,
+                &str,
+                Vec<&str>,
+                HashMap<&str, Value>,
+                i32,
+            )>()
+            .unwrap();
+        assert_eq!(
+            (app, id, icon, summary, text),
+            ("wzmach", 0, "desktop", "Switched to Tom's desk", "")
+        );
+        assert!(actions.is_empty() && hints.is_empty());
+        assert_eq!(timeout, -1);
+    }
+}
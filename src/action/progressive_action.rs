@@ -0,0 +1,162 @@
+use super::{Action, ActionError, GestureContext};
+use crate::common::Curve;
+
+/// Executes the inner action once every time the gesture progresses by `step`,
+/// after scaling the progress with a response curve
+pub struct ProgressiveAction {
+    step: f64,
+    curve: Curve,
+    gain: f64,
+    action: Box<dyn Action>,
+    /// Progress accumulated since the last execution
+    progress: f64,
+}
+
+impl ProgressiveAction {
+    /// Fails unless the step and the gain are positive. Executing every step
+    /// of 0 or less would never stop
+    pub fn new(
+        step: f64,
+        curve: Curve,
+        gain: f64,
+        action: Box<dyn Action>,
+    ) -> Result<Self, ActionError> {
+        if !(step.is_finite() && step > 0.0) {
+            return Err(ActionError(format!(
+                "Progressive step must be positive, but is {}",
+                step
+            )));
+        }
+        if !(gain.is_finite() && gain > 0.0) {
+            return Err(ActionError(format!(
+                "Progressive gain must be positive, but is {}",
+                gain
+            )));
+        }
+        Ok(ProgressiveAction {
+            step,
+            curve,
+            gain,
+            action,
+            progress: 0.0,
+        })
+    }
+}
+
+impl Action for ProgressiveAction {
+    /// With a discrete trigger this is just the inner action
+    fn execute(&mut self) -> Result<(), ActionError> {
+        self.action.execute()
+    }
+
+    fn begin(&mut self) -> Result<(), ActionError> {
+        self.action.begin()
+    }
+
+    fn update(&mut self, delta: f64) -> Result<(), ActionError> {
+        self.progress += self.curve.apply(delta, self.step, self.gain);
+        while self.progress >= self.step {
+            self.progress -= self.step;
+            self.action.execute()?;
+        }
+        Ok(())
+    }
+
+    /// Passed on as it comes, for the actions that follow the fingers
+    fn motion(&mut self, dx: f64, dy: f64) -> Result<(), ActionError> {
+        self.action.motion(dx, dy)
+    }
+
+    fn end(&mut self) -> Result<(), ActionError> {
+        self.progress = 0.0;
+        self.action.end()
+    }
+
+    fn context(&mut self, context: &GestureContext) {
+        self.action.context(context)
+    }
+
+    fn describe(&self) -> String {
+        format!("{} every {} of progress", self.action.describe(), self.step)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ProgressiveAction;
+    use crate::action::{Action, ActionError};
+    use crate::common::Curve;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct Count(Rc<Cell<u32>>);
+    impl Action for Count {
+        fn execute(&mut self) -> Result<(), ActionError> {
+            self.0.set(self.0.get() + 1);
+            Ok(())
+        }
+
+        fn motion(&mut self, dx: f64, _dy: f64) -> Result<(), ActionError> {
+            self.0.set(self.0.get() + dx as u32);
+            Ok(())
+        }
+
+        fn describe(&self) -> String {
+            "count".to_string()
+        }
+    }
+
+    fn steps(curve: Curve, gain: f64, deltas: &[f64]) -> u32 {
+        let count = Rc::new(Cell::new(0));
+        let mut action =
+            ProgressiveAction::new(10.0, curve, gain, Box::new(Count(count.clone()))).unwrap();
+        action.begin().unwrap();
+        for delta in deltas {
+            action.update(*delta).unwrap();
+        }
+        count.get()
+    }
+
+    #[test]
+    fn curve() {
+        // Slow moves of one step each behave the same with any curve
+        assert_eq!(steps(Curve::Linear, 1.0, &[10.0; 4]), 4);
+        assert_eq!(steps(Curve::Quadratic, 1.0, &[10.0; 4]), 4);
+        // A fast move goes further with the steeper curves
+        assert_eq!(steps(Curve::Linear, 1.0, &[20.0]), 2);
+        assert_eq!(steps(Curve::Quadratic, 1.0, &[20.0]), 4);
+        assert_eq!(steps(Curve::Cubic, 1.0, &[20.0]), 8);
+        assert_eq!(steps(Curve::Linear, 1.5, &[20.0]), 3);
+    }
+
+    #[test]
+    fn motion() {
+        let count = Rc::new(Cell::new(0));
+        let mut action =
+            ProgressiveAction::new(10.0, Curve::Linear, 1.0, Box::new(Count(count.clone())))
+                .unwrap();
+        action.begin().unwrap();
+        action.motion(100.0, 0.0).unwrap();
+        assert_eq!(count.get(), 100);
+    }
+
+    #[test]
+    fn bad_step() {
+        let made = |step, gain| {
+            let count = Box::new(Count(Rc::new(Cell::new(0))));
+            ProgressiveAction::new(step, Curve::Linear, gain, count).map(|_| ())
+        };
+        assert!(made(10.0, 1.0).is_ok());
+        for (step, gain) in [
+            (0.0, 1.0),
+            (-1.0, 1.0),
+            (f64::NAN, 1.0),
+            (f64::INFINITY, 1.0),
+        ] {
+            assert!(made(step, gain).is_err());
+        }
+        assert!(made(10.0, f64::NAN).is_err());
+        assert!(made(10.0, 0.0).is_err());
+    }
+}
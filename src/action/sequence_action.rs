@@ -0,0 +1,112 @@
+use super::{Action, ActionError, GestureContext};
+
+/// Executes all the actions one after another. A failing action doesn't stop
+/// the ones after it
+pub struct SequenceAction {
+    pub actions: Vec<Box<dyn Action>>,
+}
+
+impl SequenceAction {
+    /// Do the same thing to every action, logging the failures
+    fn each<F>(&mut self, mut f: F) -> Result<(), ActionError>
+    where
+        F: FnMut(&mut dyn Action) -> Result<(), ActionError>,
+    {
+        let mut failed = 0;
+        for action in self.actions.iter_mut() {
+            if let Err(ActionError(msg)) = f(action.as_mut()) {
+                log::error!("{}", msg);
+                failed += 1;
+            }
+        }
+        if failed == 0 {
+            Ok(())
+        } else {
+            Err(ActionError(format!(
+                "{} of {} actions in sequence failed",
+                failed,
+                self.actions.len()
+            )))
+        }
+    }
+}
+
+impl Action for SequenceAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        self.each(|a| a.execute())
+    }
+
+    fn begin(&mut self) -> Result<(), ActionError> {
+        self.each(|a| a.begin())
+    }
+
+    fn update(&mut self, delta: f64) -> Result<(), ActionError> {
+        self.each(|a| a.update(delta))
+    }
+
+    fn motion(&mut self, dx: f64, dy: f64) -> Result<(), ActionError> {
+        self.each(|a| a.motion(dx, dy))
+    }
+
+    fn end(&mut self) -> Result<(), ActionError> {
+        self.each(|a| a.end())
+    }
+
+    fn context(&mut self, context: &GestureContext) {
+        for action in &mut self.actions {
+            action.context(context);
+        }
+    }
+
+    fn describe(&self) -> String {
+        let all: Vec<_> = self.actions.iter().map(|a| a.describe()).collect();
+        all.join(", then ")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SequenceAction;
+    use crate::action::{Action, ActionError};
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Record(Rc<RefCell<Vec<usize>>>, usize);
+    impl Action for Record {
+        fn execute(&mut self) -> Result<(), ActionError> {
+            self.0.borrow_mut().push(self.1);
+            Ok(())
+        }
+
+        fn describe(&self) -> String {
+            format!("record {}", self.1)
+        }
+    }
+
+    struct Fail;
+    impl Action for Fail {
+        fn execute(&mut self) -> Result<(), ActionError> {
+            Err(ActionError("broken".to_string()))
+        }
+
+        fn describe(&self) -> String {
+            "fail".to_string()
+        }
+    }
+
+    #[test]
+    fn failure_is_isolated() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut seq = SequenceAction {
+            actions: vec![
+                Box::new(Record(log.clone(), 0)),
+                Box::new(Fail),
+                Box::new(Record(log.clone(), 2)),
+            ],
+        };
+        assert!(seq.execute().is_err());
+        assert_eq!(*log.borrow(), vec![0, 2]);
+        assert_eq!(seq.describe(), "record 0, then fail, then record 2");
+    }
+}
@@ -0,0 +1,147 @@
+use super::{Action, ActionError};
+
+use serde::Deserialize;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+/// Every message starts with this, both ways
+const MAGIC: &[u8] = b"i3-ipc";
+/// Message type that runs commands like those in sway's config
+const RUN_COMMAND: u32 = 0;
+/// Sway replies right away. This is against a socket that nothing answers on
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Outcome of one of the commands in a message, which can hold several
+#[derive(Deserialize, Debug)]
+struct Outcome {
+    success: bool,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Run sway commands, like `workspace next`, over the IPC socket at
+/// `$SWAYSOCK`. Works in wayland without sending keys to the compositor
+pub struct SwayCommandAction {
+    pub command: String,
+}
+
+impl SwayCommandAction {
+    /// Send the command to the socket, and return the reply, which is JSON
+    fn send(&self, path: &str) -> io::Result<String> {
+        let mut stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        stream.write_all(&message(RUN_COMMAND, self.command.as_bytes()))?;
+
+        // Magic, payload length and the message type, in native byte order
+        let mut header = [0; 14];
+        stream.read_exact(&mut header)?;
+        if &header[..6] != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "reply isn't an IPC message",
+            ));
+        }
+        let length = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]);
+        let mut payload = vec![0; length as usize];
+        stream.read_exact(&mut payload)?;
+        Ok(String::from_utf8_lossy(&payload).into_owned())
+    }
+}
+
+impl Action for SwayCommandAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        log::debug!("Execute {}", self.describe());
+
+        // The same config may run outside of sway, where this only logs
+        let path = std::env::var("SWAYSOCK")
+            .map_err(|_| ActionError("SWAYSOCK isn't set, is sway running?".to_string()))?;
+        let reply = self
+            .send(&path)
+            .map_err(|e| ActionError(format!("Can't reach sway at {}: {}", path, e)))?;
+        log::debug!("Sway replied {}", reply);
+
+        check(&reply)
+            .map_err(|e| ActionError(format!("Sway failed to run {:?}: {}", self.command, e)))
+    }
+
+    fn describe(&self) -> String {
+        format!("sway command {:?}", self.command)
+    }
+}
+
+/// Whether every command in the reply succeeded, with the errors otherwise
+fn check(reply: &str) -> Result<(), String> {
+    let outcomes: Vec<Outcome> =
+        serde_json::from_str(reply).map_err(|e| format!("Unexpected reply {:?}: {}", reply, e))?;
+    let errors: Vec<_> = outcomes
+        .into_iter()
+        .filter(|o| !o.success)
+        .map(|o| o.error.unwrap_or_else(|| "unknown error".to_string()))
+        .collect();
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join(", ")),
+    }
+}
+
+fn message(kind: u32, payload: &[u8]) -> Vec<u8> {
+    let mut message = MAGIC.to_vec();
+    message.extend((payload.len() as u32).to_ne_bytes());
+    message.extend(kind.to_ne_bytes());
+    message.extend(payload);
+    message
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check, message, SwayCommandAction, RUN_COMMAND};
+
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn runs_command() {
+        let path = std::env::temp_dir().join(format!("wzmach-sway-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let sway = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = vec![0; 14 + "workspace next".len()];
+            stream.read_exact(&mut received).unwrap();
+            let reply = br#"[ { "success": false, "error": "No workspace" } ]"#;
+            stream.write_all(&message(RUN_COMMAND, reply)).unwrap();
+            received
+        });
+
+        let action = SwayCommandAction {
+            command: "workspace next".to_string(),
+        };
+        let reply = action.send(path.to_str().unwrap()).unwrap();
+        let received = sway.join().unwrap();
+        assert!(received.starts_with(b"i3-ipc"));
+        assert_eq!(received, message(RUN_COMMAND, b"workspace next"));
+        assert!(reply.contains("No workspace"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn checks_reply() {
+        assert_eq!(check(r#"[{"success": true}, {"success":true}]"#), Ok(()));
+        assert_eq!(
+            check(
+                r#"[ { "success": true }, { "success": false, "parse_error": true, "error": "Unknown/invalid command 'worksp'" } ]"#
+            ),
+            Err("Unknown/invalid command 'worksp'".to_string())
+        );
+        // Not fooled by the text of an error, or by other replies
+        assert_eq!(
+            check(r#"[{"success": false, "error": "\"success\": true"}]"#),
+            Err(r#""success": true"#.to_string())
+        );
+        assert!(check(r#"{"success": true}"#).is_err());
+        assert!(check("").is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use super::{Action, ActionError, GestureContext};
+
+/// Executes the first action, then the second one the next time, and so on.
+/// A continuous trigger follows the gesture with one of them, and switches
+/// when the gesture ends
+pub struct ToggleAction {
+    pub first: Box<dyn Action>,
+    pub second: Box<dyn Action>,
+    /// Whether the second action is the one to execute next
+    pub flipped: bool,
+}
+
+impl ToggleAction {
+    fn current(&mut self) -> &mut dyn Action {
+        if self.flipped {
+            self.second.as_mut()
+        } else {
+            self.first.as_mut()
+        }
+    }
+}
+
+impl Action for ToggleAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        let result = self.current().execute();
+        self.flipped = !self.flipped;
+        result
+    }
+
+    fn begin(&mut self) -> Result<(), ActionError> {
+        self.current().begin()
+    }
+
+    fn update(&mut self, delta: f64) -> Result<(), ActionError> {
+        self.current().update(delta)
+    }
+
+    fn motion(&mut self, dx: f64, dy: f64) -> Result<(), ActionError> {
+        self.current().motion(dx, dy)
+    }
+
+    fn context(&mut self, context: &GestureContext) {
+        self.first.context(context);
+        self.second.context(context);
+    }
+
+    fn end(&mut self) -> Result<(), ActionError> {
+        let result = self.current().end();
+        self.flipped = !self.flipped;
+        result
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{}, and {} the other time",
+            self.first.describe(),
+            self.second.describe()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ToggleAction;
+    use crate::action::{Action, ActionError};
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Record(Rc<RefCell<Vec<usize>>>, usize);
+    impl Action for Record {
+        fn execute(&mut self) -> Result<(), ActionError> {
+            self.0.borrow_mut().push(self.1);
+            Ok(())
+        }
+
+        fn end(&mut self) -> Result<(), ActionError> {
+            self.0.borrow_mut().push(self.1 + 10);
+            Ok(())
+        }
+
+        fn describe(&self) -> String {
+            format!("record {}", self.1)
+        }
+    }
+
+    #[test]
+    fn alternates() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut action = ToggleAction {
+            first: Box::new(Record(log.clone(), 0)),
+            second: Box::new(Record(log.clone(), 1)),
+            flipped: false,
+        };
+        action.execute().unwrap();
+        action.execute().unwrap();
+        action.execute().unwrap();
+        assert_eq!(*log.borrow(), vec![0, 1, 0]);
+
+        // A followed gesture ends with the same action, and switches after
+        log.borrow_mut().clear();
+        action.begin().unwrap();
+        action.end().unwrap();
+        action.execute().unwrap();
+        assert_eq!(*log.borrow(), vec![11, 0]);
+    }
+}
@@ -0,0 +1,67 @@
+use super::command_action::DetachExt;
+use super::{Action, ActionError};
+
+/// Type any text, which key presses can't express without knowing the
+/// keyboard layout. The text goes through `xdotool type` in X11 and `wtype`
+/// in wayland, which both handle any Unicode and type newlines as Return
+pub struct TypeTextAction {
+    pub text: String,
+    pub is_wayland: bool,
+}
+
+impl TypeTextAction {
+    fn command(&self) -> std::process::Command {
+        let mut command = if self.is_wayland {
+            std::process::Command::new("wtype")
+        } else {
+            let mut xdotool = std::process::Command::new("xdotool");
+            xdotool.arg("type");
+            xdotool
+        };
+        // The text may start with a dash
+        command.arg("--").arg(&self.text);
+        command
+    }
+}
+
+impl Action for TypeTextAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        log::debug!("Execute typing {:?}", self.text);
+
+        self.command()
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::inherit())
+            .stderr(std::process::Stdio::inherit())
+            .detach()?;
+        log::trace!("Spawned the typing command");
+
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("type {:?}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TypeTextAction;
+
+    #[test]
+    fn typing_command() {
+        let text = "-👩‍💻 — done\n";
+        let command = |is_wayland| {
+            TypeTextAction {
+                text: text.to_string(),
+                is_wayland,
+            }
+            .command()
+        };
+        let x11 = command(false);
+        assert_eq!(x11.get_program(), "xdotool");
+        assert_eq!(x11.get_args().collect::<Vec<_>>(), ["type", "--", text]);
+        let wayland = command(true);
+        assert_eq!(wayland.get_program(), "wtype");
+        assert_eq!(wayland.get_args().collect::<Vec<_>>(), ["--", text]);
+    }
+}
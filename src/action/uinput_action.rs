@@ -1,43 +1,191 @@
-use super::{Action, ActionError};
-use uinput::event::keyboard::Key;
+use super::{Action, ActionError, InputDevice, KeyboardInputAction, VirtualDevice};
+use crate::common::{ButtonAction, DeviceId, MouseButtonKind};
+use uinput::event::controller::Mouse;
+use uinput::event::relative::{Position, Wheel};
 
-pub struct KeyboardInputAction {
-    pub device: std::rc::Rc<std::cell::RefCell<uinput::Device>>,
-    pub modifiers: Vec<Key>,
-    pub sequence: Vec<Key>,
+pub struct MouseScrollAction {
+    pub device: InputDevice,
+    /// Positive scrolls right
+    pub horizontal: i32,
+    /// Positive scrolls up
+    pub vertical: i32,
+}
+
+pub struct MouseButtonAction {
+    pub device: InputDevice,
+    pub button: Mouse,
+    pub action: ButtonAction,
+    /// Motion not sent yet, as the pointer moves by whole units
+    pub remainder: (f64, f64),
+}
+
+pub struct MouseMoveAction {
+    pub device: InputDevice,
+    /// Pixels to move by each time it executes. Positive is right and down
+    pub dx: i32,
+    pub dy: i32,
+    /// Move along with the fingers of a continuous trigger, by their motion
+    /// times this
+    pub speed: Option<f64>,
+    /// Motion not sent yet, as the pointer moves by whole units
+    pub remainder: (f64, f64),
+}
+
+/// Add the motion to what's left over, and take the whole units out of it
+fn whole_units(remainder: &mut (f64, f64), dx: f64, dy: f64) -> (i32, i32) {
+    let (x, y) = (remainder.0 + dx, remainder.1 + dy);
+    let (whole_x, whole_y) = (x.trunc(), y.trunc());
+    *remainder = (x - whole_x, y - whole_y);
+    (whole_x as i32, whole_y as i32)
+}
+
+impl MouseButtonAction {
+    pub fn button(kind: MouseButtonKind) -> Mouse {
+        match kind {
+            MouseButtonKind::Left => Mouse::Left,
+            MouseButtonKind::Right => Mouse::Right,
+            MouseButtonKind::Middle => Mouse::Middle,
+        }
+    }
 }
 
 impl KeyboardInputAction {
-    pub fn default_device() -> std::rc::Rc<std::cell::RefCell<uinput::Device>> {
-        let device = uinput::open("/dev/uinput")
-            .unwrap()
-            .name("wzmach_virtual")
-            .unwrap()
-            .event(uinput::event::Keyboard::All)
-            .unwrap()
-            .create()
-            .unwrap();
+    /// Fails without access to `/dev/uinput`
+    pub fn default_device(id: &DeviceId) -> Result<InputDevice, uinput::Error> {
+        // libinput only treats a device as a pointer (and so accepts its
+        // wheel events) if it can also move and click
+        let device = uinput::open("/dev/uinput")?
+            .name(&id.name)?
+            .vendor(id.vendor)
+            .product(id.product)
+            .event(uinput::event::Keyboard::All)?
+            .event(Mouse::Left)?
+            .event(Mouse::Right)?
+            .event(Mouse::Middle)?
+            .event(Position::X)?
+            .event(Position::Y)?
+            .event(Wheel::Vertical)?
+            .event(Wheel::Horizontal)?
+            .create()?;
         log::debug!("Created uinput device");
-        std::rc::Rc::new(std::cell::RefCell::new(device))
+        let mut virtual_device = VirtualDevice::new(id.name.clone());
+        virtual_device.set_uinput(device);
+        Ok(std::rc::Rc::new(std::cell::RefCell::new(virtual_device)))
     }
 }
 
-impl Action for KeyboardInputAction {
+impl Action for MouseScrollAction {
     fn execute(&mut self) -> Result<(), ActionError> {
         let mut device = self.device.borrow_mut();
-        log::debug!("Execute action {:?} + {:?}", self.modifiers, self.sequence);
-        for modifier in &self.modifiers {
-            device.press(modifier)?;
+        log::debug!("Execute scroll {} {}", self.horizontal, self.vertical);
+        if self.horizontal != 0 {
+            device.scroll(&Wheel::Horizontal, self.horizontal)?;
         }
-        for key in &self.sequence {
-            device.click(key)?;
+        if self.vertical != 0 {
+            device.scroll(&Wheel::Vertical, self.vertical)?;
         }
-        for modifier in self.modifiers.iter().rev() {
-            device.release(modifier)?;
+        device.synchronize()?;
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "scroll by {} horizontally, {} vertically",
+            self.horizontal, self.vertical
+        )
+    }
+}
+
+impl Action for MouseButtonAction {
+    /// Drags only act while following a gesture
+    fn execute(&mut self) -> Result<(), ActionError> {
+        let mut device = self.device.borrow_mut();
+        log::debug!("Execute {:?} {:?}", self.action, self.button);
+        match self.action {
+            ButtonAction::Click => {
+                device.press_button(self.button)?;
+                device.release_button(self.button)?;
+            }
+            ButtonAction::Press => device.press_button(self.button)?,
+            ButtonAction::Release => device.release_button(self.button)?,
+            ButtonAction::Drag => return Ok(()),
+        }
+        device.synchronize()?;
+        Ok(())
+    }
+
+    fn begin(&mut self) -> Result<(), ActionError> {
+        if self.action != ButtonAction::Drag {
+            return Ok(());
         }
+        let mut device = self.device.borrow_mut();
+        device.press_button(self.button)?;
         device.synchronize()?;
         Ok(())
     }
+
+    fn motion(&mut self, dx: f64, dy: f64) -> Result<(), ActionError> {
+        if self.action != ButtonAction::Drag {
+            return Ok(());
+        }
+        let (x, y) = whole_units(&mut self.remainder, dx, dy);
+        let mut device = self.device.borrow_mut();
+        device.move_by(x, y)?;
+        device.synchronize()?;
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), ActionError> {
+        if self.action != ButtonAction::Drag {
+            return Ok(());
+        }
+        self.remainder = (0.0, 0.0);
+        let mut device = self.device.borrow_mut();
+        device.release_button(self.button)?;
+        device.synchronize()?;
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("{:?} {:?} mouse button", self.action, self.button).to_lowercase()
+    }
+}
+
+impl Action for MouseMoveAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        log::debug!("Execute move by {} {}", self.dx, self.dy);
+        let mut device = self.device.borrow_mut();
+        device.move_by(self.dx, self.dy)?;
+        device.synchronize()?;
+        Ok(())
+    }
+
+    fn motion(&mut self, dx: f64, dy: f64) -> Result<(), ActionError> {
+        let speed = match self.speed {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+        let (x, y) = whole_units(&mut self.remainder, dx * speed, dy * speed);
+        let mut device = self.device.borrow_mut();
+        device.move_by(x, y)?;
+        device.synchronize()?;
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), ActionError> {
+        self.remainder = (0.0, 0.0);
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        match self.speed {
+            None => format!("move the pointer by {}, {}", self.dx, self.dy),
+            Some(speed) => format!(
+                "move the pointer by {}, {}, and with the fingers {} times as far",
+                self.dx, self.dy, speed
+            ),
+        }
+    }
 }
 
 impl From<uinput::Error> for ActionError {
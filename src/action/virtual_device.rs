@@ -0,0 +1,341 @@
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use std::time::Instant;
+
+use super::{ActionError, WaylandKeyboard};
+use crate::config::{self, Key};
+use thiserror::Error;
+#[cfg(feature = "uinput")]
+use uinput::event::controller::{Controller, Mouse};
+#[cfg(feature = "uinput")]
+use uinput::event::relative::{Position, Wheel};
+
+#[derive(Error, Debug)]
+pub enum DeviceError {
+    #[cfg(feature = "uinput")]
+    #[error(transparent)]
+    Uinput(#[from] uinput::Error),
+    #[error("Wayland virtual keyboard: {0}")]
+    Wayland(#[from] io::Error),
+    #[error("No uinput device to send {0} with")]
+    NoUinput(&'static str),
+}
+
+/// Codes of the keys sent in place of a device, with whether they went down
+pub type MockKeys = Rc<RefCell<Vec<(u32, bool)>>>;
+
+/// The uinput device and the wayland virtual keyboard, either of which can be
+/// missing, tracking the keys and buttons they hold down so that none of them
+/// stay pressed after wzmach exits
+pub struct VirtualDevice {
+    #[cfg(feature = "uinput")]
+    device: Option<uinput::Device>,
+    name: String,
+    pressed: Vec<Key>,
+    #[cfg(feature = "uinput")]
+    buttons: Vec<Mouse>,
+    /// Keys to release at a time, in the order they were pressed
+    holds: Vec<(Instant, Vec<Key>)>,
+    /// Keys to release when the next gesture begins, in the order they were
+    /// pressed
+    latched: Vec<Key>,
+    /// Keys go to this instead of the uinput device, when there is one
+    keyboard: Option<WaylandKeyboard>,
+    /// Keys go to this instead of either, in tests
+    mock: Option<MockKeys>,
+}
+
+impl VirtualDevice {
+    /// Sends nothing until it's given the uinput device or the keyboard
+    pub fn new(name: String) -> Self {
+        VirtualDevice {
+            #[cfg(feature = "uinput")]
+            device: None,
+            name,
+            pressed: Vec::new(),
+            #[cfg(feature = "uinput")]
+            buttons: Vec::new(),
+            holds: Vec::new(),
+            latched: Vec::new(),
+            keyboard: None,
+            mock: None,
+        }
+    }
+
+    /// Keep the keys in what's returned instead of sending them, to test
+    /// which keys actions press
+    pub fn mock_keys(&mut self) -> MockKeys {
+        let keys = MockKeys::default();
+        self.mock = Some(keys.clone());
+        keys
+    }
+
+    /// Send keys and mouse input on this device from now on
+    #[cfg(feature = "uinput")]
+    pub fn set_uinput(&mut self, device: uinput::Device) {
+        self.device = Some(device);
+    }
+
+    /// Send keys through the compositor from now on. Mouse input stays on the
+    /// uinput device
+    pub fn set_keyboard(&mut self, keyboard: WaylandKeyboard) {
+        self.keyboard = Some(keyboard);
+    }
+
+    /// Name it was created with, which libinput shows it by
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn key(&mut self, key: &Key, pressed: bool) -> Result<(), DeviceError> {
+        if let Some(mock) = &self.mock {
+            mock.borrow_mut().push((config::key_code(key), pressed));
+            return Ok(());
+        }
+        match &mut self.keyboard {
+            Some(keyboard) => keyboard.key(config::key_code(key), pressed)?,
+            #[cfg(feature = "uinput")]
+            None if pressed => self.uinput("keys")?.press(key)?,
+            #[cfg(feature = "uinput")]
+            None => self.uinput("keys")?.release(key)?,
+            #[cfg(not(feature = "uinput"))]
+            None => return Err(DeviceError::NoUinput("keys")),
+        }
+        Ok(())
+    }
+
+    pub fn press(&mut self, key: &Key) -> Result<(), DeviceError> {
+        self.key(key, true)?;
+        if !self.pressed.contains(key) {
+            self.pressed.push(*key);
+        }
+        Ok(())
+    }
+
+    pub fn release(&mut self, key: &Key) -> Result<(), DeviceError> {
+        self.key(key, false)?;
+        self.pressed.retain(|k| k != key);
+        Ok(())
+    }
+
+    /// Send the events so far. The keys the compositor refused before are
+    /// heard of, without waiting for it
+    pub fn synchronize(&mut self) -> Result<(), DeviceError> {
+        #[cfg(feature = "uinput")]
+        if let Some(device) = &mut self.device {
+            device.synchronize()?;
+        }
+        if let Some(keyboard) = &mut self.keyboard {
+            keyboard.check()?;
+        }
+        Ok(())
+    }
+
+    /// Press the keys and keep them down until `release_due` is called at the
+    /// time. Keys already held this way are held until then instead
+    pub fn hold(&mut self, keys: &[Key], until: Instant) -> Result<(), DeviceError> {
+        for (_, held) in &mut self.holds {
+            held.retain(|k| !keys.contains(k));
+        }
+        self.holds.retain(|(_, held)| !held.is_empty());
+        for key in keys {
+            self.press(key)?;
+        }
+        self.holds.push((until, keys.to_vec()));
+        self.synchronize()
+    }
+
+    /// When the next held key is to be released
+    pub fn next_release(&self) -> Option<Instant> {
+        self.holds.iter().map(|(until, _)| *until).min()
+    }
+
+    /// Release the keys whose hold is over by now, in the reverse order of
+    /// their presses
+    pub fn release_due(&mut self, now: Instant) -> Result<(), DeviceError> {
+        let (due, holds) = std::mem::take(&mut self.holds)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(until, _)| *until <= now);
+        self.holds = holds;
+        if due.is_empty() {
+            return Ok(());
+        }
+        for key in due.iter().flat_map(|(_, keys)| keys.iter().rev()) {
+            self.release(key)?;
+        }
+        self.synchronize()
+    }
+
+    /// Press the keys and keep them down until `release_latched`, which is
+    /// called when the next gesture begins. Keys latched already stay down
+    /// without another press
+    pub fn latch(&mut self, keys: &[Key]) -> Result<(), DeviceError> {
+        for key in keys {
+            if self.latched.contains(key) {
+                continue;
+            }
+            self.press(key)?;
+            self.latched.push(*key);
+        }
+        self.synchronize()
+    }
+
+    /// Release the keys pressed by `latch`, newest first
+    pub fn release_latched(&mut self) -> Result<(), DeviceError> {
+        if self.latched.is_empty() {
+            return Ok(());
+        }
+        while let Some(key) = self.latched.pop() {
+            self.release(&key)?;
+        }
+        self.synchronize()
+    }
+
+    /// Release every key and button still held down, newest first
+    pub fn release_all(&mut self) -> Result<(), DeviceError> {
+        self.holds.clear();
+        self.latched.clear();
+        while let Some(key) = self.pressed.last().copied() {
+            log::debug!("Releasing held {:?}", key);
+            self.release(&key)?;
+        }
+        #[cfg(feature = "uinput")]
+        while let Some(button) = self.buttons.last().copied() {
+            log::debug!("Releasing held {:?}", button);
+            self.release_button(button)?;
+        }
+        self.synchronize()
+    }
+}
+
+/// Mouse input, which only uinput can send
+#[cfg(feature = "uinput")]
+impl VirtualDevice {
+    fn uinput(&mut self, what: &'static str) -> Result<&mut uinput::Device, DeviceError> {
+        self.device.as_mut().ok_or(DeviceError::NoUinput(what))
+    }
+
+    pub fn press_button(&mut self, button: Mouse) -> Result<(), DeviceError> {
+        self.uinput("mouse buttons")?
+            .press(&Controller::Mouse(button))?;
+        if !self.buttons.contains(&button) {
+            self.buttons.push(button);
+        }
+        Ok(())
+    }
+
+    pub fn release_button(&mut self, button: Mouse) -> Result<(), DeviceError> {
+        self.uinput("mouse buttons")?
+            .release(&Controller::Mouse(button))?;
+        self.buttons.retain(|b| *b != button);
+        Ok(())
+    }
+
+    pub fn scroll(&mut self, wheel: &Wheel, value: i32) -> Result<(), DeviceError> {
+        Ok(self.uinput("scrolling")?.position(wheel, value)?)
+    }
+
+    /// Move the pointer
+    pub fn move_by(&mut self, dx: i32, dy: i32) -> Result<(), DeviceError> {
+        let device = self.uinput("pointer motion")?;
+        if dx != 0 {
+            device.position(&Position::X, dx)?;
+        }
+        if dy != 0 {
+            device.position(&Position::Y, dy)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for VirtualDevice {
+    fn drop(&mut self) {
+        if let Err(e) = self.release_all() {
+            log::error!("Can't release held keys: {}", e);
+        }
+    }
+}
+
+impl From<DeviceError> for ActionError {
+    fn from(err: DeviceError) -> ActionError {
+        ActionError(format!("{}", err))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DeviceError, VirtualDevice};
+    use crate::config::{key_code, ConfigKey};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn without_devices() {
+        let mut device = VirtualDevice::new("test".to_string());
+        let key = ConfigKey::from_name("LeftShift").unwrap().0;
+        assert!(matches!(
+            device.press(&key),
+            Err(DeviceError::NoUinput("keys"))
+        ));
+        // Nothing to release after exiting
+        assert!(device.pressed.is_empty());
+        #[cfg(feature = "uinput")]
+        assert!(matches!(
+            device.move_by(1, 0),
+            Err(DeviceError::NoUinput("pointer motion"))
+        ));
+        device.synchronize().unwrap();
+    }
+
+    #[test]
+    fn holds() {
+        let mut device = VirtualDevice::new("test".to_string());
+        let keys = device.mock_keys();
+        let ctrl = ConfigKey::from_name("LeftControl").unwrap().0;
+        let a = ConfigKey::from_name("A").unwrap().0;
+        let b = ConfigKey::from_name("B").unwrap().0;
+        let now = Instant::now();
+        let later = |ms| now + Duration::from_millis(ms);
+        device.hold(&[ctrl, a], later(100)).unwrap();
+        device.hold(&[b], later(50)).unwrap();
+        assert_eq!(device.next_release(), Some(later(50)));
+        // Held again, the key is held until the later time
+        device.hold(&[a], later(200)).unwrap();
+        device.release_due(later(60)).unwrap();
+        device.release_due(later(150)).unwrap();
+        assert_eq!(device.next_release(), Some(later(200)));
+        device.release_due(later(200)).unwrap();
+        assert_eq!(device.next_release(), None);
+
+        let (ctrl, a, b) = (key_code(&ctrl), key_code(&a), key_code(&b));
+        assert_eq!(
+            *keys.borrow(),
+            [
+                (ctrl, true),
+                (a, true),
+                (b, true),
+                (a, true),
+                (b, false),
+                (ctrl, false),
+                (a, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn latches_once() {
+        let mut device = VirtualDevice::new("test".to_string());
+        let keys = device.mock_keys();
+        let shift = ConfigKey::from_name("LeftShift").unwrap().0;
+        let ctrl = ConfigKey::from_name("LeftControl").unwrap().0;
+        device.latch(&[shift]).unwrap();
+        device.latch(&[ctrl, shift]).unwrap();
+        device.release_latched().unwrap();
+        device.release_latched().unwrap();
+        let (shift, ctrl) = (key_code(&shift), key_code(&ctrl));
+        assert_eq!(
+            *keys.borrow(),
+            [(shift, true), (ctrl, true), (ctrl, false), (shift, false)]
+        );
+    }
+}
@@ -0,0 +1,597 @@
+//! Keys sent through the compositor instead of uinput, with the wayland
+//! protocol `zwp_virtual_keyboard_v1`
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::fd::{AsFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use nix::sys::memfd::{memfd_create, MemFdCreateFlag};
+use wayland_client::backend::WaylandError;
+use wayland_client::globals::{registry_queue_init, BindError, GlobalListContents};
+use wayland_client::protocol::wl_keyboard::{self, KeymapFormat, WlKeyboard};
+use wayland_client::protocol::wl_registry::WlRegistry;
+use wayland_client::protocol::wl_seat::{self, Capability, WlSeat};
+use wayland_client::{delegate_noop, Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1;
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1;
+
+/// A US layout, which the compositor compiles from its own XKB data, for seats
+/// without a keyboard to take the keymap of. Its key codes are the kernel's,
+/// which are also what keys are sent by
+const KEYMAP: &str = "xkb_keymap {
+    xkb_keycodes { include \"evdev\" };
+    xkb_types { include \"complete\" };
+    xkb_compat { include \"complete\" };
+    xkb_symbols { include \"pc+us+inet(evdev)\" };
+};
+";
+
+/// Kernel codes of the modifier keys, and their bits in the keymap: Shift,
+/// Control, Mod1 for Alt and Mod4 for Meta
+const MODIFIERS: &[(u32, u32)] = &[
+    (42, 1),
+    (54, 1),
+    (29, 4),
+    (97, 4),
+    (56, 8),
+    (100, 8),
+    (125, 64),
+    (126, 64),
+];
+
+/// A virtual keyboard on the first seat of the compositor. Keys are sent
+/// without waiting for the compositor, which reports errors later, and after
+/// the connection is lost the next key connects again
+pub struct WaylandKeyboard {
+    path: PathBuf,
+    connection: Connected,
+    start: Instant,
+    /// Codes of the modifier keys held down
+    modifiers: Vec<u32>,
+}
+
+/// The objects on a connection to the compositor
+struct Connected {
+    conn: Connection,
+    queue: EventQueue<State>,
+    state: State,
+    keyboard: ZwpVirtualKeyboardV1,
+}
+
+/// What the compositor told about the seat
+#[derive(Default)]
+struct State {
+    capabilities: Option<Capability>,
+    /// The keymap of the seat's keyboard and its size
+    keymap: Option<(OwnedFd, u32)>,
+}
+
+impl WaylandKeyboard {
+    /// Connect to the compositor at `$WAYLAND_DISPLAY` and create the
+    /// keyboard, with the keymap of the seat's own keyboard. Fails if the
+    /// compositor doesn't have the protocol
+    pub fn connect() -> io::Result<Self> {
+        let display = std::env::var_os("WAYLAND_DISPLAY").unwrap_or_else(|| "wayland-0".into());
+        let path = match std::env::var_os("XDG_RUNTIME_DIR") {
+            Some(runtime) => PathBuf::from(runtime).join(display),
+            None => PathBuf::from(display),
+        };
+        Self::connect_to(path)
+    }
+
+    fn connect_to(path: PathBuf) -> io::Result<Self> {
+        let connection = Connected::new(UnixStream::connect(&path)?)?;
+        Ok(WaylandKeyboard {
+            path,
+            connection,
+            start: Instant::now(),
+            modifiers: Vec::new(),
+        })
+    }
+
+    /// Press or release the key with this kernel code. A connection found
+    /// lost is made again, once
+    pub fn key(&mut self, code: u32, pressed: bool) -> io::Result<()> {
+        if MODIFIERS.iter().any(|(c, _)| *c == code) {
+            self.modifiers.retain(|c| *c != code);
+            if pressed {
+                self.modifiers.push(code);
+            }
+        }
+        match self.send(code, pressed) {
+            Err(e) if self.connection.lost() => {
+                log::warn!("Lost the wayland virtual keyboard, connecting again: {}", e);
+                self.connection = Connected::new(UnixStream::connect(&self.path)?)?;
+                self.send(code, pressed)
+            }
+            result => result,
+        }
+    }
+
+    fn send(&mut self, code: u32, pressed: bool) -> io::Result<()> {
+        let time = self.start.elapsed().as_millis() as u32;
+        let keyboard = &self.connection.keyboard;
+        keyboard.key(time, code, pressed.into());
+        if MODIFIERS.iter().any(|(c, _)| *c == code) {
+            keyboard.modifiers(modifier_mask(&self.modifiers), 0, 0, 0);
+        }
+        self.connection.flush()
+    }
+
+    /// Take the events that came without waiting for more, which is how the
+    /// compositor reports the keys it refused
+    pub fn check(&mut self) -> io::Result<()> {
+        self.connection.check()
+    }
+}
+
+impl Connected {
+    fn new(socket: UnixStream) -> io::Result<Self> {
+        let conn = Connection::from_socket(socket).map_err(io::Error::other)?;
+        let (globals, mut queue) = registry_queue_init::<State>(&conn).map_err(io::Error::other)?;
+        let handle = queue.handle();
+        let bind_error = |e| match e {
+            BindError::NotPresent => io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "the compositor has no {}",
+                    ZwpVirtualKeyboardManagerV1::interface().name
+                ),
+            ),
+            e => io::Error::other(e),
+        };
+        let manager: ZwpVirtualKeyboardManagerV1 =
+            globals.bind(&handle, 1..=1, ()).map_err(bind_error)?;
+        let seat: WlSeat = globals
+            .bind(&handle, 1..=5, ())
+            .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "no seat"))?;
+
+        // Keys sent by the kernel codes mean the same as on the keyboard when
+        // the virtual one has its keymap
+        let mut state = State::default();
+        queue.roundtrip(&mut state).map_err(io::Error::other)?;
+        let has_keyboard = state
+            .capabilities
+            .is_some_and(|c| c.contains(Capability::Keyboard));
+        if has_keyboard {
+            let keyboard = seat.get_keyboard(&handle, ());
+            queue.roundtrip(&mut state).map_err(io::Error::other)?;
+            if keyboard.version() >= 3 {
+                keyboard.release();
+            }
+        }
+        let keymap = match state.keymap.take() {
+            Some(keymap) => keymap,
+            None => {
+                log::debug!("The seat has no keyboard, using a US keymap");
+                default_keymap()?
+            }
+        };
+
+        let keyboard = manager.create_virtual_keyboard(&seat, &handle, ());
+        keyboard.keymap(KeymapFormat::XkbV1.into(), keymap.0.as_fd(), keymap.1);
+        // Errors of creating the keyboard, like not being allowed to, are told
+        // as the keyboard is made instead of at the first key
+        queue.roundtrip(&mut state).map_err(io::Error::other)?;
+        Ok(Connected {
+            conn,
+            queue,
+            state,
+            keyboard,
+        })
+    }
+
+    /// Whether the compositor can't be reached any more on this connection
+    fn lost(&self) -> bool {
+        self.conn.backend().last_error().is_some()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.conn.flush() {
+            // Sent with the next flush
+            Err(WaylandError::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            result => result.map_err(io::Error::other),
+        }
+    }
+
+    fn check(&mut self) -> io::Result<()> {
+        self.flush()?;
+        if let Some(guard) = self.conn.prepare_read() {
+            match guard.read() {
+                Err(WaylandError::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => (),
+                result => {
+                    result.map_err(io::Error::other)?;
+                }
+            }
+        }
+        self.queue
+            .dispatch_pending(&mut self.state)
+            .map_err(io::Error::other)?;
+        Ok(())
+    }
+}
+
+/// `KEYMAP` in a file, which is how keymaps are passed to the compositor
+fn default_keymap() -> io::Result<(OwnedFd, u32)> {
+    let fd = memfd_create(c"wzmach-keymap", MemFdCreateFlag::MFD_CLOEXEC)?;
+    let mut file = File::from(unsafe { <OwnedFd as std::os::fd::FromRawFd>::from_raw_fd(fd) });
+    file.write_all(KEYMAP.as_bytes())?;
+    file.write_all(&[0])?;
+    Ok((file.into(), KEYMAP.len() as u32 + 1))
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlSeat, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &WlSeat,
+        event: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_seat::Event::Capabilities {
+            capabilities: WEnum::Value(capabilities),
+        } = event
+        {
+            state.capabilities = Some(capabilities);
+        }
+    }
+}
+
+impl Dispatch<WlKeyboard, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &WlKeyboard,
+        event: wl_keyboard::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_keyboard::Event::Keymap {
+            format: WEnum::Value(KeymapFormat::XkbV1),
+            fd,
+            size,
+        } = event
+        {
+            state.keymap = Some((fd, size));
+        }
+    }
+}
+
+delegate_noop!(State: ZwpVirtualKeyboardManagerV1);
+delegate_noop!(State: ZwpVirtualKeyboardV1);
+
+/// Bits of the held modifier keys, which X11 has the same as the keymap
+pub(super) fn modifier_mask(held: &[u32]) -> u32 {
+    MODIFIERS
+        .iter()
+        .filter(|(code, _)| held.contains(code))
+        .fold(0, |mask, (_, bit)| mask | bit)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::HashMap;
+    use std::io::{IoSlice, Read};
+    use std::os::fd::AsRawFd;
+    use std::os::unix::net::UnixListener;
+
+    use nix::sys::socket::{sendmsg, ControlMessage, MsgFlags, UnixAddr};
+
+    /// A request or an event: the object it's for, which of its requests or
+    /// events it is, and the arguments, in native byte order
+    #[derive(PartialEq, Debug)]
+    struct Message {
+        object: u32,
+        opcode: u16,
+        args: Vec<u8>,
+    }
+
+    impl Message {
+        fn new(object: u32, opcode: u16) -> Self {
+            Message {
+                object,
+                opcode,
+                args: Vec::new(),
+            }
+        }
+
+        fn uint(mut self, value: u32) -> Self {
+            self.args.extend(value.to_ne_bytes());
+            self
+        }
+
+        /// Length with the nul, the bytes with the nul, and padding to 4 bytes
+        fn string(mut self, value: &str) -> Self {
+            self = self.uint(value.len() as u32 + 1);
+            self.args.extend(value.as_bytes());
+            self.args.push(0);
+            while !self.args.len().is_multiple_of(4) {
+                self.args.push(0);
+            }
+            self
+        }
+
+        fn to_bytes(&self) -> Vec<u8> {
+            let size = 8 + self.args.len() as u32;
+            let mut bytes = self.object.to_ne_bytes().to_vec();
+            bytes.extend((size << 16 | u32::from(self.opcode)).to_ne_bytes());
+            bytes.extend(&self.args);
+            bytes
+        }
+
+        /// The message at the start of the bytes, and its length, once all of
+        /// it is there
+        fn parse(bytes: &[u8]) -> Option<(Message, usize)> {
+            let word =
+                |at: usize| Some(u32::from_ne_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+            let object = word(0)?;
+            let header = word(4)?;
+            let size = (header >> 16) as usize;
+            let args = bytes.get(8..size)?.to_vec();
+            Some((
+                Message {
+                    object,
+                    opcode: header as u16,
+                    args,
+                },
+                size,
+            ))
+        }
+
+        /// The arguments as words, with strings left as their bytes
+        fn words(&self) -> Vec<u32> {
+            self.args
+                .chunks(4)
+                .map(|w| u32::from_ne_bytes(w.try_into().unwrap()))
+                .collect()
+        }
+
+        /// The string argument after `at` words
+        fn string_at(&self, at: usize) -> String {
+            let length = self.words()[at] as usize;
+            let bytes = &self.args[4 * at + 4..4 * at + 4 + length - 1];
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    }
+
+    /// What the fake compositor has: its globals, and the keymap of its seat's
+    /// keyboard if the seat has one. With `hang_up` it closes the first
+    /// connection at the first key, and takes a second one
+    struct Compositor {
+        globals: &'static [(u32, &'static str)],
+        keymap: Option<&'static str>,
+        hang_up: bool,
+    }
+
+    /// Answers requests like the compositor until the client leaves, and
+    /// returns the requests by the interface of their object
+    fn compositor(listener: UnixListener, compositor: Compositor) -> Vec<(String, Message)> {
+        let mut requests = connection(&listener, &compositor, compositor.hang_up);
+        if compositor.hang_up {
+            requests.extend(connection(&listener, &compositor, false));
+        }
+        requests
+    }
+
+    fn connection(
+        listener: &UnixListener,
+        compositor: &Compositor,
+        hang_up: bool,
+    ) -> Vec<(String, Message)> {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut objects = HashMap::from([(1, "wl_display".to_string())]);
+        let mut names = HashMap::new();
+        let mut buffer = Vec::new();
+        let mut requests = Vec::new();
+        loop {
+            while let Some((request, length)) = Message::parse(&buffer) {
+                buffer.drain(..length);
+                let interface = objects[&request.object].clone();
+                let words = request.words();
+                let mut send = |event: Message| stream.write_all(&event.to_bytes()).unwrap();
+                match (interface.as_str(), request.opcode) {
+                    ("wl_display", 0) => {
+                        send(Message::new(words[0], 0).uint(0));
+                    }
+                    ("zwp_virtual_keyboard_v1", 1) if hang_up => {
+                        requests.push((interface, request));
+                        return requests;
+                    }
+                    ("wl_display", 1) => {
+                        objects.insert(words[0], "wl_registry".to_string());
+                        for &(name, interface) in compositor.globals {
+                            names.insert(name, interface);
+                            send(
+                                Message::new(words[0], 0)
+                                    .uint(name)
+                                    .string(interface)
+                                    .uint(1),
+                            );
+                        }
+                    }
+                    ("wl_registry", 0) => {
+                        let bound = request.string_at(1);
+                        let id = *words.last().unwrap();
+                        assert_eq!(names[&words[0]], bound);
+                        if bound == "wl_seat" {
+                            let capabilities = if compositor.keymap.is_some() { 3 } else { 1 };
+                            send(Message::new(id, 0).uint(capabilities));
+                        }
+                        objects.insert(id, bound);
+                    }
+                    ("wl_seat", 1) => {
+                        objects.insert(words[0], "wl_keyboard".to_string());
+                        let keymap = compositor.keymap.unwrap();
+                        let fd = memfd_create(c"test-keymap", MemFdCreateFlag::empty()).unwrap();
+                        nix::unistd::write(fd, keymap.as_bytes()).unwrap();
+                        let event = Message::new(words[0], 0)
+                            .uint(1)
+                            .uint(keymap.len() as u32)
+                            .to_bytes();
+                        sendmsg::<UnixAddr>(
+                            stream.as_raw_fd(),
+                            &[IoSlice::new(&event)],
+                            &[ControlMessage::ScmRights(&[fd])],
+                            MsgFlags::empty(),
+                            None,
+                        )
+                        .unwrap();
+                        nix::unistd::close(fd).unwrap();
+                    }
+                    ("zwp_virtual_keyboard_manager_v1", 0) => {
+                        objects.insert(words[1], "zwp_virtual_keyboard_v1".to_string());
+                    }
+                    _ => (),
+                }
+                requests.push((interface, request));
+            }
+            let mut chunk = [0; 4096];
+            let read = stream.read(&mut chunk).unwrap();
+            if read == 0 {
+                return requests;
+            }
+            buffer.extend(&chunk[..read]);
+        }
+    }
+
+    /// Run the client against the fake compositor, and return the requests
+    /// that the compositor got
+    fn run<T>(
+        name: &str,
+        fake: Compositor,
+        client: impl FnOnce(PathBuf) -> T,
+    ) -> (T, Vec<(String, Message)>) {
+        let path = std::env::temp_dir().join(format!("wzmach-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = std::thread::spawn(move || compositor(listener, fake));
+        let result = client(path.clone());
+        let requests = server.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        (result, requests)
+    }
+
+    /// The requests to the virtual keyboard, by opcode and the words of their
+    /// arguments
+    fn keyboard_requests(requests: &[(String, Message)]) -> Vec<(u16, Vec<u32>)> {
+        requests
+            .iter()
+            .filter(|(interface, _)| interface == "zwp_virtual_keyboard_v1")
+            .map(|(_, r)| (r.opcode, r.words()))
+            .collect()
+    }
+
+    #[test]
+    fn creates_keyboard() {
+        let globals = &[(3, "wl_seat"), (9, "zwp_virtual_keyboard_manager_v1")];
+        let keymap = "xkb_keymap { the user's };";
+        let ((), requests) = run(
+            "wayland",
+            Compositor {
+                globals,
+                keymap: Some(keymap),
+                hang_up: false,
+            },
+            |path| {
+                let mut keyboard = WaylandKeyboard::connect_to(path).unwrap();
+                keyboard.key(29, true).unwrap();
+                keyboard.key(30, true).unwrap();
+                keyboard.check().unwrap();
+                keyboard.connection.conn.roundtrip().unwrap();
+            },
+        );
+        // The manager is bound by the name it was listed with
+        let binds: Vec<u32> = requests
+            .iter()
+            .filter(|(interface, r)| interface == "wl_registry" && r.opcode == 0)
+            .map(|(_, r)| r.words()[0])
+            .collect();
+        assert_eq!(binds, [9, 3]);
+        // The user's keymap is passed on, and Control also sets the modifiers
+        let requests = keyboard_requests(&requests);
+        assert_eq!(requests[0], (0, vec![1, keymap.len() as u32]));
+        let kinds: Vec<u16> = requests.iter().map(|(opcode, _)| *opcode).collect();
+        assert_eq!(kinds, [0, 1, 2, 1]);
+        assert_eq!(requests[1].1[1..], [29, 1]);
+        assert_eq!(requests[2].1, [4, 0, 0, 0]);
+        assert_eq!(requests[3].1[1..], [30, 1]);
+    }
+
+    #[test]
+    fn without_seat_keyboard() {
+        let globals = &[(3, "wl_seat"), (9, "zwp_virtual_keyboard_manager_v1")];
+        let (keyboard, requests) = run(
+            "wayland-us",
+            Compositor {
+                globals,
+                keymap: None,
+                hang_up: false,
+            },
+            |path| WaylandKeyboard::connect_to(path).map(|_| ()),
+        );
+        keyboard.unwrap();
+        // The US keymap, with its nul
+        let requests = keyboard_requests(&requests);
+        assert_eq!(requests, [(0, vec![1, KEYMAP.len() as u32 + 1])]);
+    }
+
+    #[test]
+    fn without_protocol() {
+        let globals = &[(3, "wl_seat")];
+        let (keyboard, _) = run(
+            "wayland-no",
+            Compositor {
+                globals,
+                keymap: None,
+                hang_up: false,
+            },
+            |path| WaylandKeyboard::connect_to(path).map(|_| ()),
+        );
+        assert_eq!(keyboard.unwrap_err().kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn reconnects() {
+        let globals = &[(3, "wl_seat"), (9, "zwp_virtual_keyboard_manager_v1")];
+        let compositor = Compositor {
+            globals,
+            keymap: None,
+            hang_up: true,
+        };
+        let ((), requests) = run("wayland-again", compositor, |path| {
+            let mut keyboard = WaylandKeyboard::connect_to(path).unwrap();
+            keyboard.key(30, true).unwrap();
+            while keyboard.check().is_ok() {}
+            keyboard.key(30, false).unwrap();
+            keyboard.connection.conn.roundtrip().unwrap();
+        });
+        // Made again, and then the next key sent
+        let requests = keyboard_requests(&requests);
+        let kinds: Vec<u16> = requests.iter().map(|(opcode, _)| *opcode).collect();
+        assert_eq!(kinds, [0, 1, 0, 1]);
+        assert_eq!(requests[3].1[1..], [30, 0]);
+    }
+
+    #[test]
+    fn modifiers() {
+        assert_eq!(modifier_mask(&[29, 42, 30]), 5);
+    }
+}
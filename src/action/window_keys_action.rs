@@ -0,0 +1,92 @@
+use super::virtual_keyboard::modifier_mask;
+use super::{Action, ActionError, KeyboardInputAction};
+use crate::common::WindowTarget;
+use crate::config::{key_code, Key};
+use crate::window::X11Windows;
+
+/// X11 key code of the key, which is the kernel's shifted by 8. Keys beyond
+/// the byte have none, and go to the focused window instead
+fn x11_code(key: &Key) -> Option<u8> {
+    u8::try_from(key_code(key) + 8).ok()
+}
+
+/// Send the keys of a `KeyboardInput` to an X11 window, which need not have
+/// the focus. Without such a window the keys go to the focused one, through
+/// the input device
+pub struct WindowKeysAction {
+    pub target: WindowTarget,
+    pub keys: KeyboardInputAction,
+    /// Connected when first executed, and again after errors
+    pub x11: Option<X11Windows>,
+}
+
+impl WindowKeysAction {
+    /// Whether a window was found and got the keys
+    fn send(&mut self) -> Result<bool, ActionError> {
+        let error = |e| ActionError(format!("Can't send keys to an X11 window: {}", e));
+        let x11 = match &mut self.x11 {
+            Some(x11) => x11,
+            None => self.x11.insert(X11Windows::connect().map_err(error)?),
+        };
+        let window = match x11.find(&self.target).map_err(error)? {
+            Some(w) => w,
+            None => return Ok(false),
+        };
+        let mut held = Vec::new();
+        for (key, down) in self.keys.events() {
+            let code = key_code(key);
+            let x11_code = x11_code(key)
+                .ok_or_else(|| ActionError(format!("Key code {} is beyond X11's", code)))?;
+            if !down {
+                held.retain(|c| *c != code);
+            }
+            let state = modifier_mask(&held) as u16;
+            x11.send_key(window, x11_code, state, down).map_err(error)?;
+            if down {
+                held.push(code);
+            }
+            if !self.keys.key_delay.is_zero() {
+                std::thread::sleep(self.keys.key_delay);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl Action for WindowKeysAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        log::debug!("Execute {}", self.describe());
+        match self.send() {
+            Ok(true) => return Ok(()),
+            Ok(false) => log::debug!(
+                "No window like {:?}, sending keys to the focused one",
+                self.target
+            ),
+            Err(e) => {
+                self.x11 = None;
+                log::warn!("{}, sending them to the focused window", e.0);
+            }
+        }
+        self.keys.execute()
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} in the window like {:?}",
+            self.keys.describe(),
+            self.target
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::x11_code;
+    use crate::config::ConfigKey;
+
+    #[test]
+    fn key_codes() {
+        assert_eq!(x11_code(&ConfigKey::from_name("A").unwrap().0), Some(38));
+        assert_eq!(x11_code(&ConfigKey::from_name("F24").unwrap().0), Some(202));
+    }
+}
@@ -0,0 +1,112 @@
+use super::{Action, ActionError, HyprlandDispatchAction, SwayCommandAction};
+use crate::common::Workspace;
+use crate::window::X11Windows;
+
+/// Switch workspaces in whichever compositor or window manager runs: sway or
+/// Hyprland over their IPC in wayland, and `_NET_CURRENT_DESKTOP` in X11
+pub struct WorkspaceSwitchAction {
+    pub direction: Workspace,
+    pub is_wayland: bool,
+}
+
+impl WorkspaceSwitchAction {
+    fn sway_command(&self) -> String {
+        match self.direction {
+            Workspace::Next => "workspace next".to_string(),
+            Workspace::Prev => "workspace prev".to_string(),
+            Workspace::Index(n) => format!("workspace number {}", n),
+        }
+    }
+
+    fn hyprland_args(&self) -> String {
+        match self.direction {
+            Workspace::Next => "e+1".to_string(),
+            Workspace::Prev => "e-1".to_string(),
+            Workspace::Index(n) => n.to_string(),
+        }
+    }
+
+    fn switch_x11(&self) -> Result<(), ActionError> {
+        let error = |e| ActionError(format!("Can't switch desktops in X11: {}", e));
+        let x11 = X11Windows::connect().map_err(error)?;
+        let (current, count) = x11.desktops().map_err(error)?;
+        let desktop = desktop(self.direction, current, count).ok_or_else(|| {
+            ActionError(format!(
+                "No desktop {:?} of {}, from desktop {}",
+                self.direction,
+                count,
+                current + 1
+            ))
+        })?;
+        x11.set_desktop(desktop).map_err(error)
+    }
+}
+
+/// The desktop to go to, counting from 0 like EWMH. Next and previous wrap
+/// around, like in most window managers
+fn desktop(direction: Workspace, current: u32, count: u32) -> Option<u32> {
+    match direction {
+        _ if count == 0 => None,
+        Workspace::Next => Some((current + 1) % count),
+        Workspace::Prev => Some((current + count - 1) % count),
+        Workspace::Index(n) => n.checked_sub(1).filter(|d| *d < count),
+    }
+}
+
+impl Action for WorkspaceSwitchAction {
+    fn execute(&mut self) -> Result<(), ActionError> {
+        log::debug!("Execute {}", self.describe());
+
+        if !self.is_wayland {
+            return self.switch_x11();
+        }
+        if std::env::var_os("SWAYSOCK").is_some() {
+            SwayCommandAction {
+                command: self.sway_command(),
+            }
+            .execute()
+        } else if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+            HyprlandDispatchAction {
+                dispatcher: "workspace".to_string(),
+                args: self.hyprland_args(),
+            }
+            .execute()
+        } else {
+            Err(ActionError(
+                "Can't switch workspaces, as neither sway nor Hyprland is running".to_string(),
+            ))
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("switch to workspace {:?}", self.direction)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{desktop, WorkspaceSwitchAction};
+    use crate::common::Workspace;
+
+    #[test]
+    fn workspace_targets() {
+        let action = |direction| WorkspaceSwitchAction {
+            direction,
+            is_wayland: true,
+        };
+        assert_eq!(action(Workspace::Prev).sway_command(), "workspace prev");
+        assert_eq!(
+            action(Workspace::Index(3)).sway_command(),
+            "workspace number 3"
+        );
+        assert_eq!(action(Workspace::Next).hyprland_args(), "e+1");
+        assert_eq!(action(Workspace::Index(3)).hyprland_args(), "3");
+
+        assert_eq!(desktop(Workspace::Next, 3, 4), Some(0));
+        assert_eq!(desktop(Workspace::Prev, 0, 4), Some(3));
+        assert_eq!(desktop(Workspace::Index(2), 0, 4), Some(1));
+        assert_eq!(desktop(Workspace::Index(5), 0, 4), None);
+        assert_eq!(desktop(Workspace::Index(0), 0, 4), None);
+        assert_eq!(desktop(Workspace::Next, 0, 0), None);
+    }
+}
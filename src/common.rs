@@ -6,25 +6,241 @@ pub enum Direction {
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
 /// In means scale goes 1.0 -> 1.5
 /// Out means scale goes 1.0 -> 0.5
-#[derive(PartialEq, Eq, Debug, Clone, Copy, Deserialize)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize)]
 pub enum PinchDirection {
+    #[default]
     In,
     Out,
 }
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy, Deserialize)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize)]
 pub enum RotateDirection {
+    #[default]
+    #[serde(alias = "CW")]
     Clockwise,
+    #[serde(alias = "Counterclockwise", alias = "CCW")]
     Anticlockwise,
 }
 
+/// Workspace to switch to. Indices count from 1, like the workspace numbers
+/// of sway and Hyprland
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Deserialize)]
+pub enum Workspace {
+    Next,
+    Prev,
+    Index(u32),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Deserialize)]
+pub enum MouseButtonKind {
+    Left,
+    Right,
+    Middle,
+}
+
+/// What to do with a mouse button. `Drag` holds it down while a continuous
+/// trigger follows the gesture, and moves the pointer with the fingers
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize)]
+pub enum ButtonAction {
+    #[default]
+    Click,
+    Press,
+    Release,
+    Drag,
+}
+
+/// When a trigger fires. `Threshold` fires when the gesture has gone far
+/// enough, `Begin` as soon as it starts, with only the kind of gesture and the
+/// fingers known, and `End` when the fingers are lifted after going in the
+/// direction of the trigger, however far
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize)]
+pub enum FireOn {
+    Begin,
+    End,
+    #[default]
+    Threshold,
+}
+
+/// How `KeyboardInput` presses its keys. `Tap` presses and releases them,
+/// and `Hold` leaves them down until the next gesture begins
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize)]
+pub enum KeyMode {
+    #[default]
+    Tap,
+    Hold,
+}
+
+/// What to do when the action of a trigger fails. `Log` logs the error,
+/// `Ignore` only logs it at the debug level, `Retry` executes the action once
+/// more, and `Notify` also shows a desktop notification
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize)]
+pub enum OnError {
+    #[default]
+    Log,
+    Ignore,
+    Retry,
+    Notify,
+}
+
+/// How progress of a continuous trigger is scaled before `Progressive` steps
+/// through it. Faster moves go further with the steeper curves, while moving
+/// by one step at a time stays the same
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize)]
+pub enum Curve {
+    #[default]
+    Linear,
+    Quadratic,
+    Cubic,
+}
+
+impl Curve {
+    /// Progress by `delta` after the curve and `gain`
+    pub fn apply(&self, delta: f64, step: f64, gain: f64) -> f64 {
+        let power = match self {
+            Curve::Linear => 0,
+            Curve::Quadratic => 1,
+            Curve::Cubic => 2,
+        };
+        gain * delta * (delta.abs() / step).powi(power)
+    }
+}
+
+/// How pinch distances are read. `Absolute` is the scale to reach, like 1.4
+/// for fingers 1.4 times as far apart, or as close together. `Relative` is the
+/// percentage to change by from the scale the pinch began at, like 40 for 40%
+/// farther apart, or closer together
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize)]
+pub enum PinchMode {
+    #[default]
+    Absolute,
+    Relative,
+}
+
+impl PinchMode {
+    /// The distance as the scale, or its inverse for `Out`, that the pinch
+    /// triggers take
+    pub fn scale(&self, distance: f64, direction: PinchDirection) -> f64 {
+        match (self, direction) {
+            (PinchMode::Absolute, _) => distance,
+            (PinchMode::Relative, PinchDirection::In) => 1.0 + distance / 100.0,
+            (PinchMode::Relative, PinchDirection::Out) => 1.0 / (1.0 - distance / 100.0),
+        }
+    }
+}
+
+/// Part of the screen that the pointer must be in. Monitors are counted from
+/// 0, in the order the display server lists them
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Deserialize)]
+pub enum Region {
+    Monitor(usize),
+    Rect {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+}
+
+/// Edge of the touchpad that a swipe starts at
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Deserialize)]
+pub enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Edge {
+    /// Whether a point, in fractions of the touchpad from its top left corner,
+    /// is within `margin` of the edge
+    pub fn near(&self, (x, y): (f64, f64), margin: f64) -> bool {
+        match self {
+            Edge::Left => x <= margin,
+            Edge::Right => x >= 1.0 - margin,
+            Edge::Top => y <= margin,
+            Edge::Bottom => y >= 1.0 - margin,
+        }
+    }
+}
+
+/// Window that keys go to instead of the focused one. `class` is compared like
+/// `window_class` of triggers, and `title` is a part of the title. When both
+/// are given, both must match
+#[derive(PartialEq, Eq, Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowTarget {
+    #[serde(default)]
+    pub class: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+impl WindowTarget {
+    pub fn matches(&self, class: &[String], title: &str) -> bool {
+        self.class.as_ref().is_none_or(|want| class.contains(want))
+            && self.title.as_ref().is_none_or(|part| title.contains(part))
+    }
+}
+
+/// How the uinput device shows up to other programs, which can tell it apart
+/// by these
+#[derive(PartialEq, Eq, Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceId {
+    #[serde(default = "default_device_name")]
+    pub name: String,
+    #[serde(default)]
+    pub vendor: u16,
+    #[serde(default)]
+    pub product: u16,
+}
+
+impl Default for DeviceId {
+    fn default() -> Self {
+        DeviceId {
+            name: default_device_name(),
+            vendor: 0,
+            product: 0,
+        }
+    }
+}
+
+fn default_device_name() -> String {
+    crate::action::VIRTUAL_DEVICE_NAME.to_string()
+}
+
+/// What sends the keys of keyboard actions. `WaylandVirtualKeyboard` sends them
+/// through the compositor, with its `zwp_virtual_keyboard_v1` protocol, which
+/// needs no access to `/dev/uinput`. Mouse actions still go through uinput
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize)]
+pub enum InputBackend {
+    #[default]
+    Uinput,
+    WaylandVirtualKeyboard,
+}
+
 #[derive(PartialEq, Eq, Debug, Clone, Copy, Deserialize)]
 pub enum AnyDirection {
     Cardinal(Direction),
     Pinch(PinchDirection),
     Rotate(RotateDirection),
 }
+
+impl AnyDirection {
+    /// As written in configs, like "Up"
+    pub fn name(&self) -> String {
+        match self {
+            AnyDirection::Cardinal(d) => format!("{:?}", d),
+            AnyDirection::Pinch(d) => format!("{:?}", d),
+            AnyDirection::Rotate(d) => format!("{:?}", d),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use thiserror::Error;
+
+/// Why a config couldn't be loaded
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Can't read config: {0}")]
+    Read(#[from] std::io::Error),
+    /// Position of the error is 1-based, `snippet` shows the line with the
+    /// offending token underlined
+    #[error("{line}:{col}: {message}\n{snippet}")]
+    Ron {
+        line: usize,
+        col: usize,
+        message: String,
+        snippet: String,
+    },
+    #[error("{0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("{0}")]
+    Invalid(String),
+    /// An error in a file that the config includes
+    #[error("In {}: {error}", path.display())]
+    Included {
+        path: std::path::PathBuf,
+        error: Box<ConfigError>,
+    },
+}
+
+impl ConfigError {
+    /// The error itself, out of the files including the one that has it
+    pub fn root(&self) -> &ConfigError {
+        match self {
+            ConfigError::Included { error, .. } => error.root(),
+            other => other,
+        }
+    }
+
+    /// Whether the config, or a file it includes, doesn't exist. Tells that
+    /// apart from a config that's there but wrong
+    pub fn is_not_found(&self) -> bool {
+        matches!(self.root(), ConfigError::Read(e) if e.kind() == std::io::ErrorKind::NotFound)
+    }
+
+    /// Locate an error from ron in the source, underlining the offending token
+    pub(crate) fn ron(source: &str, error: ron::error::SpannedError) -> ConfigError {
+        let start = offset(source, error.span.start.line, error.span.start.col);
+        let len = token_at(&source[start..]);
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let line = source[..start].matches('\n').count() + 1;
+        let col = source[line_start..start].chars().count() + 1;
+        let snippet = format!(
+            "{}\n{}{}",
+            &source[line_start..line_end],
+            " ".repeat(col - 1),
+            "^".repeat(source[start..start + len].chars().count().max(1))
+        );
+        ConfigError::Ron {
+            line,
+            col,
+            message: error.code.to_string(),
+            snippet,
+        }
+    }
+}
+
+/// Byte offset of ron's position, which counts columns in characters
+fn offset(source: &str, line: usize, col: usize) -> usize {
+    let line_start = source
+        .split_inclusive('\n')
+        .take(line - 1)
+        .map(str::len)
+        .sum::<usize>();
+    source[line_start..]
+        .char_indices()
+        .nth(col - 1)
+        .map_or(source.len(), |(i, _)| line_start + i)
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.' || c == '-'
+}
+
+/// Length of the word or single character at the start
+fn token_at(rest: &str) -> usize {
+    match rest.find(|c: char| !is_word(c)) {
+        Some(0) => rest.chars().next().map_or(0, char::len_utf8),
+        Some(n) => n,
+        None => rest.len(),
+    }
+}
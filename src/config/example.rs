@@ -0,0 +1,185 @@
+//! Example config for new users to start from
+
+/// Commented config that shows every action and most triggers. The distances
+/// are the defaults, so the example behaves the same with them left out
+pub fn example() -> String {
+    format!(
+        r#"// Example wzmach config. Every setting is described in the README
+(
+    // Distance to move your fingers to trigger a /swipe/ gesture
+    swipe_distance: {swipe_distance},
+
+    // Distance to move your fingers to trigger a /shear/ gesture
+    shear_distance: {shear_distance},
+
+    // Scale to zoom by to trigger a /pinch/ gesture
+    pinch_distance: {pinch_distance:?},
+
+    // Degrees to rotate your fingers by to trigger a /rotate/ gesture
+    rotation_distance: {rotation_distance:?},
+
+    // Degrees a diagonal swipe may stray from the diagonal
+    angle_tolerance: {angle_tolerance:?},
+
+    // Only use gestures from devices with these names, instead of all
+    // device_filter: Some(["touchpad"]),
+
+    // Triggers that execute in any window in any display environment
+    global_triggers: [
+
+        // Press keys: open a new tab
+        (
+            trigger: Swipe(fingers: 3, direction: Up, repeated: false),
+            action: KeyboardInput(
+                modifiers: ["LeftControl"],
+                sequence: ["T"],
+            ),
+        ),
+
+        // Keep a key pressed for a while, like a long press
+        (
+            trigger: Hold(fingers: 3, duration_ms: 500),
+            action: KeyHold(key: "Space", hold_ms: 800),
+        ),
+
+        // Run a program directly, without a shell
+        (
+            trigger: Swipe(fingers: 4, direction: Up, repeated: false),
+            action: ExecuteCommand(
+                path: "~/bin/overview",
+                args: ["--all-desktops"],
+            ),
+        ),
+
+        // Run a script with /bin/sh
+        (
+            trigger: Swipe(fingers: 4, direction: Down, repeated: false),
+            action: InlineScript(code: "loginctl lock-session"),
+        ),
+
+        // Run a command line in your $SHELL, after holding for half a second
+        (
+            trigger: Hold(fingers: 4, duration_ms: 500),
+            action: ShellCommand(command: "notify-send \"$(date)\""),
+        ),
+
+        // Zoom in and out
+        (
+            trigger: Pinch(fingers: 2, direction: In, repeated: true),
+            action: KeyboardInput(
+                modifiers: ["LeftControl"],
+                sequence: ["Equal"],
+            ),
+        ),
+        (
+            trigger: Pinch(fingers: 2, direction: Out, repeated: true),
+            action: KeyboardInput(
+                modifiers: ["LeftControl"],
+                sequence: ["Minus"],
+            ),
+        ),
+
+        // Scroll once for every 20 units the fingers move
+        (
+            trigger: Swipe(fingers: 3, direction: Down, repeated: false, continuous: true),
+            action: Progressive(
+                step: 20,
+                action: MouseScroll(vertical: -1),
+            ),
+        ),
+
+        // Hold the left button and move the pointer with the fingers, to drag
+        // windows around
+        (
+            trigger: Swipe(fingers: 4, direction: Right, repeated: false, continuous: true),
+            action: MouseButton(button: Left, action: Drag),
+        ),
+
+        // Type text that has no keys of its own
+        (
+            trigger: Shear(fingers: 3, direction: Right, repeated: false),
+            action: TypeText(text: "¯\\_(ツ)_/¯"),
+        ),
+
+        // Several actions in a row: switch the desktop and say so
+        (
+            trigger: Rotate(fingers: 2, direction: Clockwise, repeated: false),
+            action: Sequence([
+                KeyboardInput(
+                    modifiers: ["LeftControl", "LeftMeta"],
+                    sequence: ["Right"],
+                ),
+                ExecuteCommand(path: "notify-send", args: ["Next desktop"]),
+            ]),
+            // Don't fire twice as the fingers settle
+            cooldown_ms: 300,
+        ),
+
+        // Go to the first workspace, in sway, Hyprland or an X11 window
+        // manager. Next and Prev go to the neighbours
+        (
+            trigger: Rotate(fingers: 3, direction: Anticlockwise, repeated: false),
+            action: WorkspaceSwitch(direction: Index(1)),
+        ),
+
+        // Pause playback, and resume it the next time
+        (
+            trigger: Rotate(fingers: 3, direction: Clockwise, repeated: false),
+            action: Toggle(
+                first: ExecuteCommand(path: "playerctl", args: ["pause"]),
+                second: ExecuteCommand(path: "playerctl", args: ["play"]),
+            ),
+        ),
+    ],
+
+    // Triggers that execute only in X11
+    x11_triggers: [
+
+        // Go back in Firefox only. window_class works only in X11
+        (
+            trigger: Swipe(fingers: 2, direction: Right, repeated: false),
+            action: KeyboardInput(
+                modifiers: ["LeftAlt"],
+                sequence: ["Left"],
+            ),
+            window_class: Some("firefox"),
+        ),
+    ],
+
+    // Triggers that execute only in wayland
+    wayland_triggers: [
+
+        // Call a DBus method: switch to the second desktop in KDE
+        (
+            trigger: Swipe(fingers: 4, direction: Left, repeated: false),
+            action: DBusCall(
+                bus: "session",
+                destination: "org.kde.KWin",
+                path: "/KWin",
+                interface: "org.kde.KWin",
+                method: "setCurrentDesktop",
+                args: [Int32(2)],
+            ),
+        ),
+
+        // Run a sway command: go to the next workspace in sway
+        (
+            trigger: Swipe(fingers: 4, direction: Right, repeated: false),
+            action: SwayCommand(command: "workspace next"),
+        ),
+
+        // Run a Hyprland dispatcher: go to the previous workspace
+        (
+            trigger: Shear(fingers: 4, direction: Left, repeated: false),
+            action: HyprlandDispatch(dispatcher: "workspace", args: "e-1"),
+        ),
+    ],
+)
+"#,
+        swipe_distance = super::default_distance(),
+        shear_distance = super::default_distance(),
+        pinch_distance = super::default_pinch(),
+        rotation_distance = super::default_rotation(),
+        angle_tolerance = super::default_angle_tolerance(),
+    )
+}
@@ -0,0 +1,75 @@
+//! Shell-like expansion of `~` and environment variables in strings from the
+//! config, for the places where no shell is involved
+
+/// Replace a leading `~` with the home directory, and `$VAR` or `${VAR}` with
+/// the values of environment variables. Unset variables expand to nothing, as
+/// they would in a shell
+pub fn expand(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+
+    if rest == "~" || rest.starts_with("~/") {
+        out.push_str(&var("HOME"));
+        rest = &rest[1..];
+    }
+
+    while let Some(at) = rest.find('$') {
+        out.push_str(&rest[..at]);
+        let after = &rest[at + 1..];
+        let (name, tail) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", after),
+            }
+        } else {
+            let end = after
+                .char_indices()
+                .find(|&(i, c)| {
+                    !(c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit()))
+                })
+                .map_or(after.len(), |(i, _)| i);
+            (&after[..end], &after[end..])
+        };
+        if name.is_empty() {
+            // Not a variable, keep the dollar as is
+            out.push('$');
+            rest = after;
+        } else {
+            out.push_str(&var(name));
+            rest = tail;
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn var(name: &str) -> String {
+    match std::env::var(name) {
+        Ok(value) => value,
+        Err(_) => {
+            log::warn!("Environment variable {} is not set", name);
+            String::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::expand;
+
+    #[test]
+    fn variables_and_home() {
+        std::env::set_var("WZMACH_TEST_EXPAND", "value");
+        std::env::remove_var("WZMACH_TEST_UNSET");
+        let home = std::env::var("HOME").unwrap_or_default();
+
+        assert_eq!(expand("~/bin/script"), format!("{}/bin/script", home));
+        assert_eq!(expand("~"), home);
+        assert_eq!(expand("a~b"), "a~b");
+        assert_eq!(expand("$WZMACH_TEST_EXPAND/x"), "value/x");
+        assert_eq!(expand("${WZMACH_TEST_EXPAND}x"), "valuex");
+        assert_eq!(expand("[$WZMACH_TEST_UNSET]"), "[]");
+        assert_eq!(expand("cost: 5$"), "cost: 5$");
+        assert_eq!(expand("$1 ${unclosed"), "$1 ${unclosed");
+    }
+}
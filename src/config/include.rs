@@ -0,0 +1,325 @@
+//! Config files that include others. The files are merged in the order they
+//! are read: a file first, then each file it includes, along with the files
+//! those include in turn. Actions and triggers are added together, and of the
+//! settings that more than one file sets, the last file's is used
+
+use super::{decode, expand, given, Config, ConfigError, DeviceFilter, Format};
+use crate::common::{DeviceId, InputBackend, KeyMode, PinchMode};
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// The settings a file gives itself, rather than leaving to the defaults, so
+/// that a file which leaves one out doesn't override the files before it.
+/// Read from the same file as its `Config`, so the other fields are left to
+/// that to reject when they're unknown
+#[derive(Deserialize)]
+struct Settings {
+    #[serde(default, deserialize_with = "given")]
+    swipe_distance: Option<u32>,
+    #[serde(default, deserialize_with = "given")]
+    shear_distance: Option<u32>,
+    #[serde(default, deserialize_with = "given")]
+    pinch_distance: Option<f64>,
+    #[serde(default, deserialize_with = "given")]
+    pinch_mode: Option<PinchMode>,
+    #[serde(default, deserialize_with = "given")]
+    rotation_distance: Option<f64>,
+    #[serde(default, deserialize_with = "given")]
+    angle_tolerance: Option<f64>,
+    #[serde(default, deserialize_with = "given")]
+    edge_margin: Option<f64>,
+    #[serde(default, deserialize_with = "given")]
+    swipe_deadzone: Option<f64>,
+    #[serde(default, deserialize_with = "given")]
+    device_filter: Option<Option<Vec<DeviceFilter>>>,
+    #[serde(default, deserialize_with = "given")]
+    natural_scroll: Option<bool>,
+    #[serde(default, deserialize_with = "given")]
+    key_mode: Option<KeyMode>,
+    #[serde(default, deserialize_with = "given")]
+    fire_all: Option<bool>,
+    #[serde(default, deserialize_with = "given")]
+    rearm_on_lift: Option<bool>,
+    #[serde(default, deserialize_with = "given")]
+    coalesce_ms: Option<u32>,
+    #[serde(default, deserialize_with = "given")]
+    event_socket: Option<Option<String>>,
+    #[serde(default, deserialize_with = "given")]
+    virtual_device: Option<DeviceId>,
+    #[serde(default, deserialize_with = "given")]
+    input_backend: Option<InputBackend>,
+    #[serde(default, deserialize_with = "given")]
+    profile: Option<Option<String>>,
+}
+
+struct File {
+    path: PathBuf,
+    /// To tell that two paths are the same file
+    canonical: PathBuf,
+    config: Config,
+    settings: Settings,
+}
+
+/// Read the config at the path and every file it includes, and merge them. The
+/// result is left for the caller to check
+pub(super) fn load(path: &Path) -> Result<Config, ConfigError> {
+    let mut files = Vec::new();
+    read(path, &mut Vec::new(), &mut files)?;
+    if files.len() == 1 {
+        return Ok(files.remove(0).config);
+    }
+
+    let mut config = Config::default();
+    let mut origins = HashMap::new();
+    for file in files {
+        let File {
+            path,
+            config: part,
+            settings,
+            ..
+        } = file;
+        let mut give = Give {
+            origins: &mut origins,
+            path: &path,
+        };
+        macro_rules! give {
+            ($($field:ident),*) => {
+                $(give.set(stringify!($field), settings.$field, &mut config.$field);)*
+            };
+        }
+        give!(
+            swipe_distance,
+            shear_distance,
+            pinch_distance,
+            pinch_mode,
+            rotation_distance,
+            angle_tolerance,
+            edge_margin,
+            swipe_deadzone,
+            device_filter,
+            natural_scroll,
+            key_mode,
+            fire_all,
+            rearm_on_lift,
+            coalesce_ms,
+            event_socket,
+            virtual_device,
+            input_backend,
+            profile
+        );
+
+        for (name, action) in part.actions {
+            if config.actions.insert(name.clone(), action).is_some() {
+                log::warn!(
+                    "Action {} is defined in more than one file, using the one in {}",
+                    name,
+                    path.display()
+                );
+            }
+        }
+        config.global_triggers.extend(part.global_triggers);
+        config.x11_triggers.extend(part.x11_triggers);
+        config.wayland_triggers.extend(part.wayland_triggers);
+        for (name, triggers) in part.profiles {
+            config.profiles.entry(name).or_default().extend(triggers);
+        }
+    }
+    Ok(config)
+}
+
+/// Sets the settings of one file, remembering which file set each
+struct Give<'a> {
+    origins: &'a mut HashMap<&'static str, PathBuf>,
+    path: &'a Path,
+}
+
+impl Give<'_> {
+    fn set<T: PartialEq + Debug>(&mut self, name: &'static str, value: Option<T>, to: &mut T) {
+        let value = match value {
+            Some(v) => v,
+            None => return,
+        };
+        if let Some(earlier) = self.origins.insert(name, self.path.to_owned()) {
+            if *to != value {
+                log::warn!(
+                    "{} is set in both {} and {}, using {:?} from the last",
+                    name,
+                    earlier.display(),
+                    self.path.display(),
+                    value
+                );
+            }
+        }
+        *to = value;
+    }
+}
+
+/// Read the file and then the files it includes. `stack` has the files
+/// including this one, to catch includes that loop back to them
+fn read(path: &Path, stack: &mut Vec<PathBuf>, files: &mut Vec<File>) -> Result<(), ConfigError> {
+    log::trace!("Reading {}", path.display());
+    let s = std::fs::read_to_string(path)?;
+    let format = Format::from_path(path);
+    let mut config: Config = decode(&s, format)?;
+    config.expand_keys().map_err(ConfigError::Invalid)?;
+    let settings: Settings = decode(&s, format)?;
+    let canonical = path.canonicalize()?;
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut includes = Vec::new();
+    for include in &config.include {
+        let include = dir.join(expand::expand(include));
+        if include.is_dir() {
+            includes.extend(directory(&include).map_err(|e| within(&include, e.into()))?);
+        } else {
+            includes.push(include);
+        }
+    }
+
+    stack.push(canonical.clone());
+    files.push(File {
+        path: path.to_owned(),
+        canonical,
+        config,
+        settings,
+    });
+    for include in includes {
+        let canonical = include
+            .canonicalize()
+            .map_err(|e| within(&include, e.into()))?;
+        if stack.contains(&canonical) {
+            return Err(ConfigError::Invalid(format!(
+                "Includes loop back: {} includes {}",
+                path.display(),
+                include.display()
+            )));
+        }
+        if files.iter().any(|f| f.canonical == canonical) {
+            log::warn!(
+                "{} is included more than once, using it only the first time",
+                include.display()
+            );
+            continue;
+        }
+        read(&include, stack, files).map_err(|e| within(&include, e))?;
+    }
+    stack.pop();
+    Ok(())
+}
+
+/// The config files in a directory, in the order of their names
+fn directory(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let config = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("ron" | "toml")
+        );
+        if config && path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Say which file the error is in, unless a file it includes already does
+fn within(path: &Path, error: ConfigError) -> ConfigError {
+    match error {
+        ConfigError::Included { .. } => error,
+        _ => ConfigError::Included {
+            path: path.to_owned(),
+            error: Box::new(error),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{load, ConfigError};
+
+    use std::path::Path;
+
+    fn write(dir: &Path, name: &str, s: &str) {
+        std::fs::write(dir.join(name), s).unwrap();
+    }
+
+    #[test]
+    fn merges_includes() {
+        let dir = std::env::temp_dir().join(format!("wzmach-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+        write(
+            &dir,
+            "config.ron",
+            r#"(
+                swipe_distance: 80,
+                pinch_distance: 1.2,
+                include: ["more.ron", "conf.d"],
+                actions: { "lock": ShellCommand(command: "loginctl lock-session") },
+                global_triggers: [
+                    (trigger: Hold(fingers: 4), action: Ref("lock")),
+                ],
+            )"#,
+        );
+        write(
+            &dir,
+            "more.ron",
+            r#"(
+                swipe_distance: 120,
+                x11_triggers: [
+                    (trigger: Pinch(fingers: 2, direction: In, repeated: false), action: Ref("lock")),
+                ],
+            )"#,
+        );
+        write(
+            &dir.join("conf.d"),
+            "b.toml",
+            "natural_scroll = true\nswipe_distance = 150\n",
+        );
+        write(
+            &dir.join("conf.d"),
+            "a.ron",
+            r#"(global_triggers: [
+                (trigger: Hold(fingers: 3), action: Ref("lock")),
+            ])"#,
+        );
+        write(&dir.join("conf.d"), "notes.txt", "not a config");
+
+        let config = load(&dir.join("config.ron")).unwrap();
+        assert_eq!(config.swipe_distance, 150);
+        assert_eq!(config.pinch_distance, 1.2);
+        assert!(config.natural_scroll);
+        assert_eq!(config.global_triggers.len(), 2);
+        assert_eq!(config.x11_triggers.len(), 1);
+        assert!(config.actions.contains_key("lock"));
+
+        // back to the including file
+        write(&dir, "more.ron", r#"(include: ["conf.d/../config.ron"])"#);
+        let error = load(&dir.join("config.ron")).unwrap_err().to_string();
+        assert!(error.contains("Includes loop back"), "{}", error);
+
+        write(&dir, "more.ron", r#"(include: ["missing.ron"])"#);
+        let error = load(&dir.join("config.ron")).unwrap_err();
+        assert!(error.is_not_found());
+        let error = error.to_string();
+        assert!(error.starts_with("In "), "{}", error);
+        assert!(error.contains("missing.ron"), "{}", error);
+
+        // Wrong, rather than missing
+        write(&dir, "more.ron", "(swipe_distance: )");
+        let error = load(&dir.join("config.ron")).unwrap_err();
+        assert!(!error.is_not_found());
+        assert!(
+            matches!(error.root(), ConfigError::Ron { line: 1, .. }),
+            "{}",
+            error
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,10 +1,48 @@
 use serde::Deserialize;
-use uinput::event::keyboard::Key;
+
+/// Key to press on the uinput device
+#[cfg(feature = "uinput")]
+pub type Key = uinput::event::keyboard::Key;
+/// Without uinput, keys are known by their name and their code
+#[cfg(not(feature = "uinput"))]
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct Key {
+    name: &'static str,
+    code: u32,
+}
+
+#[cfg(not(feature = "uinput"))]
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+/// Code of the key in the kernel, by which libinput reports key presses and
+/// the wayland virtual keyboard sends them
+pub fn key_code(key: &Key) -> u32 {
+    #[cfg(feature = "uinput")]
+    return uinput::event::Code::code(key) as u32;
+    #[cfg(not(feature = "uinput"))]
+    key.code
+}
 
 /// Keyboard key with instances for deserialization
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct ConfigKey(pub Key);
 
+impl ConfigKey {
+    /// Look the key up by its name in the config, like `"LeftControl"`
+    pub fn from_name(name: &str) -> Option<ConfigKey> {
+        KEYS_TABLE.get(name).copied().map(ConfigKey)
+    }
+
+    /// Code of the key in the kernel, by which libinput reports key presses
+    pub fn code(&self) -> u32 {
+        key_code(&self.0)
+    }
+}
+
 impl<'de> Deserialize<'de> for ConfigKey {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -23,115 +61,239 @@ impl<'de> serde::de::Visitor<'de> for KeyVisitor {
     }
 
     fn visit_str<E: serde::de::Error>(self, data: &str) -> Result<ConfigKey, E> {
-        static VARIANTS: &[&str] = &[&"KEY"];
-        match KEYS_TABLE.get(data) {
-            Some(k) => Ok(ConfigKey(*k)),
-            None => Err(E::unknown_variant(data, VARIANTS)),
-        }
+        static VARIANTS: &[&str] = &["KEY"];
+        ConfigKey::from_name(data).ok_or_else(|| E::unknown_variant(data, VARIANTS))
+    }
+}
+
+/// Keys pressed together, written like `"super+shift+T"`: modifiers first and
+/// the key last, joined by `+`
+#[derive(PartialEq, Debug, Clone)]
+pub struct KeyChord {
+    pub modifiers: Vec<ConfigKey>,
+    pub key: ConfigKey,
+}
+
+/// Names of the modifiers as they're usually written, for the left ones
+const MODIFIERS: &[(&str, &str)] = &[
+    ("super", "LeftMeta"),
+    ("meta", "LeftMeta"),
+    ("win", "LeftMeta"),
+    ("ctrl", "LeftControl"),
+    ("control", "LeftControl"),
+    ("alt", "LeftAlt"),
+    ("shift", "LeftShift"),
+    ("altgr", "RightAlt"),
+];
+
+impl std::str::FromStr for KeyChord {
+    type Err = String;
+
+    /// Fails naming the first key that has no such name
+    fn from_str(chord: &str) -> Result<KeyChord, String> {
+        let mut keys = chord
+            .split('+')
+            .map(|name| loose_key(name.trim()).ok_or_else(|| name.trim()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|name| format!("No key named {:?} in {:?}", name, chord))?;
+        let key = keys.pop().expect("split gives at least one name");
+        Ok(KeyChord {
+            modifiers: keys,
+            key,
+        })
     }
 }
 
-// copy-pasted from uinput sources
-const KEYS_TABLE: phf::Map<&'static str, Key> = phf::phf_map! {
-    "Esc" => Key::Esc,
-    "1" => Key::_1,
-    "2" => Key::_2,
-    "3" => Key::_3,
-    "4" => Key::_4,
-    "5" => Key::_5,
-    "6" => Key::_6,
-    "7" => Key::_7,
-    "8" => Key::_8,
-    "9" => Key::_9,
-    "0" => Key::_0,
-    "Minus" => Key::Minus,
-    "Equal" => Key::Equal,
-    "BackSpace" => Key::BackSpace,
-    "Tab" => Key::Tab,
-    "Q" => Key::Q,
-    "W" => Key::W,
-    "E" => Key::E,
-    "R" => Key::R,
-    "T" => Key::T,
-    "Y" => Key::Y,
-    "U" => Key::U,
-    "I" => Key::I,
-    "O" => Key::O,
-    "P" => Key::P,
-    "LeftBrace" => Key::LeftBrace,
-    "RightBrace" => Key::RightBrace,
-    "Enter" => Key::Enter,
-    "LeftControl" => Key::LeftControl,
-    "A" => Key::A,
-    "S" => Key::S,
-    "D" => Key::D,
-    "F" => Key::F,
-    "G" => Key::G,
-    "H" => Key::H,
-    "J" => Key::J,
-    "K" => Key::K,
-    "L" => Key::L,
-    "SemiColon" => Key::SemiColon,
-    "Apostrophe" => Key::Apostrophe,
-    "Grave" => Key::Grave,
-    "LeftShift" => Key::LeftShift,
-    "BackSlash" => Key::BackSlash,
-    "Z" => Key::Z,
-    "X" => Key::X,
-    "C" => Key::C,
-    "V" => Key::V,
-    "B" => Key::B,
-    "N" => Key::N,
-    "M" => Key::M,
-    "Comma" => Key::Comma,
-    "Dot" => Key::Dot,
-    "Slash" => Key::Slash,
-    "RightShift" => Key::RightShift,
-    "LeftAlt" => Key::LeftAlt,
-    "Space" => Key::Space,
-    "CapsLock" => Key::CapsLock,
-    "F1" => Key::F1,
-    "F2" => Key::F2,
-    "F3" => Key::F3,
-    "F4" => Key::F4,
-    "F5" => Key::F5,
-    "F6" => Key::F6,
-    "F7" => Key::F7,
-    "F8" => Key::F8,
-    "F9" => Key::F9,
-    "F10" => Key::F10,
-    "NumLock" => Key::NumLock,
-    "ScrollLock" => Key::ScrollLock,
-    "F11" => Key::F11,
-    "F12" => Key::F12,
-    "RightControl" => Key::RightControl,
-    "SysRq" => Key::SysRq,
-    "RightAlt" => Key::RightAlt,
-    "LineFeed" => Key::LineFeed,
-    "Home" => Key::Home,
-    "Up" => Key::Up,
-    "PageUp" => Key::PageUp,
-    "Left" => Key::Left,
-    "Right" => Key::Right,
-    "End" => Key::End,
-    "Down" => Key::Down,
-    "PageDown" => Key::PageDown,
-    "Insert" => Key::Insert,
-    "Delete" => Key::Delete,
-    "LeftMeta" => Key::LeftMeta,
-    "RightMeta" => Key::RightMeta,
-    "ScrollUp" => Key::ScrollUp,
-    "ScrollDown" => Key::ScrollDown,
-    "F13" => Key::F13,
-    "F14" => Key::F14,
-    "F15" => Key::F15,
-    "F16" => Key::F16,
-    "F17" => Key::F17,
-    "F18" => Key::F18,
-    "F19" => Key::F19,
-    "F20" => Key::F20,
-    "F21" => Key::F21,
-    "F22" => Key::F22,
-    "F23" => Key::F23,
-    "F24" => Key::F24,
-};
+/// Key by its name in any case, or by the usual name of a modifier
+fn loose_key(name: &str) -> Option<ConfigKey> {
+    let lower = name.to_lowercase();
+    let name = MODIFIERS
+        .iter()
+        .find(|(alias, _)| *alias == lower)
+        .map_or(name, |(_, key)| key);
+    ConfigKey::from_name(name).or_else(|| {
+        KEYS_TABLE
+            .entries()
+            .find(|(n, _)| n.to_lowercase() == lower)
+            .map(|(_, &key)| ConfigKey(key))
+    })
+}
+
+impl<'de> Deserialize<'de> for KeyChord {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let chord = String::deserialize(deserializer)?;
+        chord.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Define the table of keys both with and without uinput, which doesn't know
+/// their codes by itself
+macro_rules! keys_table {
+    ($($name:literal => $key:ident = $code:literal,)*) => {
+        #[cfg(feature = "uinput")]
+        const KEYS_TABLE: phf::Map<&'static str, Key> = phf::phf_map! {
+            $($name => Key::$key,)*
+        };
+        #[cfg(not(feature = "uinput"))]
+        const KEYS_TABLE: phf::Map<&'static str, Key> = phf::phf_map! {
+            $($name => Key { name: $name, code: $code },)*
+        };
+        /// To check the codes against the ones of uinput
+        #[cfg(all(test, feature = "uinput"))]
+        const CODES: &[(&str, u32)] = &[$(($name, $code),)*];
+    };
+}
+
+// copy-pasted from uinput sources, with the codes of the keys
+keys_table! {
+    "Esc" => Esc = 1,
+    "1" => _1 = 2,
+    "2" => _2 = 3,
+    "3" => _3 = 4,
+    "4" => _4 = 5,
+    "5" => _5 = 6,
+    "6" => _6 = 7,
+    "7" => _7 = 8,
+    "8" => _8 = 9,
+    "9" => _9 = 10,
+    "0" => _0 = 11,
+    "Minus" => Minus = 12,
+    "Equal" => Equal = 13,
+    "BackSpace" => BackSpace = 14,
+    "Tab" => Tab = 15,
+    "Q" => Q = 16,
+    "W" => W = 17,
+    "E" => E = 18,
+    "R" => R = 19,
+    "T" => T = 20,
+    "Y" => Y = 21,
+    "U" => U = 22,
+    "I" => I = 23,
+    "O" => O = 24,
+    "P" => P = 25,
+    "LeftBrace" => LeftBrace = 26,
+    "RightBrace" => RightBrace = 27,
+    "Enter" => Enter = 28,
+    "LeftControl" => LeftControl = 29,
+    "A" => A = 30,
+    "S" => S = 31,
+    "D" => D = 32,
+    "F" => F = 33,
+    "G" => G = 34,
+    "H" => H = 35,
+    "J" => J = 36,
+    "K" => K = 37,
+    "L" => L = 38,
+    "SemiColon" => SemiColon = 39,
+    "Apostrophe" => Apostrophe = 40,
+    "Grave" => Grave = 41,
+    "LeftShift" => LeftShift = 42,
+    "BackSlash" => BackSlash = 43,
+    "Z" => Z = 44,
+    "X" => X = 45,
+    "C" => C = 46,
+    "V" => V = 47,
+    "B" => B = 48,
+    "N" => N = 49,
+    "M" => M = 50,
+    "Comma" => Comma = 51,
+    "Dot" => Dot = 52,
+    "Slash" => Slash = 53,
+    "RightShift" => RightShift = 54,
+    "LeftAlt" => LeftAlt = 56,
+    "Space" => Space = 57,
+    "CapsLock" => CapsLock = 58,
+    "F1" => F1 = 59,
+    "F2" => F2 = 60,
+    "F3" => F3 = 61,
+    "F4" => F4 = 62,
+    "F5" => F5 = 63,
+    "F6" => F6 = 64,
+    "F7" => F7 = 65,
+    "F8" => F8 = 66,
+    "F9" => F9 = 67,
+    "F10" => F10 = 68,
+    "NumLock" => NumLock = 69,
+    "ScrollLock" => ScrollLock = 70,
+    "F11" => F11 = 87,
+    "F12" => F12 = 88,
+    "RightControl" => RightControl = 97,
+    "SysRq" => SysRq = 99,
+    "RightAlt" => RightAlt = 100,
+    "LineFeed" => LineFeed = 101,
+    "Home" => Home = 102,
+    "Up" => Up = 103,
+    "PageUp" => PageUp = 104,
+    "Left" => Left = 105,
+    "Right" => Right = 106,
+    "End" => End = 107,
+    "Down" => Down = 108,
+    "PageDown" => PageDown = 109,
+    "Insert" => Insert = 110,
+    "Delete" => Delete = 111,
+    "LeftMeta" => LeftMeta = 125,
+    "RightMeta" => RightMeta = 126,
+    "ScrollUp" => ScrollUp = 177,
+    "ScrollDown" => ScrollDown = 178,
+    "F13" => F13 = 183,
+    "F14" => F14 = 184,
+    "F15" => F15 = 185,
+    "F16" => F16 = 186,
+    "F17" => F17 = 187,
+    "F18" => F18 = 188,
+    "F19" => F19 = 189,
+    "F20" => F20 = 190,
+    "F21" => F21 = 191,
+    "F22" => F22 = 192,
+    "F23" => F23 = 193,
+    "F24" => F24 = 194,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ConfigKey, KeyChord};
+
+    use std::str::FromStr;
+
+    #[cfg(feature = "uinput")]
+    #[test]
+    fn codes() {
+        for &(name, code) in super::CODES {
+            assert_eq!(ConfigKey::from_name(name).unwrap().code(), code, "{}", name);
+        }
+    }
+
+    #[test]
+    fn parses_chords() {
+        let key = |name| ConfigKey::from_name(name).unwrap();
+        assert_eq!(
+            KeyChord::from_str("super+1"),
+            Ok(KeyChord {
+                modifiers: vec![key("LeftMeta")],
+                key: key("1"),
+            })
+        );
+        assert_eq!(
+            KeyChord::from_str("Ctrl + Shift + t"),
+            Ok(KeyChord {
+                modifiers: vec![key("LeftControl"), key("LeftShift")],
+                key: key("T"),
+            })
+        );
+        assert_eq!(
+            KeyChord::from_str("RightControl+pageup"),
+            Ok(KeyChord {
+                modifiers: vec![key("RightControl")],
+                key: key("PageUp"),
+            })
+        );
+        assert_eq!(
+            KeyChord::from_str("super+"),
+            Err("No key named \"\" in \"super+\"".to_string())
+        );
+        assert!(KeyChord::from_str("hyper+1").is_err());
+    }
+}
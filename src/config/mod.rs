@@ -1,139 +1,1816 @@
+mod error;
+mod example;
+mod expand;
+mod include;
 mod key;
 mod trigger;
 
+pub use error::ConfigError;
+pub use example::example;
+pub use key::{key_code, ConfigKey, Key, KeyChord};
+pub use trigger::Trigger;
+
 use crate::action;
+use crate::common::{
+    ButtonAction, Curve, DeviceId, FireOn, InputBackend, KeyMode, MouseButtonKind, OnError,
+    PinchMode, Region, WindowTarget, Workspace,
+};
+use crate::dispatch::Binding;
 use crate::gesture_event::trigger as gesture;
-use trigger::Trigger;
+use crate::window::Windows;
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use serde::Deserialize;
 
-#[derive(PartialEq, Debug, Default, Deserialize)]
+#[derive(PartialEq, Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     /// Distance for fingers to travel to trigger. Default: 100
     #[serde(default = "default_distance")]
-    swipe_distance: u32,
+    pub swipe_distance: u32,
 
     /// Distance for fingers to travel to trigger. Default: 100
     #[serde(default = "default_distance")]
-    shear_distance: u32,
+    pub shear_distance: u32,
 
     /// Scale to achieve to trigger. Default: 1.4
     #[serde(default = "default_pinch")]
-    pinch_distance: f64,
+    pub pinch_distance: f64,
+
+    /// Whether `pinch_distance` and the distances of pinch triggers are the
+    /// scale to reach, or the percentage to change by from the scale the
+    /// pinch began at. Default: Absolute
+    #[serde(default)]
+    pub pinch_mode: PinchMode,
 
     /// Spin to achieve to trigger. Default: 60
     #[serde(default = "default_rotation")]
-    rotation_distance: f64,
+    pub rotation_distance: f64,
+
+    /// Degrees a diagonal swipe or shear may stray from the diagonal.
+    /// Default: 20
+    #[serde(default = "default_angle_tolerance")]
+    pub angle_tolerance: f64,
+
+    /// How close to its edge a swipe with an `edge` must start, as a fraction
+    /// of the touchpad. Default: 0.1
+    #[serde(default = "default_edge_margin")]
+    pub edge_margin: f64,
+
+    /// Distance a swipe travels before its direction counts, for fingers that
+    /// wobble as they land. The direction is told from where the swipe left
+    /// it, and the distance is still measured from the start. Default: 0
+    #[serde(default)]
+    pub swipe_deadzone: f64,
+
+    /// Only take gestures from devices whose names match one of these. A
+    /// pattern with `*` or `?` is a glob, otherwise a part of the name. Case
+    /// is ignored. Default: all devices
+    #[serde(default)]
+    pub device_filter: Option<Vec<DeviceFilter>>,
+
+    /// Reverse all `MouseScroll` actions, so that positive values scroll left
+    /// and down, like natural scrolling on touchpads. Default: false
+    #[serde(default)]
+    pub natural_scroll: bool,
+
+    /// How `KeyboardInput` actions without a `mode` of their own press their
+    /// keys: `Tap` or `Hold`. Default: Tap
+    #[serde(default)]
+    pub key_mode: KeyMode,
+
+    /// Execute every trigger that fires on the same motion, instead of only
+    /// the one with the highest `priority`. Default: false
+    #[serde(default)]
+    pub fire_all: bool,
+
+    /// Fire each trigger, unless it's `repeated`, only once until the
+    /// fingers are lifted, instead of again when the gesture turns back its
+    /// way. Default: false
+    #[serde(default)]
+    pub rearm_on_lift: bool,
+
+    /// Milliseconds between the updates that continuous triggers pass to
+    /// their actions, which get the moves in between added up. Bounds how
+    /// many actions a long gesture executes. Default: 8
+    #[serde(default = "default_coalesce")]
+    pub coalesce_ms: u32,
+
+    /// Unix socket to send the triggers that match gestures to, as lines of
+    /// JSON. `~` and environment variables are expanded. Default: none
+    #[serde(default)]
+    pub event_socket: Option<String>,
+
+    /// Name and IDs of the uinput device that keyboard and mouse actions go
+    /// through. Changes take a restart. Default: named `wzmach_virtual`, with
+    /// IDs 0
+    #[serde(default)]
+    pub virtual_device: DeviceId,
+
+    /// What sends the keys of keyboard actions, uinput or the virtual keyboard
+    /// of the wayland compositor. Changes take a restart. Default: `Uinput`
+    #[serde(default)]
+    pub input_backend: InputBackend,
+
+    /// Other config files whose actions and triggers are added to these. A
+    /// relative path is relative to this file, and a directory includes every
+    /// `.ron` and `.toml` file in it. `~` and environment variables are
+    /// expanded. Default: none
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Actions that triggers can refer to by name with `Ref`
+    #[serde(default)]
+    pub actions: HashMap<String, ConfigAction>,
 
     /// Triggers executed with any display manager and any window
     #[serde(default = "default_triggers")]
-    global_triggers: Vec<ConfigTrigger>,
+    pub global_triggers: Vec<ConfigTrigger>,
 
     /// Triggers executed in x11 on any window
     #[serde(default = "default_triggers")]
-    x11_triggers: Vec<ConfigTrigger>,
+    pub x11_triggers: Vec<ConfigTrigger>,
 
     /// Triggers executed in wayland on any window
     #[serde(default = "default_triggers")]
-    wayland_triggers: Vec<ConfigTrigger>,
+    pub wayland_triggers: Vec<ConfigTrigger>,
+
+    /// Sets of triggers by name, of which the active one is executed along
+    /// with the others, with any display manager. Default: none
+    #[serde(default)]
+    pub profiles: HashMap<String, Vec<ConfigTrigger>>,
+
+    /// Profile to start with, until another is switched to. Default: none
+    #[serde(default)]
+    pub profile: Option<String>,
 }
 
-#[derive(PartialEq, Debug, Deserialize)]
+#[derive(PartialEq, Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ConfigTrigger {
     pub trigger: Trigger,
+    /// Can be left out for `keys`. A list of actions is a `Sequence`
+    #[serde(default = "no_action", deserialize_with = "one_or_many")]
     pub action: ConfigAction,
+    /// Shorthand for a `KeyboardInput` pressing keys together, like
+    /// `"super+1"`, instead of an action
+    #[serde(default, deserialize_with = "given")]
+    pub keys: Option<KeyChord>,
+    /// Only execute when the focused window has this class. Compared with both
+    /// parts of X11's `WM_CLASS`, or the class and initial class in Hyprland
+    #[serde(default)]
+    pub window_class: Option<String>,
+    /// Milliseconds after firing during which the trigger doesn't fire again,
+    /// so that settling fingers don't fire it twice. Default: 0
+    #[serde(default)]
+    pub cooldown_ms: u32,
+    /// With a continuous trigger, execute the action as the trigger begins
+    /// and then every this many milliseconds until the gesture ends
+    #[serde(default)]
+    pub repeat_ms: Option<u32>,
+    /// Don't execute while the focused window is fullscreen. Works only in
+    /// X11 and Hyprland. Default: false
+    #[serde(default)]
+    pub disable_on_fullscreen: bool,
+    /// Only execute when the pointer is in this part of the screen, when the
+    /// gesture completes. Works only in X11
+    #[serde(default)]
+    pub region: Option<Region>,
+    /// Fire as the gesture begins, when it ends, or when it goes far enough.
+    /// Default: Threshold
+    #[serde(default)]
+    pub fire_on: FireOn,
+    /// Fire only when the gesture ends, and not at all if it went back by
+    /// more than this after reaching the trigger's distance. Measured like
+    /// the distances
+    #[serde(default)]
+    pub cancel_threshold: Option<f64>,
+    /// When several triggers fire on the same motion, only the one with the
+    /// highest priority executes, unless `fire_all` is set. Default: 0
+    #[serde(default)]
+    pub priority: i32,
+    /// Only execute while this key is held down on a keyboard, like
+    /// `"LeftMeta"`
+    #[serde(default)]
+    pub require_modifier: Option<ConfigKey>,
+    /// Load the trigger. `false` leaves it out, while still checking it for
+    /// errors, to try things without it. Default: true
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// What to do when the action fails: `Log`, `Ignore`, `Retry` or
+    /// `Notify`. Default: Log
+    #[serde(default)]
+    pub on_error: OnError,
+    /// Fire only when the gesture ends without having matched any trigger
+    /// that isn't a fallback, going the way of this one. Default: false
+    #[serde(default)]
+    pub fallback: bool,
+    /// Measure the distance of a swipe or shear from where the gesture last
+    /// turned this trigger's way, instead of from where it began or the last
+    /// trigger fired. Going back and forth then fires it one distance after
+    /// each turn. Default: false
+    #[serde(default)]
+    pub from_turn: bool,
+}
+
+/// Device to take gestures from, by a pattern of its name, like `"touchpad"`.
+/// With factors, the gestures of the device need to go that much further
+#[derive(PartialEq, Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum DeviceFilter {
+    Name(String),
+    Scaled(ScaledDevice),
 }
 
-#[derive(PartialEq, Debug, Deserialize)]
+#[derive(PartialEq, Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScaledDevice {
+    pub name: String,
+    /// For the distances of swipes and shears. Default: 1
+    #[serde(default = "default_factor")]
+    pub distance: f64,
+    /// For the scale of pinches, as a power: 2 needs twice as many steps
+    /// of `pinch_distance`. Default: 1
+    #[serde(default = "default_factor")]
+    pub pinch: f64,
+    /// For the angles of rotations. Default: 1
+    #[serde(default = "default_factor")]
+    pub rotation: f64,
+}
+
+impl DeviceFilter {
+    pub fn pattern(&self) -> &str {
+        match self {
+            DeviceFilter::Name(name) => name,
+            DeviceFilter::Scaled(device) => &device.name,
+        }
+    }
+
+    /// The factors of the device, if it has any
+    pub fn scaled(&self) -> Option<&ScaledDevice> {
+        match self {
+            DeviceFilter::Name(_) => None,
+            DeviceFilter::Scaled(device) => Some(device),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub enum ConfigAction {
     KeyboardInput {
-        modifiers: Vec<key::ConfigKey>,
-        sequence: Vec<key::ConfigKey>,
+        #[serde(default)]
+        modifiers: Vec<ConfigKey>,
+        #[serde(default)]
+        sequence: Vec<ConfigKey>,
+        /// Keys like `"ctrl+alt+t"`, adding to the modifiers and pressing the
+        /// last key after the sequence
+        #[serde(default, deserialize_with = "given")]
+        keys: Option<KeyChord>,
+        /// Milliseconds to wait between key presses and releases, for
+        /// applications that drop keys coming too fast. The waits of all the
+        /// keys add up to a second at most. Default: 0
+        #[serde(default)]
+        key_delay_ms: u32,
+        /// Times to press the sequence, with the modifiers held throughout.
+        /// Default: 1
+        #[serde(default = "default_repeat")]
+        repeat: u32,
+        /// Press all the sequence keys down before releasing any, like a
+        /// chord, instead of pressing and releasing each in turn. Default:
+        /// false
+        #[serde(default)]
+        chord: bool,
+        /// `Tap` the keys, or `Hold` them down until the next gesture begins.
+        /// Default: the config's `key_mode`
+        #[serde(default, deserialize_with = "given")]
+        mode: Option<KeyMode>,
+        /// Send the keys to this window instead of the focused one, when
+        /// there is such a window. Works only in X11
+        #[serde(default)]
+        target: Option<WindowTarget>,
+    },
+    /// Press the key with the modifiers and keep them down for `hold_ms`, like
+    /// a long press. Gestures are still handled while the keys are held
+    KeyHold {
+        #[serde(default)]
+        modifiers: Vec<ConfigKey>,
+        key: ConfigKey,
+        hold_ms: u32,
     },
     ExecuteCommand {
         path: String,
         args: Vec<String>,
+        /// Directory to run in. Relative to the home directory, and expanded
+        /// like the path. Default: the directory of wzmach
+        #[serde(default)]
+        cwd: Option<String>,
+        /// Environment variables to set for the command, with the values
+        /// expanded like the path. Default: none
+        #[serde(default)]
+        env: Option<HashMap<String, String>>,
+        /// Kill the command if it still runs after this many milliseconds.
+        /// Default: never
+        #[serde(default)]
+        timeout_ms: Option<u32>,
+        /// Pass the gesture that ran the command in the `WZMACH_GESTURE`,
+        /// `WZMACH_FINGERS`, `WZMACH_DIRECTION` and `WZMACH_SCALE` variables,
+        /// so that one script can handle several gestures. Default: false
+        #[serde(default)]
+        gesture_env: bool,
     },
     InlineScript {
         code: String,
+        /// Run in a shell that's kept running, which starts faster and keeps
+        /// the variables set between gestures. Default: false
+        #[serde(default)]
+        persistent: bool,
+    },
+    /// Run in `$SHELL`, or in `sh` when it's not set
+    ShellCommand {
+        command: String,
+        /// Like for `ExecuteCommand`
+        #[serde(default)]
+        timeout_ms: Option<u32>,
+        /// Like for `ExecuteCommand`
+        #[serde(default)]
+        gesture_env: bool,
+    },
+    /// Run Lua code, in a state kept between gestures. It sees the gesture in
+    /// the `gesture` table, and can call `window()`, `emit_keys("ctrl+t")`
+    /// and `run_command("...")`. Needs wzmach built with the lua feature
+    LuaScript { code: String },
+    /// Scroll by that many wheel clicks. Positive is right and up
+    MouseScroll {
+        #[serde(default)]
+        horizontal: i32,
+        #[serde(default)]
+        vertical: i32,
+    },
+    /// Move the pointer by that many pixels. Positive is right and down
+    MouseMove {
+        #[serde(default)]
+        dx: i32,
+        #[serde(default)]
+        dy: i32,
+        /// With a continuous trigger, also move the pointer along with the
+        /// fingers, by their motion times this. Default: none
+        #[serde(default)]
+        speed: Option<f64>,
+    },
+    /// Click, press or release a button. Dragging needs a continuous trigger
+    MouseButton {
+        button: MouseButtonKind,
+        #[serde(default)]
+        action: ButtonAction,
+    },
+    /// With a continuous trigger, execute the action every time the gesture
+    /// moves by `step`
+    Progressive {
+        step: f64,
+        /// Response curve scaling the progress, for example to scroll further
+        /// with fast swipes. Default: Linear
+        #[serde(default)]
+        curve: Curve,
+        /// Factor for the progress after the curve. Default: 1
+        #[serde(default = "default_gain")]
+        gain: f64,
+        action: Box<ConfigAction>,
+    },
+    /// Execute all of these in order
+    Sequence(Vec<ConfigAction>),
+    /// Call a method over DBus. `bus` is `session`, `system`, or a bus address
+    DBusCall {
+        bus: String,
+        destination: String,
+        path: String,
+        interface: String,
+        method: String,
+        #[serde(default)]
+        args: Vec<action::DBusArg>,
+    },
+    /// Type the text, with `xdotool` in X11 and `wtype` in wayland
+    TypeText { text: String },
+    /// Show a desktop notification
+    Notify {
+        summary: String,
+        #[serde(default)]
+        body: Option<String>,
+        /// Icon name from the icon theme, or path to an image
+        #[serde(default)]
+        icon: Option<String>,
+    },
+    /// Run a sway command over its IPC socket, like `workspace next`
+    SwayCommand { command: String },
+    /// Run a Hyprland dispatcher over its IPC socket, like `hyprctl dispatch`
+    HyprlandDispatch {
+        dispatcher: String,
+        #[serde(default)]
+        args: String,
+    },
+    /// Switch workspaces through sway or Hyprland in wayland, and through the
+    /// window manager in X11
+    WorkspaceSwitch { direction: Workspace },
+    /// The action of that name in the config's `actions`
+    Ref(String),
+    /// Execute `then` when the focused window has this class, and `otherwise`
+    /// in other windows. The window is checked when the action executes
+    Conditional {
+        window_class: String,
+        then: Box<ConfigAction>,
+        otherwise: Box<ConfigAction>,
+    },
+    /// Execute `first`, then `second` the next time the trigger fires, and so
+    /// on. Starts over from `first` when the config is reloaded
+    Toggle {
+        first: Box<ConfigAction>,
+        second: Box<ConfigAction>,
     },
 }
 
 impl ConfigAction {
+    /// Does this action follow the progress of continuous triggers
+    fn progressive(&self) -> bool {
+        matches!(self, ConfigAction::Progressive { .. })
+            || self.drags()
+            || self.moves()
+            || self.has(ConfigAction::progressive)
+    }
+
+    /// Does this action move the pointer with the fingers, which needs a
+    /// continuous trigger
+    fn moves(&self) -> bool {
+        match self {
+            ConfigAction::MouseMove { speed, .. } => speed.is_some(),
+            _ => self.has(ConfigAction::moves),
+        }
+    }
+
+    /// Does this action drag a mouse button, which needs a continuous trigger
+    fn drags(&self) -> bool {
+        match self {
+            ConfigAction::MouseButton {
+                action: ButtonAction::Drag,
+                ..
+            } => true,
+            _ => self.has(ConfigAction::drags),
+        }
+    }
+
+    /// Whether any of the nested actions is like that
+    fn has(&self, f: fn(&ConfigAction) -> bool) -> bool {
+        match self {
+            ConfigAction::Progressive { action, .. } => f(action),
+            ConfigAction::Sequence(actions) => actions.iter().any(f),
+            ConfigAction::Conditional {
+                then, otherwise, ..
+            } => f(then) || f(otherwise),
+            ConfigAction::Toggle { first, second } => f(first) || f(second),
+            _ => false,
+        }
+    }
+
+    /// Replace references with the named actions they refer to
+    fn resolve(&self, named: &HashMap<String, ConfigAction>) -> Result<ConfigAction, String> {
+        self.resolve_within(named, &mut Vec::new())
+    }
+
+    /// `within` are the names being resolved already, which must not be
+    /// referred to again
+    fn resolve_within<'a>(
+        &'a self,
+        named: &'a HashMap<String, ConfigAction>,
+        within: &mut Vec<&'a str>,
+    ) -> Result<ConfigAction, String> {
+        Ok(match self {
+            ConfigAction::Ref(name) => {
+                if within.contains(&name.as_str()) {
+                    return Err(format!("Action {:?} refers to itself", name));
+                }
+                let action = named
+                    .get(name)
+                    .ok_or_else(|| format!("No action named {:?} in actions", name))?;
+                within.push(name);
+                let resolved = action.resolve_within(named, within)?;
+                within.pop();
+                resolved
+            }
+            ConfigAction::Progressive {
+                step,
+                curve,
+                gain,
+                action,
+            } => ConfigAction::Progressive {
+                step: *step,
+                curve: *curve,
+                gain: *gain,
+                action: Box::new(action.resolve_within(named, within)?),
+            },
+            ConfigAction::Sequence(actions) => ConfigAction::Sequence(
+                actions
+                    .iter()
+                    .map(|a| a.resolve_within(named, within))
+                    .collect::<Result<_, _>>()?,
+            ),
+            ConfigAction::Conditional {
+                window_class,
+                then,
+                otherwise,
+            } => ConfigAction::Conditional {
+                window_class: window_class.clone(),
+                then: Box::new(then.resolve_within(named, within)?),
+                otherwise: Box::new(otherwise.resolve_within(named, within)?),
+            },
+            ConfigAction::Toggle { first, second } => ConfigAction::Toggle {
+                first: Box::new(first.resolve_within(named, within)?),
+                second: Box::new(second.resolve_within(named, within)?),
+            },
+            // Into the lists, which is what the action is made from
+            ConfigAction::KeyboardInput {
+                modifiers,
+                sequence,
+                keys: Some(keys),
+                key_delay_ms,
+                repeat,
+                chord,
+                mode,
+                target,
+            } => ConfigAction::KeyboardInput {
+                modifiers: modifiers.iter().chain(&keys.modifiers).cloned().collect(),
+                sequence: sequence.iter().chain([&keys.key]).cloned().collect(),
+                keys: None,
+                key_delay_ms: *key_delay_ms,
+                repeat: *repeat,
+                chord: *chord,
+                mode: *mode,
+                target: target.clone(),
+            },
+            other => other.clone(),
+        })
+    }
+
+    /// Flip the direction of scrolling, in nested actions too
+    fn reverse_scroll(&mut self) {
+        match self {
+            ConfigAction::MouseScroll {
+                horizontal,
+                vertical,
+            } => {
+                *horizontal = -*horizontal;
+                *vertical = -*vertical;
+            }
+            ConfigAction::Progressive { action, .. } => action.reverse_scroll(),
+            ConfigAction::Sequence(actions) => {
+                actions.iter_mut().for_each(ConfigAction::reverse_scroll)
+            }
+            ConfigAction::Conditional {
+                then, otherwise, ..
+            } => {
+                then.reverse_scroll();
+                otherwise.reverse_scroll();
+            }
+            ConfigAction::Toggle { first, second } => {
+                first.reverse_scroll();
+                second.reverse_scroll();
+            }
+            _ => (),
+        }
+    }
+
+    /// Give the mode to the `KeyboardInput` actions that have none
+    fn default_key_mode(&mut self, default: KeyMode) {
+        match self {
+            ConfigAction::KeyboardInput { mode, .. } => {
+                mode.get_or_insert(default);
+            }
+            ConfigAction::Progressive { action, .. } => action.default_key_mode(default),
+            ConfigAction::Sequence(actions) => {
+                actions.iter_mut().for_each(|a| a.default_key_mode(default))
+            }
+            ConfigAction::Conditional {
+                then, otherwise, ..
+            } => {
+                then.default_key_mode(default);
+                otherwise.default_key_mode(default);
+            }
+            ConfigAction::Toggle { first, second } => {
+                first.default_key_mode(default);
+                second.default_key_mode(default);
+            }
+            _ => (),
+        }
+    }
+
+    /// Reject actions that would do nothing or can't be created
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            ConfigAction::KeyboardInput {
+                modifiers,
+                sequence,
+                ..
+            } if modifiers.is_empty() && sequence.is_empty() => {
+                Err("KeyboardInput has no keys to press".to_string())
+            }
+            ConfigAction::KeyboardInput { repeat: 0, .. } => {
+                Err("KeyboardInput repeat must be at least 1".to_string())
+            }
+            ConfigAction::KeyboardInput {
+                modifiers,
+                sequence,
+                keys,
+                key_delay_ms,
+                repeat,
+                ..
+            } if key_delays(modifiers, sequence, keys.as_ref(), *key_delay_ms, *repeat)
+                > MAX_KEY_DELAYS_MS =>
+            {
+                Err(format!(
+                    "KeyboardInput would wait {} ms between its keys, without handling gestures. It can wait up to {} ms",
+                    key_delays(modifiers, sequence, keys.as_ref(), *key_delay_ms, *repeat),
+                    MAX_KEY_DELAYS_MS
+                ))
+            }
+            ConfigAction::KeyboardInput {
+                target:
+                    Some(WindowTarget {
+                        class: None,
+                        title: None,
+                    }),
+                ..
+            } => Err("KeyboardInput target needs a class or a title".to_string()),
+            ConfigAction::KeyHold { hold_ms: 0, .. } => {
+                Err("KeyHold hold_ms must be positive".to_string())
+            }
+            ConfigAction::MouseMove {
+                dx: 0,
+                dy: 0,
+                speed: None,
+            } => Err("MouseMove has no dx, dy or speed to move by".to_string()),
+            ConfigAction::MouseMove {
+                speed: Some(speed), ..
+            } if !(*speed > 0.0 && speed.is_finite()) => Err(format!(
+                "MouseMove speed must be positive, but is {}",
+                speed
+            )),
+            ConfigAction::TypeText { text } if text.is_empty() => {
+                Err("TypeText has no text to type".to_string())
+            }
+            ConfigAction::Notify { summary, .. } if summary.is_empty() => {
+                Err("Notify has no summary to show".to_string())
+            }
+            #[cfg(feature = "lua")]
+            ConfigAction::LuaScript { code } => action::LuaScriptAction::check(code)
+                .map_err(|e| format!("LuaScript doesn't compile: {}", e)),
+            #[cfg(not(feature = "lua"))]
+            ConfigAction::LuaScript { .. } => {
+                Err("LuaScript needs wzmach built with the lua feature".to_string())
+            }
+            ConfigAction::SwayCommand { command } if command.trim().is_empty() => {
+                Err("SwayCommand has no command to run".to_string())
+            }
+            ConfigAction::HyprlandDispatch { dispatcher, .. } if dispatcher.trim().is_empty() => {
+                Err("HyprlandDispatch has no dispatcher to run".to_string())
+            }
+            ConfigAction::WorkspaceSwitch {
+                direction: Workspace::Index(0),
+            } => Err("WorkspaceSwitch indices count from 1".to_string()),
+            ConfigAction::Progressive { step, .. } if !(step.is_finite() && *step > 0.0) => Err(
+                format!("Progressive step must be positive, but is {}", step),
+            ),
+            ConfigAction::Progressive { gain, .. } if !(gain.is_finite() && *gain > 0.0) => Err(
+                format!("Progressive gain must be positive, but is {}", gain),
+            ),
+            ConfigAction::Progressive { action, .. } => action.validate(),
+            ConfigAction::ExecuteCommand {
+                timeout_ms: Some(0),
+                ..
+            }
+            | ConfigAction::ShellCommand {
+                timeout_ms: Some(0),
+                ..
+            } => Err("Command timeout_ms must be positive".to_string()),
+            ConfigAction::Sequence(actions) if actions.is_empty() => {
+                Err("Sequence has no actions".to_string())
+            }
+            ConfigAction::Sequence(actions) => actions.iter().try_for_each(ConfigAction::validate),
+            ConfigAction::Conditional {
+                then, otherwise, ..
+            } => {
+                then.validate()?;
+                otherwise.validate()
+            }
+            ConfigAction::Toggle { first, second } => {
+                first.validate()?;
+                second.validate()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Create the action. Keyboard and mouse actions need the input device,
+    /// so without it they can't be created and this returns `None`. Built
+    /// without uinput, mouse actions only log what they would send, and so do
+    /// keyboard actions without the device. Conditional actions check the
+    /// focused window in `windows`
     pub fn make(
         self,
-        input_device: &std::rc::Rc<std::cell::RefCell<uinput::Device>>,
-    ) -> Box<dyn action::Action> {
-        match self {
+        is_wayland: bool,
+        input_device: Option<&action::InputDevice>,
+        windows: Option<&Windows>,
+    ) -> Option<Box<dyn action::Action>> {
+        if self.is_send() {
+            return self.make_send(is_wayland).map(|a| a as _);
+        }
+        Some(match self {
+            ConfigAction::KeyboardInput {
+                modifiers,
+                sequence,
+                key_delay_ms,
+                repeat,
+                chord,
+                mode,
+                target,
+                ..
+            } if cfg!(feature = "uinput") || input_device.is_some() => {
+                let mode = mode.unwrap_or_default();
+                let keys = action::KeyboardInputAction {
+                    device: input_device?.clone(),
+                    modifiers: modifiers.iter().map(|x| x.0).collect(),
+                    sequence: sequence.iter().map(|x| x.0).collect(),
+                    key_delay: std::time::Duration::from_millis(key_delay_ms.into()),
+                    repeat,
+                    chord,
+                    mode,
+                };
+                match target {
+                    Some(target) if mode == KeyMode::Hold => {
+                        log::warn!(
+                            "KeyboardInput target {:?} can't hold keys, holding them in the focused window",
+                            target
+                        );
+                        Box::new(keys)
+                    }
+                    Some(target) if is_wayland => {
+                        log::warn!(
+                            "KeyboardInput target {:?} works only in X11, sending keys to the focused window",
+                            target
+                        );
+                        Box::new(keys)
+                    }
+                    Some(target) => Box::new(action::WindowKeysAction {
+                        target,
+                        keys,
+                        x11: None,
+                    }),
+                    None => Box::new(keys),
+                }
+            }
             ConfigAction::KeyboardInput {
                 modifiers,
                 sequence,
-            } => Box::new(action::KeyboardInputAction {
-                device: input_device.clone(),
-                modifiers: modifiers.iter().map(|x| x.0).collect(),
-                sequence: sequence.iter().map(|x| x.0).collect(),
+                repeat,
+                chord,
+                mode,
+                ..
+            } => Box::new(action::LogInputAction {
+                input: {
+                    let together = if chord { " together" } else { "" };
+                    match repeat {
+                        _ if mode == Some(KeyMode::Hold) => format!(
+                            "hold {:?} + {:?} until the next gesture",
+                            modifiers, sequence
+                        ),
+                        1 => format!("press {:?} + {:?}{}", modifiers, sequence, together),
+                        n => format!(
+                            "press {:?} + {:?}{} {} times",
+                            modifiers, sequence, together, n
+                        ),
+                    }
+                },
             }),
-            ConfigAction::ExecuteCommand { path, args } => {
-                Box::new(action::ExecuteCommandAction { path, args })
+            ConfigAction::KeyHold {
+                modifiers,
+                key,
+                hold_ms,
+            } if cfg!(feature = "uinput") || input_device.is_some() => {
+                Box::new(action::KeyHoldAction {
+                    device: input_device?.clone(),
+                    keys: modifiers.iter().chain([&key]).map(|x| x.0).collect(),
+                    hold: std::time::Duration::from_millis(hold_ms.into()),
+                })
             }
-            ConfigAction::InlineScript { code: command } => {
-                Box::new(action::InlineScriptAction { command })
+            ConfigAction::KeyHold {
+                modifiers,
+                key,
+                hold_ms,
+            } => Box::new(action::LogInputAction {
+                input: format!("hold {:?} + {:?} for {}ms", modifiers, key, hold_ms),
+            }),
+            #[cfg(feature = "uinput")]
+            ConfigAction::MouseScroll {
+                horizontal,
+                vertical,
+            } => Box::new(action::MouseScrollAction {
+                device: input_device?.clone(),
+                horizontal,
+                vertical,
+            }),
+            #[cfg(not(feature = "uinput"))]
+            ConfigAction::MouseScroll {
+                horizontal,
+                vertical,
+            } => Box::new(action::LogInputAction {
+                input: format!(
+                    "scroll by {} horizontally, {} vertically",
+                    horizontal, vertical
+                ),
+            }),
+            #[cfg(feature = "uinput")]
+            ConfigAction::MouseMove { dx, dy, speed } => Box::new(action::MouseMoveAction {
+                device: input_device?.clone(),
+                dx,
+                dy,
+                speed,
+                remainder: (0.0, 0.0),
+            }),
+            #[cfg(not(feature = "uinput"))]
+            ConfigAction::MouseMove { dx, dy, speed } => Box::new(action::LogInputAction {
+                input: match speed {
+                    None => format!("move the pointer by {}, {}", dx, dy),
+                    Some(speed) => format!(
+                        "move the pointer by {}, {}, and with the fingers {} times as far",
+                        dx, dy, speed
+                    ),
+                },
+            }),
+            #[cfg(feature = "uinput")]
+            ConfigAction::MouseButton { button, action } => Box::new(action::MouseButtonAction {
+                device: input_device?.clone(),
+                button: action::MouseButtonAction::button(button),
+                action,
+                remainder: (0.0, 0.0),
+            }),
+            #[cfg(not(feature = "uinput"))]
+            ConfigAction::MouseButton { button, action } => Box::new(action::LogInputAction {
+                input: format!("{:?} {:?} mouse button", action, button).to_lowercase(),
+            }),
+            ConfigAction::Progressive {
+                step,
+                curve,
+                gain,
+                action,
+            } => {
+                let action = action.make(is_wayland, input_device, windows)?;
+                match action::ProgressiveAction::new(step, curve, gain, action) {
+                    Ok(progressive) => Box::new(progressive),
+                    Err(action::ActionError(e)) => {
+                        log::error!("{}", e);
+                        return None;
+                    }
+                }
+            }
+            ConfigAction::Sequence(actions) => Box::new(action::SequenceAction {
+                actions: actions
+                    .into_iter()
+                    .map(|a| a.make(is_wayland, input_device, windows))
+                    .collect::<Option<_>>()?,
+            }),
+            ConfigAction::Conditional {
+                window_class,
+                then,
+                otherwise,
+            } => Box::new(action::ConditionalAction {
+                window_class,
+                then: then.make(is_wayland, input_device, windows)?,
+                otherwise: otherwise.make(is_wayland, input_device, windows)?,
+                windows: windows.cloned(),
+                taken: false,
+            }),
+            ConfigAction::Toggle { first, second } => Box::new(action::ToggleAction {
+                first: first.make(is_wayland, input_device, windows)?,
+                second: second.make(is_wayland, input_device, windows)?,
+                flipped: false,
+            }),
+            #[cfg(feature = "lua")]
+            ConfigAction::LuaScript { code } => Box::new(action::LuaScriptAction::new(
+                code,
+                emit_keys(input_device),
+                windows.cloned(),
+            )),
+            // Rejected when validating
+            #[cfg(not(feature = "lua"))]
+            ConfigAction::LuaScript { .. } => return None,
+            // References are resolved before the actions are made
+            ConfigAction::Ref(_) => return None,
+            _ => unreachable!("made by make_send"),
+        })
+    }
+
+    /// Mocks in place of the actions that would be made, inside the same
+    /// Sequence, Progressive, Conditional and Toggle actions. Needs no input
+    /// device, and is `None` only where `make` fails for the Progressive
+    /// actions too
+    pub fn make_mock(
+        self,
+        windows: Option<&Windows>,
+        calls: &MockCalls,
+    ) -> Option<Box<dyn action::Action>> {
+        Some(match self {
+            ConfigAction::Progressive {
+                step,
+                curve,
+                gain,
+                action,
+            } => {
+                let action = action.make_mock(windows, calls)?;
+                match action::ProgressiveAction::new(step, curve, gain, action) {
+                    Ok(progressive) => Box::new(progressive),
+                    Err(action::ActionError(e)) => {
+                        log::error!("{}", e);
+                        return None;
+                    }
+                }
+            }
+            ConfigAction::Sequence(actions) => Box::new(action::SequenceAction {
+                actions: actions
+                    .into_iter()
+                    .map(|a| a.make_mock(windows, calls))
+                    .collect::<Option<_>>()?,
+            }),
+            ConfigAction::Conditional {
+                window_class,
+                then,
+                otherwise,
+            } => Box::new(action::ConditionalAction {
+                window_class,
+                then: then.make_mock(windows, calls)?,
+                otherwise: otherwise.make_mock(windows, calls)?,
+                windows: windows.cloned(),
+                taken: false,
+            }),
+            ConfigAction::Toggle { first, second } => Box::new(action::ToggleAction {
+                first: first.make_mock(windows, calls)?,
+                second: second.make_mock(windows, calls)?,
+                flipped: false,
+            }),
+            action => Box::new(action::MockAction {
+                action,
+                calls: calls.clone(),
+            }),
+        })
+    }
+
+    /// Whether the action needs neither the input device nor the windows, and
+    /// has no actions inside, so that it can run on another thread
+    pub fn is_send(&self) -> bool {
+        matches!(
+            self,
+            ConfigAction::ExecuteCommand { .. }
+                | ConfigAction::InlineScript { .. }
+                | ConfigAction::ShellCommand { .. }
+                | ConfigAction::DBusCall { .. }
+                | ConfigAction::TypeText { .. }
+                | ConfigAction::Notify { .. }
+                | ConfigAction::SwayCommand { .. }
+                | ConfigAction::HyprlandDispatch { .. }
+                | ConfigAction::WorkspaceSwitch { .. }
+        )
+    }
+
+    /// Create the action if it `is_send`
+    pub fn make_send(self, is_wayland: bool) -> Option<Box<dyn action::Action + Send>> {
+        Some(match self {
+            // No shell is involved, so do the common expansions here
+            ConfigAction::ExecuteCommand {
+                path,
+                args,
+                cwd,
+                env,
+                timeout_ms,
+                gesture_env,
+            } => Box::new(action::ExecuteCommandAction {
+                path: expand::expand(&path),
+                args: args.iter().map(|a| expand::expand(a)).collect(),
+                cwd: cwd.map(|c| {
+                    let home = std::env::var_os("HOME").unwrap_or_default();
+                    std::path::Path::new(&home).join(expand::expand(&c))
+                }),
+                env: env
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(k, v)| (k, expand::expand(&v)))
+                    .collect(),
+                timeout: timeout_ms.map(|t| Duration::from_millis(t.into())),
+                gesture_env,
+                gesture: None,
+            }),
+            ConfigAction::InlineScript {
+                code: command,
+                persistent,
+            } => Box::new(action::InlineScriptAction {
+                command,
+                shell: persistent.then(action::PersistentShell::default),
+            }),
+            ConfigAction::ShellCommand {
+                command,
+                timeout_ms,
+                gesture_env,
+            } => Box::new(action::ShellCommandAction {
+                shell: std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string()),
+                command,
+                timeout: timeout_ms.map(|t| Duration::from_millis(t.into())),
+                gesture_env,
+                gesture: None,
+            }),
+            ConfigAction::DBusCall {
+                bus,
+                destination,
+                path,
+                interface,
+                method,
+                args,
+            } => Box::new(action::DBusCallAction {
+                bus,
+                destination,
+                path,
+                interface,
+                method,
+                args,
+            }),
+            ConfigAction::TypeText { text } => {
+                Box::new(action::TypeTextAction { text, is_wayland })
+            }
+            ConfigAction::Notify {
+                summary,
+                body,
+                icon,
+            } => Box::new(action::NotifyAction {
+                summary,
+                body,
+                icon: icon.map(|i| expand::expand(&i)),
+            }),
+            ConfigAction::SwayCommand { command } => {
+                Box::new(action::SwayCommandAction { command })
+            }
+            ConfigAction::HyprlandDispatch { dispatcher, args } => {
+                Box::new(action::HyprlandDispatchAction { dispatcher, args })
+            }
+            ConfigAction::WorkspaceSwitch { direction } => {
+                Box::new(action::WorkspaceSwitchAction {
+                    direction,
+                    is_wayland,
+                })
+            }
+            _ => return None,
+        })
+    }
+}
+
+impl ConfigTrigger {
+    /// Whether this fires on the gestures `other` fires on, and executes
+    /// wherever `other` would
+    fn covers(&self, other: &ConfigTrigger) -> bool {
+        fn wider<T: PartialEq>(this: &Option<T>, other: &Option<T>) -> bool {
+            this.is_none() || this == other
+        }
+        self.trigger == other.trigger
+            && self.fire_on == other.fire_on
+            && self.cancel_threshold == other.cancel_threshold
+            // A fallback only fires for what the others left alone
+            && self.fallback == other.fallback
+            // Measured from the turn, it fires no later
+            && (self.from_turn || !other.from_turn)
+            && wider(&self.window_class, &other.window_class)
+            && wider(&self.region, &other.region)
+            && wider(&self.require_modifier, &other.require_modifier)
+            && (!self.disable_on_fullscreen || other.disable_on_fullscreen)
+    }
+}
+
+/// Pairs of triggers, by index, where the first takes every gesture the
+/// second would execute for. Unless all fire, the second never executes then
+fn overlaps(triggers: &[&ConfigTrigger]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for (i, a) in triggers.iter().enumerate() {
+        for (j, b) in triggers.iter().enumerate().skip(i + 1) {
+            // Like the dispatcher: priority first, then the order
+            if a.priority >= b.priority && a.covers(b) {
+                pairs.push((i, j));
+            } else if b.priority > a.priority && b.covers(a) {
+                pairs.push((j, i));
             }
         }
     }
+    pairs
+}
+
+/// Presses the chords that LuaScript actions emit, like the KeyboardInput
+/// actions would, and fails naming a key that doesn't exist
+#[cfg(feature = "lua")]
+fn emit_keys(input_device: Option<&action::InputDevice>) -> action::EmitKeys {
+    use action::Action;
+    let mut keys = input_device.map(|device| action::KeyboardInputAction {
+        device: device.clone(),
+        modifiers: Vec::new(),
+        sequence: Vec::new(),
+        key_delay: Duration::ZERO,
+        repeat: 1,
+        chord: false,
+        mode: KeyMode::Tap,
+    });
+    Box::new(move |chord| {
+        let chord: KeyChord = chord.parse()?;
+        let result = match keys.as_mut() {
+            Some(keys) => {
+                keys.modifiers = chord.modifiers.iter().map(|x| x.0).collect();
+                keys.sequence = vec![chord.key.0];
+                keys.execute()
+            }
+            None if cfg!(feature = "uinput") => {
+                return Err("No input device to emit keys with".to_string())
+            }
+            None => action::LogInputAction {
+                input: format!("press {:?} + {:?}", chord.modifiers, [chord.key]),
+            }
+            .execute(),
+        };
+        result.map_err(|e| e.0)
+    })
 }
 
+/// Calls of the mocks made by `make_mock`
+pub type MockCalls = action::MockCalls<ConfigAction>;
+
 /* Impls */
 
+/// Decode a config, or the part of it that `T` takes, without checking it
+fn decode<'de, T: Deserialize<'de>>(s: &'de str, format: Format) -> Result<T, ConfigError> {
+    match format {
+        Format::Ron => {
+            let mut de = ron::Deserializer::from_str(s).map_err(|e| ConfigError::ron(s, e))?;
+            let result = T::deserialize(&mut de).and_then(|c| de.end().map(|_| c));
+            result.map_err(|e| ConfigError::ron(s, de.span_error(e)))
+        }
+        Format::Toml => Ok(toml::from_str(s)?),
+    }
+}
+
+/// Set when the field is there, without the `Some` that ron would want
+pub(super) fn given<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// An action, or a list of them to execute one after the other
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "an action, or a list of actions")]
+enum OneOrMany {
+    One(ConfigAction),
+    Many(Vec<ConfigAction>),
+    Invalid(InvalidAction),
+}
+
+/// Take a list of actions as a `Sequence` of them
+fn one_or_many<'de, D>(deserializer: D) -> Result<ConfigAction, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(action) => Ok(action),
+        OneOrMany::Many(actions) => Ok(ConfigAction::Sequence(actions)),
+        OneOrMany::Invalid(InvalidAction(error)) => Err(serde::de::Error::custom(error)),
+    }
+}
+
+/// Why the action, or one in the list, is wrong. Untagged enums only tell
+/// that nothing matched, so this reads it again for the error
+struct InvalidAction(String);
+
+impl<'de> Deserialize<'de> for InvalidAction {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(InvalidActionVisitor)
+    }
+}
+
+struct InvalidActionVisitor;
+impl<'de> serde::de::Visitor<'de> for InvalidActionVisitor {
+    type Value = InvalidAction;
+
+    fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "an action, or a list of actions")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, data: &str) -> Result<InvalidAction, E> {
+        use serde::de::IntoDeserializer;
+        let action = ConfigAction::deserialize(data.into_deserializer());
+        Ok(InvalidAction(
+            action.err().map_or_else(String::new, |e: E| e.to_string()),
+        ))
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, map: A) -> Result<InvalidAction, A::Error> {
+        let action = ConfigAction::deserialize(serde::de::value::MapAccessDeserializer::new(map));
+        Ok(InvalidAction(
+            action.err().map_or_else(String::new, |e| e.to_string()),
+        ))
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> Result<InvalidAction, A::Error> {
+        loop {
+            match seq.next_element::<ConfigAction>() {
+                Ok(Some(_)) => (),
+                Ok(None) => return Ok(InvalidAction(String::new())),
+                Err(e) => return Ok(InvalidAction(e.to_string())),
+            }
+        }
+    }
+}
+
+/// File formats a config can be written in
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Format {
+    Ron,
+    Toml,
+}
+
+impl Format {
+    /// Guess the format from file extension. Unknown extensions are read as RON
+    pub fn from_path(path: &std::path::Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("ron") => Format::Ron,
+            _ => {
+                log::debug!("Unknown config extension, reading as RON");
+                Format::Ron
+            }
+        }
+    }
+}
+
 impl Config {
-    pub fn load<P>(path: P) -> std::io::Result<Config>
+    /// The config file to use when none is given: the first that exists of
+    /// `wzmach.ron`, `wzmach.toml`, `wzmach/config.ron` and `wzmach/config.toml`
+    /// in `$XDG_CONFIG_HOME`, then in `~/.config`, and then in each of
+    /// `$XDG_CONFIG_DIRS`, which is `/etc/xdg` when unset
+    pub fn discover() -> Option<PathBuf> {
+        discover(|name| std::env::var_os(name), |path| path.is_file())
+    }
+
+    /// Read, decode and check the config file. The errors are left for the
+    /// caller to report
+    pub fn load<P>(path: P) -> Result<Config, ConfigError>
     where
         P: AsRef<std::path::Path>,
     {
-        let path = path.as_ref();
-        log::trace!("Reading {}", path.display());
-        let s = std::fs::read_to_string(path).map_err(|e| {
-            log::error!("Error reading config: {}", e);
-            e
-        })?;
-        ron::from_str(&s).map_err(|e| {
-            log::error!("Error decoding RON: {}", e);
-            std::io::Error::new(std::io::ErrorKind::Other, e)
-        })
+        let config = include::load(path.as_ref())?;
+        config.validate().map_err(ConfigError::Invalid)?;
+        Ok(config)
+    }
+
+    pub fn parse(s: &str, format: Format) -> Result<Config, ConfigError> {
+        let mut config: Config = decode(s, format)?;
+        config.expand_keys().map_err(ConfigError::Invalid)?;
+        config.validate().map_err(ConfigError::Invalid)?;
+        Ok(config)
     }
 
+    /// Turn the `keys` of triggers into the actions they're short for
+    fn expand_keys(&mut self) -> Result<(), String> {
+        let triggers = self
+            .global_triggers
+            .iter_mut()
+            .chain(&mut self.x11_triggers)
+            .chain(&mut self.wayland_triggers)
+            .chain(self.profiles.values_mut().flatten());
+        for t in triggers {
+            let chord = match t.keys.take() {
+                Some(c) => c,
+                None if t.action == no_action() => {
+                    return Err(format!("{:?} has neither an action nor keys", t.trigger))
+                }
+                None => continue,
+            };
+            if t.action != no_action() {
+                return Err(format!("{:?} has both an action and keys", t.trigger));
+            }
+            t.action = ConfigAction::KeyboardInput {
+                modifiers: chord.modifiers,
+                sequence: vec![chord.key],
+                keys: None,
+                key_delay_ms: 0,
+                repeat: 1,
+                chord: false,
+                mode: None,
+                target: None,
+            };
+        }
+        Ok(())
+    }
+
+    /// Check what the format itself can't express
+    fn validate(&self) -> Result<(), String> {
+        // Over 45 degrees diagonals would take over the cardinals completely
+        if !(self.angle_tolerance > 0.0 && self.angle_tolerance < 45.0) {
+            return Err(format!(
+                "angle_tolerance must be between 0 and 45 degrees, but is {}",
+                self.angle_tolerance
+            ));
+        }
+        if !(self.edge_margin > 0.0 && self.edge_margin < 0.5) {
+            return Err(format!(
+                "edge_margin must be between 0 and 0.5, but is {}",
+                self.edge_margin
+            ));
+        }
+        if self.swipe_distance == 0 || self.shear_distance == 0 {
+            return Err("swipe_distance and shear_distance must be above 0".to_string());
+        }
+        // At 100% the fingers would have to meet
+        match self.pinch_mode {
+            PinchMode::Absolute
+                if !(self.pinch_distance.is_finite() && self.pinch_distance > 1.0) =>
+            {
+                return Err(format!(
+                    "pinch_distance must be a scale above 1, but is {}",
+                    self.pinch_distance
+                ));
+            }
+            PinchMode::Relative if !(self.pinch_distance > 0.0 && self.pinch_distance < 100.0) => {
+                return Err(format!(
+                    "pinch_distance must be between 0 and 100 percent with pinch_mode Relative, but is {}",
+                    self.pinch_distance
+                ));
+            }
+            _ => (),
+        }
+        if !(self.rotation_distance.is_finite() && self.rotation_distance > 0.0) {
+            return Err(format!(
+                "rotation_distance must be above 0 degrees, but is {}",
+                self.rotation_distance
+            ));
+        }
+        if !(self.swipe_deadzone >= 0.0 && self.swipe_deadzone.is_finite()) {
+            return Err(format!(
+                "swipe_deadzone can't be negative, but is {}",
+                self.swipe_deadzone
+            ));
+        }
+        // uinput takes names of up to 80 bytes, with the nul
+        if self.virtual_device.name.is_empty() || self.virtual_device.name.len() >= 80 {
+            return Err(format!(
+                "virtual_device name must be 1 to 79 bytes long, but is {:?}",
+                self.virtual_device.name
+            ));
+        }
+        if self.device_filter.as_ref().is_some_and(Vec::is_empty) {
+            return Err("device_filter is empty, so no device would be used".to_string());
+        }
+        let scaled = self
+            .device_filter
+            .iter()
+            .flatten()
+            .filter_map(DeviceFilter::scaled);
+        for device in scaled {
+            if [device.distance, device.pinch, device.rotation]
+                .iter()
+                .any(|f| !(f.is_finite() && *f > 0.0))
+            {
+                return Err(format!(
+                    "Factors of device {:?} in device_filter must be positive",
+                    device.name
+                ));
+            }
+        }
+        for action in self.actions.values() {
+            action.resolve(&self.actions)?.validate()?;
+        }
+        if let Some(profile) = &self.profile {
+            if !self.profiles.contains_key(profile) {
+                return Err(format!("profile {:?} isn't in profiles", profile));
+            }
+        }
+        self.global_triggers
+            .iter()
+            .chain(&self.x11_triggers)
+            .chain(&self.wayland_triggers)
+            .chain(self.profiles.values().flatten())
+            .try_for_each(|t| {
+                t.trigger.validate(self.pinch_mode)?;
+                let action = t.action.resolve(&self.actions)?;
+                action.validate()?;
+                if action.drags() && !t.trigger.continuous() {
+                    return Err(format!(
+                        "Dragging a mouse button needs a continuous trigger, but {:?} isn't",
+                        t.trigger
+                    ));
+                }
+                if action.moves() && !t.trigger.continuous() {
+                    return Err(format!(
+                        "Moving the pointer with the fingers needs a continuous trigger, but {:?} isn't",
+                        t.trigger
+                    ));
+                }
+                if let Some(cancel) = t.cancel_threshold {
+                    if !(cancel.is_finite() && cancel >= 0.0) {
+                        return Err(format!(
+                            "cancel_threshold of {:?} must be a distance of 0 or more",
+                            t.trigger
+                        ));
+                    }
+                    if t.trigger.continuous()
+                        || t.trigger.repeated()
+                        || t.fire_on != FireOn::Threshold
+                        || matches!(t.trigger, Trigger::Hold { .. } | Trigger::Sequence { .. })
+                    {
+                        return Err(format!(
+                            "cancel_threshold needs a trigger that fires once on the threshold, but {:?} doesn't",
+                            t.trigger
+                        ));
+                    }
+                }
+                if t.fallback
+                    && (t.trigger.continuous()
+                        || t.fire_on != FireOn::Threshold
+                        || matches!(t.trigger, Trigger::Sequence { .. }))
+                {
+                    return Err(format!(
+                        "Fallback trigger {:?} fires when the gesture ends, so it can't be continuous, a sequence, or have fire_on",
+                        t.trigger
+                    ));
+                }
+                if t.from_turn
+                    && (t.trigger.continuous()
+                        || t.fallback
+                        || t.fire_on != FireOn::Threshold
+                        || !matches!(t.trigger, Trigger::Swipe { .. } | Trigger::Shear { .. }))
+                {
+                    return Err(format!(
+                        "from_turn needs a swipe or shear that fires on the threshold as it goes, but {:?} isn't",
+                        t.trigger
+                    ));
+                }
+                if t.fire_on != FireOn::Threshold && t.trigger.continuous() {
+                    return Err(format!(
+                        "Continuous trigger {:?} can fire only on Threshold, not {:?}",
+                        t.trigger, t.fire_on
+                    ));
+                }
+                if t.fire_on == FireOn::Begin
+                    && matches!(
+                        t.trigger,
+                        Trigger::Swipe { min_velocity, max_velocity, min_duration_ms, .. }
+                            if min_velocity.is_some()
+                                || max_velocity.is_some()
+                                || min_duration_ms.is_some()
+                    )
+                {
+                    return Err(format!(
+                        "A swipe has no velocity or duration yet as it begins, so {:?} can't fire on Begin",
+                        t.trigger
+                    ));
+                }
+                if t.fire_on == FireOn::Begin
+                    && matches!(t.trigger, Trigger::Hold { duration_ms, .. } if duration_ms > 0)
+                {
+                    return Err(format!(
+                        "A timed hold hasn't lasted yet as it begins, so {:?} can't fire on Begin",
+                        t.trigger
+                    ));
+                }
+                if t.fire_on != FireOn::Threshold && matches!(t.trigger, Trigger::Sequence { .. }) {
+                    return Err(format!(
+                        "Sequence fires when its last step does, so can't fire on {:?}",
+                        t.fire_on
+                    ));
+                }
+                match t.repeat_ms {
+                    Some(_) if !t.trigger.continuous() => Err(format!(
+                        "repeat_ms needs a continuous trigger, but {:?} isn't",
+                        t.trigger
+                    )),
+                    Some(0) => Err(format!("repeat_ms of {:?} must not be 0", t.trigger)),
+                    _ => Ok(()),
+                }
+            })
+    }
+
+    /// Path of `event_socket`, expanded
+    pub fn event_socket_path(&self) -> Option<PathBuf> {
+        self.event_socket
+            .as_deref()
+            .map(|s| expand::expand(s).into())
+    }
+
+    /// Triggers that `make_triggers` would load that never execute, as another
+    /// takes all their gestures, or with `fire_all` that execute together
+    pub fn overlap_warnings(&self, is_wayland: bool, profile: Option<&str>) -> Vec<String> {
+        let server = if is_wayland {
+            &self.wayland_triggers
+        } else {
+            &self.x11_triggers
+        };
+        let triggers: Vec<&ConfigTrigger> = self
+            .global_triggers
+            .iter()
+            .chain(server)
+            .chain(
+                profile
+                    .and_then(|p| self.profiles.get(p))
+                    .into_iter()
+                    .flatten(),
+            )
+            .filter(|t| t.enabled)
+            .collect();
+        overlaps(&triggers)
+            .into_iter()
+            .map(|(first, second)| {
+                if self.fire_all {
+                    format!(
+                        "Triggers {} and {} both execute on the same gestures: {:?}",
+                        first, second, triggers[first].trigger
+                    )
+                } else {
+                    format!(
+                        "Trigger {} never executes, as trigger {} takes the same gestures first: {:?}",
+                        second, first, triggers[first].trigger
+                    )
+                }
+            })
+            .collect()
+    }
+
+    /// Create the triggers to recognize for this display server and the
+    /// profile, along with their actions. Triggers whose actions need an input
+    /// device are skipped when there's none. With `mock`, the actions are
+    /// mocks keeping their calls there instead
     pub fn make_triggers(
         self,
         is_wayland: bool,
-    ) -> (Vec<gesture::Trigger>, Vec<Box<dyn action::Action>>) {
-        let input_device = action::KeyboardInputAction::default_device();
-        self.global_triggers
+        profile: Option<&str>,
+        input_device: Option<&action::InputDevice>,
+        windows: Option<&Windows>,
+        mock: Option<&MockCalls>,
+    ) -> (Vec<gesture::Trigger>, Vec<Binding>) {
+        let (mut triggers, mut bindings) =
+            self.make_shared_triggers(is_wayland, input_device, windows, mock);
+        let (profile_triggers, profile_bindings) =
+            self.make_profile_triggers(is_wayland, profile, input_device, windows, mock);
+        triggers.extend(profile_triggers);
+        bindings.extend(profile_bindings);
+        (triggers, bindings)
+    }
+
+    /// The first part of `make_triggers`, with the global triggers and the
+    /// ones of the display server, which stay the same across profiles
+    pub fn make_shared_triggers(
+        &self,
+        is_wayland: bool,
+        input_device: Option<&action::InputDevice>,
+        windows: Option<&Windows>,
+        mock: Option<&MockCalls>,
+    ) -> (Vec<gesture::Trigger>, Vec<Binding>) {
+        let (name, server, unused) = if is_wayland {
+            ("wayland", &self.wayland_triggers, self.x11_triggers.len())
+        } else {
+            ("x11", &self.x11_triggers, self.wayland_triggers.len())
+        };
+        if unused > 0 {
+            log::debug!("Running on {}, leaving out {} triggers", name, unused);
+        }
+        let triggers = self.global_triggers.iter().chain(server);
+        self.make(triggers, is_wayland, input_device, windows, mock)
+    }
+
+    /// The rest of `make_triggers`, with the triggers of the profile. Warns of
+    /// the triggers that overlap, counting the shared ones
+    pub fn make_profile_triggers(
+        &self,
+        is_wayland: bool,
+        profile: Option<&str>,
+        input_device: Option<&action::InputDevice>,
+        windows: Option<&Windows>,
+        mock: Option<&MockCalls>,
+    ) -> (Vec<gesture::Trigger>, Vec<Binding>) {
+        for warning in self.overlap_warnings(is_wayland, profile) {
+            log::warn!("{}", warning);
+        }
+        let triggers = profile
+            .and_then(|p| self.profiles.get(p))
             .into_iter()
-            .chain(
-                if is_wayland {
-                    self.wayland_triggers
-                } else {
-                    self.x11_triggers
+            .flatten();
+        self.make(triggers, is_wayland, input_device, windows, mock)
+    }
+
+    fn make<'a>(
+        &self,
+        triggers: impl Iterator<Item = &'a ConfigTrigger>,
+        is_wayland: bool,
+        input_device: Option<&action::InputDevice>,
+        windows: Option<&Windows>,
+        mock: Option<&MockCalls>,
+    ) -> (Vec<gesture::Trigger>, Vec<Binding>) {
+        triggers
+            .filter(|x| {
+                if !x.enabled {
+                    log::debug!("Leaving out disabled trigger {:?}", x.trigger);
                 }
-                .into_iter(),
-            )
-            .map(|x| {
-                (
-                    x.trigger.make(
+                x.enabled
+            })
+            .cloned()
+            .filter_map(|x| {
+                let mut action = match x.action.resolve(&self.actions) {
+                    Ok(a) => a,
+                    Err(e) => {
+                        log::error!("{} for {:?}, skipping", e, x.trigger);
+                        return None;
+                    }
+                };
+                if self.natural_scroll {
+                    action.reverse_scroll();
+                }
+                action.default_key_mode(self.key_mode);
+                let progressive = action.progressive();
+                if x.trigger.continuous() && !progressive && x.repeat_ms.is_none() {
+                    log::warn!(
+                        "Continuous trigger {:?} needs a Progressive action or repeat_ms to follow the gesture",
+                        x.trigger
+                    );
+                }
+                if let Trigger::Swipe { fingers: 2, .. }
+                | Trigger::Shear { fingers: 2, .. } = x.trigger
+                {
+                    log::info!(
+                        "{:?} only fires if the touchpad reports 2 finger gestures, which most scroll with instead",
+                        x.trigger
+                    );
+                }
+                let trigger = x.trigger;
+                let sequence = matches!(trigger, Trigger::Sequence { .. });
+                // Only whole actions of triggers run on a thread, which keeps
+                // the calls of a trigger in order
+                let action = if let Some(calls) = mock {
+                    action.make_mock(windows, calls)
+                } else if action.is_send() {
+                    match action
+                        .make_send(is_wayland)
+                        .map(|a| action::BackgroundAction::new(a, x.on_error))
+                    {
+                        Some(Ok(a)) => Some(Box::new(a) as Box<dyn action::Action>),
+                        Some(Err(e)) => {
+                            log::error!(
+                                "Can't start a thread for the action of {:?}, skipping: {}",
+                                trigger,
+                                e
+                            );
+                            return None;
+                        }
+                        None => None,
+                    }
+                } else {
+                    action.make(is_wayland, input_device, windows)
+                };
+                let action = match action {
+                    Some(a) => a,
+                    None => {
+                        log::warn!(
+                            "Can't make the action of {:?}, which needs an input device or is invalid, skipping",
+                            trigger
+                        );
+                        return None;
+                    }
+                };
+                Some((
+                    trigger.make(
                         self.swipe_distance,
                         self.shear_distance,
                         self.pinch_distance,
+                        self.pinch_mode,
                         self.rotation_distance,
+                        self.angle_tolerance,
+                        self.edge_margin,
+                        x.cooldown_ms,
+                        x.repeat_ms,
+                        x.fire_on,
+                        x.cancel_threshold,
                     ),
-                    x.action.make(&input_device),
-                )
+                    Binding {
+                        action,
+                        window_class: x.window_class,
+                        disable_on_fullscreen: x.disable_on_fullscreen,
+                        region: x.region,
+                        priority: x.priority,
+                        on_error: x.on_error,
+                        fallback: x.fallback,
+                        from_turn: x.from_turn,
+                        sequence,
+                        require_key: x.require_modifier.map(|k| k.code()),
+                    },
+                ))
             })
             .unzip()
     }
 }
 
+impl Default for Config {
+    /// Same as an empty config file
+    fn default() -> Self {
+        Config {
+            swipe_distance: default_distance(),
+            shear_distance: default_distance(),
+            pinch_distance: default_pinch(),
+            pinch_mode: PinchMode::default(),
+            rotation_distance: default_rotation(),
+            angle_tolerance: default_angle_tolerance(),
+            edge_margin: default_edge_margin(),
+            swipe_deadzone: 0.0,
+            device_filter: None,
+            natural_scroll: false,
+            key_mode: KeyMode::default(),
+            fire_all: false,
+            rearm_on_lift: false,
+            coalesce_ms: default_coalesce(),
+            event_socket: None,
+            virtual_device: DeviceId::default(),
+            input_backend: InputBackend::default(),
+            include: Vec::new(),
+            actions: HashMap::new(),
+            global_triggers: default_triggers(),
+            x11_triggers: default_triggers(),
+            wayland_triggers: default_triggers(),
+            profiles: HashMap::new(),
+            profile: None,
+        }
+    }
+}
+
+/// Where [`Config::discover`] looks, with the environment and the files that
+/// exist given. Relative directories in the variables are ignored, like the
+/// XDG base directory spec says
+fn discover(
+    var: impl Fn(&str) -> Option<OsString>,
+    exists: impl Fn(&Path) -> bool,
+) -> Option<PathBuf> {
+    let absolute = |dir: PathBuf| Some(dir).filter(|d| d.is_absolute());
+    let home = var("XDG_CONFIG_HOME").map(PathBuf::from).and_then(absolute);
+    let dot_config = var("HOME").map(|home| PathBuf::from(home).join(".config"));
+    let system = var("XDG_CONFIG_DIRS")
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".into());
+    let system: Vec<PathBuf> = std::env::split_paths(&system)
+        .filter_map(absolute)
+        .collect();
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for dir in home.into_iter().chain(dot_config).chain(system) {
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs.iter()
+        .flat_map(|dir| {
+            [
+                "wzmach.ron",
+                "wzmach.toml",
+                "wzmach/config.ron",
+                "wzmach/config.toml",
+            ]
+            .map(|name| dir.join(name))
+        })
+        .find(|path| exists(path))
+}
+
 /* Serde defaults */
 
+/// Longest that the waits between the keys of a KeyboardInput can add up to,
+/// as the main loop waits with them
+const MAX_KEY_DELAYS_MS: u64 = 1000;
+
+/// Milliseconds the KeyboardInput waits in all, once before each key event
+fn key_delays(
+    modifiers: &[ConfigKey],
+    sequence: &[ConfigKey],
+    keys: Option<&KeyChord>,
+    key_delay_ms: u32,
+    repeat: u32,
+) -> u64 {
+    let modifiers = modifiers.len() + keys.map_or(0, |k| k.modifiers.len());
+    let taps = sequence.len() + usize::from(keys.is_some());
+    let events = 2 * taps as u64 * u64::from(repeat) + modifiers as u64;
+    events.saturating_mul(key_delay_ms.into())
+}
+
 fn default_distance() -> u32 {
     log::debug!("Using default distance");
     100
@@ -146,7 +1823,1000 @@ fn default_rotation() -> f64 {
     log::debug!("Using default rotation");
     60.0
 }
+fn default_angle_tolerance() -> f64 {
+    log::debug!("Using default angle tolerance");
+    20.0
+}
+fn default_edge_margin() -> f64 {
+    log::debug!("Using default edge margin");
+    0.1
+}
+fn default_enabled() -> bool {
+    true
+}
+fn default_coalesce() -> u32 {
+    log::debug!("Using default coalesce");
+    8
+}
+fn default_gain() -> f64 {
+    log::debug!("Using default gain");
+    1.0
+}
+fn default_repeat() -> u32 {
+    1
+}
+fn default_factor() -> f64 {
+    1.0
+}
+/// Stands in for the action left out of a trigger, until `keys` replace it.
+/// No action can have an empty name
+fn no_action() -> ConfigAction {
+    ConfigAction::Ref(String::new())
+}
+
 fn default_triggers() -> Vec<ConfigTrigger> {
     log::debug!("Using default triggers");
     Vec::new()
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Config, ConfigAction, ConfigKey, DeviceFilter, Format, ScaledDevice};
+    use crate::common::{Curve, DeviceId, InputBackend, KeyMode, PinchMode, WindowTarget};
+
+    #[test]
+    fn ron_and_toml_agree() {
+        let ron = r#"(
+            swipe_distance: 120,
+            global_triggers: [
+                (
+                    trigger: Swipe(fingers: 3, direction: Up, repeated: false),
+                    action: KeyboardInput(modifiers: ["RightControl"], sequence: ["T"], key_delay_ms: 20),
+                ),
+                (
+                    trigger: Pinch(fingers: 2, direction: In, repeated: true),
+                    action: ExecuteCommand(path: "notify-send", args: ["pinch"]),
+                ),
+                (
+                    trigger: Hold(fingers: 4),
+                    action: InlineScript(code: "echo hold"),
+                ),
+                (
+                    trigger: Rotate(fingers: 2, direction: Clockwise, repeated: false),
+                    action: Sequence([
+                        MouseScroll(vertical: 1),
+                        ShellCommand(command: "echo rotate"),
+                    ]),
+                ),
+            ],
+        )"#;
+        let toml = r#"
+            swipe_distance = 120
+
+            [[global_triggers]]
+            trigger = { Swipe = { fingers = 3, direction = "Up", repeated = false } }
+            action = { KeyboardInput = { modifiers = ["RightControl"], sequence = ["T"], key_delay_ms = 20 } }
+
+            [[global_triggers]]
+            trigger = { Pinch = { fingers = 2, direction = "In", repeated = true } }
+            action = { ExecuteCommand = { path = "notify-send", args = ["pinch"] } }
+
+            [[global_triggers]]
+            trigger = { Hold = { fingers = 4 } }
+            action = { InlineScript = { code = "echo hold" } }
+
+            [[global_triggers]]
+            trigger = { Rotate = { fingers = 2, direction = "Clockwise", repeated = false } }
+            action = { Sequence = [
+                { MouseScroll = { vertical = 1 } },
+                { ShellCommand = { command = "echo rotate" } },
+            ] }
+        "#;
+        let from_ron = Config::parse(ron, Format::Ron).unwrap();
+        let from_toml = Config::parse(toml, Format::Toml).unwrap();
+        assert_eq!(from_ron, from_toml);
+        assert_eq!(from_ron.global_triggers.len(), 4);
+    }
+
+    #[test]
+    fn action_list() {
+        let ron = r#"(global_triggers: [(
+            trigger: Hold(fingers: 4),
+            action: [MouseScroll(vertical: 1), ShellCommand(command: "echo hold")],
+        )])"#;
+        let toml = r#"
+            [[global_triggers]]
+            trigger = { Hold = { fingers = 4 } }
+            action = [{ MouseScroll = { vertical = 1 } }, { ShellCommand = { command = "echo hold" } }]
+        "#;
+        let sequence = r#"(global_triggers: [(
+            trigger: Hold(fingers: 4),
+            action: Sequence([MouseScroll(vertical: 1), ShellCommand(command: "echo hold")]),
+        )])"#;
+        let from_ron = Config::parse(ron, Format::Ron).unwrap();
+        assert_eq!(from_ron, Config::parse(toml, Format::Toml).unwrap());
+        assert_eq!(from_ron, Config::parse(sequence, Format::Ron).unwrap());
+
+        let error = r#"(global_triggers: [(trigger: Hold(fingers: 4), action: 4)])"#;
+        let error = Config::parse(error, Format::Ron).unwrap_err().to_string();
+        assert!(
+            error.contains("an action, or a list of actions"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let error = |s: &str| Config::parse(s, Format::Ron).unwrap_err().to_string();
+        assert!(error("(swipe_distnace: 10)").contains("swipe_distnace"));
+        let trigger = |trigger: &str, action: &str| {
+            error(&format!(
+                "(global_triggers: [(trigger: {}, action: {})])",
+                trigger, action
+            ))
+        };
+        let action = r#"ShellCommand(command: "true")"#;
+        assert!(
+            trigger("Hold(fingers: 3)", &format!("{}, cooldwn_ms: 0", action))
+                .contains("cooldwn_ms")
+        );
+        assert!(trigger("Hold(fingers: 3, tim_ms: 5)", action).contains("tim_ms"));
+        let action = r#"ShellCommand(command: "true", detach: true)"#;
+        assert!(trigger("Hold(fingers: 3)", action).contains("detach"));
+        let actions = r#"[ShellCommand(command: "true"), MouseScroll(vrtical: 1)]"#;
+        assert!(trigger("Hold(fingers: 3)", actions).contains("vrtical"));
+
+        let toml = r#"
+            [[global_triggers]]
+            trigger = { Hold = { fingers = 3 } }
+            action = { ShellCommand = { command = "true", detach = true } }
+        "#;
+        let error = Config::parse(toml, Format::Toml).unwrap_err().to_string();
+        assert!(error.contains("detach"), "{}", error);
+    }
+
+    #[test]
+    fn format_from_extension() {
+        use std::path::Path;
+        assert_eq!(Format::from_path(Path::new("a/config.toml")), Format::Toml);
+        assert_eq!(Format::from_path(Path::new("a/config.ron")), Format::Ron);
+        assert_eq!(Format::from_path(Path::new("a/config")), Format::Ron);
+    }
+
+    #[test]
+    fn direction_defaults() {
+        use super::Trigger;
+        use crate::common::{PinchDirection, RotateDirection};
+        let config = Config::parse(
+            r#"(
+                global_triggers: [
+                    (trigger: Pinch(fingers: 2, repeated: false), action: InlineScript(code: "")),
+                    (trigger: Rotate(fingers: 2, repeated: false), action: InlineScript(code: "")),
+                ],
+            )"#,
+            Format::Ron,
+        )
+        .unwrap();
+        let triggers: Vec<_> = config
+            .global_triggers
+            .iter()
+            .map(|t| t.trigger.clone())
+            .collect();
+        assert_eq!(
+            triggers,
+            [
+                Trigger::Pinch {
+                    fingers: 2,
+                    direction: PinchDirection::In,
+                    repeated: false,
+                    continuous: false,
+                    distance: None,
+                },
+                Trigger::Rotate {
+                    fingers: 2,
+                    direction: RotateDirection::Clockwise,
+                    repeated: false,
+                    continuous: false,
+                    distance: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn finger_counts() {
+        let config = r#"(
+            x11_triggers: [
+                (
+                    trigger: Swipe(fingers: 1, direction: Up, repeated: false),
+                    action: InlineScript(code: ""),
+                ),
+            ],
+        )"#;
+        let e = Config::parse(config, Format::Ron).unwrap_err();
+        assert!(e.to_string().contains("at least 2 fingers"), "{}", e);
+
+        let e = Config::parse(
+            &config.replace("fingers: 1", "fingers: 3000000000"),
+            Format::Ron,
+        )
+        .unwrap_err();
+        assert!(e.to_string().contains("at most 5 fingers"), "{}", e);
+        let hold = config.replace(
+            "Swipe(fingers: 1, direction: Up, repeated: false)",
+            "Hold(fingers: 0)",
+        );
+        let e = Config::parse(&hold, Format::Ron).unwrap_err();
+        assert!(e.to_string().contains("at least 1 finger"), "{}", e);
+
+        let config = config.replace("fingers: 1", "fingers: 2");
+        assert!(Config::parse(&config, Format::Ron).is_ok());
+        assert!(Config::parse(&hold.replace("fingers: 0", "fingers: 1"), Format::Ron).is_ok());
+    }
+
+    #[test]
+    fn sequence_steps() {
+        let config = |steps: &str| {
+            format!(
+                r#"(
+                    global_triggers: [
+                        (
+                            trigger: Sequence(steps: [{}], timeout_ms: 400),
+                            action: InlineScript(code: ""),
+                        ),
+                    ],
+                )"#,
+                steps
+            )
+        };
+        let up = "Swipe(fingers: 3, direction: Up, repeated: false)";
+        let check = |steps: &str| Config::parse(&config(steps), Format::Ron).map(|_| ());
+        assert!(check(&format!("{}, {}", up, up)).is_ok());
+        assert!(check(up).is_err());
+        assert!(check(&format!(
+            "{}, Swipe(fingers: 1, direction: Up, repeated: false)",
+            up
+        ))
+        .is_err());
+        assert!(check(&format!(
+            "{}, Swipe(fingers: 3, direction: Up, repeated: false, continuous: true)",
+            up
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn velocity_limits() {
+        let config = |velocity: &str, fire_on: &str| {
+            format!(
+                r#"(
+                    global_triggers: [
+                        (
+                            trigger: Swipe(fingers: 3, direction: Up, repeated: false, {}),
+                            action: InlineScript(code: ""),
+                            fire_on: {},
+                        ),
+                    ],
+                )"#,
+                velocity, fire_on
+            )
+        };
+        let check = |velocity, fire_on| Config::parse(&config(velocity, fire_on), Format::Ron);
+        assert!(check("min_velocity: Some(800)", "Threshold").is_ok());
+        assert!(check(
+            "min_velocity: Some(800), max_velocity: Some(400)",
+            "Threshold"
+        )
+        .is_err());
+        assert!(check("max_velocity: Some(400)", "End").is_ok());
+        assert!(check("max_velocity: Some(400)", "Begin").is_err());
+        assert!(check("min_duration_ms: Some(100)", "Threshold").is_ok());
+        assert!(check("min_duration_ms: Some(100)", "Begin").is_err());
+    }
+
+    #[test]
+    fn timed_hold_fire_on() {
+        let check = |duration: u32, fire_on: &str| {
+            let config = format!(
+                r#"(
+                    global_triggers: [
+                        (
+                            trigger: Hold(fingers: 4, duration_ms: {}),
+                            action: InlineScript(code: ""),
+                            fire_on: {},
+                        ),
+                    ],
+                )"#,
+                duration, fire_on
+            );
+            Config::parse(&config, Format::Ron)
+        };
+        assert!(check(500, "End").is_ok());
+        assert!(check(500, "Begin").is_err());
+        assert!(check(0, "Begin").is_ok());
+    }
+
+    #[test]
+    fn repeat_needs_continuous() {
+        let config = |continuous| {
+            format!(
+                r#"(
+                    global_triggers: [
+                        (
+                            trigger: Swipe(fingers: 3, direction: Up, repeated: false, continuous: {}),
+                            action: InlineScript(code: ""),
+                            repeat_ms: Some(100),
+                        ),
+                    ],
+                )"#,
+                continuous
+            )
+        };
+        let e = Config::parse(&config(false), Format::Ron).unwrap_err();
+        assert!(
+            e.to_string().contains("needs a continuous trigger"),
+            "{}",
+            e
+        );
+        assert!(Config::parse(&config(true), Format::Ron).is_ok());
+    }
+
+    #[test]
+    fn empty_actions() {
+        let config = |action| {
+            format!(
+                "(global_triggers: [(trigger: Hold(fingers: 3), action: {})])",
+                action
+            )
+        };
+        let check = |action| Config::parse(&config(action), Format::Ron).map_err(|e| e.to_string());
+
+        assert_eq!(
+            check("Sequence([])").unwrap_err(),
+            "Sequence has no actions"
+        );
+        assert_eq!(
+            check("KeyboardInput(modifiers: [], sequence: [])").unwrap_err(),
+            "KeyboardInput has no keys to press"
+        );
+        assert_eq!(
+            check("TypeText(text: \"\")").unwrap_err(),
+            "TypeText has no text to type"
+        );
+        assert!(check("Progressive(step: 0, action: MouseScroll(vertical: 1))").is_err());
+        assert!(check("Progressive(step: 20, gain: 0, action: MouseScroll(vertical: 1))").is_err());
+        assert_eq!(
+            check("Progressive(step: NaN, action: MouseScroll(vertical: 1))").unwrap_err(),
+            "Progressive step must be positive, but is NaN"
+        );
+        assert!(check("Progressive(step: inf, action: MouseScroll(vertical: 1))").is_err());
+        assert!(
+            check("Progressive(step: 20, gain: NaN, action: MouseScroll(vertical: 1))").is_err()
+        );
+        assert!(check("ShellCommand(command: \"true\", timeout_ms: Some(0))").is_err());
+        assert!(check("KeyboardInput(modifiers: [\"LeftMeta\"], sequence: [])").is_ok());
+        assert_eq!(
+            check("KeyHold(key: \"Space\", hold_ms: 0)").unwrap_err(),
+            "KeyHold hold_ms must be positive"
+        );
+        assert!(check("KeyHold(key: \"Space\", hold_ms: 800)").is_ok());
+        assert_eq!(
+            check("KeyboardInput(modifiers: [], sequence: [\"Tab\"], repeat: 0)").unwrap_err(),
+            "KeyboardInput repeat must be at least 1"
+        );
+        assert!(check("KeyboardInput(modifiers: [], sequence: [\"Tab\"], repeat: 3)").is_ok());
+        // The waits between the keys add up
+        assert!(check("KeyboardInput(modifiers: [\"LeftAlt\"], sequence: [\"Tab\"], repeat: 5, key_delay_ms: 50)").is_ok());
+        assert!(
+            check("KeyboardInput(sequence: [\"Down\"], repeat: 1000, key_delay_ms: 50)")
+                .unwrap_err()
+                .starts_with("KeyboardInput would wait 100000 ms")
+        );
+        assert!(check("KeyboardInput(keys: \"ctrl+c\", key_delay_ms: 4000000000)").is_err());
+    }
+
+    #[test]
+    fn keys_shorthand() {
+        let ron = r#"(global_triggers: [
+            (trigger: Swipe(fingers: 3, direction: Up, repeated: false), keys: "super+1"),
+        ])"#;
+        let toml = r#"
+            [[global_triggers]]
+            trigger = { Swipe = { fingers = 3, direction = "Up", repeated = false } }
+            keys = "super+1"
+        "#;
+        let expected = ConfigAction::KeyboardInput {
+            modifiers: vec![ConfigKey::from_name("LeftMeta").unwrap()],
+            sequence: vec![ConfigKey::from_name("1").unwrap()],
+            keys: None,
+            key_delay_ms: 0,
+            repeat: 1,
+            chord: false,
+            mode: None,
+            target: None,
+        };
+        for (s, format) in [(ron, Format::Ron), (toml, Format::Toml)] {
+            let config = Config::parse(s, format).unwrap();
+            assert_eq!(config.global_triggers[0].action, expected);
+            assert_eq!(config.global_triggers[0].keys, None);
+        }
+
+        let check = |trigger| {
+            let s = format!(
+                "(global_triggers: [(trigger: Hold(fingers: 3), {})])",
+                trigger
+            );
+            Config::parse(&s, Format::Ron).map_err(|e| e.to_string())
+        };
+        assert!(check(r#"keys: "hyper+1""#).is_err());
+        assert!(check(r#"keys: "super+1", action: Ref("tab")"#)
+            .unwrap_err()
+            .contains("both an action and keys"));
+        assert!(check("cooldown_ms: 10")
+            .unwrap_err()
+            .contains("neither an action nor keys"));
+    }
+
+    #[test]
+    fn keyboard_input_keys() {
+        let config = |action| {
+            format!(
+                "(global_triggers: [(trigger: Hold(fingers: 3), action: {})])",
+                action
+            )
+        };
+        let key = |name| ConfigKey::from_name(name).unwrap();
+        let s = config(r#"KeyboardInput(modifiers: ["LeftMeta"], keys: "ctrl+alt+t")"#);
+        let parsed = Config::parse(&s, Format::Ron).unwrap();
+        assert_eq!(
+            parsed.global_triggers[0].action.resolve(&parsed.actions),
+            Ok(ConfigAction::KeyboardInput {
+                modifiers: vec![key("LeftMeta"), key("LeftControl"), key("LeftAlt")],
+                sequence: vec![key("T")],
+                keys: None,
+                key_delay_ms: 0,
+                repeat: 1,
+                chord: false,
+                mode: None,
+                target: None,
+            })
+        );
+
+        // Kept when the shorthand is taken apart
+        let s = config(r#"KeyboardInput(sequence: ["A"], keys: "shift+b", chord: true)"#);
+        let parsed = Config::parse(&s, Format::Ron).unwrap();
+        assert!(matches!(
+            parsed.global_triggers[0].action.resolve(&parsed.actions),
+            Ok(ConfigAction::KeyboardInput { chord: true, sequence, .. })
+                if sequence == [key("A"), key("B")]
+        ));
+
+        let s = config(r#"KeyboardInput(keys: "ctrl+hyper+t")"#);
+        let error = Config::parse(&s, Format::Ron).unwrap_err().to_string();
+        assert!(error.contains(r#"No key named "hyper""#), "{}", error);
+    }
+
+    #[test]
+    fn scaled_devices() {
+        let ron = r#"(device_filter: Some([
+            "laptop",
+            (name: "Magic Trackpad", distance: 2.5, pinch: 1.5),
+        ]))"#;
+        let toml = r#"device_filter = ["laptop", { name = "Magic Trackpad", distance = 2.5, pinch = 1.5 }]"#;
+        let expected = vec![
+            DeviceFilter::Name("laptop".to_string()),
+            DeviceFilter::Scaled(ScaledDevice {
+                name: "Magic Trackpad".to_string(),
+                distance: 2.5,
+                pinch: 1.5,
+                rotation: 1.0,
+            }),
+        ];
+        for (s, format) in [(ron, Format::Ron), (toml, Format::Toml)] {
+            let config = Config::parse(s, format).unwrap();
+            assert_eq!(config.device_filter.as_ref(), Some(&expected));
+        }
+
+        for factor in ["rotation: 0", "distance: -2", "pinch: inf", "distance: NaN"] {
+            let s = format!(r#"(device_filter: Some([(name: "pad", {})]))"#, factor);
+            let error = Config::parse(&s, Format::Ron).unwrap_err().to_string();
+            assert!(error.contains("must be positive"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn virtual_device() {
+        let s = r#"(virtual_device: (name: "wzmach gestures", vendor: 0x1234))"#;
+        let config = Config::parse(s, Format::Ron).unwrap();
+        assert_eq!(
+            config.virtual_device,
+            DeviceId {
+                name: "wzmach gestures".to_string(),
+                vendor: 0x1234,
+                product: 0,
+            }
+        );
+        assert_eq!(
+            Config::default().virtual_device.name,
+            crate::action::VIRTUAL_DEVICE_NAME
+        );
+        let error = Config::parse(r#"(virtual_device: (name: ""))"#, Format::Ron)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("1 to 79 bytes"), "{}", error);
+    }
+
+    #[test]
+    fn discover() {
+        use std::path::{Path, PathBuf};
+        let find = |vars: &[(&str, &str)], files: &[&str]| {
+            super::discover(
+                |name| {
+                    let var = vars.iter().find(|(n, _)| *n == name);
+                    var.map(|(_, value)| value.into())
+                },
+                |path| files.iter().any(|f| Path::new(f) == path),
+            )
+        };
+        let home = [("HOME", "/home/me")];
+        assert_eq!(
+            find(&home, &["/home/me/.config/wzmach/config.ron"]),
+            Some(PathBuf::from("/home/me/.config/wzmach/config.ron"))
+        );
+        assert_eq!(find(&home, &[]), None);
+        // XDG_CONFIG_HOME comes first, and relative paths are ignored
+        let vars = [("XDG_CONFIG_HOME", "/cfg"), ("HOME", "/home/me")];
+        let files = ["/cfg/wzmach/config.toml", "/home/me/.config/wzmach.ron"];
+        assert_eq!(
+            find(&vars, &files),
+            Some(PathBuf::from("/cfg/wzmach/config.toml"))
+        );
+        let vars = [("XDG_CONFIG_HOME", "cfg"), ("HOME", "/home/me")];
+        assert_eq!(
+            find(&vars, &files),
+            Some(PathBuf::from("/home/me/.config/wzmach.ron"))
+        );
+        // Then the system directories, /etc/xdg when unset
+        assert_eq!(
+            find(&home, &["/etc/xdg/wzmach/config.ron"]),
+            Some(PathBuf::from("/etc/xdg/wzmach/config.ron"))
+        );
+        let vars = [("HOME", "/home/me"), ("XDG_CONFIG_DIRS", "/a:/b")];
+        assert_eq!(
+            find(&vars, &["/etc/xdg/wzmach.ron", "/b/wzmach.ron"]),
+            Some(PathBuf::from("/b/wzmach.ron"))
+        );
+    }
+
+    #[test]
+    fn window_target() {
+        let config = |target| {
+            format!(
+                r#"(global_triggers: [(
+                    trigger: Hold(fingers: 3),
+                    action: KeyboardInput(sequence: ["Space"], target: Some({})),
+                )])"#,
+                target
+            )
+        };
+        let parsed = Config::parse(&config(r#"(class: Some("mpv"))"#), Format::Ron).unwrap();
+        let target = match &parsed.global_triggers[0].action {
+            ConfigAction::KeyboardInput {
+                target: Some(target),
+                ..
+            } => target,
+            other => panic!("{:?}", other),
+        };
+        let class = ["mpv".to_string()];
+        assert!(target.matches(&class, "video.mkv - mpv"));
+        assert!(!target.matches(&["vlc".to_string()], "video.mkv - mpv"));
+        let target = WindowTarget {
+            class: Some("mpv".to_string()),
+            title: Some("music".to_string()),
+        };
+        assert!(!target.matches(&class, "video.mkv - mpv"));
+
+        let error = Config::parse(&config("(class: None)"), Format::Ron).unwrap_err();
+        assert!(error.to_string().contains("class or a title"), "{}", error);
+    }
+
+    #[test]
+    fn input_backend() {
+        assert_eq!(Config::default().input_backend, InputBackend::Uinput);
+        let s = "(input_backend: WaylandVirtualKeyboard)";
+        let config = Config::parse(s, Format::Ron).unwrap();
+        assert_eq!(config.input_backend, InputBackend::WaylandVirtualKeyboard);
+        let s = "input_backend = \"WaylandVirtualKeyboard\"";
+        let config = Config::parse(s, Format::Toml).unwrap();
+        assert_eq!(config.input_backend, InputBackend::WaylandVirtualKeyboard);
+    }
+
+    #[test]
+    fn drag_needs_continuous() {
+        let config = |continuous| {
+            format!(
+                r#"(
+                    global_triggers: [
+                        (
+                            trigger: Swipe(fingers: 3, direction: Up, repeated: false, continuous: {}),
+                            action: Sequence([MouseButton(button: Left, action: Drag)]),
+                        ),
+                    ],
+                )"#,
+                continuous
+            )
+        };
+        let e = Config::parse(&config(false), Format::Ron).unwrap_err();
+        assert!(
+            e.to_string().contains("needs a continuous trigger"),
+            "{}",
+            e
+        );
+        assert!(Config::parse(&config(true), Format::Ron).is_ok());
+    }
+
+    #[test]
+    fn mouse_move() {
+        let config = |continuous, action| {
+            format!(
+                "(global_triggers: [(trigger: Swipe(fingers: 4, direction: Up, repeated: false, continuous: {}), action: {})])",
+                continuous, action
+            )
+        };
+        let error = |continuous, action| {
+            Config::parse(&config(continuous, action), Format::Ron)
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(Config::parse(&config(false, "MouseMove(dy: -20)"), Format::Ron).is_ok());
+        assert!(error(false, "MouseMove(dx: 0)").contains("MouseMove has no dx, dy or speed"));
+        assert!(error(true, "MouseMove(speed: Some(0.0))").contains("must be positive"));
+        // Following the fingers needs their motion
+        assert!(error(false, "MouseMove(speed: Some(1.5))").contains("needs a continuous trigger"));
+        assert!(Config::parse(&config(true, "MouseMove(speed: Some(1.5))"), Format::Ron).is_ok());
+    }
+
+    #[test]
+    fn lua_script() {
+        let config = |code| {
+            format!(
+                "(global_triggers: [(trigger: Hold(fingers: 3), action: LuaScript(code: {:?}))])",
+                code
+            )
+        };
+        let check = |code| Config::parse(&config(code), Format::Ron).map_err(|e| e.to_string());
+        #[cfg(feature = "lua")]
+        {
+            assert!(check("if window() then emit_keys('ctrl+t') end").is_ok());
+            assert!(check("if then")
+                .unwrap_err()
+                .starts_with("LuaScript doesn't compile"));
+        }
+        #[cfg(not(feature = "lua"))]
+        assert_eq!(
+            check("emit_keys('ctrl+t')").unwrap_err(),
+            "LuaScript needs wzmach built with the lua feature"
+        );
+    }
+
+    #[test]
+    fn named_actions() {
+        let config = |named, action| {
+            format!(
+                r#"(
+                    actions: {{
+                        "tab": KeyboardInput(modifiers: ["LeftControl"], sequence: ["T"]),
+                        {}
+                    }},
+                    global_triggers: [(trigger: Hold(fingers: 3), action: {})],
+                )"#,
+                named, action
+            )
+        };
+        let check = |named, action| {
+            Config::parse(&config(named, action), Format::Ron).map_err(|e| e.to_string())
+        };
+
+        let tabs = r#""tabs": Sequence([Ref("tab"), Ref("tab")]),"#;
+        let config = check(tabs, r#"Ref("tabs")"#).unwrap();
+        let tab = config.actions["tab"].clone();
+        assert_eq!(
+            config.global_triggers[0].action.resolve(&config.actions),
+            Ok(ConfigAction::Sequence(vec![tab.clone(), tab]))
+        );
+
+        let e = check(tabs, r#"Ref("tbas")"#).unwrap_err();
+        assert!(e.contains(r#""tbas""#), "{}", e);
+        let e = check(
+            r#""loop": Sequence([Ref("tab"), Ref("loop")]),"#,
+            r#"Ref("tab")"#,
+        )
+        .unwrap_err();
+        assert!(e.contains(r#""loop" refers to itself"#), "{}", e);
+    }
+
+    #[test]
+    fn natural_scroll() {
+        let scroll = |horizontal, vertical| ConfigAction::MouseScroll {
+            horizontal,
+            vertical,
+        };
+        let mut action = ConfigAction::Sequence(vec![
+            scroll(1, 0),
+            ConfigAction::Progressive {
+                step: 20.0,
+                curve: Curve::Linear,
+                gain: 1.0,
+                action: Box::new(scroll(0, -1)),
+            },
+        ]);
+        action.reverse_scroll();
+        assert_eq!(
+            action,
+            ConfigAction::Sequence(vec![
+                scroll(-1, 0),
+                ConfigAction::Progressive {
+                    step: 20.0,
+                    curve: Curve::Linear,
+                    gain: 1.0,
+                    action: Box::new(scroll(0, 1)),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn fallback() {
+        let config = |options| {
+            format!(
+                "(global_triggers: [(trigger: Swipe(fingers: 3, direction: Up, repeated: false{}), action: InlineScript(code: \"\"), fallback: true)])",
+                options
+            )
+        };
+        assert!(Config::parse(&config(""), Format::Ron).is_ok());
+        let error = Config::parse(&config(", continuous: true"), Format::Ron)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("can't be continuous"), "{}", error);
+    }
+
+    #[test]
+    fn from_turn() {
+        let config = |trigger| {
+            format!(
+                "(global_triggers: [(trigger: {}, action: InlineScript(code: \"\"), from_turn: true)])",
+                trigger
+            )
+        };
+        let check =
+            |trigger| Config::parse(&config(trigger), Format::Ron).map_err(|e| e.to_string());
+        assert!(
+            check("Swipe(fingers: 3, direction: Left, repeated: false)")
+                .unwrap()
+                .global_triggers[0]
+                .from_turn
+        );
+        assert!(check("Shear(fingers: 3, direction: Up, repeated: true)").is_ok());
+        for trigger in [
+            "Swipe(fingers: 3, direction: Left, repeated: false, continuous: true)",
+            "Pinch(fingers: 2, repeated: false)",
+            "Hold(fingers: 3)",
+        ] {
+            assert!(check(trigger)
+                .unwrap_err()
+                .starts_with("from_turn needs a swipe or shear"));
+        }
+    }
+
+    #[test]
+    fn pinch_mode() {
+        let config = |settings, distance| {
+            format!(
+                "({} global_triggers: [(trigger: Pinch(fingers: 2, direction: Out, repeated: false, distance: {}), action: InlineScript(code: \"\"))])",
+                settings, distance
+            )
+        };
+        let check = |settings, distance| {
+            Config::parse(&config(settings, distance), Format::Ron).map_err(|e| e.to_string())
+        };
+        assert_eq!(
+            check("", "Some(1.5)").unwrap().pinch_mode,
+            PinchMode::Absolute
+        );
+        let relative = check("pinch_mode: Relative, pinch_distance: 30,", "Some(50)").unwrap();
+        assert_eq!(relative.pinch_mode, PinchMode::Relative);
+        assert!(check("pinch_mode: Relative,", "Some(100)")
+            .unwrap_err()
+            .contains("between 0 and 100 percent"));
+        assert!(check("pinch_mode: Relative,", "Some(0)").is_err());
+        assert!(check("pinch_mode: Relative,", "Some(NaN)").is_err());
+        assert!(check("", "Some(inf)").is_err());
+        // Percentages below 1 are fine, but not such scales
+        assert!(check("pinch_mode: Relative,", "Some(0.5)").is_ok());
+        assert!(check("", "Some(1)")
+            .unwrap_err()
+            .contains("needs a distance above 1"));
+        assert!(check("pinch_mode: Relative, pinch_distance: 100,", "None")
+            .unwrap_err()
+            .starts_with("pinch_distance must be between 0 and 100"));
+        // Only relative pinches can go past 100
+        assert!(check("", "Some(150)").is_ok());
+        // The global distance is checked like the ones of triggers
+        for settings in [
+            "pinch_distance: 1,",
+            "pinch_distance: 0.5,",
+            "pinch_distance: NaN,",
+        ] {
+            assert!(check(settings, "None")
+                .unwrap_err()
+                .starts_with("pinch_distance must be a scale above 1"));
+        }
+    }
+
+    #[test]
+    fn global_distances() {
+        let check = |settings: &str| {
+            let s = format!("({} global_triggers: [])", settings);
+            Config::parse(&s, Format::Ron).map_err(|e| e.to_string())
+        };
+        assert!(check("swipe_distance: 50, shear_distance: 50, rotation_distance: 15,").is_ok());
+        assert!(check("swipe_distance: 0,").is_err());
+        assert!(check("shear_distance: 0,").is_err());
+        for distance in ["0", "-30", "NaN", "inf"] {
+            let error = check(&format!("rotation_distance: {},", distance)).unwrap_err();
+            assert!(
+                error.starts_with("rotation_distance must be above 0"),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn cancel_threshold() {
+        let check = |cancel| {
+            let s = format!(
+                "(global_triggers: [(trigger: Swipe(fingers: 3, direction: Up, repeated: false), cancel_threshold: Some({}), action: InlineScript(code: \"\"))])",
+                cancel
+            );
+            Config::parse(&s, Format::Ron).map_err(|e| e.to_string())
+        };
+        assert!(check("0").is_ok());
+        assert!(check("40.5").is_ok());
+        for cancel in ["-1", "NaN", "inf"] {
+            let error = check(cancel).unwrap_err();
+            assert!(
+                error.contains("must be a distance of 0 or more"),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn key_mode() {
+        let s = r#"(
+            key_mode: Hold,
+            global_triggers: [(
+                trigger: Hold(fingers: 3),
+                action: Sequence([
+                    KeyboardInput(sequence: ["A"]),
+                    KeyboardInput(sequence: ["B"], mode: Tap),
+                ]),
+            )],
+        )"#;
+        let config = Config::parse(s, Format::Ron).unwrap();
+        assert_eq!(config.key_mode, KeyMode::Hold);
+        let mut action = config.global_triggers[0].action.clone();
+        action.default_key_mode(config.key_mode);
+        let modes = match action {
+            ConfigAction::Sequence(actions) => actions
+                .into_iter()
+                .map(|a| match a {
+                    ConfigAction::KeyboardInput { mode, .. } => mode,
+                    other => panic!("{:?}", other),
+                })
+                .collect::<Vec<_>>(),
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(modes, vec![Some(KeyMode::Hold), Some(KeyMode::Tap)]);
+    }
+
+    #[test]
+    fn ron_error_position() {
+        let config = "(\n    global_triggers: [(trigger: Swip)],\n)";
+        match Config::parse(config, Format::Ron).unwrap_err() {
+            super::ConfigError::Ron {
+                line, col, snippet, ..
+            } => {
+                assert_eq!((line, col), (2, 33));
+                assert_eq!(
+                    snippet,
+                    "    global_triggers: [(trigger: Swip)],\n                                ^^^^"
+                );
+            }
+            e => panic!("Not a RON error: {}", e),
+        }
+
+        let config = "(swipe_distance: 10 shear_distance: 10)";
+        match Config::parse(config, Format::Ron).unwrap_err() {
+            super::ConfigError::Ron { line, col, .. } => assert_eq!((line, col), (1, 21)),
+            e => panic!("Not a RON error: {}", e),
+        }
+
+        // Columns count characters, not bytes
+        let config = r#"(profile: Some("ü"), swipe_distance: 10 shear_distance: 10)"#;
+        match Config::parse(config, Format::Ron).unwrap_err() {
+            super::ConfigError::Ron {
+                line, col, snippet, ..
+            } => {
+                assert_eq!((line, col), (1, 41));
+                assert!(snippet.ends_with(&format!("\n{}{}", " ".repeat(40), "^".repeat(14))));
+            }
+            e => panic!("Not a RON error: {}", e),
+        }
+    }
+
+    #[test]
+    fn example_is_valid() {
+        let example = Config::parse(&super::example(), Format::Ron).unwrap();
+        let defaults = Config::default();
+        assert_eq!(example.swipe_distance, defaults.swipe_distance);
+        assert_eq!(example.shear_distance, defaults.shear_distance);
+        assert_eq!(example.pinch_distance, defaults.pinch_distance);
+        assert_eq!(example.rotation_distance, defaults.rotation_distance);
+        assert_eq!(example.angle_tolerance, defaults.angle_tolerance);
+        assert_eq!(example.overlap_warnings(false, None), Vec::<String>::new());
+        assert_eq!(example.overlap_warnings(true, None), Vec::<String>::new());
+    }
+
+    #[test]
+    fn overlapping_triggers() {
+        let ron = r#"(
+            global_triggers: [
+                (trigger: Hold(fingers: 3), keys: "super+1"),
+                (trigger: Hold(fingers: 3), keys: "super+2", window_class: Some("firefox")),
+                (trigger: Hold(fingers: 4), keys: "super+3", window_class: Some("firefox")),
+                (trigger: Hold(fingers: 4), keys: "super+4"),
+                (trigger: Hold(fingers: 5), keys: "super+5"),
+                (trigger: Hold(fingers: 5), keys: "super+6", priority: 1),
+            ],
+            wayland_triggers: [
+                (trigger: Hold(fingers: 3), keys: "super+7", fire_on: End),
+            ],
+        )"#;
+        let mut config = Config::parse(ron, Format::Ron).unwrap();
+        let warnings = config.overlap_warnings(true, None);
+        // The first is everywhere the second is, and the third and fourth
+        // each execute somewhere. A higher priority wins from later
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+        assert!(warnings[0].starts_with("Trigger 1 never executes, as trigger 0"));
+        assert!(warnings[1].starts_with("Trigger 4 never executes, as trigger 5"));
+
+        config.fire_all = true;
+        let warnings = config.overlap_warnings(true, None);
+        assert!(warnings[0].starts_with("Triggers 0 and 1 both execute"));
+    }
+
+    #[test]
+    fn disabled_triggers() {
+        let ron = r#"(
+            global_triggers: [
+                (trigger: Hold(fingers: 3), keys: "super+1", enabled: false),
+                (trigger: Hold(fingers: 3), keys: "super+2"),
+                (trigger: Hold(fingers: 4), keys: "super+3"),
+            ],
+        )"#;
+        let config = Config::parse(ron, Format::Ron).unwrap();
+        // The disabled one neither loads nor takes the gestures of another
+        assert!(config.overlap_warnings(true, None).is_empty());
+        let (triggers, _) = config.make_triggers(true, None, None, None, Some(&Default::default()));
+        assert_eq!(triggers.len(), 2);
+        assert_eq!(triggers[0].fingers(), 3);
+
+        // Disabled ones are still checked
+        let ron = r#"(global_triggers: [(trigger: Hold(fingers: 1), enabled: false)])"#;
+        assert!(Config::parse(ron, Format::Ron).is_err());
+    }
+}
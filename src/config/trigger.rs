@@ -1,107 +1,428 @@
-use crate::common::{Direction, PinchDirection, RotateDirection};
+use crate::common::{Direction, Edge, FireOn, PinchDirection, PinchMode, RotateDirection};
 use crate::gesture_event::trigger as gesture;
 
 use serde::Deserialize;
 
-#[derive(PartialEq, Debug, Clone, Copy, Deserialize)]
+/// Most fingers libinput tells apart in a gesture
+pub const MAX_FINGERS: u32 = 5;
+
+/// Fingers of a validated trigger, which are at most `MAX_FINGERS`
+fn finger_count(fingers: u32) -> i32 {
+    fingers.min(MAX_FINGERS) as i32
+}
+
+#[derive(PartialEq, Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub enum Trigger {
+    /// All fingers move together the same way. libinput reports these as
+    /// swipe gestures, and the fingers go `swipe_distance`
     Swipe {
         fingers: u32,
         direction: Direction,
         repeated: bool,
+        #[serde(default)]
+        continuous: bool,
+        /// Distance instead of `swipe_distance`
+        #[serde(default)]
+        distance: Option<u32>,
+        /// Only match swipes at least this fast, in pixels per second
+        #[serde(default)]
+        min_velocity: Option<u32>,
+        /// Only match swipes at most this fast, in pixels per second
+        #[serde(default)]
+        max_velocity: Option<u32>,
+        /// Only match swipes that went on for at least this many milliseconds,
+        /// against brushes of the touchpad that go far quickly
+        #[serde(default)]
+        min_duration_ms: Option<u32>,
+        /// Only match swipes that start within `edge_margin` of this edge of
+        /// the touchpad
+        #[serde(default)]
+        edge: Option<Edge>,
     },
+    /// The fingers move apart from each other, like the thumb moving while the
+    /// other fingers rest. libinput reports these as pinch gestures, which
+    /// never match swipes, so a shear and a swipe of the same fingers and
+    /// direction can have different actions. The fingers go `shear_distance`,
+    /// and `direction` is where the center of the fingers goes, which is
+    /// mostly the way of the thumb
     Shear {
         fingers: u32,
         direction: Direction,
         repeated: bool,
+        #[serde(default)]
+        continuous: bool,
+        /// Distance instead of `shear_distance`
+        #[serde(default)]
+        distance: Option<u32>,
     },
     Pinch {
         fingers: u32,
+        #[serde(default)]
         direction: PinchDirection,
         repeated: bool,
+        #[serde(default)]
+        continuous: bool,
+        /// Instead of `pinch_distance`, and read the same way
+        #[serde(default)]
+        distance: Option<f64>,
     },
     Rotate {
         fingers: u32,
+        #[serde(default)]
         direction: RotateDirection,
         repeated: bool,
+        #[serde(default)]
+        continuous: bool,
+        /// Degrees instead of `rotation_distance`
+        #[serde(default)]
+        distance: Option<f64>,
     },
     Hold {
         fingers: u32,
+        /// Fire after this long with the fingers down. 0 fires when the
+        /// fingers are lifted
+        #[serde(default)]
+        duration_ms: u32,
+    },
+    /// Fire when these triggers fire one after the other, each within
+    /// `timeout_ms` of the one before
+    Sequence {
+        steps: Vec<Trigger>,
+        #[serde(default = "default_timeout")]
+        timeout_ms: u32,
     },
 }
 
-#[derive(PartialEq, Debug, Clone, Copy, Deserialize)]
-struct Cardinal {
-    fingers: u32,
-    direction: Direction,
-    repeated: bool,
-}
+impl Trigger {
+    pub fn continuous(&self) -> bool {
+        match self {
+            Trigger::Swipe { continuous, .. }
+            | Trigger::Shear { continuous, .. }
+            | Trigger::Pinch { continuous, .. }
+            | Trigger::Rotate { continuous, .. } => *continuous,
+            Trigger::Hold { .. } | Trigger::Sequence { .. } => false,
+        }
+    }
 
-#[derive(PartialEq, Debug, Clone, Copy, Deserialize)]
-struct Pinch {
-    fingers: u32,
-    direction: PinchDirection,
-    repeated: bool,
-}
+    pub fn repeated(&self) -> bool {
+        match self {
+            Trigger::Swipe { repeated, .. }
+            | Trigger::Shear { repeated, .. }
+            | Trigger::Pinch { repeated, .. }
+            | Trigger::Rotate { repeated, .. } => *repeated,
+            Trigger::Hold { .. } | Trigger::Sequence { .. } => false,
+        }
+    }
 
-#[derive(PartialEq, Debug, Clone, Copy, Deserialize)]
-struct Hold {
-    fingers: u32,
-}
+    /// Libinput only reports swipes and pinches of two fingers or more, holds
+    /// of one or more, and none of more than `MAX_FINGERS`, so triggers with
+    /// other counts would never match
+    pub fn validate(&self, pinch_mode: PinchMode) -> Result<(), String> {
+        match self {
+            Trigger::Swipe { fingers, .. }
+            | Trigger::Shear { fingers, .. }
+            | Trigger::Pinch { fingers, .. }
+            | Trigger::Rotate { fingers, .. }
+            | Trigger::Hold { fingers, .. }
+                if *fingers > MAX_FINGERS =>
+            {
+                Err(format!(
+                    "{:?} needs at most {} fingers, but has {}",
+                    self, MAX_FINGERS, fingers
+                ))
+            }
+            Trigger::Hold { fingers: 0, .. } => Err(format!("{:?} needs at least 1 finger", self)),
+            Trigger::Swipe { fingers, .. }
+            | Trigger::Shear { fingers, .. }
+            | Trigger::Pinch { fingers, .. }
+            | Trigger::Rotate { fingers, .. }
+                if *fingers < 2 =>
+            {
+                Err(format!(
+                    "{:?} needs at least 2 fingers, but has {}",
+                    self, fingers
+                ))
+            }
+            Trigger::Swipe {
+                distance: Some(0), ..
+            }
+            | Trigger::Shear {
+                distance: Some(0), ..
+            } => Err(format!("{:?} needs a distance above 0", self)),
+            Trigger::Swipe {
+                min_velocity: Some(min),
+                max_velocity: Some(max),
+                ..
+            } if min > max => Err(format!(
+                "{:?} has a min_velocity above its max_velocity",
+                self
+            )),
+            // A scale, or the percentage it changes by
+            Trigger::Pinch {
+                distance: Some(d), ..
+            } => match pinch_mode {
+                PinchMode::Absolute if !(d.is_finite() && *d > 1.0) => {
+                    Err(format!("{:?} needs a distance above 1", self))
+                }
+                PinchMode::Relative if !(*d > 0.0 && *d < 100.0) => Err(format!(
+                    "{:?} needs a distance between 0 and 100 percent with pinch_mode Relative",
+                    self
+                )),
+                _ => Ok(()),
+            },
+            Trigger::Rotate {
+                distance: Some(d), ..
+            } if !(d.is_finite() && *d > 0.0) => {
+                Err(format!("{:?} needs a distance above 0", self))
+            }
+            Trigger::Sequence { steps, .. } if steps.len() < 2 => Err(format!(
+                "Sequence needs at least 2 steps, but has {}",
+                steps.len()
+            )),
+            Trigger::Sequence { steps, .. } => steps.iter().try_for_each(|step| match step {
+                Trigger::Sequence { .. } => Err("Sequence steps can't be sequences".to_string()),
+                _ if step.continuous() => Err(format!(
+                    "Sequence steps can't be continuous, but {:?} is",
+                    step
+                )),
+                _ => step.validate(pinch_mode),
+            }),
+            _ => Ok(()),
+        }
+    }
 
-impl Trigger {
+    #[allow(clippy::too_many_arguments)]
     pub fn make(
         self,
         swipe_distance: u32,
         shear_distance: u32,
         pinch_distance: f64,
+        pinch_mode: PinchMode,
         rotate_distance: f64,
+        angle_tolerance: f64,
+        edge_margin: f64,
+        cooldown: u32,
+        repeat: Option<u32>,
+        fire_on: FireOn,
+        cancel: Option<f64>,
     ) -> gesture::Trigger {
         match self {
             Trigger::Swipe {
                 fingers,
                 direction,
                 repeated,
+                continuous,
+                distance,
+                min_velocity,
+                max_velocity,
+                min_duration_ms,
+                edge,
             } => gesture::Trigger::Swipe(gesture::CardinalTrigger {
-                fingers: fingers.try_into().expect("Too many fingers"),
+                fingers: finger_count(fingers),
                 direction,
-                distance: swipe_distance.try_into().expect("Incorrect distance"),
+                distance: distance.unwrap_or(swipe_distance).into(),
+                angle_tolerance,
+                min_velocity: min_velocity.map(f64::from),
+                max_velocity: max_velocity.map(f64::from),
+                min_duration: min_duration_ms,
                 repeated,
+                continuous,
+                cooldown,
+                fire_on,
+                cancel,
+                repeat,
+                edge: edge.map(|e| (e, edge_margin)),
             }),
             Trigger::Shear {
                 fingers,
                 direction,
                 repeated,
+                continuous,
+                distance,
             } => gesture::Trigger::Shear(gesture::CardinalTrigger {
-                fingers: fingers.try_into().expect("Too many fingers"),
+                fingers: finger_count(fingers),
                 direction,
-                distance: shear_distance.try_into().expect("Incorrect distance"),
+                distance: distance.unwrap_or(shear_distance).into(),
+                angle_tolerance,
+                min_velocity: None,
+                max_velocity: None,
+                min_duration: None,
                 repeated,
+                continuous,
+                cooldown,
+                fire_on,
+                cancel,
+                repeat,
+                edge: None,
             }),
             Trigger::Pinch {
                 fingers,
                 direction,
                 repeated,
+                continuous,
+                distance,
             } => gesture::Trigger::Pinch(gesture::PinchTrigger {
-                fingers: fingers.try_into().expect("Too many fingers"),
+                fingers: finger_count(fingers),
                 direction,
-                scale: pinch_distance,
+                scale: pinch_mode.scale(distance.unwrap_or(pinch_distance), direction),
                 repeated,
+                continuous,
+                cooldown,
+                fire_on,
+                cancel,
+                repeat,
             }),
             Trigger::Rotate {
                 fingers,
                 direction,
                 repeated,
+                continuous,
+                distance,
             } => gesture::Trigger::Rotate(gesture::RotateTrigger {
-                fingers: fingers.try_into().expect("Too many fingers"),
+                fingers: finger_count(fingers),
                 direction,
-                distance: rotate_distance,
+                distance: distance.unwrap_or(rotate_distance),
                 repeated,
+                continuous,
+                cooldown,
+                fire_on,
+                cancel,
+                repeat,
             }),
-            Trigger::Hold { fingers } => gesture::Trigger::Hold(gesture::HoldTrigger {
-                fingers: fingers.try_into().expect("Too many fingers"),
-                time: 1, // Time not implemented currently
+            Trigger::Hold {
+                fingers,
+                duration_ms,
+            } => gesture::Trigger::Hold(gesture::HoldTrigger {
+                fingers: finger_count(fingers),
+                time: duration_ms,
+                cooldown,
+                fire_on,
             }),
+            Trigger::Sequence { steps, timeout_ms } => {
+                gesture::Trigger::Sequence(gesture::SequenceTrigger {
+                    steps: steps
+                        .into_iter()
+                        .map(|step| {
+                            step.make(
+                                swipe_distance,
+                                shear_distance,
+                                pinch_distance,
+                                pinch_mode,
+                                rotate_distance,
+                                angle_tolerance,
+                                edge_margin,
+                                0,
+                                None,
+                                FireOn::Threshold,
+                                None,
+                            )
+                        })
+                        .collect(),
+                    timeout: timeout_ms,
+                    cooldown,
+                })
+            }
+        }
+    }
+}
+
+fn default_timeout() -> u32 {
+    log::debug!("Using default sequence timeout");
+    400
+}
+
+#[cfg(test)]
+mod test {
+    use super::Trigger;
+    use crate::common::{Direction, FireOn, PinchMode};
+    use crate::gesture_event::trigger as gesture;
+
+    #[test]
+    fn distance_override() {
+        // The distances of swipes and shears apart, and otherwise the defaults
+        let made = |trigger: Trigger| {
+            let made = trigger.make(
+                100,
+                80,
+                1.4,
+                PinchMode::Absolute,
+                60.0,
+                20.0,
+                0.1,
+                0,
+                None,
+                FireOn::Threshold,
+                None,
+            );
+            match made {
+                gesture::Trigger::Swipe(t) | gesture::Trigger::Shear(t) => t.distance,
+                t => panic!("Made {:?}", t),
+            }
+        };
+        let swipe = |distance| Trigger::Swipe {
+            fingers: 3,
+            direction: Direction::Up,
+            repeated: false,
+            continuous: false,
+            distance,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration_ms: None,
+            edge: None,
+        };
+        assert_eq!(made(swipe(None)), 100.0);
+        assert_eq!(made(swipe(Some(40))), 40.0);
+        let shear = |distance| Trigger::Shear {
+            fingers: 4,
+            direction: Direction::Left,
+            repeated: false,
+            continuous: false,
+            distance,
+        };
+        assert_eq!(made(shear(None)), 80.0);
+        assert_eq!(made(shear(Some(40))), 40.0);
+
+        let rotate = |distance| Trigger::Rotate {
+            fingers: 2,
+            direction: Default::default(),
+            repeated: false,
+            continuous: false,
+            distance: Some(distance),
+        };
+        assert!(rotate(15.0).validate(PinchMode::Absolute).is_ok());
+        for distance in [0.0, f64::NAN, f64::INFINITY] {
+            assert!(rotate(distance).validate(PinchMode::Absolute).is_err());
+        }
+    }
+
+    #[test]
+    fn shear_diagonals() {
+        let s = "Shear(fingers: 3, direction: DownLeft, repeated: false, distance: Some(150))";
+        match ron::from_str(s).unwrap() {
+            Trigger::Shear {
+                direction,
+                distance,
+                ..
+            } => assert_eq!((direction, distance), (Direction::DownLeft, Some(150))),
+            t => panic!("Parsed {:?}", t),
+        }
+    }
+
+    #[test]
+    fn rotate_directions() {
+        use crate::common::RotateDirection::*;
+        for (name, direction) in [
+            ("Clockwise", Clockwise),
+            ("CW", Clockwise),
+            ("Counterclockwise", Anticlockwise),
+            ("CCW", Anticlockwise),
+        ] {
+            let s = format!("Rotate(fingers: 2, direction: {}, repeated: false)", name);
+            match ron::from_str(&s).unwrap() {
+                Trigger::Rotate { direction: d, .. } => assert_eq!(d, direction),
+                t => panic!("Parsed {:?}", t),
+            }
         }
     }
 }
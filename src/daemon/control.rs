@@ -0,0 +1,183 @@
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use zbus::blocking::{connection, Connection};
+use zbus::fdo::{self, RequestNameFlags, RequestNameReply};
+
+use crate::display::DisplayServer;
+
+pub const NAME: &str = "org.wzmach";
+pub const PATH: &str = "/org/wzmach";
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Command {
+    Pause,
+    Resume,
+    Toggle,
+    /// Switch to the named profile, or to none for an empty name
+    SetProfile(Option<String>),
+    /// Load the triggers of the display server, or of the one the
+    /// environment tells for an empty name
+    SetDisplayServer(Option<DisplayServer>),
+}
+
+/// The object served at `PATH`. The calls are answered on the thread of the
+/// connection, which hands the commands over and wakes the main loop
+struct Service {
+    commands: Sender<Command>,
+    wake: UnixStream,
+}
+
+impl Service {
+    fn send(&self, command: Command) -> fdo::Result<()> {
+        log::debug!("DBus call for {:?}", command);
+        self.commands
+            .send(command)
+            .map_err(|_| fdo::Error::Failed("wzmach is exiting".to_string()))?;
+        // A full socket has a wake-up waiting already
+        let _ = (&self.wake).write(&[0]);
+        Ok(())
+    }
+}
+
+#[zbus::interface(name = "org.wzmach.Control")]
+impl Service {
+    fn pause(&self) -> fdo::Result<()> {
+        self.send(Command::Pause)
+    }
+
+    fn resume(&self) -> fdo::Result<()> {
+        self.send(Command::Resume)
+    }
+
+    fn toggle(&self) -> fdo::Result<()> {
+        self.send(Command::Toggle)
+    }
+
+    fn set_profile(&self, profile: String) -> fdo::Result<()> {
+        self.send(Command::SetProfile(Some(profile).filter(|p| !p.is_empty())))
+    }
+
+    fn set_display_server(&self, server: String) -> fdo::Result<()> {
+        let server = match server.as_str() {
+            "" => None,
+            name => Some(name.parse().map_err(fdo::Error::InvalidArgs)?),
+        };
+        self.send(Command::SetDisplayServer(server))
+    }
+}
+
+/// Service on the session bus that lets other programs pause the daemon and
+/// switch profiles
+pub struct Control {
+    conn: Connection,
+    commands: Receiver<Command>,
+    /// Readable when commands came in
+    wake: UnixStream,
+}
+
+impl Control {
+    /// Connect to the session bus and take the name. Fails if another wzmach
+    /// has it already
+    pub fn new() -> io::Result<Self> {
+        let (service, commands, wake) = Service::new()?;
+        let conn = connection::Builder::session()
+            .and_then(|b| b.serve_at(PATH, service))
+            .and_then(|b| b.build())
+            .map_err(io::Error::other)?;
+        // Don't queue for the name, so that the error is right away
+        match conn.request_name_with_flags(NAME, RequestNameFlags::DoNotQueue.into()) {
+            Ok(RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner) => (),
+            Ok(_) | Err(zbus::Error::NameTaken) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is owned by another process", NAME),
+                ))
+            }
+            Err(e) => return Err(io::Error::other(e)),
+        }
+        log::debug!("Listening on DBus as {}", NAME);
+        Ok(Control {
+            conn,
+            commands,
+            wake,
+        })
+    }
+
+    /// The commands that came in since the last time. An error means the bus
+    /// went away
+    pub fn commands(&mut self) -> io::Result<Vec<Command>> {
+        let mut buf = [0; 64];
+        loop {
+            match self.wake.read(&mut buf) {
+                Ok(0) => break,
+                Ok(_) => continue,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        let commands = self.commands.try_iter().collect();
+        if self.conn.is_closed() {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "The session bus closed the connection",
+            ));
+        }
+        Ok(commands)
+    }
+}
+
+impl Service {
+    /// The service, with the end that receives its commands and the socket
+    /// that's readable then
+    fn new() -> io::Result<(Self, Receiver<Command>, UnixStream)> {
+        let (commands, received) = mpsc::channel();
+        let (wake, woken) = UnixStream::pair()?;
+        wake.set_nonblocking(true)?;
+        woken.set_nonblocking(true)?;
+        Ok((Service { commands, wake }, received, woken))
+    }
+}
+
+impl AsRawFd for Control {
+    fn as_raw_fd(&self) -> RawFd {
+        self.wake.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use super::{Command, Service};
+    use crate::display::DisplayServer;
+
+    #[test]
+    fn answers_calls() {
+        let (service, commands, mut woken) = Service::new().unwrap();
+        service.toggle().unwrap();
+        service.set_profile("coding".to_string()).unwrap();
+        service.set_profile(String::new()).unwrap();
+        service.set_display_server("Wayland".to_string()).unwrap();
+        service.set_display_server(String::new()).unwrap();
+        assert!(matches!(
+            service.set_display_server("mir".to_string()),
+            Err(zbus::fdo::Error::InvalidArgs(_))
+        ));
+        assert_eq!(
+            commands.try_iter().collect::<Vec<_>>(),
+            [
+                Command::Toggle,
+                Command::SetProfile(Some("coding".to_string())),
+                Command::SetProfile(None),
+                Command::SetDisplayServer(Some(DisplayServer::Wayland)),
+                Command::SetDisplayServer(None),
+            ]
+        );
+        // Woken once for each command
+        let mut buf = [0; 16];
+        assert_eq!(woken.read(&mut buf).unwrap(), 5);
+    }
+}
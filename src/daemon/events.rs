@@ -0,0 +1,165 @@
+//! Tells programs listening on a Unix socket which triggers matched, one line
+//! of JSON for each
+
+use std::io::{self, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+use wzmach::common::AnyDirection;
+use wzmach::Match;
+
+pub struct EventSocket {
+    path: PathBuf,
+    listener: UnixListener,
+    clients: Vec<UnixStream>,
+}
+
+impl EventSocket {
+    /// Listen at the path. A socket left there by an earlier run is replaced,
+    /// but any other file is an error
+    pub fn bind(path: &Path) -> io::Result<Self> {
+        if let Ok(meta) = std::fs::symlink_metadata(path) {
+            if !meta.file_type().is_socket() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "file exists and is not a socket",
+                ));
+            }
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Ok(EventSocket {
+            path: path.to_owned(),
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Take in the programs that connected, when the socket is readable
+    pub fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    // A client that doesn't keep up is dropped rather than
+                    // holding up the gestures
+                    if let Err(e) = stream.set_nonblocking(true) {
+                        log::warn!("Can't set up event socket client: {}", e);
+                        continue;
+                    }
+                    log::debug!("Event socket client connected");
+                    self.clients.push(stream);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    log::warn!("Can't accept event socket client: {}", e);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Send the matches to every connected program. Programs that went away
+    /// or don't read are disconnected
+    pub fn send(&mut self, matches: &[Match]) {
+        if matches.is_empty() || self.clients.is_empty() {
+            return;
+        }
+        let lines: String = matches.iter().map(|m| to_json(m) + "\n").collect();
+        self.clients
+            .retain_mut(|client| match client.write_all(lines.as_bytes()) {
+                Ok(()) => true,
+                Err(e) => {
+                    log::debug!("Dropping event socket client: {}", e);
+                    false
+                }
+            });
+    }
+}
+
+impl AsRawFd for EventSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+}
+
+impl Drop for EventSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// One line of JSON. All the values are numbers or names without characters
+/// that need escaping
+fn to_json(m: &Match) -> String {
+    let direction = match m.direction {
+        Some(AnyDirection::Cardinal(d)) => format!("\"{:?}\"", d),
+        Some(AnyDirection::Pinch(d)) => format!("\"{:?}\"", d),
+        Some(AnyDirection::Rotate(d)) => format!("\"{:?}\"", d),
+        None => "null".to_string(),
+    };
+    format!(
+        r#"{{"time":{},"event":"{}","trigger":{},"gesture":"{}","fingers":{},"direction":{}}}"#,
+        m.time,
+        if m.began { "began" } else { "fired" },
+        m.index,
+        m.gesture,
+        m.fingers,
+        direction,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{to_json, EventSocket};
+    use wzmach::common::{AnyDirection, Direction};
+    use wzmach::Match;
+
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn sends_lines() {
+        let swipe = Match {
+            time: 1200,
+            index: 2,
+            began: false,
+            gesture: "Swipe",
+            fingers: 3,
+            direction: Some(AnyDirection::Cardinal(Direction::UpLeft)),
+        };
+        let hold = Match {
+            time: 1500,
+            index: 0,
+            began: false,
+            gesture: "Hold",
+            fingers: 4,
+            direction: None,
+        };
+        assert_eq!(
+            to_json(&hold),
+            r#"{"time":1500,"event":"fired","trigger":0,"gesture":"Hold","fingers":4,"direction":null}"#
+        );
+
+        let path = std::env::temp_dir().join(format!("wzmach-events-{}", std::process::id()));
+        let mut socket = EventSocket::bind(&path).unwrap();
+        let client = UnixStream::connect(&path).unwrap();
+        socket.accept();
+        socket.send(&[swipe, hold]);
+        let mut lines = BufReader::new(client).lines();
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            r#"{"time":1200,"event":"fired","trigger":2,"gesture":"Swipe","fingers":3,"direction":"UpLeft"}"#
+        );
+        assert_eq!(lines.next().unwrap().unwrap(), to_json(&hold));
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+}
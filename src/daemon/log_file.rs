@@ -0,0 +1,112 @@
+//! Log file that moves itself aside when it grows too big, keeping a few of the
+//! older ones
+
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Bytes a log file grows to before it's rotated
+pub const MAX_SIZE: u64 = 1024 * 1024;
+/// Rotated log files to keep, as `wzmach.log.1` and so on, 1 the newest
+pub const KEEP: usize = 3;
+
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    keep: usize,
+}
+
+impl RotatingFile {
+    /// Append to the file at the path, creating it if needed
+    pub fn open(path: &Path, max_size: u64, keep: usize) -> io::Result<Self> {
+        let file = Self::append(path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path: path.to_owned(),
+            file,
+            size,
+            max_size,
+            keep,
+        })
+    }
+
+    fn append(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// The path with a number added, like `wzmach.log.2`
+    fn numbered(&self, n: usize) -> PathBuf {
+        let mut name = OsString::from(&self.path);
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                match std::fs::rename(self.numbered(n), self.numbered(n + 1)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => (),
+                }
+            }
+            std::fs::rename(&self.path, self.numbered(1))?;
+        }
+        self.file = Self::append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            // Losing the older logs is better than losing the new ones
+            if let Err(e) = self.rotate() {
+                eprintln!("Can't rotate {}: {}", self.path.display(), e);
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RotatingFile;
+
+    use std::io::Write;
+
+    #[test]
+    fn rotates() {
+        let dir = std::env::temp_dir().join(format!("wzmach-log-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("wzmach.log");
+        let mut log = RotatingFile::open(&path, 10, 2).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            log.write_all(line.as_bytes()).unwrap();
+        }
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("wzmach.log"), "fourth\n");
+        assert_eq!(read("wzmach.log.1"), "third\n");
+        assert_eq!(read("wzmach.log.2"), "second\n");
+        assert!(!dir.join("wzmach.log.3").exists());
+
+        // Picks up the size of the file it appends to
+        let mut log = RotatingFile::open(&path, 10, 2).unwrap();
+        log.write_all(b"fifth\n").unwrap();
+        assert_eq!(read("wzmach.log"), "fifth\n");
+        assert_eq!(read("wzmach.log.1"), "fourth\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,367 @@
+//! The main loop of wzmach: waits for gestures and everything else that can
+//! happen to a running daemon, and reacts to them
+
+mod control;
+mod events;
+pub mod log_file;
+mod notify;
+mod signals;
+mod watch;
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use input::event::{DeviceEvent, EventTrait};
+use input::DeviceCapability;
+use nix::poll::{PollFd, PollFlags};
+use nix::sys::signal::Signal;
+
+use control::{Command, Control};
+use events::EventSocket;
+use notify::Notifier;
+use signals::Signals;
+use watch::ConfigWatcher;
+#[cfg(feature = "uinput")]
+use wzmach::action::KeyboardInputAction;
+use wzmach::action::{InputDevice, VirtualDevice, WaylandKeyboard};
+use wzmach::common::InputBackend;
+use wzmach::input_producer::{event::RawEvent, GestureProducer};
+use wzmach::{Config, Engine};
+
+pub struct Daemon {
+    config_path: Option<PathBuf>,
+    producer: GestureProducer,
+    engine: Engine,
+    signals: Signals,
+    watcher: Option<ConfigWatcher>,
+    notifier: Option<Notifier>,
+    control: Option<Control>,
+    events: Option<EventSocket>,
+}
+
+impl Daemon {
+    /// Set up input and output devices for running the config. The config
+    /// will be reloaded from the path when that file changes or on SIGHUP.
+    /// Gesture events are written to the recording as they come
+    pub fn new(
+        config_path: Option<PathBuf>,
+        config: Config,
+        is_wayland: bool,
+        dry_run: bool,
+        recording: Option<impl Write + 'static>,
+    ) -> Self {
+        // Before anything spawns commands, which shouldn't inherit the socket
+        let notifier = Notifier::from_env(now());
+        let signals = Signals::new().expect("Can't set up signal handling");
+        let event_socket = config.event_socket_path();
+        // Dry runs send no input, and don't open /dev/uinput
+        let device = (!dry_run)
+            .then(|| input_device(&config, is_wayland))
+            .flatten();
+        let mut engine = Engine::new(config, is_wayland, device, dry_run);
+        let events = event_socket.as_deref().and_then(bind_events);
+        engine.set_reporting(events.is_some());
+        if let Some(to) = recording {
+            engine.record(Box::new(to));
+        }
+        let watcher = config_path.as_deref().and_then(|path| {
+            ConfigWatcher::new(path)
+                .map_err(|e| log::warn!("Can't watch config for changes: {}", e))
+                .ok()
+        });
+        let control = Control::new()
+            .map_err(|e| log::warn!("Can't offer pausing over DBus: {}", e))
+            .ok();
+        let producer = GestureProducer::new();
+        log::debug!("Created input connection");
+        Daemon {
+            config_path,
+            producer,
+            engine,
+            signals,
+            watcher,
+            notifier,
+            control,
+            events,
+        }
+    }
+
+    /// Run until SIGINT or SIGTERM. Keys held on the input device are released
+    /// as the daemon is dropped
+    pub fn run(mut self) {
+        self.warm_up();
+        if let Some(n) = &self.notifier {
+            n.ready();
+        }
+        loop {
+            let mut fds = vec![
+                PollFd::new(self.producer.as_raw_fd(), PollFlags::POLLIN),
+                PollFd::new(self.signals.as_raw_fd(), PollFlags::POLLIN),
+            ];
+            let mut watch_fd = None;
+            if let Some(watcher) = &self.watcher {
+                watch_fd = Some(fds.len());
+                fds.push(PollFd::new(watcher.as_raw_fd(), PollFlags::POLLIN));
+            }
+            let mut control_fd = None;
+            if let Some(control) = &self.control {
+                control_fd = Some(fds.len());
+                fds.push(PollFd::new(control.as_raw_fd(), PollFlags::POLLIN));
+            }
+            let mut events_fd = None;
+            if let Some(events) = &self.events {
+                events_fd = Some(fds.len());
+                fds.push(PollFd::new(events.as_raw_fd(), PollFlags::POLLIN));
+            }
+            // Wake up for timed triggers, held keys and the watchdog too
+            let now_ms = now();
+            let release = self.engine.release_wait().map(|wait| {
+                // Rounded up, so as not to wake up just before
+                let ms = wait.as_nanos().div_ceil(1_000_000);
+                now_ms.saturating_add(u32::try_from(ms).unwrap_or(u32::MAX))
+            });
+            let timeout = [
+                self.engine.deadline(),
+                self.notifier.as_ref().and_then(Notifier::deadline),
+                release,
+            ]
+            .into_iter()
+            .flatten()
+            .map(|deadline| i32::try_from(deadline.saturating_sub(now_ms)).unwrap_or(i32::MAX))
+            .min()
+            .unwrap_or(-1);
+            match nix::poll::poll(&mut fds, timeout) {
+                Ok(_) => (),
+                Err(nix::errno::Errno::EINTR) => continue,
+                Err(e) => panic!("Waiting for events failed: {}", e),
+            }
+            let readable =
+                |fd: &PollFd| fd.revents().is_some_and(|r| r.contains(PollFlags::POLLIN));
+
+            if readable(&fds[0]) {
+                self.handle_input();
+            }
+            if self.engine.deadline().is_some() {
+                // Take in the events that came after the poll, so that a
+                // gesture that just ended doesn't repeat once more
+                if self.producer.wait(0) {
+                    self.handle_input();
+                }
+                self.engine.tick(now());
+            }
+            self.engine.release_held();
+            if let Some(events) = &mut self.events {
+                if events_fd.is_some_and(|i| readable(&fds[i])) {
+                    events.accept();
+                }
+                events.send(&self.engine.take_matches());
+            }
+            if let Some(n) = &mut self.notifier {
+                n.keep_alive(now());
+            }
+            if readable(&fds[1]) && !self.handle_signals() {
+                log::info!("Shutting down");
+                if let Some(n) = &self.notifier {
+                    n.stopping();
+                }
+                return;
+            }
+            let config_changed = watch_fd.is_some_and(|i| readable(&fds[i]))
+                && self.watcher.as_mut().is_some_and(|w| w.changed());
+            if config_changed {
+                self.reload();
+            }
+            if control_fd.is_some_and(|i| readable(&fds[i])) {
+                self.handle_control();
+            }
+        }
+    }
+
+    /// Take in the devices that libinput found as it started, before the first
+    /// gesture, so that its touchpad is set up when it comes. Only then is the
+    /// daemon ready
+    fn warm_up(&mut self) {
+        self.producer.dispatch();
+        let mut devices = 0;
+        while let Some(event) = self.producer.next_libinput() {
+            if let input::Event::Device(DeviceEvent::Added(_)) = &event {
+                if event.device().has_capability(DeviceCapability::Gesture) {
+                    devices += 1;
+                }
+            }
+            self.engine.handle_libinput(&event);
+        }
+        match devices {
+            0 => log::warn!(
+                "Ready, but found no gesture devices yet. Is your user in the input group?"
+            ),
+            n => log::info!("Ready, with {} gesture devices", n),
+        }
+    }
+
+    /// Read from libinput, which must have something to read, so that the
+    /// daemon sleeps while there's no input
+    fn handle_input(&mut self) {
+        self.producer.dispatch();
+        while let Some(event) = self.producer.next_libinput() {
+            self.engine.handle_libinput(&event);
+        }
+    }
+
+    fn handle_control(&mut self) {
+        let commands = match self.control.as_mut().map(Control::commands) {
+            Some(Ok(c)) => c,
+            Some(Err(e)) => {
+                log::warn!("Lost the DBus connection, can't be paused anymore: {}", e);
+                self.control = None;
+                return;
+            }
+            None => return,
+        };
+        for command in commands {
+            let paused = match command {
+                Command::Pause => true,
+                Command::Resume => false,
+                Command::Toggle => !self.engine.paused(),
+                Command::SetProfile(profile) => {
+                    if let Err(e) = self.engine.set_profile(profile.as_deref()) {
+                        log::warn!("Can't switch profiles: {}", e);
+                    }
+                    continue;
+                }
+                Command::SetDisplayServer(server) => {
+                    let (server, why) = match server {
+                        Some(s) => (s, "a DBus call says so".to_string()),
+                        None => crate::display::detect(|name| std::env::var(name).ok()),
+                    };
+                    if server.is_wayland() != self.engine.is_wayland() {
+                        log::info!("Switching to the {} triggers, as {}", server, why);
+                        self.engine.set_wayland(server.is_wayland());
+                    }
+                    continue;
+                }
+            };
+            if paused != self.engine.paused() {
+                log::info!("{}", if paused { "Paused" } else { "Resumed" });
+                self.engine.set_paused(paused);
+            }
+        }
+    }
+
+    /// Returns whether to keep running
+    fn handle_signals(&mut self) -> bool {
+        for signal in self.signals.pending() {
+            log::debug!("Received {}", signal);
+            match signal {
+                Signal::SIGHUP => self.reload(),
+                Signal::SIGINT | Signal::SIGTERM => return false,
+                Signal::SIGUSR1 => {
+                    for line in self.engine.take_stats() {
+                        log::info!("{}", line);
+                    }
+                }
+                _ => (),
+            }
+        }
+        true
+    }
+
+    /// Load the config file again and replace the running triggers with it. If
+    /// the file has errors, the old triggers keep running
+    fn reload(&mut self) {
+        let path = match &self.config_path {
+            Some(p) => p,
+            None => return,
+        };
+        log::info!("Reloading config from {}", path.display());
+        match Config::load(path) {
+            Ok(config) => {
+                let event_socket = config.event_socket_path();
+                if event_socket.as_deref() != self.events.as_ref().map(EventSocket::path) {
+                    self.events = None;
+                    self.events = event_socket.as_deref().and_then(bind_events);
+                    self.engine.set_reporting(self.events.is_some());
+                }
+                self.engine.set_config(config);
+            }
+            Err(e) => log::error!("Can't load config, keeping the previous one: {}", e),
+        }
+    }
+}
+
+/// Run the actions of the triggers that the recorded gestures match, instead of
+/// the ones of gestures from libinput
+pub fn replay(config: Config, is_wayland: bool, dry_run: bool, events: &[RawEvent]) {
+    let device = (!dry_run)
+        .then(|| input_device(&config, is_wayland))
+        .flatten();
+    let mut engine = Engine::new(config, is_wayland, device, dry_run);
+    engine.replay(events);
+    log::info!("Replayed {} events", events.len());
+}
+
+/// The uinput device, with the keys going through the wayland virtual keyboard
+/// instead if the config says so. Either one is enough
+fn input_device(config: &Config, is_wayland: bool) -> Option<InputDevice> {
+    let device = uinput_device(config);
+    let otherwise = if cfg!(feature = "uinput") {
+        "using uinput"
+    } else {
+        "keyboard actions only log"
+    };
+    if config.input_backend != InputBackend::WaylandVirtualKeyboard {
+        return device;
+    }
+    if !is_wayland {
+        log::warn!(
+            "The wayland virtual keyboard only works in wayland, {}",
+            otherwise
+        );
+        return device;
+    }
+    match WaylandKeyboard::connect() {
+        Ok(keyboard) => {
+            let device = device.unwrap_or_else(|| {
+                let name = config.virtual_device.name.clone();
+                Rc::new(RefCell::new(VirtualDevice::new(name)))
+            });
+            device.borrow_mut().set_keyboard(keyboard);
+            Some(device)
+        }
+        Err(e) => {
+            log::warn!(
+                "Can't use the wayland virtual keyboard, {}: {}",
+                otherwise,
+                e
+            );
+            device
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "uinput"), allow(unused_variables))]
+fn uinput_device(config: &Config) -> Option<InputDevice> {
+    #[cfg(feature = "uinput")]
+    return KeyboardInputAction::default_device(&config.virtual_device)
+        .map_err(|e| log::error!("Can't create the uinput device: {}", e))
+        .ok();
+    #[cfg(not(feature = "uinput"))]
+    None
+}
+
+fn bind_events(path: &std::path::Path) -> Option<EventSocket> {
+    EventSocket::bind(path)
+        .map_err(|e| log::error!("Can't listen on {}: {}", path.display(), e))
+        .ok()
+}
+
+/// Current time in the clock of libinput's event times: milliseconds of the
+/// monotonic clock, wrapping like libinput's
+fn now() -> u32 {
+    let time = nix::time::clock_gettime(nix::time::ClockId::CLOCK_MONOTONIC)
+        .expect("Can't read the monotonic clock");
+    (time.tv_sec() as u64 * 1000 + time.tv_nsec() as u64 / 1_000_000) as u32
+}
@@ -0,0 +1,119 @@
+use std::ffi::OsStr;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+/// Tells systemd how the daemon is doing, for services of `Type=notify`. Only
+/// exists when systemd asked for that with `$NOTIFY_SOCKET`
+pub struct Notifier {
+    socket: UnixDatagram,
+    addr: SocketAddr,
+    /// Milliseconds between watchdog pings, half of what systemd allows
+    watchdog: Option<u32>,
+    /// Time of the next watchdog ping, in the clock of `super::now`
+    next_ping: u32,
+}
+
+impl Notifier {
+    /// Take the socket and watchdog from the environment, and remove them
+    /// from it so that commands run by actions don't see them
+    pub fn from_env(now: u32) -> Option<Self> {
+        let path = std::env::var_os("NOTIFY_SOCKET")?;
+        let usec = std::env::var("WATCHDOG_USEC").ok();
+        let pid = std::env::var("WATCHDOG_PID").ok();
+        for var in ["NOTIFY_SOCKET", "WATCHDOG_USEC", "WATCHDOG_PID"] {
+            std::env::remove_var(var);
+        }
+        // The watchdog may be meant for another process of the service
+        let for_us = pid.is_none_or(|p| p == std::process::id().to_string());
+        let watchdog = usec
+            .filter(|_| for_us)
+            .and_then(|u| u.parse::<u64>().ok())
+            .map(|u| (u / 2000).clamp(1, u32::MAX.into()) as u32);
+        match Notifier::connect(&path, watchdog, now) {
+            Ok(n) => Some(n),
+            Err(e) => {
+                log::warn!("Can't notify systemd at {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Names starting with `@` are in the abstract namespace
+    fn connect(path: &OsStr, watchdog: Option<u32>, now: u32) -> std::io::Result<Self> {
+        let addr = match path.as_bytes().strip_prefix(b"@") {
+            Some(name) => SocketAddr::from_abstract_name(name)?,
+            None => SocketAddr::from_pathname(path)?,
+        };
+        Ok(Notifier {
+            socket: UnixDatagram::unbound()?,
+            addr,
+            watchdog,
+            next_ping: now.wrapping_add(watchdog.unwrap_or(0)),
+        })
+    }
+
+    fn send(&self, state: &str) {
+        log::trace!("Notifying systemd: {}", state);
+        if let Err(e) = self.socket.send_to_addr(state.as_bytes(), &self.addr) {
+            log::warn!("Can't notify systemd: {}", e);
+        }
+    }
+
+    /// Everything is set up and gestures are handled from now on
+    pub fn ready(&self) {
+        self.send("READY=1");
+    }
+
+    pub fn stopping(&self) {
+        self.send("STOPPING=1");
+    }
+
+    /// When `keep_alive` has to be called next
+    pub fn deadline(&self) -> Option<u32> {
+        self.watchdog.map(|_| self.next_ping)
+    }
+
+    /// Ping the watchdog if it's time to
+    pub fn keep_alive(&mut self, now: u32) {
+        if let Some(interval) = self.watchdog {
+            if now >= self.next_ping {
+                self.send("WATCHDOG=1");
+                self.next_ping = now.wrapping_add(interval);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Notifier;
+
+    #[test]
+    fn pings_watchdog() {
+        let path = std::env::temp_dir().join(format!("wzmach-notify-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let systemd = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+        systemd.set_nonblocking(true).unwrap();
+        let receive = || {
+            let mut buf = [0; 64];
+            match systemd.recv(&mut buf) {
+                Ok(n) => String::from_utf8_lossy(&buf[..n]).into_owned(),
+                Err(_) => String::new(),
+            }
+        };
+
+        let mut notifier = Notifier::connect(path.as_os_str(), Some(500), 1000).unwrap();
+        notifier.ready();
+        assert_eq!(receive(), "READY=1");
+        assert_eq!(notifier.deadline(), Some(1500));
+
+        notifier.keep_alive(1200);
+        assert_eq!(receive(), "");
+        notifier.keep_alive(1500);
+        assert_eq!(receive(), "WATCHDOG=1");
+        assert_eq!(notifier.deadline(), Some(2000));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
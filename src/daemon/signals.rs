@@ -0,0 +1,41 @@
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use nix::sys::signal::{SigSet, Signal};
+use nix::sys::signalfd::{SfdFlags, SignalFd};
+
+/// Signals the daemon reacts to. They are blocked and read from a file
+/// descriptor in the main loop, so handling them needs no async-signal-safety
+pub struct Signals {
+    fd: SignalFd,
+}
+
+impl Signals {
+    pub fn new() -> nix::Result<Self> {
+        let mut mask = SigSet::empty();
+        mask.add(Signal::SIGHUP);
+        mask.add(Signal::SIGINT);
+        mask.add(Signal::SIGTERM);
+        mask.add(Signal::SIGUSR1);
+        mask.thread_block()?;
+        let fd = SignalFd::with_flags(&mask, SfdFlags::SFD_NONBLOCK | SfdFlags::SFD_CLOEXEC)?;
+        Ok(Signals { fd })
+    }
+
+    /// All the signals received since the last call
+    pub fn pending(&mut self) -> Vec<Signal> {
+        let mut signals = Vec::new();
+        while let Ok(Some(info)) = self.fd.read_signal() {
+            match Signal::try_from(info.ssi_signo as i32) {
+                Ok(signal) => signals.push(signal),
+                Err(e) => log::warn!("Unknown signal {}: {}", info.ssi_signo, e),
+            }
+        }
+        signals
+    }
+}
+
+impl AsRawFd for Signals {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
@@ -0,0 +1,77 @@
+use std::ffi::OsString;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+
+/// Watches the config file for modifications. Editors usually replace the file
+/// instead of writing into it, so this watches the whole directory and picks
+/// out the events for the file by name
+pub struct ConfigWatcher {
+    inotify: Inotify,
+    name: OsString,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path) -> nix::Result<Self> {
+        let dir = match path.parent() {
+            Some(d) if !d.as_os_str().is_empty() => d,
+            _ => Path::new("."),
+        };
+        let name = path.file_name().unwrap_or_default().to_owned();
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        inotify.add_watch(
+            dir,
+            AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_MOVED_TO,
+        )?;
+        log::debug!("Watching {} for changes", path.display());
+        Ok(ConfigWatcher { inotify, name })
+    }
+
+    /// Read all pending notifications. True if any of them were about the
+    /// config file
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        // Reads fail with EAGAIN when there's nothing left
+        while let Ok(events) = self.inotify.read_events() {
+            if events.is_empty() {
+                break;
+            }
+            changed |= events.iter().any(|e| e.name.as_ref() == Some(&self.name));
+        }
+        changed
+    }
+}
+
+impl AsRawFd for ConfigWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inotify.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConfigWatcher;
+
+    #[test]
+    fn sees_replaced_file() {
+        let dir = std::env::temp_dir().join(format!("wzmach-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.ron");
+        std::fs::write(&path, "()").unwrap();
+
+        let mut watcher = ConfigWatcher::new(&path).unwrap();
+        assert!(!watcher.changed());
+
+        std::fs::write(dir.join("other.ron"), "()").unwrap();
+        assert!(!watcher.changed());
+
+        // like an editor saving through a temporary file
+        let temp = dir.join(".config.ron.swp");
+        std::fs::write(&temp, "(swipe_distance: 50)").unwrap();
+        std::fs::rename(&temp, &path).unwrap();
+        assert!(watcher.changed());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
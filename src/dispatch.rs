@@ -0,0 +1,569 @@
+//! Decides which of the triggered actions should run, and runs them
+
+use crate::action::{self, Action, ActionError, GestureContext};
+use crate::common::{OnError, Region};
+use crate::gesture_event::TriggerEvent;
+use crate::window::{ActiveWindow, Pointer, Windows};
+
+use std::collections::HashSet;
+
+/// An action together with the conditions under which it is allowed to run
+pub struct Binding {
+    pub action: Box<dyn Action>,
+    /// Run only when the focused window has this class. `None` runs anywhere
+    pub window_class: Option<String>,
+    /// Don't run while the focused window is fullscreen. When that can't be
+    /// told, the binding runs
+    pub disable_on_fullscreen: bool,
+    /// Run only when the pointer is in this part of the screen. `None` runs
+    /// anywhere
+    pub region: Option<Region>,
+    /// Of the bindings firing together, only the one with the highest priority
+    /// runs, unless all are allowed to
+    pub priority: i32,
+    /// What to do when the action fails
+    pub on_error: OnError,
+    /// Fires only for gestures that no other trigger matched, which the
+    /// recognizer decides
+    pub fallback: bool,
+    /// Swipes and shears measured from where the gesture last turned their
+    /// way, which the recognizer decides
+    pub from_turn: bool,
+    /// Completes a sequence of gestures. Its last step can be a trigger of its
+    /// own that fires together with it, so sequences are only weighed against
+    /// each other, and not against the other triggers
+    pub sequence: bool,
+    /// Run only while the key with this code is held down. `None` runs
+    /// without keys
+    pub require_key: Option<u32>,
+}
+
+impl Binding {
+    /// Deal with the action failing on the event as `on_error` says. Actions
+    /// running in the background deal with their failures themselves
+    fn failed(&mut self, event: &TriggerEvent, msg: String) {
+        let retry = matches!(event, TriggerEvent::Fired(_) | TriggerEvent::Repeated(_));
+        action::recover(self.action.as_mut(), self.on_error, retry, msg);
+    }
+
+    fn needs_window(&self) -> bool {
+        self.window_class.is_some() || self.disable_on_fullscreen
+    }
+
+    /// Check the conditions against the focused window and the pointer.
+    /// Returns why the binding can't run
+    fn refusal(
+        &self,
+        window: Option<&ActiveWindow>,
+        pointer: Option<&Pointer>,
+        keys: &HashSet<u32>,
+    ) -> Option<&'static str> {
+        let class_matches = match (&self.window_class, window) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(want), Some(have)) => have.class.iter().any(|c| c == want),
+        };
+        if self.require_key.is_some_and(|key| !keys.contains(&key)) {
+            Some("required key isn't held")
+        } else if !class_matches {
+            Some("window class doesn't match")
+        } else if self.disable_on_fullscreen && window.is_some_and(|w| w.fullscreen) {
+            Some("window is fullscreen")
+        } else if let Some(region) = &self.region {
+            match pointer {
+                Some(p) if p.within(region) => None,
+                Some(_) => Some("pointer is outside the region"),
+                None => Some("pointer position is unknown"),
+            }
+        } else {
+            None
+        }
+    }
+}
+
+/// Executes triggered bindings whose conditions hold
+pub struct Dispatcher {
+    bindings: Vec<Binding>,
+    windows: Option<Windows>,
+    /// Continuous bindings that passed the conditions when they began, and so
+    /// receive progress until the gesture ends
+    following: Vec<usize>,
+    /// Run every binding that fires with the same event, instead of only the
+    /// one with the highest priority
+    fire_all: bool,
+    /// Codes of the keys held down on the keyboards
+    keys: HashSet<u32>,
+}
+
+impl Dispatcher {
+    pub fn new(bindings: Vec<Binding>, windows: Option<Windows>) -> Self {
+        Dispatcher {
+            bindings,
+            windows,
+            following: Vec::new(),
+            fire_all: false,
+            keys: HashSet::new(),
+        }
+    }
+
+    /// Take in a key going down or up on a keyboard
+    pub fn set_key(&mut self, code: u32, pressed: bool) {
+        if pressed {
+            self.keys.insert(code);
+        } else {
+            self.keys.remove(&code);
+        }
+    }
+
+    /// Query this for the focused window and the pointer from now on
+    pub fn set_windows(&mut self, windows: Option<Windows>) {
+        self.windows = windows;
+    }
+
+    pub fn set_fire_all(&mut self, fire_all: bool) {
+        self.fire_all = fire_all;
+    }
+
+    /// Give the action of the binding the gesture that its next event comes
+    /// from
+    pub fn set_context(&mut self, index: usize, context: &GestureContext) {
+        self.bindings[index].action.context(context);
+    }
+
+    /// Replace all the bindings, for example after the config changed. Indices
+    /// in the next events are for the new bindings. The ones following the
+    /// gesture end first, as they won't hear of the gesture anymore
+    pub fn set_bindings(&mut self, bindings: Vec<Binding>) {
+        let ended: Vec<_> = self
+            .following
+            .iter()
+            .map(|&i| TriggerEvent::Ended(i))
+            .collect();
+        self.dispatch(&ended);
+        self.bindings = bindings;
+    }
+
+    /// Replace the bindings from the index on, keeping the ones before and
+    /// their state. All the ones following the gesture end first, like with
+    /// `set_bindings`
+    pub fn set_bindings_from(&mut self, from: usize, bindings: Vec<Binding>) {
+        let ended: Vec<_> = self
+            .following
+            .iter()
+            .map(|&i| TriggerEvent::Ended(i))
+            .collect();
+        self.dispatch(&ended);
+        self.bindings.truncate(from);
+        self.bindings.extend(bindings);
+    }
+
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// Run the bindings for events returned by the event adapter. Returns
+    /// the bindings that fired or began following the gesture
+    pub fn dispatch(&mut self, events: &[TriggerEvent]) -> Vec<usize> {
+        // Querying the window is a round trip to the display server, so only
+        // do it when some binding cares
+        let needs_window = events.iter().any(|e| match e {
+            TriggerEvent::Fired(i) | TriggerEvent::Began(i) => self.bindings[*i].needs_window(),
+            _ => false,
+        });
+        let window = if needs_window {
+            self.windows
+                .as_ref()
+                .and_then(|w| w.borrow_mut().active_window())
+        } else {
+            None
+        };
+        let needs_pointer = events.iter().any(|e| match e {
+            TriggerEvent::Fired(i) | TriggerEvent::Began(i) => self.bindings[*i].region.is_some(),
+            _ => false,
+        });
+        let pointer = if needs_pointer {
+            self.windows.as_ref().and_then(|w| w.borrow_mut().pointer())
+        } else {
+            None
+        };
+
+        // The bindings that start running, and of them the ones that win, one
+        // of the sequences and one of the rest. Ties go to the one first in the
+        // config
+        let allowed: Vec<usize> = events
+            .iter()
+            .filter_map(|e| match e {
+                TriggerEvent::Fired(i) | TriggerEvent::Began(i) => Some(*i),
+                _ => None,
+            })
+            .filter(|&i| {
+                match self.bindings[i].refusal(window.as_ref(), pointer.as_ref(), &self.keys) {
+                    Some(reason) => {
+                        log::debug!("Skipping trigger {}: {}", i, reason);
+                        false
+                    }
+                    None => true,
+                }
+            })
+            .collect();
+        let winner = |sequence: bool| {
+            allowed
+                .iter()
+                .copied()
+                .filter(|&i| self.bindings[i].sequence == sequence)
+                .max_by_key(|&i| (self.bindings[i].priority, std::cmp::Reverse(i)))
+        };
+        let winners = [winner(false), winner(true)];
+        let running: Vec<usize> = allowed
+            .iter()
+            .copied()
+            .filter(|&i| match winners[self.bindings[i].sequence as usize] {
+                Some(w) if !self.fire_all && w != i => {
+                    log::debug!("Skipping trigger {}: trigger {} takes priority", i, w);
+                    false
+                }
+                _ => true,
+            })
+            .collect();
+        let runs = |index: usize| running.contains(&index);
+
+        let mut ran = Vec::new();
+        for event in events {
+            let binding = &mut self.bindings[index(event)];
+            let result = match *event {
+                TriggerEvent::Fired(index) => {
+                    if !runs(index) {
+                        continue;
+                    }
+                    ran.push(index);
+                    binding.action.execute()
+                }
+                TriggerEvent::Began(index) => {
+                    if !runs(index) {
+                        continue;
+                    }
+                    ran.push(index);
+                    self.following.push(index);
+                    binding.action.begin()
+                }
+                TriggerEvent::Progress(index, delta) => {
+                    if !self.following.contains(&index) {
+                        continue;
+                    }
+                    binding.action.update(delta)
+                }
+                TriggerEvent::Moved(index, dx, dy) => {
+                    if !self.following.contains(&index) {
+                        continue;
+                    }
+                    binding.action.motion(dx, dy)
+                }
+                TriggerEvent::Repeated(index) => {
+                    if !self.following.contains(&index) {
+                        continue;
+                    }
+                    binding.action.execute()
+                }
+                TriggerEvent::Ended(index) => {
+                    if !self.following.contains(&index) {
+                        continue;
+                    }
+                    self.following.retain(|i| *i != index);
+                    binding.action.end()
+                }
+            };
+            if let Err(ActionError(msg)) = result {
+                binding.failed(event, msg);
+            }
+        }
+        ran
+    }
+}
+
+/// Index of the binding the event is for
+fn index(event: &TriggerEvent) -> usize {
+    match *event {
+        TriggerEvent::Fired(i)
+        | TriggerEvent::Began(i)
+        | TriggerEvent::Progress(i, _)
+        | TriggerEvent::Moved(i, _, _)
+        | TriggerEvent::Repeated(i)
+        | TriggerEvent::Ended(i) => i,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Dispatcher;
+    use crate::action::{Action, ActionError, MockAction, MockCall, MockCalls};
+    use crate::common::{OnError, Region};
+    use crate::gesture_event::TriggerEvent::{Began, Ended, Fired};
+    use crate::window::{ActiveWindow, Pointer, WindowSource};
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Record(Rc<RefCell<Vec<usize>>>, usize);
+    impl Action for Record {
+        fn execute(&mut self) -> Result<(), ActionError> {
+            self.0.borrow_mut().push(self.1);
+            Ok(())
+        }
+
+        fn describe(&self) -> String {
+            format!("record {}", self.1)
+        }
+    }
+
+    struct Fail;
+    impl Action for Fail {
+        fn execute(&mut self) -> Result<(), ActionError> {
+            Err(ActionError("failed".to_string()))
+        }
+
+        fn describe(&self) -> String {
+            "fail".to_string()
+        }
+    }
+
+    struct Fixed(Option<ActiveWindow>);
+    impl WindowSource for Fixed {
+        fn active_window(&mut self) -> Option<ActiveWindow> {
+            self.0.clone()
+        }
+    }
+
+    struct At(Pointer);
+    impl WindowSource for At {
+        fn active_window(&mut self) -> Option<ActiveWindow> {
+            None
+        }
+
+        fn pointer(&mut self) -> Option<Pointer> {
+            Some(self.0)
+        }
+    }
+
+    fn dispatcher(
+        classes: &[Option<&str>],
+        active: Option<ActiveWindow>,
+    ) -> (Dispatcher, Rc<RefCell<Vec<usize>>>) {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let bindings = classes
+            .iter()
+            .enumerate()
+            .map(|(i, c)| super::Binding {
+                action: Box::new(Record(log.clone(), i)),
+                window_class: c.map(String::from),
+                disable_on_fullscreen: false,
+                region: None,
+                priority: 0,
+                on_error: OnError::Log,
+                fallback: false,
+                from_turn: false,
+                sequence: false,
+                require_key: None,
+            })
+            .collect();
+        (
+            Dispatcher::new(bindings, Some(Rc::new(RefCell::new(Fixed(active))))),
+            log,
+        )
+    }
+
+    #[test]
+    fn window_class_scoping() {
+        let classes = [None, Some("firefox"), Some("Alacritty")];
+        let active = ActiveWindow {
+            class: vec!["Navigator".to_string(), "firefox".to_string()],
+            fullscreen: false,
+        };
+        let (mut d, log) = dispatcher(&classes, Some(active));
+        d.set_fire_all(true);
+        d.dispatch(&[Fired(0), Fired(1), Fired(2)]);
+        assert_eq!(*log.borrow(), vec![0, 1]);
+    }
+
+    #[test]
+    fn priority() {
+        let classes = [None, Some("firefox"), None];
+        let active = |class: &str| ActiveWindow {
+            class: vec![class.to_string()],
+            fullscreen: false,
+        };
+        let (mut d, log) = dispatcher(&classes, Some(active("firefox")));
+        d.bindings[1].priority = 1;
+        d.dispatch(&[Fired(0), Fired(1), Fired(2)]);
+        assert_eq!(*log.borrow(), vec![1]);
+
+        // The specific one can't run, so of the tied ones the first does
+        d.windows = Some(Rc::new(RefCell::new(Fixed(Some(active("Alacritty"))))));
+        d.dispatch(&[Fired(0), Fired(1), Fired(2)]);
+        assert_eq!(*log.borrow(), vec![1, 0]);
+
+        d.bindings[2].priority = 2;
+        d.dispatch(&[Fired(0), Fired(2)]);
+        assert_eq!(*log.borrow(), vec![1, 0, 2]);
+
+        d.set_fire_all(true);
+        d.dispatch(&[Fired(0), Fired(2)]);
+        assert_eq!(*log.borrow(), vec![1, 0, 2, 0, 2]);
+    }
+
+    #[test]
+    fn sequence_with_its_last_step() {
+        let (mut d, log) = dispatcher(&[None, None, None], None);
+        d.bindings[2].sequence = true;
+        // The step wins over the other trigger, and the sequence it completes
+        // runs too
+        d.dispatch(&[Fired(0), Fired(1), Fired(2)]);
+        assert_eq!(*log.borrow(), vec![0, 2]);
+
+        // Whichever comes first in the config or has the priority
+        d.bindings[1].priority = 1;
+        d.bindings[2].priority = -1;
+        d.dispatch(&[Fired(2), Fired(0), Fired(1)]);
+        assert_eq!(*log.borrow(), vec![0, 2, 2, 1]);
+    }
+
+    #[test]
+    fn ends_followed_when_replaced() {
+        let (mut d, _) = dispatcher(&[None, None], None);
+        let calls = MockCalls::default();
+        for (i, binding) in d.bindings.iter_mut().enumerate() {
+            binding.action = Box::new(MockAction {
+                action: i,
+                calls: calls.clone(),
+            });
+        }
+        d.dispatch(&[Began(1)]);
+        let (replacement, _) = dispatcher(&[None], None);
+        d.set_bindings(replacement.bindings);
+        assert_eq!(*calls.borrow(), [(1, MockCall::Begin), (1, MockCall::End)]);
+        // And the new ones don't get the end of the gesture
+        d.dispatch(&[Ended(0)]);
+        assert_eq!(calls.borrow().len(), 2);
+    }
+
+    #[test]
+    fn no_focused_window() {
+        let classes = [None, Some("firefox")];
+        let (mut d, log) = dispatcher(&classes, None);
+        d.dispatch(&[Fired(0), Fired(1)]);
+        assert_eq!(*log.borrow(), vec![0]);
+    }
+
+    #[test]
+    fn fullscreen() {
+        let active = ActiveWindow {
+            class: vec!["mpv".to_string()],
+            fullscreen: true,
+        };
+        let (mut d, log) = dispatcher(&[None, None], Some(active));
+        d.bindings[1].disable_on_fullscreen = true;
+        d.dispatch(&[Fired(0), Fired(1)]);
+        assert_eq!(*log.borrow(), vec![0]);
+    }
+
+    #[test]
+    fn region() {
+        let (mut d, log) = dispatcher(&[None, None, None, None], None);
+        d.set_fire_all(true);
+        d.windows = Some(Rc::new(RefCell::new(At(Pointer {
+            x: 2000,
+            y: 100,
+            monitor: Some(1),
+        }))));
+        d.bindings[1].region = Some(Region::Monitor(0));
+        d.bindings[2].region = Some(Region::Monitor(1));
+        d.bindings[3].region = Some(Region::Rect {
+            x: 1920,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        });
+        d.dispatch(&[Fired(0), Fired(1), Fired(2), Fired(3)]);
+        assert_eq!(*log.borrow(), vec![0, 2, 3]);
+
+        // Without a pointer position, only the unscoped binding runs
+        d.windows = None;
+        d.dispatch(&[Fired(0), Fired(1), Fired(2), Fired(3)]);
+        assert_eq!(*log.borrow(), vec![0, 2, 3, 0]);
+    }
+
+    #[test]
+    fn required_key() {
+        let (mut d, log) = dispatcher(&[None, None], None);
+        d.set_fire_all(true);
+        d.bindings[1].require_key = Some(125);
+        d.dispatch(&[Fired(0), Fired(1)]);
+        d.set_key(125, true);
+        d.dispatch(&[Fired(0), Fired(1)]);
+        d.set_key(125, false);
+        d.dispatch(&[Fired(1)]);
+        assert_eq!(*log.borrow(), vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn failure_is_logged() {
+        let (mut d, log) = dispatcher(&[None, None], None);
+        d.set_fire_all(true);
+        d.bindings.insert(
+            0,
+            super::Binding {
+                action: Box::new(Fail),
+                window_class: None,
+                disable_on_fullscreen: false,
+                region: None,
+                priority: 0,
+                on_error: OnError::Log,
+                fallback: false,
+                from_turn: false,
+                sequence: false,
+                require_key: None,
+            },
+        );
+        d.dispatch(&[Fired(0), Fired(1), Fired(0), Fired(2)]);
+        assert_eq!(*log.borrow(), vec![0, 1]);
+    }
+
+    /// Fails the first few times, and records the times it was executed
+    struct Flaky(Rc<RefCell<Vec<usize>>>, usize);
+    impl Action for Flaky {
+        fn execute(&mut self) -> Result<(), ActionError> {
+            let mut log = self.0.borrow_mut();
+            let tries = log.len();
+            log.push(tries);
+            if log.len() <= self.1 {
+                return Err(ActionError("failed".to_string()));
+            }
+            Ok(())
+        }
+
+        fn describe(&self) -> String {
+            "flaky".to_string()
+        }
+    }
+
+    #[test]
+    fn retry() {
+        let (mut d, log) = dispatcher(&[None], None);
+        d.bindings[0].action = Box::new(Flaky(log.clone(), 1));
+        d.bindings[0].on_error = OnError::Retry;
+        d.dispatch(&[Fired(0)]);
+        assert_eq!(*log.borrow(), vec![0, 1]);
+
+        // Retried only once
+        let (mut d, log) = dispatcher(&[None], None);
+        d.bindings[0].action = Box::new(Flaky(log.clone(), 5));
+        d.bindings[0].on_error = OnError::Retry;
+        d.dispatch(&[Fired(0)]);
+        assert_eq!(*log.borrow(), vec![0, 1]);
+
+        // And not without on_error
+        let (mut d, log) = dispatcher(&[None], None);
+        d.bindings[0].action = Box::new(Flaky(log.clone(), 1));
+        d.dispatch(&[Fired(0)]);
+        assert_eq!(*log.borrow(), vec![0]);
+    }
+}
@@ -0,0 +1,125 @@
+//! Which display server wzmach runs in, which picks the triggers it loads
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum DisplayServer {
+    X11,
+    Wayland,
+}
+
+impl DisplayServer {
+    /// Tell from the environment, or take the one given. Logs which it is and
+    /// why
+    pub fn choose(given: Option<DisplayServer>) -> DisplayServer {
+        let (server, why) = match given {
+            Some(s) => (s, "--display-server says so".to_string()),
+            None => detect(|name| std::env::var(name).ok()),
+        };
+        log::info!("Using the {} triggers, as {}", server, why);
+        server
+    }
+
+    pub fn is_wayland(self) -> bool {
+        self == DisplayServer::Wayland
+    }
+}
+
+/// The display server the variables point to, and why. The session type comes
+/// first, as under XWayland `DISPLAY` is set too, and a nested compositor sets
+/// `WAYLAND_DISPLAY` in an X11 session. Services may not have it though
+pub fn detect(var: impl Fn(&str) -> Option<String>) -> (DisplayServer, String) {
+    let set = |name| var(name).filter(|v| !v.is_empty());
+    match set("XDG_SESSION_TYPE").as_deref() {
+        Some("wayland") => {
+            return (
+                DisplayServer::Wayland,
+                "XDG_SESSION_TYPE is wayland".to_string(),
+            )
+        }
+        Some("x11") => return (DisplayServer::X11, "XDG_SESSION_TYPE is x11".to_string()),
+        _ => (),
+    }
+    if let Some(display) = set("WAYLAND_DISPLAY") {
+        (
+            DisplayServer::Wayland,
+            format!("WAYLAND_DISPLAY is {}", display),
+        )
+    } else if let Some(display) = set("DISPLAY") {
+        (DisplayServer::X11, format!("DISPLAY is {}", display))
+    } else {
+        (
+            DisplayServer::X11,
+            "none of XDG_SESSION_TYPE, WAYLAND_DISPLAY and DISPLAY tell".to_string(),
+        )
+    }
+}
+
+impl FromStr for DisplayServer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "x11" => Ok(DisplayServer::X11),
+            "wayland" => Ok(DisplayServer::Wayland),
+            _ => Err(format!("{:?} isn't x11 or wayland", s)),
+        }
+    }
+}
+
+impl fmt::Display for DisplayServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            DisplayServer::X11 => "X11",
+            DisplayServer::Wayland => "wayland",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{detect, DisplayServer};
+
+    fn detect_with(vars: &[(&str, &str)]) -> DisplayServer {
+        detect(|name| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        })
+        .0
+    }
+
+    #[test]
+    fn detects() {
+        use DisplayServer::*;
+        // XWayland
+        let xwayland = [
+            ("XDG_SESSION_TYPE", "wayland"),
+            ("WAYLAND_DISPLAY", "wayland-1"),
+            ("DISPLAY", ":0"),
+        ];
+        assert_eq!(detect_with(&xwayland), Wayland);
+        // Nested compositor
+        let nested = [
+            ("XDG_SESSION_TYPE", "x11"),
+            ("WAYLAND_DISPLAY", "wayland-1"),
+            ("DISPLAY", ":0"),
+        ];
+        assert_eq!(detect_with(&nested), X11);
+        // Started by a service, which has no session type
+        assert_eq!(detect_with(&[("WAYLAND_DISPLAY", "wayland-0")]), Wayland);
+        assert_eq!(
+            detect_with(&[("XDG_SESSION_TYPE", "tty"), ("DISPLAY", ":1")]),
+            X11
+        );
+        assert_eq!(
+            detect_with(&[("WAYLAND_DISPLAY", ""), ("DISPLAY", ":1")]),
+            X11
+        );
+        assert_eq!(detect_with(&[]), X11);
+
+        assert_eq!("Wayland".parse(), Ok(Wayland));
+        assert!("mir".parse::<DisplayServer>().is_err());
+    }
+}
@@ -0,0 +1,623 @@
+//! Gestures in, actions out. The engine recognizes the triggers of a config in
+//! the events it's given and runs their actions, without owning a main loop or
+//! the connection to libinput
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::rc::Rc;
+
+use input::event::keyboard::{KeyState, KeyboardEvent, KeyboardEventTrait};
+use input::event::{DeviceEvent, EventTrait};
+use input::DeviceCapability;
+
+use crate::action::{DryRunAction, GestureContext, InputDevice, VirtualDevice};
+use crate::common::{AnyDirection, DeviceId, InputBackend, PinchMode};
+use crate::config::{Config, DeviceFilter, MockCalls, ScaledDevice};
+use crate::dispatch::{Binding, Dispatcher};
+use crate::gesture_event::trigger::Trigger;
+use crate::gesture_event::{Recognizer, TriggerEvent};
+use crate::input_producer::{
+    self,
+    event::{Gesture, GestureKind, InputEvent, Phase, RawEvent},
+    touch::Touchpad,
+};
+use crate::stats::Stats;
+use crate::window::{self, Windows};
+
+pub struct Engine {
+    is_wayland: bool,
+    /// Kept across config changes, so that keys pressed on it stay consistent
+    input_device: Option<InputDevice>,
+    /// What the input device was created as, by the first config
+    virtual_device: DeviceId,
+    input_backend: InputBackend,
+    /// Focused window and pointer, for triggers and actions limited to some
+    windows: Option<Windows>,
+    /// Only log the actions instead of running them
+    dry_run: bool,
+    /// Where mocks in place of the actions keep their calls
+    mock: Option<MockCalls>,
+    /// Kept to make the triggers again when switching profiles
+    config: Config,
+    /// Profile whose triggers are loaded with the others
+    profile: Option<String>,
+    /// Patterns of the devices to take gestures from, or `None` for all
+    device_filter: Option<Vec<DeviceFilter>>,
+    /// Event nodes of the gesture devices by their names, like `event5`, for
+    /// where swipes start
+    touchpads: HashMap<String, Touchpad>,
+    /// Gesture being built from libinput events
+    current: Gesture,
+    /// Event node of the device the gesture came from, to cancel it when that
+    /// device goes away
+    gesture_device: Option<String>,
+    /// Time of the last gesture event, to end gestures at when their device
+    /// goes away
+    time: u32,
+    /// Gesture of the last event fed, while it goes on
+    ongoing: Option<Gesture>,
+    /// Don't run any actions
+    paused: bool,
+    /// Events are dropped until the gesture going on is over, because it was
+    /// going on while paused
+    ignoring: bool,
+    /// Triggers that matched since `take_matches`, or `None` when not asked to
+    /// keep them
+    matches: Option<Vec<Match>>,
+    /// Counts of the gestures and of the triggers that ran, until taken
+    stats: Stats,
+    /// Where to write the gesture events to, to replay them later
+    recording: Option<Box<dyn Write>>,
+    triggers: Vec<Trigger>,
+    /// How many of the triggers are the global ones and the ones of the
+    /// display server, which stay loaded when the profile switches
+    shared: usize,
+    recognizer: Recognizer,
+    dispatcher: Dispatcher,
+}
+
+impl Engine {
+    /// Set up the triggers of the config. Keyboard and mouse actions are sent
+    /// to the input device; without one, triggers with such actions are left
+    /// out. In a dry run the actions are only logged, and don't need the
+    /// device
+    pub fn new(
+        config: Config,
+        is_wayland: bool,
+        input_device: Option<InputDevice>,
+        dry_run: bool,
+    ) -> Self {
+        let windows = window::detect(is_wayland);
+        // A device that has nothing to send with, for actions that never run
+        let input_device = input_device.or_else(|| {
+            let name = config.virtual_device.name.clone();
+            dry_run.then(|| Rc::new(RefCell::new(VirtualDevice::new(name))))
+        });
+        let mut engine = Engine {
+            is_wayland,
+            input_device,
+            virtual_device: config.virtual_device.clone(),
+            input_backend: config.input_backend,
+            windows: windows.clone(),
+            dry_run,
+            mock: None,
+            config: Config::default(),
+            profile: config.profile.clone(),
+            device_filter: None,
+            touchpads: HashMap::new(),
+            current: Gesture::None,
+            gesture_device: None,
+            time: 0,
+            ongoing: None,
+            paused: false,
+            ignoring: false,
+            matches: None,
+            stats: Stats::default(),
+            recording: None,
+            triggers: Vec::new(),
+            shared: 0,
+            recognizer: Recognizer::new(&[]),
+            dispatcher: Dispatcher::new(Vec::new(), windows),
+        };
+        engine.set_config(config);
+        engine
+    }
+
+    /// Replace the running triggers with the ones of this config. The active
+    /// profile stays active if the config still has it
+    pub fn set_config(&mut self, config: Config) {
+        if config.virtual_device != self.virtual_device {
+            log::warn!("virtual_device changed, which takes a restart");
+        }
+        if config.input_backend != self.input_backend {
+            log::warn!("input_backend changed, which takes a restart");
+        }
+        if let Some(profile) = &self.profile {
+            if !config.profiles.contains_key(profile) {
+                log::warn!("Profile {:?} is gone from the config, leaving it", profile);
+                self.profile = None;
+            }
+        }
+        self.device_filter = config.device_filter.clone();
+        self.dispatcher.set_fire_all(config.fire_all);
+        self.config = config;
+        self.load_triggers();
+    }
+
+    /// Switch to the profile, or to none, loading its triggers in place of the
+    /// ones of the profile before. The other triggers keep their actions, with
+    /// the state of toggles and shells. Fails if the config has no such
+    /// profile
+    pub fn set_profile(&mut self, profile: Option<&str>) -> Result<(), String> {
+        if let Some(name) = profile {
+            if !self.config.profiles.contains_key(name) {
+                return Err(format!("No profile named {:?}", name));
+            }
+        }
+        log::info!("Switching to profile {:?}", profile);
+        self.profile = profile.map(str::to_string);
+        self.load_profile();
+        Ok(())
+    }
+
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// Load the triggers for the other display server in place of the ones
+    /// loaded, and look up windows in it, as when the session changed. The
+    /// input device stays as it was created
+    pub fn set_wayland(&mut self, is_wayland: bool) {
+        if is_wayland == self.is_wayland {
+            return;
+        }
+        self.is_wayland = is_wayland;
+        self.windows = window::detect(is_wayland);
+        self.dispatcher.set_windows(self.windows.clone());
+        self.load_triggers();
+    }
+
+    pub fn is_wayland(&self) -> bool {
+        self.is_wayland
+    }
+
+    /// Replace the actions with mocks that keep their calls in what's
+    /// returned, to test which actions gestures lead to. The triggers of later
+    /// configs and profiles get mocks too
+    pub fn mock_actions(&mut self) -> MockCalls {
+        let calls = MockCalls::default();
+        self.mock = Some(calls.clone());
+        self.load_triggers();
+        calls
+    }
+
+    fn load_triggers(&mut self) {
+        let (mut triggers, mut bindings) = self.config.make_shared_triggers(
+            self.is_wayland,
+            self.input_device.as_ref(),
+            self.windows.as_ref(),
+            self.mock.as_ref(),
+        );
+        self.shared = triggers.len();
+        let (profile_triggers, profile_bindings) = self.make_profile_triggers();
+        triggers.extend(profile_triggers);
+        bindings.extend(profile_bindings);
+        if self.dry_run {
+            bindings = dry_run(&triggers, bindings);
+        }
+        self.dispatcher.set_bindings(bindings);
+        self.set_triggers(triggers);
+    }
+
+    /// Load the triggers of the profile in place of the ones loaded for it
+    /// before, leaving the shared ones as they are
+    fn load_profile(&mut self) {
+        let (profile_triggers, mut bindings) = self.make_profile_triggers();
+        if self.dry_run {
+            bindings = dry_run(&profile_triggers, bindings);
+        }
+        let mut triggers = self.triggers[..self.shared].to_vec();
+        triggers.extend(profile_triggers);
+        self.dispatcher.set_bindings_from(self.shared, bindings);
+        self.set_triggers(triggers);
+    }
+
+    fn make_profile_triggers(&self) -> (Vec<Trigger>, Vec<Binding>) {
+        self.config.make_profile_triggers(
+            self.is_wayland,
+            self.profile.as_deref(),
+            self.input_device.as_ref(),
+            self.windows.as_ref(),
+            self.mock.as_ref(),
+        )
+    }
+
+    /// Recognize the triggers, which the bindings of the dispatcher are for
+    fn set_triggers(&mut self, triggers: Vec<Trigger>) {
+        let bindings = self.dispatcher.bindings();
+        self.recognizer = Recognizer::new(&triggers);
+        self.recognizer.set_coalesce(self.config.coalesce_ms);
+        self.recognizer.set_rearm_on_lift(self.config.rearm_on_lift);
+        self.recognizer
+            .set_pinch_from_begin(self.config.pinch_mode == PinchMode::Relative);
+        self.recognizer.set_deadzone(self.config.swipe_deadzone);
+        self.recognizer
+            .set_fallbacks(bindings.iter().map(|b| b.fallback).collect());
+        self.recognizer
+            .set_from_turn(bindings.iter().map(|b| b.from_turn).collect());
+        self.stats.reload(&self.triggers, &triggers);
+        log::info!("Loaded {} triggers", triggers.len());
+        self.triggers = triggers;
+        self.check_fingers();
+    }
+
+    /// Warn of the triggers that need more fingers than any gesture device
+    /// tells apart, which never match. Nothing is known before a device is
+    /// found
+    fn check_fingers(&self) {
+        let max = match self.touchpads.values().map(Touchpad::max_fingers).max() {
+            Some(m) => m,
+            None => return,
+        };
+        for (i, trigger) in self.triggers.iter().enumerate() {
+            if trigger.fingers() > max {
+                log::warn!(
+                    "Trigger {}, {} finger {} never matches, as the gesture devices report up to {} fingers",
+                    i,
+                    trigger.fingers(),
+                    trigger.name(),
+                    max
+                );
+            }
+        }
+    }
+
+    /// Keep the triggers that match gestures for `take_matches`, or stop
+    /// keeping them
+    pub fn set_reporting(&mut self, reporting: bool) {
+        self.matches = reporting.then(Vec::new);
+    }
+
+    /// Triggers that matched gestures since the last call. Empty unless
+    /// reporting. Matches are kept even when the conditions of the trigger
+    /// don't let its action run
+    pub fn take_matches(&mut self) -> Vec<Match> {
+        self.matches
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// How many gestures of each kind were made, and how many times each
+    /// trigger ran, as lines of text. The counts start over after
+    pub fn take_stats(&mut self) -> Vec<String> {
+        let lines = self.stats.report(&self.triggers);
+        self.stats = Stats::default();
+        lines
+    }
+
+    /// Feed an event from libinput. Gestures from devices left out by the
+    /// config's `device_filter` are ignored, and so are events other than
+    /// gestures, except that new gesture devices are logged
+    pub fn handle_libinput(&mut self, event: &input::Event) {
+        let device = event.device();
+        // Read along with libinput, so that the kernel doesn't drop touches
+        let touchpad = self.touchpads.get_mut(device.sysname());
+        if let Some(touchpad) = touchpad {
+            touchpad.read();
+        }
+        match event {
+            input::Event::Device(DeviceEvent::Added(_))
+                if device.has_capability(DeviceCapability::Gesture) =>
+            {
+                match Touchpad::open(device.sysname()) {
+                    Ok(touchpad) => {
+                        log::debug!(
+                            "{:?} reports up to {} fingers",
+                            device.name(),
+                            touchpad.max_fingers()
+                        );
+                        // Warned already of the ones it doesn't change
+                        let more = self
+                            .touchpads
+                            .values()
+                            .all(|t| t.max_fingers() < touchpad.max_fingers());
+                        self.touchpads
+                            .insert(device.sysname().to_string(), touchpad);
+                        if more {
+                            self.check_fingers();
+                        }
+                    }
+                    Err(e) => log::warn!(
+                        "Can't read where fingers touch {:?}, so its swipes never start at an edge: {}",
+                        device.name(),
+                        e
+                    ),
+                }
+                if self.uses(&device) {
+                    log::info!("Found gesture device {:?}", device.name());
+                } else {
+                    log::info!(
+                        "Found gesture device {:?}, not in device_filter",
+                        device.name()
+                    );
+                }
+            }
+            // Devices come and go with suspend and docking, and libinput picks
+            // up new ones by itself. A gesture cut short must not stay going
+            input::Event::Device(DeviceEvent::Removed(_))
+                if device.has_capability(DeviceCapability::Gesture) =>
+            {
+                log::info!("Lost gesture device {:?}", device.name());
+                self.remove_device(device.sysname());
+            }
+            // Keys pressed by actions don't count as held by the user
+            input::Event::Keyboard(KeyboardEvent::Key(key))
+                if device.name() != self.virtual_device.name =>
+            {
+                self.dispatcher
+                    .set_key(key.key(), key.key_state() == KeyState::Pressed);
+            }
+            input::Event::Gesture(gest) if self.uses(&device) => {
+                if let Some(mut raw) = RawEvent::from_libinput(gest) {
+                    if raw.kind == GestureKind::Swipe && raw.phase == Phase::Begin {
+                        raw.start = self
+                            .touchpads
+                            .get(device.sysname())
+                            .and_then(Touchpad::start);
+                    }
+                    // Recorded scaled, so that replays recognize the same
+                    if let Some(d) = self.scaling(&device) {
+                        raw.scale_down(d.distance, d.pinch, d.rotation);
+                    }
+                    self.handle_raw_from(device.sysname(), &raw);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Feed a gesture event of the device with the event node, like `event5`
+    pub fn handle_raw_from(&mut self, device: &str, raw: &RawEvent) {
+        if raw.phase == Phase::Begin {
+            self.gesture_device = Some(device.to_string());
+        }
+        self.handle_raw(raw);
+    }
+
+    /// Forget the device with the event node. The gesture going on is
+    /// cancelled if it came from that device, and goes on otherwise
+    pub fn remove_device(&mut self, device: &str) {
+        self.touchpads.remove(device);
+        if self.gesture_device.as_deref() != Some(device) {
+            return;
+        }
+        self.gesture_device = None;
+        if let Some(raw) = RawEvent::cancel(&self.current, self.time) {
+            self.handle_raw(&raw);
+        }
+    }
+
+    /// Feed a gesture event that was taken from libinput earlier, as if it
+    /// came from libinput now
+    pub fn handle_raw(&mut self, raw: &RawEvent) {
+        if let Some(to) = &mut self.recording {
+            let written = ron::to_string(raw)
+                .map_err(|e| e.to_string())
+                .and_then(|line| writeln!(to, "{}", line).map_err(|e| e.to_string()));
+            if let Err(e) = written {
+                log::error!("Can't record gestures anymore: {}", e);
+                self.recording = None;
+            }
+        }
+        let event = InputEvent::from_raw(raw, &mut self.current);
+        self.handle(event);
+    }
+
+    /// Feed recorded gesture events, as fast as possible. Timed triggers fire
+    /// at the times they would have between the events. Keys held by actions
+    /// are held for their time still, and the replay ends after them
+    pub fn replay(&mut self, events: &[RawEvent]) {
+        for event in events {
+            while let Some(deadline) = self.deadline().filter(|d| *d <= event.time) {
+                self.tick(deadline);
+            }
+            self.handle_raw(event);
+            self.release_held();
+        }
+        while let Some(wait) = self.release_wait() {
+            std::thread::sleep(wait);
+            self.release_held();
+        }
+    }
+
+    /// Write the gesture events from libinput to this as they come, one line
+    /// each, so that they can be replayed
+    pub fn record(&mut self, to: Box<dyn Write>) {
+        self.recording = Some(to);
+    }
+
+    fn uses(&self, device: &input::Device) -> bool {
+        match &self.device_filter {
+            Some(patterns) => patterns
+                .iter()
+                .any(|p| input_producer::device_matches(p.pattern(), device.name())),
+            None => true,
+        }
+    }
+
+    /// Factors of the first pattern the device matches, if that has any
+    fn scaling(&self, device: &input::Device) -> Option<&ScaledDevice> {
+        self.device_filter
+            .iter()
+            .flatten()
+            .find(|p| input_producer::device_matches(p.pattern(), device.name()))?
+            .scaled()
+    }
+
+    /// Stop or start running actions. A gesture going on when pausing is
+    /// cancelled, and one going on when resuming is left alone until it ends
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused == self.paused {
+            return;
+        }
+        if paused {
+            if let Some(gesture) = self.ongoing.clone() {
+                self.handle(InputEvent::Cancelled(gesture, self.time));
+            }
+            self.ignoring = true;
+        } else {
+            self.ignoring = self.ongoing.is_some();
+        }
+        self.paused = paused;
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Feed a gesture event, and run the actions of the triggers it completes
+    pub fn handle(&mut self, event: InputEvent) {
+        self.time = event.time();
+        if self.ongoing.is_none() {
+            self.release_latched();
+        }
+        self.ongoing = match &event {
+            InputEvent::Ongoing(g, _) => Some(g.clone()),
+            InputEvent::Ended(g, _) | InputEvent::Cancelled(g, _) => {
+                self.stats.gesture(g);
+                None
+            }
+        };
+        if self.ignoring {
+            self.ignoring = self.paused || self.ongoing.is_some();
+            return;
+        }
+        let time = event.time();
+        let gesture = match &event {
+            InputEvent::Ongoing(g, _) | InputEvent::Ended(g, _) | InputEvent::Cancelled(g, _) => {
+                g.clone()
+            }
+        };
+        let trigger_events = self.recognizer.adapt(event);
+        self.run(&trigger_events, time, Some(&gesture));
+    }
+
+    /// When to call `tick` next, in the milliseconds of libinput's event
+    /// times. `None` while no timed trigger is waiting
+    pub fn deadline(&self) -> Option<u32> {
+        self.recognizer.deadline()
+    }
+
+    /// Run the actions of triggers that fire on time alone, like holds, which
+    /// libinput sends no events for while they last
+    pub fn tick(&mut self, now: u32) {
+        let trigger_events = self.recognizer.tick(now);
+        let gesture = self.ongoing.clone();
+        self.run(&trigger_events, now, gesture.as_ref());
+    }
+
+    /// How long until keys held by actions are to be released. `None` while
+    /// none are held
+    pub fn release_wait(&self) -> Option<std::time::Duration> {
+        let until = self.input_device.as_ref()?.borrow().next_release()?;
+        Some(until.saturating_duration_since(std::time::Instant::now()))
+    }
+
+    /// Release the keys held by actions whose time is up
+    pub fn release_held(&mut self) {
+        if let Some(device) = &self.input_device {
+            if let Err(e) = device.borrow_mut().release_due(std::time::Instant::now()) {
+                log::error!("Can't release held keys: {}", e);
+            }
+        }
+    }
+
+    /// Release the keys that actions hold until the next gesture
+    fn release_latched(&mut self) {
+        if let Some(device) = &self.input_device {
+            if let Err(e) = device.borrow_mut().release_latched() {
+                log::error!("Can't release held keys: {}", e);
+            }
+        }
+    }
+
+    fn run(&mut self, trigger_events: &[TriggerEvent], time: u32, gesture: Option<&Gesture>) {
+        if trigger_events.is_empty() {
+            return;
+        }
+        if let Some(matches) = &mut self.matches {
+            for event in trigger_events {
+                let (index, began) = match *event {
+                    TriggerEvent::Fired(i) => (i, false),
+                    TriggerEvent::Began(i) => (i, true),
+                    _ => continue,
+                };
+                let trigger = &self.triggers[index];
+                matches.push(Match {
+                    time,
+                    index,
+                    began,
+                    gesture: trigger.name(),
+                    fingers: trigger.fingers(),
+                    direction: trigger.direction(),
+                });
+            }
+        }
+        for event in trigger_events {
+            let index = match *event {
+                TriggerEvent::Fired(i) | TriggerEvent::Began(i) | TriggerEvent::Repeated(i) => i,
+                _ => continue,
+            };
+            let trigger = &self.triggers[index];
+            let context = GestureContext {
+                gesture: trigger.name(),
+                fingers: trigger.fingers(),
+                direction: trigger.direction().map(|d| d.name()),
+                scale: match gesture {
+                    Some(Gesture::Pinch(p)) => Some(p.scale),
+                    _ => None,
+                },
+            };
+            self.dispatcher.set_context(index, &context);
+        }
+        for index in self.dispatcher.dispatch(trigger_events) {
+            self.stats.ran(index);
+        }
+    }
+}
+
+/// A trigger that matched a gesture
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Match {
+    /// In the milliseconds of libinput's event times
+    pub time: u32,
+    /// Index of the trigger among the loaded ones: the global triggers first,
+    /// then the ones of the display server, leaving out those that failed to
+    /// load
+    pub index: usize,
+    /// The trigger is continuous and follows the gesture from now on, instead
+    /// of having fired
+    pub began: bool,
+    /// Kind of gesture, named as in the config
+    pub gesture: &'static str,
+    pub fingers: i32,
+    /// `None` for holds
+    pub direction: Option<AnyDirection>,
+}
+
+/// Replace the actions with ones that log what would happen
+fn dry_run(triggers: &[Trigger], bindings: Vec<Binding>) -> Vec<Binding> {
+    triggers
+        .iter()
+        .zip(bindings)
+        .map(|(trigger, binding)| Binding {
+            action: Box::new(DryRunAction {
+                trigger: format!("{:?}", trigger),
+                action: binding.action,
+            }),
+            ..binding
+        })
+        .collect()
+}
@@ -2,65 +2,433 @@
 /// gesture events. Register your 'Trigger's for events and observe them
 /// triggered
 pub mod trigger;
-use trigger::{Origin, Trigger};
+use trigger::{Origin, SequenceTrigger, Trigger};
 
-use crate::input_producer::event::{Gesture, InputEvent};
+use crate::common::FireOn;
+use crate::input_producer::event::{Gesture, HoldGesture, InputEvent};
 use sorted_vec::SortedSet;
 
-/// Adapt low-level gesture events into high-level events by triggers
-pub struct EventAdapter<T: Iterator<Item = InputEvent>> {
-    source: T,
+/// What happened to a trigger, by its index
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TriggerEvent {
+    /// Trigger fired
+    Fired(usize),
+    /// Continuous trigger reached its threshold and follows the gesture now
+    Began(usize),
+    /// Gesture moved by this much in the direction of a continuous trigger.
+    /// The first progress after `Began` includes all the way from the gesture
+    /// start
+    Progress(usize, f64),
+    /// Fingers of a gesture followed by a continuous trigger moved by dx, dy.
+    /// Like progress, the first motion is from the gesture start
+    Moved(usize, f64, f64),
+    /// Gesture followed by a continuous trigger ended
+    Ended(usize),
+    /// Continuous trigger with a repeat interval is due to execute, once as it
+    /// begins and again every interval
+    Repeated(usize),
+}
+
+/// Recognizes triggers in low-level gesture events, one event at a time
+pub struct Recognizer {
     triggers: Vec<Trigger>,
     /// When trigger has happened, adjust the event displacements for triggers in
     /// other directions
     adjust: Origin,
     triggered: SortedSet<usize>,
+    /// Continuous triggers following the current gesture
+    following: Vec<Following>,
+    /// Milliseconds to wait before reporting more progress of a continuous
+    /// trigger, adding up the moves in between
+    coalesce: u32,
+    /// Pixels a swipe moves before its direction counts, so that fingers
+    /// wobbling as they land don't decide it
+    deadzone: f64,
+    /// Where the current swipe left the deadzone, which its direction is told
+    /// from until a trigger moves the origin. `None` while it's still in it
+    exit: Option<(f64, f64)>,
+    /// Whether anything fired during the current gesture
+    fired: bool,
+    /// Triggers that fired stay fired until the fingers are lifted, instead
+    /// of firing again when the gesture turns back their way
+    rearm_on_lift: bool,
+    /// Measure pinches from the scale they began at, instead of from 1
+    pinch_from_begin: bool,
+    /// Scale the current pinch began at, when measured from it, and 1
+    /// otherwise
+    begin_scale: f64,
+    /// Which triggers only fire when the gesture ends without anything else
+    /// firing. Missing ones aren't fallbacks
+    fallbacks: Vec<bool>,
+    /// Which swipes and shears are measured from where the gesture last
+    /// turned their way. Missing ones are measured from the origin
+    from_turn: Vec<bool>,
+    /// For each trigger, how far the gesture went the other way from the
+    /// origin at most, where it turned. 0 when it hasn't
+    turns: Vec<f64>,
+    /// Event time each trigger last fired at, for cooldowns
+    last_fired: Vec<Option<u32>>,
+    /// Hold in progress, which timed hold triggers fire on without events
+    holding: Option<HoldGesture>,
+    /// Repeating triggers following the current gesture, with the event time
+    /// they repeat at next
+    repeating: Vec<(usize, u32)>,
+    /// Whether events of the current gesture came before, so that the next
+    /// one isn't its beginning
+    begun: bool,
+    /// Triggers with a cancel threshold that reached their distance, with the
+    /// farthest progress. They fire when the gesture ends
+    armed: Vec<(usize, f64)>,
+    sequences: Vec<SequenceState>,
+    /// Log completed gestures. Off for the steps of sequences, which see the
+    /// same gestures
+    explains: bool,
 }
 
-impl<T: Iterator<Item = InputEvent>> EventAdapter<T> {
-    /// Create event source from a low-level source. The created adapter will
-    /// observe the given triggers. If the triggers conflict, the harder ones
-    /// may never trigger
-    pub fn new(source: T, triggers: &Vec<Trigger>) -> Self {
-        EventAdapter {
-            source,
-            triggers: (*triggers).clone(),
-            adjust: Origin {
-                x: 0.0,
-                y: 0.0,
-                scale: 1.0,
-                rotation: 0.0,
-            },
+/// A continuous trigger following the current gesture
+struct Following {
+    index: usize,
+    /// Progress and finger position reported so far
+    reported: f64,
+    moved: (f64, f64),
+    /// Event time of the last report
+    at: u32,
+    /// Progress and position that came too soon after the last report, which
+    /// go in the next one
+    pending: Option<(f64, (f64, f64))>,
+}
+
+impl Following {
+    /// Report how far the gesture went since the last report
+    fn report(&mut self, progress: f64, position: (f64, f64), ctime: u32) -> Vec<TriggerEvent> {
+        let mut events = Vec::new();
+        self.at = ctime;
+        self.pending = None;
+        if progress != self.reported {
+            events.push(TriggerEvent::Progress(self.index, progress - self.reported));
+            self.reported = progress;
+        }
+        if position != self.moved {
+            let (dx, dy) = (position.0 - self.moved.0, position.1 - self.moved.1);
+            events.push(TriggerEvent::Moved(self.index, dx, dy));
+            self.moved = position;
+        }
+        events
+    }
+}
+
+/// How far a sequence trigger got
+struct SequenceState {
+    /// Index of the sequence trigger
+    index: usize,
+    timeout: u32,
+    /// Recognizes the steps. Steps that are the same trigger share it, so
+    /// that it firing once counts as one step
+    steps: Recognizer,
+    /// For each step, the index of its trigger in `steps`
+    order: Vec<usize>,
+    /// Steps that fired in order
+    done: usize,
+    /// Event time the last step fired at
+    last: u32,
+}
+
+impl Recognizer {
+    /// The created recognizer will observe the given triggers. If the triggers
+    /// conflict, the harder ones may never trigger
+    pub fn new(triggers: &[Trigger]) -> Self {
+        Recognizer {
+            triggers: triggers.to_vec(),
+            adjust: Origin::NEUTRAL,
             triggered: SortedSet::new(),
+            following: Vec::new(),
+            coalesce: 0,
+            deadzone: 0.0,
+            exit: None,
+            fired: false,
+            rearm_on_lift: false,
+            pinch_from_begin: false,
+            begin_scale: 1.0,
+            fallbacks: Vec::new(),
+            from_turn: Vec::new(),
+            turns: vec![0.0; triggers.len()],
+            last_fired: vec![None; triggers.len()],
+            holding: None,
+            repeating: Vec::new(),
+            begun: false,
+            armed: Vec::new(),
+            sequences: triggers
+                .iter()
+                .enumerate()
+                .filter_map(|(index, t)| match t {
+                    Trigger::Sequence(q) => Some(SequenceState::new(index, q)),
+                    _ => None,
+                })
+                .collect(),
+            explains: true,
+        }
+    }
+
+    /// Report the progress of continuous triggers at most once every this
+    /// many milliseconds. What's held back is reported when `tick` is called
+    /// after the interval, or when the gesture ends
+    pub fn set_coalesce(&mut self, coalesce: u32) {
+        self.coalesce = coalesce;
+    }
+
+    /// Tell the direction of swipes only from where they moved this many
+    /// pixels away from their start. The distance they need is still
+    /// measured from the start
+    pub fn set_deadzone(&mut self, deadzone: f64) {
+        self.deadzone = deadzone;
+        for sequence in &mut self.sequences {
+            sequence.steps.set_deadzone(deadzone);
+        }
+    }
+
+    /// Let triggers that aren't `repeated` fire only once per gesture, so that
+    /// turning back and forth doesn't fire them again until the fingers lift
+    pub fn set_rearm_on_lift(&mut self, rearm_on_lift: bool) {
+        self.rearm_on_lift = rearm_on_lift;
+        for sequence in &mut self.sequences {
+            sequence.steps.set_rearm_on_lift(rearm_on_lift);
+        }
+    }
+
+    /// Measure the scale of pinches from where it was as they began, for
+    /// thresholds relative to it. Libinput begins them at 1, but other
+    /// sources of events may not
+    pub fn set_pinch_from_begin(&mut self, pinch_from_begin: bool) {
+        self.pinch_from_begin = pinch_from_begin;
+        for sequence in &mut self.sequences {
+            sequence.steps.set_pinch_from_begin(pinch_from_begin);
+        }
+    }
+
+    /// Make these triggers, by index, fallbacks. A fallback is left out while
+    /// the gesture goes on, and fires when it ends if nothing else fired and
+    /// the gesture went its way, like with `FireOn::End`
+    pub fn set_fallbacks(&mut self, fallbacks: Vec<bool>) {
+        self.fallbacks = fallbacks;
+    }
+
+    fn is_fallback(&self, i: usize) -> bool {
+        self.fallbacks.get(i).copied().unwrap_or(false)
+    }
+
+    /// Measure these swipes and shears, by index, from where the gesture last
+    /// turned their way, so that going back and forth fires them one distance
+    /// after each turn
+    pub fn set_from_turn(&mut self, from_turn: Vec<bool>) {
+        self.from_turn = from_turn;
+    }
+
+    /// Where the trigger is measured from
+    fn origin(&self, i: usize) -> Origin {
+        match self.from_turn.get(i) {
+            Some(true) => self.triggers[i].turned(self.adjust, self.turns[i]),
+            _ => self.adjust,
+        }
+    }
+
+    /// Where swipes from this origin are told the direction from. `None`
+    /// while the swipe is in the deadzone
+    fn aim(&self, origin: Origin) -> Option<(f64, f64)> {
+        if self.deadzone == 0.0 || origin != Origin::NEUTRAL {
+            Some((origin.x, origin.y))
+        } else {
+            self.exit
+        }
+    }
+
+    /// Event time at which `tick` should be called next, as a hold trigger
+    /// may fire, a trigger repeat or progress held back be reported then
+    pub fn deadline(&self) -> Option<u32> {
+        let holds = self.holding.iter().flat_map(|hold| {
+            self.triggers
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| self.triggered.binary_search(i).is_err() && !self.is_fallback(*i))
+                .filter_map(|(i, t)| match t {
+                    // A hold that is still cooling down fires once it's over
+                    Trigger::Hold(th) => th.deadline(hold).map(|d| d.max(self.cooled_down_at(i))),
+                    _ => None,
+                })
+        });
+        let repeats = self.repeating.iter().map(|&(_, next)| next);
+        let steps = self.sequences.iter().filter_map(|s| s.steps.deadline());
+        let pending = self
+            .following
+            .iter()
+            .filter(|f| f.pending.is_some())
+            .map(|f| f.at.saturating_add(self.coalesce));
+        holds.chain(repeats).chain(steps).chain(pending).min()
+    }
+
+    /// Returns events of hold triggers that fired by this time, as libinput
+    /// reports nothing while the fingers rest
+    pub fn tick(&mut self, now: u32) -> Vec<TriggerEvent> {
+        // Progress held back is reported once the fingers have rested long
+        // enough, instead of waiting for them to move again
+        let mut due = Vec::new();
+        for follow in &mut self.following {
+            if let Some((progress, position)) = follow.pending {
+                if now >= follow.at.saturating_add(self.coalesce) {
+                    due.extend(follow.report(progress, position, now));
+                }
+            }
+        }
+        // A late tick repeats once, and the interval starts over from it
+        for (i, next) in &mut self.repeating {
+            if now >= *next {
+                due.push(TriggerEvent::Repeated(*i));
+                *next = now.saturating_add(self.triggers[*i].repeat().unwrap_or(0));
+            }
+        }
+        let hold = match &self.holding {
+            Some(h) => h,
+            None => return due,
+        };
+        let inds = (0..self.triggers.len())
+            .filter(|&i| !self.is_fallback(i))
+            .filter(|&i| match &self.triggers[i] {
+                Trigger::Hold(th) => {
+                    th.time != 0 && th.fire_on == FireOn::Threshold && th.matches(hold, now, false)
+                }
+                _ => false,
+            })
+            .filter(|&i| self.cooled_down(i, now))
+            .collect::<Vec<usize>>()
+            .into_iter()
+            .filter(|&i| {
+                matches!(
+                    self.triggered.find_or_insert(i),
+                    sorted_vec::FindOrInsert::Inserted(_)
+                )
+            })
+            .collect::<Vec<usize>>();
+        for &i in &inds {
+            self.last_fired[i] = Some(now);
+        }
+        let sequenced = self.advance(|steps| steps.tick(now), now);
+        self.fired |= !inds.is_empty() || !sequenced.is_empty();
+        inds.into_iter()
+            .chain(sequenced)
+            .map(TriggerEvent::Fired)
+            .chain(due)
+            .collect()
+    }
+
+    /// Feed the steps of the sequence triggers, and return the sequences whose
+    /// last step fired in time
+    fn advance(
+        &mut self,
+        mut feed: impl FnMut(&mut Recognizer) -> Vec<TriggerEvent>,
+        ctime: u32,
+    ) -> Vec<usize> {
+        let mut completed = Vec::new();
+        for seq in &mut self.sequences {
+            for event in feed(&mut seq.steps) {
+                if let TriggerEvent::Fired(step) = event {
+                    if seq.step(step, ctime) {
+                        completed.push(seq.index);
+                    }
+                }
+            }
+        }
+        completed.retain(|&i| self.cooled_down(i, ctime));
+        for &i in &completed {
+            self.last_fired[i] = Some(ctime);
+        }
+        completed
+    }
+
+    /// Event time the cooldown of this trigger is over at
+    fn cooled_down_at(&self, i: usize) -> u32 {
+        self.last_fired[i].map_or(0, |t| t.saturating_add(self.triggers[i].cooldown()))
+    }
+
+    fn cooled_down(&self, i: usize, ctime: u32) -> bool {
+        match self.last_fired[i] {
+            Some(t) => ctime.saturating_sub(t) >= self.triggers[i].cooldown(),
+            None => true,
         }
     }
 
-    /// Returns index of matched trigger
-    fn adapt(&mut self, event: InputEvent) -> Vec<usize> {
+    /// Returns events of matched triggers
+    pub fn adapt(&mut self, event: InputEvent) -> Vec<TriggerEvent> {
+        let sequenced = self.advance(|steps| steps.adapt(event.clone()), event.time());
         let (gesture, ctime, ended) = match event {
             InputEvent::Ongoing(g, t) => (g, t, false),
             InputEvent::Ended(g, t) => (g, t, true),
-            InputEvent::Cancelled(_, t) => (Gesture::None, t, true),
+            // Like when libinput tells a palm apart from the fingers. What
+            // fired on the way stays fired, but nothing fires at the end
+            InputEvent::Cancelled(g, t) => {
+                if self.explains {
+                    log::debug!("Dropping {}, which libinput cancelled", g);
+                }
+                (Gesture::None, t, true)
+            }
         };
-        // first collect matching indicies that we will return from the function
-        let inds = self.triggers.iter().enumerate().filter_map(|(i, t)| {
-            match (&gesture, t) {
-                (Gesture::None, _) => false,
-                (Gesture::Swipe(gs), Trigger::Swipe(ts)) => ts.matches_swipe(gs, self.adjust),
-                (Gesture::Swipe(_), _) => false,
-
-                (Gesture::Pinch(gp), Trigger::Pinch(tp)) => tp.matches(gp, self.adjust.scale),
-                (Gesture::Pinch(gs), Trigger::Shear(ts)) => ts.matches_shear(gs, self.adjust),
-                (Gesture::Pinch(gr), Trigger::Rotate(tr)) => tr.matches(gr, self.adjust.rotation),
-                (Gesture::Pinch(_), _) => false,
-
-                (Gesture::Hold(gh), Trigger::Hold(th)) => th.matches(gh, ctime),
-                (Gesture::Hold(_), _) => false,
+        let beginning = !self.begun && !ended;
+        self.begun = !ended;
+        if let Gesture::Pinch(p) = &gesture {
+            if beginning && self.pinch_from_begin {
+                self.begin_scale = p.scale;
+                self.adjust.scale = p.scale;
             }
-            .then(|| i)
-        });
+        }
+        if let Gesture::Swipe(s) = &gesture {
+            if self.exit.is_none() && s.dx.hypot(s.dy) >= self.deadzone {
+                self.exit = Some((s.dx, s.dy));
+            }
+        }
+        for (i, trigger) in self.triggers.iter().enumerate() {
+            if let Some(p) = trigger.progress_from(&gesture, self.adjust) {
+                self.turns[i] = self.turns[i].min(p);
+            }
+        }
+        // first collect matching indicies that we will return from the function
+        let inds = self
+            .triggers
+            .iter()
+            .enumerate()
+            .filter(|&(i, t)| {
+                !t.continuous()
+                    && !self.is_fallback(i)
+                    && match t.fire_on() {
+                        FireOn::Threshold => matches(
+                            &gesture,
+                            t,
+                            self.origin(i),
+                            self.aim(self.origin(i)),
+                            ctime,
+                            ended,
+                        ),
+                        FireOn::Begin => beginning && t.fits(&gesture),
+                        FireOn::End => ended && t.heads(&gesture, self.aim(Origin::NEUTRAL), ctime),
+                    }
+            })
+            .map(|(i, _)| i);
+        // Cardinals give way to diagonals that matched the same motion
+        let inds = inds.collect::<Vec<usize>>();
+        let inds = inds
+            .iter()
+            .copied()
+            .filter(|&i| {
+                !inds
+                    .iter()
+                    .any(|&j| self.triggers[j].overrides(&self.triggers[i]))
+            })
+            .collect::<Vec<usize>>();
+        // Those that fired too recently are still cooling down
+        let inds = inds
+            .into_iter()
+            .filter(|&i| self.cooled_down(i, ctime))
+            .collect::<Vec<usize>>();
         // From them remove the ones that were triggered and are not repeated
         let inds = inds
+            .into_iter()
             .filter(|i| {
                 if !self.triggers[*i].repeated() {
                     match self.triggered.find_or_insert(*i) {
@@ -72,22 +440,33 @@ impl<T: Iterator<Item = InputEvent>> EventAdapter<T> {
                 }
             })
             .collect::<Vec<usize>>();
+        for &i in &inds {
+            self.last_fired[i] = Some(ctime);
+        }
+        self.holding = match &gesture {
+            Gesture::Hold(h) if !ended => Some(h.clone()),
+            _ => None,
+        };
+        // Continuous triggers don't take part in the origin adjustments, so
+        // can be computed right away
+        let follow = self.follow(&gesture, ctime, ended);
+        let (inds, unarmed) = self.arm(inds, &gesture, ended);
+        // Before the cleanup forgets where the swipe left the deadzone
+        let aim = self.aim(Origin::NEUTRAL);
         // Cleanup and adjustments
         if ended {
             // adjust to neutral when end
-            self.adjust = Origin {
-                x: 0.0,
-                y: 0.0,
-                scale: 1.0,
-                rotation: 0.0,
-            };
+            self.adjust = Origin::NEUTRAL;
+            self.begin_scale = 1.0;
+            self.turns.fill(0.0);
+            self.exit = None;
             // we can retrigger everything again
             self.triggered = sorted_vec::SortedSet::new();
         } else {
             // Move origin for the next triggers in this gesture if something
             // triggered
-            if inds.len() != 0 {
-                self.move_origin(&gesture);
+            if !inds.is_empty() {
+                self.move_origin(&gesture, ctime);
             }
             // We can retrigger cardinals in other directions
             let trigger_dirs = inds
@@ -95,7 +474,7 @@ impl<T: Iterator<Item = InputEvent>> EventAdapter<T> {
                 .map(|i| self.triggers[*i].direction())
                 .filter(|i| i.is_some())
                 .collect::<Vec<_>>();
-            if trigger_dirs.len() != 0 {
+            if !trigger_dirs.is_empty() && !self.rearm_on_lift {
                 log::trace!("Triggered directions: {:?}", trigger_dirs);
                 self.triggered.mutate_vec(|ts| {
                     // retain only those directions that were triggered just now
@@ -103,12 +482,164 @@ impl<T: Iterator<Item = InputEvent>> EventAdapter<T> {
                 });
             }
         }
+        let mut events: Vec<_> = unarmed
+            .into_iter()
+            .chain(sequenced)
+            .map(TriggerEvent::Fired)
+            .chain(follow)
+            .collect();
+        self.fired |= !events.is_empty();
+        if ended && !self.fired {
+            let fallbacks = self.fallback(&gesture, aim, ctime);
+            self.fired = !fallbacks.is_empty();
+            events.extend(fallbacks.into_iter().map(TriggerEvent::Fired));
+        }
+        if ended {
+            self.explain(&gesture, ctime);
+            self.fired = false;
+        }
+        events
+    }
+
+    /// The fallbacks that the gesture, which fired nothing else, went the way
+    /// of
+    fn fallback(&mut self, gesture: &Gesture, aim: Option<(f64, f64)>, ctime: u32) -> Vec<usize> {
+        let inds: Vec<usize> = (0..self.triggers.len())
+            .filter(|&i| self.is_fallback(i))
+            .filter(|&i| self.triggers[i].heads(gesture, aim, ctime))
+            .filter(|&i| self.cooled_down(i, ctime))
+            .collect();
+        for &i in &inds {
+            self.last_fired[i] = Some(ctime);
+        }
         inds
     }
 
+    /// Log the completed gesture, and if it fired nothing, why the triggers
+    /// didn't match it
+    fn explain(&self, gesture: &Gesture, ctime: u32) {
+        if !self.explains || *gesture == Gesture::None || !log::log_enabled!(log::Level::Debug) {
+            return;
+        }
+        log::debug!("Completed {}", gesture);
+        if self.fired {
+            return;
+        }
+        let misses: Vec<_> = self
+            .triggers
+            .iter()
+            .filter_map(|t| Some((t, t.miss(gesture, ctime)?)))
+            .collect();
+        if misses.is_empty() {
+            log::debug!("No triggers for this kind of gesture");
+        }
+        for (trigger, reason) in misses {
+            log::debug!("Not {:?}: {}", trigger, reason);
+        }
+    }
+
+    /// Hold back the matched triggers that have a cancel threshold until the
+    /// gesture ends, and fire them then unless the gesture went back too far.
+    /// Returns all the matched triggers, and those that fire now
+    fn arm(
+        &mut self,
+        inds: Vec<usize>,
+        gesture: &Gesture,
+        ended: bool,
+    ) -> (Vec<usize>, Vec<usize>) {
+        for (i, peak) in &mut self.armed {
+            if let Some(p) = self.triggers[*i].progress(gesture) {
+                *peak = peak.max(p);
+            }
+        }
+        let mut fire = Vec::new();
+        for &i in &inds {
+            match self.triggers[i].progress(gesture) {
+                Some(p) if self.triggers[i].cancel().is_some() => self.armed.push((i, p)),
+                _ => fire.push(i),
+            }
+        }
+        if ended {
+            for (i, peak) in self.armed.drain(..) {
+                let trigger = &self.triggers[i];
+                let back = match trigger.progress(gesture) {
+                    Some(p) => peak - p,
+                    // Cancelled gestures fire nothing
+                    None => continue,
+                };
+                if back <= trigger.cancel().unwrap_or(f64::INFINITY) {
+                    fire.push(i);
+                } else {
+                    log::debug!("Not {:?}: went back {:.1} before the end", trigger, back);
+                }
+            }
+        }
+        (inds, fire)
+    }
+
+    /// Start following the gesture with continuous triggers that reached
+    /// their threshold, and report progress for those already following
+    fn follow(&mut self, gesture: &Gesture, ctime: u32, ended: bool) -> Vec<TriggerEvent> {
+        let mut events = Vec::new();
+        let position = position(gesture);
+        let aim = self.aim(Origin::NEUTRAL);
+        let begin = Origin {
+            scale: self.begin_scale,
+            ..Origin::NEUTRAL
+        };
+        for (i, trigger) in self.triggers.iter().enumerate() {
+            if !trigger.continuous() {
+                continue;
+            }
+            let progress = match trigger.progress(gesture) {
+                Some(p) => p,
+                None => continue,
+            };
+            let cooled_down = self.cooled_down(i, ctime);
+            match self.following.iter_mut().find(|f| f.index == i) {
+                // Too soon after the last report, so it goes in the next
+                Some(follow) if !ended && ctime.saturating_sub(follow.at) < self.coalesce => {
+                    follow.pending = Some((progress, position));
+                }
+                Some(follow) => events.extend(follow.report(progress, position, ctime)),
+                // Thresholds for continuous triggers are always measured from
+                // the gesture start
+                None if cooled_down && matches(gesture, trigger, begin, aim, ctime, ended) => {
+                    self.last_fired[i] = Some(ctime);
+                    events.push(TriggerEvent::Began(i));
+                    events.push(TriggerEvent::Progress(i, progress));
+                    if position != (0.0, 0.0) {
+                        events.push(TriggerEvent::Moved(i, position.0, position.1));
+                    }
+                    self.following.push(Following {
+                        index: i,
+                        reported: progress,
+                        moved: position,
+                        at: ctime,
+                        pending: None,
+                    });
+                    if let Some(repeat) = trigger.repeat() {
+                        events.push(TriggerEvent::Repeated(i));
+                        self.repeating.push((i, ctime.saturating_add(repeat)));
+                    }
+                }
+                None => (),
+            }
+        }
+        if ended {
+            self.repeating.clear();
+            events.extend(
+                self.following
+                    .drain(..)
+                    .map(|f| TriggerEvent::Ended(f.index)),
+            );
+        }
+        events
+    }
+
     /// Move origin based on what was triggered, so that next triggers execute
     /// correctly from new origin (new finger resting place)
-    fn move_origin(&mut self, gesture: &Gesture) {
+    fn move_origin(&mut self, gesture: &Gesture, ctime: u32) {
         match gesture {
             Gesture::None => (),
             Gesture::Swipe(s) => {
@@ -123,18 +654,135 @@ impl<T: Iterator<Item = InputEvent>> EventAdapter<T> {
             }
             Gesture::Hold(_) => (),
         }
+        self.adjust.time = ctime;
+        self.turns.fill(0.0);
         log::trace!("Adjusted origin: {:?}", self.adjust);
     }
 }
 
+impl SequenceState {
+    fn new(index: usize, trigger: &SequenceTrigger) -> Self {
+        let mut unique: Vec<Trigger> = Vec::new();
+        let order = trigger
+            .steps
+            .iter()
+            .map(|step| match unique.iter().position(|t| t == step) {
+                Some(i) => i,
+                None => {
+                    unique.push(step.clone());
+                    unique.len() - 1
+                }
+            })
+            .collect();
+        let mut steps = Recognizer::new(&unique);
+        steps.explains = false;
+        SequenceState {
+            index,
+            timeout: trigger.timeout,
+            steps,
+            order,
+            done: 0,
+            last: 0,
+        }
+    }
+
+    /// Take in a step that fired. A step out of order starts over, from the
+    /// first step if it's that one. Returns whether the sequence completed
+    fn step(&mut self, step: usize, ctime: u32) -> bool {
+        if self.done > 0 && ctime.saturating_sub(self.last) > self.timeout {
+            log::debug!(
+                "Sequence trigger {} timed out after {} steps",
+                self.index,
+                self.done
+            );
+            self.done = 0;
+        }
+        self.done = if self.order[self.done] == step {
+            self.done + 1
+        } else if self.order[0] == step {
+            1
+        } else {
+            0
+        };
+        self.last = ctime;
+        if self.done == self.order.len() {
+            self.done = 0;
+            true
+        } else {
+            if self.done > 0 {
+                log::debug!(
+                    "Sequence trigger {} at step {} of {}",
+                    self.index,
+                    self.done,
+                    self.order.len()
+                );
+            }
+            false
+        }
+    }
+}
+
+/// Where the fingers are relative to the gesture start
+fn position(gesture: &Gesture) -> (f64, f64) {
+    match gesture {
+        Gesture::Swipe(s) => (s.dx, s.dy),
+        Gesture::Pinch(p) => (p.dx, p.dy),
+        Gesture::None | Gesture::Hold(_) => (0.0, 0.0),
+    }
+}
+
+/// Does the gesture reach the trigger from this origin, going its way from
+/// `aim`. Swipes without an aim match nothing
+fn matches(
+    gesture: &Gesture,
+    trigger: &Trigger,
+    origin: Origin,
+    aim: Option<(f64, f64)>,
+    ctime: u32,
+    ended: bool,
+) -> bool {
+    match (gesture, trigger) {
+        (Gesture::None, _) => false,
+        (Gesture::Swipe(gs), Trigger::Swipe(ts)) => {
+            aim.is_some_and(|aim| ts.matches_swipe(gs, origin, aim, ctime))
+        }
+        (Gesture::Swipe(_), _) => false,
+
+        (Gesture::Pinch(gp), Trigger::Pinch(tp)) => tp.matches(gp, origin.scale),
+        (Gesture::Pinch(gs), Trigger::Shear(ts)) => ts.matches_shear(gs, origin),
+        (Gesture::Pinch(gr), Trigger::Rotate(tr)) => tr.matches(gr, origin.rotation),
+        (Gesture::Pinch(_), _) => false,
+
+        (Gesture::Hold(gh), Trigger::Hold(th)) => th.matches(gh, ctime, ended),
+        (Gesture::Hold(_), _) => false,
+    }
+}
+
+/// Adapt low-level gesture events into high-level events by triggers
+pub struct EventAdapter<T: Iterator<Item = InputEvent>> {
+    source: T,
+    recognizer: Recognizer,
+}
+
+impl<T: Iterator<Item = InputEvent>> EventAdapter<T> {
+    /// Create event source from a low-level source. The created adapter will
+    /// observe the given triggers
+    pub fn new(source: T, triggers: &[Trigger]) -> Self {
+        EventAdapter {
+            source,
+            recognizer: Recognizer::new(triggers),
+        }
+    }
+}
+
 impl<T: Iterator<Item = InputEvent>> Iterator for EventAdapter<T> {
-    type Item = Vec<usize>;
+    type Item = Vec<TriggerEvent>;
     fn next(&mut self) -> Option<Self::Item> {
         // should I maybe yield all the empty events?
         loop {
             if let Some(event) = self.source.next() {
-                let r = self.adapt(event);
-                if r.len() != 0 {
+                let r = self.recognizer.adapt(event);
+                if !r.is_empty() {
                     break Some(r);
                 }
             }
@@ -144,8 +792,9 @@ impl<T: Iterator<Item = InputEvent>> Iterator for EventAdapter<T> {
 
 #[cfg(test)]
 mod test {
-    use crate::common::Direction;
-    use crate::gesture_event::trigger::{CardinalTrigger, Trigger};
+    use super::TriggerEvent;
+    use crate::common::{Direction, Edge, FireOn, RotateDirection};
+    use crate::gesture_event::trigger::{CardinalTrigger, RotateTrigger, Trigger};
 
     #[test]
     fn swipe_up_down() {
@@ -153,18 +802,35 @@ mod test {
             fingers: 3,
             direction: Direction::Up,
             distance: 200.0,
+            angle_tolerance: 20.0,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration: None,
             repeated: false,
+            continuous: false,
+            cooldown: 0,
+            fire_on: FireOn::Threshold,
+            cancel: None,
+            repeat: None,
+            edge: None,
         });
         let trigger_down = Trigger::Swipe(CardinalTrigger {
             fingers: 3,
             direction: Direction::Down,
             distance: 200.0,
+            angle_tolerance: 20.0,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration: None,
             repeated: false,
+            continuous: false,
+            cooldown: 0,
+            fire_on: FireOn::Threshold,
+            cancel: None,
+            repeat: None,
+            edge: None,
         });
-        let mut adapter = super::EventAdapter::new(
-            crate::input_producer::GestureProducer::new(),
-            &vec![trigger_up, trigger_down],
-        );
+        let mut adapter = super::Recognizer::new(&[trigger_up, trigger_down]);
 
         use crate::input_producer::event::*;
         let event_up_half = InputEvent::Ongoing(
@@ -173,6 +839,7 @@ mod test {
                 fingers: 3,
                 dx: 10.0,
                 dy: -101.0,
+                start: None,
             }),
             10,
         );
@@ -182,6 +849,7 @@ mod test {
                 fingers: 3,
                 dx: -20.0,
                 dy: -202.0,
+                start: None,
             }),
             10,
         );
@@ -191,6 +859,7 @@ mod test {
                 fingers: 3,
                 dx: 30.0,
                 dy: 10.0,
+                start: None,
             }),
             20,
         );
@@ -200,10 +869,1348 @@ mod test {
         let r = adapter.adapt(event_up_half.clone());
         assert_eq!(r, Vec::new());
         let r = adapter.adapt(event_up.clone());
-        assert_eq!(r, vec![0]);
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
         let r = adapter.adapt(event_up_half.clone());
         assert_eq!(r, Vec::new());
         let r = adapter.adapt(event_down.clone());
-        assert_eq!(r, vec![1]);
+        assert_eq!(r, vec![TriggerEvent::Fired(1)]);
+    }
+
+    #[test]
+    fn rearm_on_lift() {
+        let cardinal = |direction| {
+            Trigger::Swipe(CardinalTrigger {
+                fingers: 3,
+                direction,
+                distance: 100.0,
+                angle_tolerance: 20.0,
+                min_velocity: None,
+                max_velocity: None,
+                min_duration: None,
+                repeated: false,
+                continuous: false,
+                cooldown: 0,
+                fire_on: FireOn::Threshold,
+                cancel: None,
+                repeat: None,
+                edge: None,
+            })
+        };
+        let mut adapter =
+            super::Recognizer::new(&[cardinal(Direction::Up), cardinal(Direction::Down)]);
+        adapter.set_rearm_on_lift(true);
+
+        use crate::input_producer::event::*;
+        let swipe = |begin_time, dy| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time,
+                fingers: 3,
+                dx: 0.0,
+                dy,
+                start: None,
+            })
+        };
+        // Up, down and up again in one gesture fires each once
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(0, -120.0), 10));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(0, 0.0), 20));
+        assert_eq!(r, vec![TriggerEvent::Fired(1)]);
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(0, -120.0), 30));
+        assert_eq!(r, Vec::new());
+        adapter.adapt(InputEvent::Ended(swipe(0, -120.0), 40));
+
+        // Until the fingers lift
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(100, -120.0), 110));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+    }
+
+    #[test]
+    fn from_turn() {
+        let cardinal = |direction| {
+            Trigger::Swipe(CardinalTrigger {
+                fingers: 3,
+                direction,
+                distance: 100.0,
+                angle_tolerance: 20.0,
+                min_velocity: None,
+                max_velocity: None,
+                min_duration: None,
+                repeated: false,
+                continuous: false,
+                cooldown: 0,
+                fire_on: FireOn::Threshold,
+                cancel: None,
+                repeat: None,
+                edge: None,
+            })
+        };
+        let triggers = [cardinal(Direction::Right), cardinal(Direction::Left)];
+        let mut adapter = super::Recognizer::new(&triggers);
+        adapter.set_from_turn(vec![true, true]);
+
+        use crate::input_producer::event::*;
+        let swipe = |begin_time, dx| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time,
+                fingers: 3,
+                dx,
+                dy: 0.0,
+                start: None,
+            })
+        };
+        let scrub = |adapter: &mut super::Recognizer, begin_time, path: &[f64]| {
+            path.iter()
+                .enumerate()
+                .map(|(t, &dx)| adapter.adapt(InputEvent::Ongoing(swipe(begin_time, dx), t as u32)))
+                .collect::<Vec<_>>()
+        };
+        // Right fires at 120 and the fingers go on to 180, where they turn.
+        // Left fires 100 from there, and not only at 20
+        let r = scrub(&mut adapter, 0, &[120.0, 180.0, 100.0, 70.0, 0.0, 90.0]);
+        assert_eq!(
+            r,
+            vec![
+                vec![TriggerEvent::Fired(0)],
+                vec![],
+                vec![],
+                vec![TriggerEvent::Fired(1)],
+                vec![],
+                vec![]
+            ]
+        );
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(0, 100.0), 10));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+        adapter.adapt(InputEvent::Ended(swipe(0, 100.0), 20));
+
+        // Measured from where the last trigger fired, Left needs to get to 20
+        adapter.set_from_turn(Vec::new());
+        let r = scrub(&mut adapter, 100, &[120.0, 180.0, 70.0, 20.0]);
+        assert_eq!(
+            r,
+            vec![
+                vec![TriggerEvent::Fired(0)],
+                vec![],
+                vec![],
+                vec![TriggerEvent::Fired(1)]
+            ]
+        );
+    }
+
+    #[test]
+    fn rotations_apart() {
+        let rotate = |fingers, direction| {
+            Trigger::Rotate(RotateTrigger {
+                fingers,
+                direction,
+                distance: 60.0,
+                repeated: false,
+                continuous: false,
+                cooldown: 0,
+                fire_on: FireOn::Threshold,
+                cancel: None,
+                repeat: None,
+            })
+        };
+        let triggers = [
+            rotate(2, RotateDirection::Clockwise),
+            rotate(3, RotateDirection::Anticlockwise),
+        ];
+        let mut adapter = super::Recognizer::new(&triggers);
+
+        use crate::input_producer::event::*;
+        let pinch = |fingers, angle| {
+            Gesture::Pinch(PinchGesture {
+                begin_time: 0,
+                fingers,
+                scale: 1.0,
+                angle,
+                dx: 0.0,
+                dy: 0.0,
+            })
+        };
+        let mut fired = |fingers, angle| {
+            adapter.adapt(InputEvent::Ongoing(pinch(fingers, angle / 2.0), 10));
+            let r = adapter.adapt(InputEvent::Ongoing(pinch(fingers, angle), 20));
+            adapter.adapt(InputEvent::Ended(pinch(fingers, angle), 30));
+            r
+        };
+        assert_eq!(fired(2, 70.0), vec![TriggerEvent::Fired(0)]);
+        assert_eq!(fired(3, -70.0), vec![TriggerEvent::Fired(1)]);
+        // Each only turns its own way
+        assert_eq!(fired(2, -70.0), Vec::new());
+        assert_eq!(fired(3, 70.0), Vec::new());
+        // And not as far as the threshold
+        assert_eq!(fired(2, 50.0), Vec::new());
+    }
+
+    #[test]
+    fn two_fingers() {
+        use crate::common::PinchDirection;
+        use crate::gesture_event::trigger::PinchTrigger;
+        let pinch_trigger = |fingers| {
+            Trigger::Pinch(PinchTrigger {
+                fingers,
+                direction: PinchDirection::Out,
+                scale: 0.8,
+                repeated: false,
+                continuous: false,
+                cooldown: 0,
+                fire_on: FireOn::Threshold,
+                cancel: None,
+                repeat: None,
+            })
+        };
+        let swipe_trigger = Trigger::Swipe(CardinalTrigger {
+            fingers: 2,
+            direction: Direction::Left,
+            distance: 100.0,
+            angle_tolerance: 20.0,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration: None,
+            repeated: false,
+            continuous: false,
+            cooldown: 0,
+            fire_on: FireOn::Threshold,
+            cancel: None,
+            repeat: None,
+            edge: None,
+        });
+        let mut adapter =
+            super::Recognizer::new(&[pinch_trigger(2), pinch_trigger(3), swipe_trigger]);
+
+        use crate::input_producer::event::*;
+        let pinch = |fingers, scale| {
+            Gesture::Pinch(PinchGesture {
+                begin_time: 0,
+                fingers,
+                scale,
+                angle: 0.0,
+                dx: 0.0,
+                dy: 0.0,
+            })
+        };
+        let r = adapter.adapt(InputEvent::Ongoing(pinch(2, 0.5), 10));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+        adapter.adapt(InputEvent::Ended(pinch(2, 0.5), 20));
+
+        // Where libinput scrolls instead, the swipe only ends, or never comes
+        let swipe = Gesture::Swipe(SwipeGesture {
+            begin_time: 100,
+            fingers: 2,
+            dx: 0.0,
+            dy: 0.0,
+            start: None,
+        });
+        assert_eq!(
+            adapter.adapt(InputEvent::Ended(swipe.clone(), 110)),
+            Vec::new()
+        );
+        assert_eq!(adapter.adapt(InputEvent::Cancelled(swipe, 120)), Vec::new());
+        let swipe = Gesture::Swipe(SwipeGesture {
+            begin_time: 200,
+            fingers: 2,
+            dx: -120.0,
+            dy: 0.0,
+            start: None,
+        });
+        let r = adapter.adapt(InputEvent::Ongoing(swipe, 210));
+        assert_eq!(r, vec![TriggerEvent::Fired(2)]);
+    }
+
+    #[test]
+    fn five_fingers() {
+        use crate::common::PinchDirection;
+        use crate::gesture_event::trigger::PinchTrigger;
+        let pinch_trigger = |fingers| {
+            Trigger::Pinch(PinchTrigger {
+                fingers,
+                direction: PinchDirection::In,
+                scale: 1.2,
+                repeated: false,
+                continuous: false,
+                cooldown: 0,
+                fire_on: FireOn::Threshold,
+                cancel: None,
+                repeat: None,
+            })
+        };
+        let mut adapter = super::Recognizer::new(&[pinch_trigger(4), pinch_trigger(5)]);
+
+        use crate::input_producer::event::*;
+        let pinch = |fingers, scale| {
+            Gesture::Pinch(PinchGesture {
+                begin_time: 0,
+                fingers,
+                scale,
+                angle: 0.0,
+                dx: 0.0,
+                dy: 0.0,
+            })
+        };
+        let r = adapter.adapt(InputEvent::Ongoing(pinch(5, 1.5), 10));
+        assert_eq!(r, vec![TriggerEvent::Fired(1)]);
+        adapter.adapt(InputEvent::Ended(pinch(5, 1.5), 20));
+        let r = adapter.adapt(InputEvent::Ongoing(pinch(4, 1.5), 30));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+    }
+
+    #[test]
+    fn pinch_from_begin() {
+        use crate::common::{PinchDirection, PinchMode};
+        use crate::gesture_event::trigger::PinchTrigger;
+        // 40% apart, and 40% together
+        let pinch_trigger = |direction| {
+            Trigger::Pinch(PinchTrigger {
+                fingers: 2,
+                direction,
+                scale: PinchMode::Relative.scale(40.0, direction),
+                repeated: false,
+                continuous: false,
+                cooldown: 0,
+                fire_on: FireOn::Threshold,
+                cancel: None,
+                repeat: None,
+            })
+        };
+        let triggers = [
+            pinch_trigger(PinchDirection::In),
+            pinch_trigger(PinchDirection::Out),
+        ];
+        let mut adapter = super::Recognizer::new(&triggers);
+        adapter.set_pinch_from_begin(true);
+
+        use crate::input_producer::event::*;
+        let pinch = |begin_time, scale| {
+            Gesture::Pinch(PinchGesture {
+                begin_time,
+                fingers: 2,
+                scale,
+                angle: 0.0,
+                dx: 0.0,
+                dy: 0.0,
+            })
+        };
+        // Begun at 1.5, 1.5 times 1.4 is 2.1
+        assert_eq!(adapter.adapt(InputEvent::Ongoing(pinch(0, 1.5), 0)), vec![]);
+        assert_eq!(
+            adapter.adapt(InputEvent::Ongoing(pinch(0, 2.0), 10)),
+            vec![]
+        );
+        let r = adapter.adapt(InputEvent::Ongoing(pinch(0, 2.2), 20));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+        adapter.adapt(InputEvent::Ended(pinch(0, 2.2), 30));
+
+        // From 1, 0.6 is 40% together, where 1 / 1.4 would be only 29%
+        assert_eq!(
+            adapter.adapt(InputEvent::Ongoing(pinch(100, 1.0), 100)),
+            vec![]
+        );
+        assert_eq!(
+            adapter.adapt(InputEvent::Ongoing(pinch(100, 0.7), 110)),
+            vec![]
+        );
+        let r = adapter.adapt(InputEvent::Ongoing(pinch(100, 0.6), 120));
+        assert_eq!(r, vec![TriggerEvent::Fired(1)]);
+        adapter.adapt(InputEvent::Ended(pinch(100, 0.6), 130));
+
+        // Measured from 1, the first pinch would have fired at 1.5 already
+        adapter.set_pinch_from_begin(false);
+        let r = adapter.adapt(InputEvent::Ongoing(pinch(200, 1.5), 200));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+    }
+
+    #[test]
+    fn fallback() {
+        let cardinal = |direction| {
+            Trigger::Swipe(CardinalTrigger {
+                fingers: 3,
+                direction,
+                distance: 100.0,
+                angle_tolerance: 20.0,
+                min_velocity: None,
+                max_velocity: None,
+                min_duration: None,
+                repeated: false,
+                continuous: false,
+                cooldown: 0,
+                fire_on: FireOn::Threshold,
+                cancel: None,
+                repeat: None,
+                edge: None,
+            })
+        };
+        let triggers = [
+            cardinal(Direction::Left),
+            cardinal(Direction::Left),
+            cardinal(Direction::Right),
+        ];
+        let mut adapter = super::Recognizer::new(&triggers);
+        adapter.set_fallbacks(vec![false, true, true]);
+
+        use crate::input_producer::event::*;
+        let swipe = |begin_time, dx| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time,
+                fingers: 3,
+                dx,
+                dy: 0.0,
+                start: None,
+            })
+        };
+        // Matched by the other trigger, and not the fallback, even at the end
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(0, -150.0), 10));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+        let r = adapter.adapt(InputEvent::Ended(swipe(0, -150.0), 20));
+        assert_eq!(r, Vec::new());
+
+        // Too short for anything else, the fallback of its way fires
+        assert_eq!(
+            adapter.adapt(InputEvent::Ongoing(swipe(100, 40.0), 110)),
+            Vec::new()
+        );
+        let r = adapter.adapt(InputEvent::Ended(swipe(100, 40.0), 120));
+        assert_eq!(r, vec![TriggerEvent::Fired(2)]);
+        let r = adapter.adapt(InputEvent::Ended(swipe(200, -40.0), 220));
+        assert_eq!(r, vec![TriggerEvent::Fired(1)]);
+        let r = adapter.adapt(InputEvent::Cancelled(swipe(300, -40.0), 320));
+        assert_eq!(r, Vec::new());
+    }
+
+    #[test]
+    fn swipe_and_shear_apart() {
+        let cardinal = |distance| CardinalTrigger {
+            fingers: 4,
+            direction: Direction::Left,
+            distance,
+            angle_tolerance: 20.0,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration: None,
+            repeated: false,
+            continuous: false,
+            cooldown: 0,
+            fire_on: FireOn::Threshold,
+            cancel: None,
+            repeat: None,
+            edge: None,
+        };
+        let triggers = [
+            Trigger::Swipe(cardinal(200.0)),
+            Trigger::Shear(cardinal(100.0)),
+        ];
+        let mut adapter = super::Recognizer::new(&triggers);
+
+        use crate::input_producer::event::*;
+        let swipe = |dx| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time: 0,
+                fingers: 4,
+                dx,
+                dy: 0.0,
+                start: None,
+            })
+        };
+        let shear = |dx| {
+            Gesture::Pinch(PinchGesture {
+                begin_time: 100,
+                fingers: 4,
+                scale: 1.0,
+                angle: 0.0,
+                dx,
+                dy: 0.0,
+            })
+        };
+        // Far enough for a shear, but swipes go further
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(-150.0), 10));
+        assert_eq!(r, Vec::new());
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(-250.0), 20));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+        adapter.adapt(InputEvent::Ended(swipe(-250.0), 30));
+
+        let r = adapter.adapt(InputEvent::Ongoing(shear(-150.0), 110));
+        assert_eq!(r, vec![TriggerEvent::Fired(1)]);
+        adapter.adapt(InputEvent::Ended(shear(-250.0), 120));
+    }
+
+    #[test]
+    fn continuous_swipe() {
+        let trigger = Trigger::Swipe(CardinalTrigger {
+            fingers: 3,
+            direction: Direction::Right,
+            distance: 100.0,
+            angle_tolerance: 20.0,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration: None,
+            repeated: false,
+            continuous: true,
+            cooldown: 0,
+            fire_on: FireOn::Threshold,
+            cancel: None,
+            repeat: None,
+            edge: None,
+        });
+        let mut adapter = super::Recognizer::new(&[trigger]);
+
+        use crate::input_producer::event::*;
+        let swipe = |dx| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time: 0,
+                fingers: 3,
+                dx,
+                dy: 5.0,
+                start: None,
+            })
+        };
+
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(50.0), 10));
+        assert_eq!(r, Vec::new());
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(120.0), 20));
+        assert_eq!(
+            r,
+            vec![
+                TriggerEvent::Began(0),
+                TriggerEvent::Progress(0, 120.0),
+                TriggerEvent::Moved(0, 120.0, 5.0)
+            ]
+        );
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(90.0), 30));
+        assert_eq!(
+            r,
+            vec![
+                TriggerEvent::Progress(0, -30.0),
+                TriggerEvent::Moved(0, -30.0, 0.0)
+            ]
+        );
+        let r = adapter.adapt(InputEvent::Ended(swipe(90.0), 40));
+        assert_eq!(r, vec![TriggerEvent::Ended(0)]);
+        // Next gesture needs to reach the threshold again
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(50.0), 50));
+        assert_eq!(r, Vec::new());
+    }
+
+    #[test]
+    fn coalesce() {
+        let trigger = Trigger::Swipe(CardinalTrigger {
+            fingers: 3,
+            direction: Direction::Right,
+            distance: 100.0,
+            angle_tolerance: 20.0,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration: None,
+            repeated: false,
+            continuous: true,
+            cooldown: 0,
+            fire_on: FireOn::Threshold,
+            cancel: None,
+            repeat: None,
+            edge: None,
+        });
+        let mut adapter = super::Recognizer::new(&[trigger]);
+        adapter.set_coalesce(8);
+
+        use crate::input_producer::event::*;
+        let swipe = |dx| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time: 0,
+                fingers: 3,
+                dx,
+                dy: 0.0,
+                start: None,
+            })
+        };
+
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(120.0), 10));
+        assert_eq!(
+            r,
+            vec![
+                TriggerEvent::Began(0),
+                TriggerEvent::Progress(0, 120.0),
+                TriggerEvent::Moved(0, 120.0, 0.0)
+            ]
+        );
+        // Moves within the interval are added up into one update
+        for (dx, time) in [(122.0, 12), (125.0, 14), (129.0, 16)] {
+            assert_eq!(adapter.adapt(InputEvent::Ongoing(swipe(dx), time)), []);
+        }
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(130.0), 18));
+        assert_eq!(
+            r,
+            vec![
+                TriggerEvent::Progress(0, 10.0),
+                TriggerEvent::Moved(0, 10.0, 0.0)
+            ]
+        );
+        // Or once the fingers rest for the interval
+        assert_eq!(adapter.adapt(InputEvent::Ongoing(swipe(135.0), 20)), []);
+        assert_eq!(adapter.deadline(), Some(26));
+        assert_eq!(
+            adapter.tick(26),
+            [
+                TriggerEvent::Progress(0, 5.0),
+                TriggerEvent::Moved(0, 5.0, 0.0)
+            ]
+        );
+        assert_eq!(adapter.deadline(), None);
+        // What's left is reported as the gesture ends
+        assert_eq!(adapter.adapt(InputEvent::Ongoing(swipe(138.0), 28)), []);
+        let r = adapter.adapt(InputEvent::Ended(swipe(138.0), 29));
+        assert_eq!(
+            r,
+            vec![
+                TriggerEvent::Progress(0, 3.0),
+                TriggerEvent::Moved(0, 3.0, 0.0),
+                TriggerEvent::Ended(0)
+            ]
+        );
+    }
+
+    #[test]
+    fn deadzone() {
+        let swipe_trigger = |direction| {
+            Trigger::Swipe(CardinalTrigger {
+                fingers: 3,
+                direction,
+                distance: 100.0,
+                angle_tolerance: 20.0,
+                min_velocity: None,
+                max_velocity: None,
+                min_duration: None,
+                repeated: false,
+                continuous: false,
+                cooldown: 0,
+                fire_on: FireOn::Threshold,
+                cancel: None,
+                repeat: None,
+                edge: None,
+            })
+        };
+        let triggers = [swipe_trigger(Direction::Left), swipe_trigger(Direction::Up)];
+
+        use crate::input_producer::event::*;
+        let swipe = |dx, dy| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time: 0,
+                fingers: 3,
+                dx,
+                dy,
+                start: None,
+            })
+        };
+        // Slid left as the fingers landed, then went up
+        let fired = |deadzone, path: &[(f64, f64)]| {
+            let mut adapter = super::Recognizer::new(&triggers);
+            adapter.set_deadzone(deadzone);
+            let mut fired = Vec::new();
+            for (time, &(dx, dy)) in path.iter().enumerate() {
+                fired.extend(adapter.adapt(InputEvent::Ongoing(swipe(dx, dy), time as u32)));
+            }
+            let (dx, dy) = path[path.len() - 1];
+            fired.extend(adapter.adapt(InputEvent::Ended(swipe(dx, dy), 5)));
+            fired
+        };
+        let path = [(-45.0, 0.0), (-55.0, 0.0), (-100.0, -60.0)];
+        assert_eq!(fired(0.0, &path), [TriggerEvent::Fired(0)]);
+        // Told up from the deadzone, but it didn't go far enough up. The slide
+        // left doesn't count for it
+        assert_eq!(fired(50.0, &path), []);
+        // The distance still counts from the start
+        let path = [
+            (-45.0, 0.0),
+            (-55.0, 0.0),
+            (-100.0, -60.0),
+            (-100.0, -100.0),
+        ];
+        assert_eq!(fired(50.0, &path), [TriggerEvent::Fired(1)]);
+    }
+
+    #[test]
+    fn edge_swipe() {
+        let trigger = Trigger::Swipe(CardinalTrigger {
+            fingers: 2,
+            direction: Direction::Right,
+            distance: 100.0,
+            angle_tolerance: 20.0,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration: None,
+            repeated: false,
+            continuous: false,
+            cooldown: 0,
+            fire_on: FireOn::Threshold,
+            cancel: None,
+            repeat: None,
+            edge: Some((Edge::Left, 0.1)),
+        });
+        let mut adapter = super::Recognizer::new(&[trigger]);
+
+        use crate::input_producer::event::*;
+        let swipe = |start| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time: 0,
+                fingers: 2,
+                dx: 150.0,
+                dy: 0.0,
+                start,
+            })
+        };
+
+        let mut fired = |start| {
+            let r = adapter.adapt(InputEvent::Ongoing(swipe(start), 10));
+            adapter.adapt(InputEvent::Ended(swipe(start), 20));
+            r == vec![TriggerEvent::Fired(0)]
+        };
+        assert!(fired(Some((0.05, 0.5))));
+        assert!(!fired(Some((0.5, 0.5))));
+        // Without the touch position, the start is unknown
+        assert!(!fired(None));
+    }
+
+    #[test]
+    fn diagonal_swipe() {
+        let swipe_trigger = |direction| {
+            Trigger::Swipe(CardinalTrigger {
+                fingers: 3,
+                direction,
+                distance: 100.0,
+                angle_tolerance: 20.0,
+                min_velocity: None,
+                max_velocity: None,
+                min_duration: None,
+                repeated: false,
+                continuous: false,
+                cooldown: 0,
+                fire_on: FireOn::Threshold,
+                cancel: None,
+                repeat: None,
+                edge: None,
+            })
+        };
+        let mut adapter = super::Recognizer::new(&[
+            swipe_trigger(Direction::Up),
+            swipe_trigger(Direction::Right),
+            swipe_trigger(Direction::UpRight),
+        ]);
+
+        use crate::input_producer::event::*;
+        let swipe = |dx, dy| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time: 0,
+                fingers: 3,
+                dx,
+                dy,
+                start: None,
+            })
+        };
+
+        // Cardinal up and the diagonal both reach, diagonal wins
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(85.0, -110.0), 10));
+        assert_eq!(r, vec![TriggerEvent::Fired(2)]);
+        adapter.adapt(InputEvent::Ended(swipe(85.0, -110.0), 20));
+
+        // Too steep for the diagonal
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(30.0, -150.0), 30));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+        adapter.adapt(InputEvent::Ended(swipe(30.0, -150.0), 40));
+
+        // Diagonal direction, but not far enough along it
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(60.0, -50.0), 50));
+        assert_eq!(r, Vec::new());
+        adapter.adapt(InputEvent::Ended(swipe(60.0, -50.0), 60));
+
+        // Far enough along the diagonal, though not on each axis
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(90.0, -60.0), 70));
+        assert_eq!(r, vec![TriggerEvent::Fired(2)]);
+    }
+
+    #[test]
+    fn miss_reasons() {
+        let trigger = Trigger::Swipe(CardinalTrigger {
+            fingers: 3,
+            direction: Direction::Up,
+            distance: 100.0,
+            angle_tolerance: 20.0,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration: None,
+            repeated: false,
+            continuous: false,
+            cooldown: 0,
+            fire_on: FireOn::Threshold,
+            cancel: None,
+            repeat: None,
+            edge: None,
+        });
+
+        use crate::input_producer::event::*;
+        let swipe = |fingers, dy| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time: 0,
+                fingers,
+                dx: 0.0,
+                dy,
+                start: None,
+            })
+        };
+        assert_eq!(
+            trigger.miss(&swipe(4, -150.0), 0).as_deref(),
+            Some("needs 3 fingers, not 4")
+        );
+        assert_eq!(
+            trigger.miss(&swipe(3, -60.0), 0).as_deref(),
+            Some("went 60.0 of 100 Up")
+        );
+        let hold = Gesture::Hold(HoldGesture {
+            begin_time: 0,
+            fingers: 3,
+        });
+        assert_eq!(trigger.miss(&hold, 0), None);
+    }
+
+    #[test]
+    fn cooldown() {
+        let trigger = Trigger::Swipe(CardinalTrigger {
+            fingers: 3,
+            direction: Direction::Up,
+            distance: 100.0,
+            angle_tolerance: 20.0,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration: None,
+            repeated: false,
+            continuous: false,
+            cooldown: 300,
+            fire_on: FireOn::Threshold,
+            cancel: None,
+            repeat: None,
+            edge: None,
+        });
+        let mut adapter = super::Recognizer::new(&[trigger]);
+
+        use crate::input_producer::event::*;
+        let swipe = Gesture::Swipe(SwipeGesture {
+            begin_time: 0,
+            fingers: 3,
+            dx: 0.0,
+            dy: -150.0,
+            start: None,
+        });
+        let mut gesture_at = |time| {
+            let r = adapter.adapt(InputEvent::Ongoing(swipe.clone(), time));
+            adapter.adapt(InputEvent::Ended(swipe.clone(), time + 10));
+            r
+        };
+
+        assert_eq!(gesture_at(1000), vec![TriggerEvent::Fired(0)]);
+        assert_eq!(gesture_at(1200), Vec::new());
+        assert_eq!(gesture_at(1300), vec![TriggerEvent::Fired(0)]);
+
+        // Continuous triggers cool down from when they began
+        let trigger = Trigger::Swipe(CardinalTrigger {
+            fingers: 3,
+            direction: Direction::Up,
+            distance: 100.0,
+            angle_tolerance: 20.0,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration: None,
+            repeated: false,
+            continuous: true,
+            cooldown: 300,
+            fire_on: FireOn::Threshold,
+            cancel: None,
+            repeat: None,
+            edge: None,
+        });
+        let mut adapter = super::Recognizer::new(&[trigger]);
+        let mut began_at = |time| {
+            let r = adapter.adapt(InputEvent::Ongoing(swipe.clone(), time));
+            adapter.adapt(InputEvent::Ended(swipe.clone(), time + 10));
+            r.contains(&TriggerEvent::Began(0))
+        };
+        assert!(began_at(1000));
+        assert!(!began_at(1200));
+        assert!(began_at(1300));
+    }
+
+    #[test]
+    fn velocity() {
+        let swipe_up = |min_velocity, max_velocity| {
+            Trigger::Swipe(CardinalTrigger {
+                fingers: 3,
+                direction: Direction::Up,
+                distance: 100.0,
+                angle_tolerance: 20.0,
+                min_velocity,
+                max_velocity,
+                min_duration: None,
+                repeated: false,
+                continuous: false,
+                cooldown: 0,
+                fire_on: FireOn::Threshold,
+                cancel: None,
+                repeat: None,
+                edge: None,
+            })
+        };
+        let flick = swipe_up(Some(1000.0), None);
+        let slow = swipe_up(None, Some(500.0));
+        let mut adapter = super::Recognizer::new(&[flick, slow]);
+
+        use crate::input_producer::event::*;
+        let mut swipe = |begin_time, time| {
+            let swipe = Gesture::Swipe(SwipeGesture {
+                begin_time,
+                fingers: 3,
+                dx: 0.0,
+                dy: -120.0,
+                start: None,
+            });
+            let r = adapter.adapt(InputEvent::Ongoing(swipe.clone(), time));
+            adapter.adapt(InputEvent::Ended(swipe, time + 10));
+            r
+        };
+
+        // 2400 px/s
+        assert_eq!(swipe(1000, 1050), vec![TriggerEvent::Fired(0)]);
+        // 300 px/s
+        assert_eq!(swipe(2000, 2400), vec![TriggerEvent::Fired(1)]);
+        // 800 px/s is in neither
+        assert_eq!(swipe(3000, 3150), Vec::new());
+
+        // Repeated, the pace is from where the last one fired
+        let flick = swipe_up(Some(1000.0), None);
+        let slow = swipe_up(None, Some(500.0));
+        let repeated = |t| match t {
+            Trigger::Swipe(t) => Trigger::Swipe(CardinalTrigger {
+                repeated: true,
+                ..t
+            }),
+            t => t,
+        };
+        let mut adapter = super::Recognizer::new(&[repeated(flick), repeated(slow)]);
+        let mut swipe = |dy, time| {
+            let swipe = Gesture::Swipe(SwipeGesture {
+                begin_time: 1000,
+                fingers: 3,
+                dx: 0.0,
+                dy,
+                start: None,
+            });
+            adapter.adapt(InputEvent::Ongoing(swipe, time))
+        };
+        // 120 px/s, and then 2400 px/s of the next 120 px, where the whole
+        // swipe went only 229 px/s
+        assert_eq!(swipe(-120.0, 2000), vec![TriggerEvent::Fired(1)]);
+        assert_eq!(swipe(-240.0, 2050), vec![TriggerEvent::Fired(0)]);
+        assert_eq!(swipe(-360.0, 3050), vec![TriggerEvent::Fired(1)]);
+    }
+
+    #[test]
+    fn min_duration() {
+        let trigger = Trigger::Swipe(CardinalTrigger {
+            fingers: 3,
+            direction: Direction::Up,
+            distance: 100.0,
+            angle_tolerance: 20.0,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration: Some(100),
+            repeated: false,
+            continuous: false,
+            cooldown: 0,
+            fire_on: FireOn::Threshold,
+            cancel: None,
+            repeat: None,
+            edge: None,
+        });
+        let mut adapter = super::Recognizer::new(&[trigger]);
+
+        use crate::input_producer::event::*;
+        let swipe = |dy| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time: 1000,
+                fingers: 3,
+                dx: 0.0,
+                dy,
+                start: None,
+            })
+        };
+        // A brush goes far, but not for long
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(-150.0), 1040));
+        assert_eq!(r, Vec::new());
+        let r = adapter.adapt(InputEvent::Ended(swipe(-150.0), 1050));
+        assert_eq!(r, Vec::new());
+        // A swipe that's still going fires once it went on for long enough
+        let swipe = |dy| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time: 2000,
+                fingers: 3,
+                dx: 0.0,
+                dy,
+                start: None,
+            })
+        };
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(-150.0), 2040));
+        assert_eq!(r, Vec::new());
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(-160.0), 2100));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+    }
+
+    #[test]
+    fn hold() {
+        use crate::gesture_event::trigger::HoldTrigger;
+        let hold_trigger = |time| {
+            Trigger::Hold(HoldTrigger {
+                fingers: 3,
+                time,
+                cooldown: 0,
+                fire_on: FireOn::Threshold,
+            })
+        };
+        let mut adapter = super::Recognizer::new(&[hold_trigger(0), hold_trigger(500)]);
+
+        use crate::input_producer::event::*;
+        let hold = Gesture::Hold(HoldGesture {
+            begin_time: 1000,
+            fingers: 3,
+        });
+
+        assert_eq!(
+            adapter.adapt(InputEvent::Ongoing(hold.clone(), 1000)),
+            Vec::new()
+        );
+        assert_eq!(adapter.deadline(), Some(1500));
+        assert_eq!(adapter.tick(1400), Vec::new());
+        assert_eq!(adapter.tick(1500), vec![TriggerEvent::Fired(1)]);
+        assert_eq!(adapter.deadline(), None);
+        // The timed hold already fired, the other waits for the fingers to lift
+        let r = adapter.adapt(InputEvent::Ended(hold.clone(), 1600));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+
+        // Moving the fingers cancels the hold before either fires
+        adapter.adapt(InputEvent::Ongoing(hold.clone(), 2000));
+        let r = adapter.adapt(InputEvent::Cancelled(hold.clone(), 2100));
+        assert_eq!(r, Vec::new());
+        assert_eq!(adapter.deadline(), None);
+    }
+
+    #[test]
+    fn timed_hold_on_end() {
+        use crate::gesture_event::trigger::HoldTrigger;
+        let trigger = Trigger::Hold(HoldTrigger {
+            fingers: 4,
+            time: 500,
+            cooldown: 0,
+            fire_on: FireOn::End,
+        });
+        let mut adapter = super::Recognizer::new(&[trigger]);
+
+        use crate::input_producer::event::*;
+        let hold_at = |begin_time| {
+            Gesture::Hold(HoldGesture {
+                begin_time,
+                fingers: 4,
+            })
+        };
+        // Lifted too soon, it doesn't fire
+        adapter.adapt(InputEvent::Ongoing(hold_at(1000), 1000));
+        assert_eq!(adapter.deadline(), None);
+        let r = adapter.adapt(InputEvent::Ended(hold_at(1000), 1300));
+        assert_eq!(r, Vec::new());
+
+        // Held long enough, it waits for the fingers to lift
+        adapter.adapt(InputEvent::Ongoing(hold_at(2000), 2000));
+        assert_eq!(adapter.deadline(), None);
+        assert_eq!(adapter.tick(2600), Vec::new());
+        let r = adapter.adapt(InputEvent::Ended(hold_at(2000), 2700));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+    }
+
+    #[test]
+    fn hold_cooldown() {
+        use crate::gesture_event::trigger::HoldTrigger;
+        let trigger = Trigger::Hold(HoldTrigger {
+            fingers: 3,
+            time: 500,
+            cooldown: 2000,
+            fire_on: FireOn::Threshold,
+        });
+        let mut adapter = super::Recognizer::new(&[trigger]);
+
+        use crate::input_producer::event::*;
+        let hold_at = |begin_time| {
+            Gesture::Hold(HoldGesture {
+                begin_time,
+                fingers: 3,
+            })
+        };
+        adapter.adapt(InputEvent::Ongoing(hold_at(1000), 1000));
+        assert_eq!(adapter.tick(1500), vec![TriggerEvent::Fired(0)]);
+        adapter.adapt(InputEvent::Ended(hold_at(1000), 1600));
+
+        // The next hold waits for the cooldown, instead of being due already
+        adapter.adapt(InputEvent::Ongoing(hold_at(2000), 2000));
+        assert_eq!(adapter.deadline(), Some(3500));
+        assert_eq!(adapter.tick(2500), Vec::new());
+        assert_eq!(adapter.tick(3500), vec![TriggerEvent::Fired(0)]);
+        assert_eq!(adapter.deadline(), None);
+    }
+
+    #[test]
+    fn fallback_hold() {
+        use crate::gesture_event::trigger::HoldTrigger;
+        let trigger = Trigger::Hold(HoldTrigger {
+            fingers: 3,
+            time: 500,
+            cooldown: 0,
+            fire_on: FireOn::Threshold,
+        });
+        let mut adapter = super::Recognizer::new(&[trigger]);
+        adapter.set_fallbacks(vec![true]);
+
+        use crate::input_producer::event::*;
+        let hold = Gesture::Hold(HoldGesture {
+            begin_time: 1000,
+            fingers: 3,
+        });
+        // Fallbacks only fire at the end, so there's no time to tick at
+        adapter.adapt(InputEvent::Ongoing(hold.clone(), 1000));
+        assert_eq!(adapter.deadline(), None);
+        let r = adapter.adapt(InputEvent::Ended(hold.clone(), 1600));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+    }
+
+    #[test]
+    fn repeat() {
+        let trigger = Trigger::Swipe(CardinalTrigger {
+            fingers: 3,
+            direction: Direction::Up,
+            distance: 100.0,
+            angle_tolerance: 20.0,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration: None,
+            repeated: false,
+            continuous: true,
+            cooldown: 0,
+            fire_on: FireOn::Threshold,
+            cancel: None,
+            repeat: Some(200),
+            edge: None,
+        });
+        let mut adapter = super::Recognizer::new(&[trigger]);
+
+        use crate::input_producer::event::*;
+        let swipe = Gesture::Swipe(SwipeGesture {
+            begin_time: 0,
+            fingers: 3,
+            dx: 0.0,
+            dy: -150.0,
+            start: None,
+        });
+
+        let r = adapter.adapt(InputEvent::Ongoing(swipe.clone(), 1000));
+        assert_eq!(
+            r,
+            vec![
+                TriggerEvent::Began(0),
+                TriggerEvent::Progress(0, 150.0),
+                TriggerEvent::Moved(0, 0.0, -150.0),
+                TriggerEvent::Repeated(0)
+            ]
+        );
+        assert_eq!(adapter.deadline(), Some(1200));
+        assert_eq!(adapter.tick(1100), Vec::new());
+        assert_eq!(adapter.tick(1200), vec![TriggerEvent::Repeated(0)]);
+        // Late ticks repeat once, and the next interval starts from them
+        assert_eq!(adapter.tick(1700), vec![TriggerEvent::Repeated(0)]);
+        assert_eq!(adapter.deadline(), Some(1900));
+        let r = adapter.adapt(InputEvent::Ended(swipe.clone(), 1800));
+        assert_eq!(r, vec![TriggerEvent::Ended(0)]);
+        assert_eq!(adapter.deadline(), None);
+        assert_eq!(adapter.tick(2000), Vec::new());
+    }
+
+    #[test]
+    fn fire_on() {
+        let trigger = |direction, fire_on| {
+            Trigger::Swipe(CardinalTrigger {
+                fingers: 3,
+                direction,
+                distance: 100.0,
+                angle_tolerance: 20.0,
+                min_velocity: None,
+                max_velocity: None,
+                min_duration: None,
+                repeated: false,
+                continuous: false,
+                cooldown: 0,
+                fire_on,
+                cancel: None,
+                repeat: None,
+                edge: None,
+            })
+        };
+        let mut adapter = super::Recognizer::new(&[
+            trigger(Direction::Up, FireOn::Begin),
+            trigger(Direction::Up, FireOn::End),
+            trigger(Direction::Down, FireOn::End),
+        ]);
+
+        use crate::input_producer::event::*;
+        let swipe = |dy| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time: 0,
+                fingers: 3,
+                dx: 0.0,
+                dy,
+                start: None,
+            })
+        };
+
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(0.0), 10));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(-20.0), 20));
+        assert_eq!(r, Vec::new());
+        // Short of any distance, but up
+        let r = adapter.adapt(InputEvent::Ended(swipe(-20.0), 30));
+        assert_eq!(r, vec![TriggerEvent::Fired(1)]);
+
+        // The next gesture begins again
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(0.0), 40));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+        let r = adapter.adapt(InputEvent::Cancelled(swipe(30.0), 50));
+        assert_eq!(r, Vec::new());
+    }
+
+    #[test]
+    fn cancel_on_reversal() {
+        let trigger = Trigger::Swipe(CardinalTrigger {
+            fingers: 3,
+            direction: Direction::Up,
+            distance: 100.0,
+            angle_tolerance: 20.0,
+            min_velocity: None,
+            max_velocity: None,
+            min_duration: None,
+            repeated: false,
+            continuous: false,
+            cooldown: 0,
+            fire_on: FireOn::Threshold,
+            cancel: Some(50.0),
+            repeat: None,
+            edge: None,
+        });
+        let mut adapter = super::Recognizer::new(&[trigger]);
+
+        use crate::input_producer::event::*;
+        let swipe = |dy| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time: 0,
+                fingers: 3,
+                dx: 0.0,
+                dy,
+                start: None,
+            })
+        };
+
+        // Past the distance, but nothing fires until the end
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(-150.0), 10));
+        assert_eq!(r, Vec::new());
+        let r = adapter.adapt(InputEvent::Ongoing(swipe(-120.0), 20));
+        assert_eq!(r, Vec::new());
+        let r = adapter.adapt(InputEvent::Ended(swipe(-120.0), 30));
+        assert_eq!(r, vec![TriggerEvent::Fired(0)]);
+
+        // Went back from 150 to 60
+        adapter.adapt(InputEvent::Ongoing(swipe(-150.0), 40));
+        adapter.adapt(InputEvent::Ongoing(swipe(-60.0), 50));
+        let r = adapter.adapt(InputEvent::Ended(swipe(-60.0), 60));
+        assert_eq!(r, Vec::new());
+
+        // Far enough, but cancelled
+        adapter.adapt(InputEvent::Ongoing(swipe(-150.0), 70));
+        let r = adapter.adapt(InputEvent::Cancelled(swipe(-150.0), 80));
+        assert_eq!(r, Vec::new());
+    }
+
+    #[test]
+    fn sequence() {
+        use crate::gesture_event::trigger::SequenceTrigger;
+        let swipe_trigger = |direction| {
+            Trigger::Swipe(CardinalTrigger {
+                fingers: 3,
+                direction,
+                distance: 100.0,
+                angle_tolerance: 20.0,
+                min_velocity: None,
+                max_velocity: None,
+                min_duration: None,
+                repeated: false,
+                continuous: false,
+                cooldown: 0,
+                fire_on: FireOn::Threshold,
+                cancel: None,
+                repeat: None,
+                edge: None,
+            })
+        };
+        let combo = Trigger::Sequence(SequenceTrigger {
+            steps: vec![
+                swipe_trigger(Direction::Up),
+                swipe_trigger(Direction::Up),
+                swipe_trigger(Direction::Down),
+            ],
+            timeout: 400,
+            cooldown: 0,
+        });
+        let mut adapter = super::Recognizer::new(&[swipe_trigger(Direction::Up), combo]);
+
+        use crate::input_producer::event::*;
+        let swipe = |dy| {
+            Gesture::Swipe(SwipeGesture {
+                begin_time: 0,
+                fingers: 3,
+                dx: 0.0,
+                dy,
+                start: None,
+            })
+        };
+        let up = |adapter: &mut super::Recognizer, time| {
+            let mut r = adapter.adapt(InputEvent::Ongoing(swipe(-150.0), time));
+            r.extend(adapter.adapt(InputEvent::Ended(swipe(-150.0), time + 10)));
+            r
+        };
+        let up_down = |adapter: &mut super::Recognizer, time| {
+            let mut r = adapter.adapt(InputEvent::Ongoing(swipe(-150.0), time));
+            r.extend(adapter.adapt(InputEvent::Ongoing(swipe(0.0), time + 10)));
+            r.extend(adapter.adapt(InputEvent::Ended(swipe(0.0), time + 20)));
+            r
+        };
+
+        // Up, then up and down in one gesture
+        assert_eq!(up(&mut adapter, 0), vec![TriggerEvent::Fired(0)]);
+        assert_eq!(
+            up_down(&mut adapter, 300),
+            vec![TriggerEvent::Fired(0), TriggerEvent::Fired(1)]
+        );
+
+        // The second gesture comes too late, so the sequence starts over with
+        // its first step
+        up(&mut adapter, 1000);
+        up(&mut adapter, 2000);
+        assert_eq!(
+            up_down(&mut adapter, 2200),
+            vec![TriggerEvent::Fired(0), TriggerEvent::Fired(1)]
+        );
+
+        // A step out of order starts over too
+        up(&mut adapter, 3000);
+        adapter.adapt(InputEvent::Ongoing(swipe(150.0), 3200));
+        adapter.adapt(InputEvent::Ended(swipe(150.0), 3210));
+        assert_eq!(up_down(&mut adapter, 3400), vec![TriggerEvent::Fired(0)]);
     }
 }
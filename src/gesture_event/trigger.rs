@@ -3,13 +3,13 @@
 //! pub(crate): These triggers can also perform computations to see if events
 //! match them
 
-use crate::common::{AnyDirection, Direction, PinchDirection, RotateDirection};
-use crate::input_producer::event::{HoldGesture, PinchGesture, SwipeGesture};
+use crate::common::{AnyDirection, Direction, Edge, FireOn, PinchDirection, RotateDirection};
+use crate::input_producer::event::{Gesture, HoldGesture, PinchGesture, SwipeGesture};
 
 const VSLOPE: f64 = 1.0;
 const HSLOPE: f64 = 1.0 / VSLOPE;
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum Trigger {
     Swipe(CardinalTrigger),
     Pinch(PinchTrigger),
@@ -24,6 +24,9 @@ pub enum Trigger {
     /// Sent only when hold ended
     Hold(HoldTrigger),
     // TODO: hold in progress. Need to track my own time, bleh
+    /// Several triggers firing one after the other, in the same gesture or in
+    /// separate ones
+    Sequence(SequenceTrigger),
 }
 
 /// Common struct for triggers in a certain direction over a certain distance:
@@ -35,7 +38,30 @@ pub struct CardinalTrigger {
     pub fingers: i32,
     pub direction: Direction,
     pub distance: f64,
+    /// How far in degrees a diagonal gesture may stray from the diagonal.
+    /// Unused by the other directions
+    pub angle_tolerance: f64,
+    /// Slowest and fastest the gesture may go to match, in pixels per second
+    /// over the whole gesture so far
+    pub min_velocity: Option<f64>,
+    pub max_velocity: Option<f64>,
+    /// Milliseconds the gesture must have gone on for to match
+    pub min_duration: Option<u32>,
     pub repeated: bool,
+    /// Report progress of the gesture after the distance is reached
+    pub continuous: bool,
+    /// Milliseconds after firing during which the trigger doesn't fire again
+    pub cooldown: u32,
+    pub fire_on: FireOn,
+    /// Wait for the gesture to end before firing, and don't fire if it went
+    /// back from the farthest point by more than this
+    pub cancel: Option<f64>,
+    /// With `continuous`, execute the action again every this many
+    /// milliseconds until the gesture ends
+    pub repeat: Option<u32>,
+    /// Edge that a swipe must start at, and how close to it, as a fraction of
+    /// the touchpad
+    pub edge: Option<(Edge, f64)>,
 }
 
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -44,6 +70,13 @@ pub struct PinchTrigger {
     pub direction: PinchDirection,
     pub scale: f64,
     pub repeated: bool,
+    pub continuous: bool,
+    pub cooldown: u32,
+    pub fire_on: FireOn,
+    pub cancel: Option<f64>,
+    /// With `continuous`, execute the action again every this many
+    /// milliseconds until the gesture ends
+    pub repeat: Option<u32>,
 }
 
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -54,12 +87,32 @@ pub struct RotateTrigger {
     /// don't match a real 90 degree rotation, but it's pretty close
     pub distance: f64,
     pub repeated: bool,
+    pub continuous: bool,
+    pub cooldown: u32,
+    pub fire_on: FireOn,
+    pub cancel: Option<f64>,
+    /// With `continuous`, execute the action again every this many
+    /// milliseconds until the gesture ends
+    pub repeat: Option<u32>,
 }
 
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub struct HoldTrigger {
     pub fingers: i32,
+    /// Milliseconds to hold for before firing, while the fingers are still
+    /// down. 0 fires when the fingers are lifted instead
     pub time: u32,
+    pub cooldown: u32,
+    pub fire_on: FireOn,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct SequenceTrigger {
+    pub steps: Vec<Trigger>,
+    /// Milliseconds from one step firing to the next, after which the sequence
+    /// starts over
+    pub timeout: u32,
+    pub cooldown: u32,
 }
 
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -68,20 +121,62 @@ pub(crate) struct Origin {
     pub y: f64,
     pub scale: f64,
     pub rotation: f64,
+    /// Event time the origin moved at, or 0 for the start of the gesture
+    pub time: u32,
+}
+
+impl Origin {
+    /// Origin at the start of a gesture
+    pub(crate) const NEUTRAL: Origin = Origin {
+        x: 0.0,
+        y: 0.0,
+        scale: 1.0,
+        rotation: 0.0,
+        time: 0,
+    };
 }
 
 /* Impls for matchins */
 
 impl Direction {
-    fn matches(&self, dx: f64, dy: f64) -> bool {
+    /// Whether the gesture went this way. Diagonals only accept gestures
+    /// within the angle tolerance, while cardinals split the plane between
+    /// themselves
+    fn matches(&self, dx: f64, dy: f64, angle_tolerance: f64) -> bool {
         // from running libinput: up is negative, left is positive
         match self {
             Direction::Up => dy <= VSLOPE * dx && dy <= -VSLOPE * dx,
             Direction::Down => dy >= VSLOPE * dx && dy >= -VSLOPE * dx,
             Direction::Right => dx >= HSLOPE * dy && dx >= -HSLOPE * dy,
             Direction::Left => dx <= HSLOPE * dy && dx <= -HSLOPE * dy,
+            _ => {
+                let length = dx.hypot(dy);
+                length > 0.0 && self.progress(dx, dy) >= length * angle_tolerance.to_radians().cos()
+            }
+        }
+    }
+
+    /// Signed distance travelled in this direction
+    fn progress(&self, dx: f64, dy: f64) -> f64 {
+        use std::f64::consts::FRAC_1_SQRT_2;
+        match self {
+            Direction::Up => -dy,
+            Direction::Down => dy,
+            Direction::Right => dx,
+            Direction::Left => -dx,
+            Direction::UpLeft => (-dx - dy) * FRAC_1_SQRT_2,
+            Direction::UpRight => (dx - dy) * FRAC_1_SQRT_2,
+            Direction::DownLeft => (-dx + dy) * FRAC_1_SQRT_2,
+            Direction::DownRight => (dx + dy) * FRAC_1_SQRT_2,
         }
     }
+
+    pub(crate) fn diagonal(&self) -> bool {
+        matches!(
+            self,
+            Direction::UpLeft | Direction::UpRight | Direction::DownLeft | Direction::DownRight
+        )
+    }
 }
 
 impl RotateDirection {
@@ -94,17 +189,71 @@ impl RotateDirection {
 }
 
 impl CardinalTrigger {
-    pub(crate) fn matches_swipe(&self, gest: &SwipeGesture, o: Origin) -> bool {
+    /// The distance goes from the origin, and the direction from `aim`, which
+    /// is past it when the swipe had to leave a deadzone first
+    pub(crate) fn matches_swipe(
+        &self,
+        gest: &SwipeGesture,
+        o: Origin,
+        aim: (f64, f64),
+        ctime: u32,
+    ) -> bool {
         self.fingers == gest.fingers
-            && self.direction.matches(gest.dx - o.x, gest.dy - o.y)
-            && ((gest.dx - o.x).abs() >= self.distance || (gest.dy - o.y).abs() >= self.distance)
+            && self.reaches(gest.dx - o.x, gest.dy - o.y)
+            && self.aims(gest.dx - aim.0, gest.dy - aim.1)
+            && self.paced(gest, o, ctime)
+            && self.starts(gest)
     }
-    // TODO: deduplicate. Same implementation, different types with same shape
+    // Same shape of gesture, without the deadzone
     pub(crate) fn matches_shear(&self, gest: &PinchGesture, o: Origin) -> bool {
-        self.fingers == gest.fingers
-            && self.direction.matches(gest.dx - o.x, gest.dy - o.y)
-            && ((gest.dx - o.x).abs() >= self.distance || (gest.dy - o.y).abs() >= self.distance)
+        let (dx, dy) = (gest.dx - o.x, gest.dy - o.y);
+        self.fingers == gest.fingers && self.reaches(dx, dy) && self.aims(dx, dy)
+    }
+
+    /// Whether the swipe started at the edge. Swipes whose start isn't known
+    /// never do
+    fn starts(&self, gest: &SwipeGesture) -> bool {
+        match self.edge {
+            Some((edge, margin)) => gest.start.is_some_and(|s| edge.near(s, margin)),
+            None => true,
+        }
+    }
+
+    /// Whether the swipe went within the velocity limits since the origin,
+    /// and long enough since it began
+    fn paced(&self, gest: &SwipeGesture, o: Origin, ctime: u32) -> bool {
+        if self
+            .min_duration
+            .is_some_and(|min| ctime.saturating_sub(gest.begin_time) < min)
+        {
+            return false;
+        }
+        if self.min_velocity.is_none() && self.max_velocity.is_none() {
+            return true;
+        }
+        let v = velocity(gest, o, ctime);
+        self.min_velocity.is_none_or(|min| v >= min) && self.max_velocity.is_none_or(|max| v <= max)
+    }
+
+    /// Whether the motion went far enough. In the trigger's direction, so the
+    /// slide out of a deadzone doesn't count for a trigger going the other
+    /// way, and a diagonal goes as far as a cardinal would
+    fn reaches(&self, dx: f64, dy: f64) -> bool {
+        self.direction.progress(dx, dy) >= self.distance
     }
+
+    /// Whether the motion went in the trigger's direction
+    fn aims(&self, dx: f64, dy: f64) -> bool {
+        (dx, dy) != (0.0, 0.0) && self.direction.matches(dx, dy, self.angle_tolerance)
+    }
+}
+
+/// Pixels per second from the origin, which is the start of the swipe until a
+/// trigger fires and moves it. A swipe that went anywhere in no time at all is
+/// infinitely fast
+fn velocity(gest: &SwipeGesture, o: Origin, ctime: u32) -> f64 {
+    let seconds = f64::from(ctime.saturating_sub(gest.begin_time.max(o.time))) / 1000.0;
+    (gest.dx - o.x).hypot(gest.dy - o.y) / seconds
 }
 
 impl PinchTrigger {
@@ -138,8 +287,20 @@ impl RotateTrigger {
 }
 
 impl HoldTrigger {
-    pub(crate) fn matches(&self, gest: &HoldGesture, ctime: u32) -> bool {
-        self.fingers == gest.fingers && ctime.saturating_sub(gest.begin_time) >= self.time
+    pub(crate) fn matches(&self, gest: &HoldGesture, ctime: u32, ended: bool) -> bool {
+        self.fingers == gest.fingers
+            && if self.time == 0 {
+                ended
+            } else {
+                ctime.saturating_sub(gest.begin_time) >= self.time
+            }
+    }
+
+    /// Event time at which a hold of this gesture fires, if it is timed and
+    /// fires on its threshold, without waiting for the fingers to lift
+    pub(crate) fn deadline(&self, gest: &HoldGesture) -> Option<u32> {
+        (self.time != 0 && self.fire_on == FireOn::Threshold && self.fingers == gest.fingers)
+            .then(|| gest.begin_time.saturating_add(self.time))
     }
 }
 
@@ -152,8 +313,9 @@ impl Trigger {
             Trigger::Pinch(p) => p.repeated,
             Trigger::Shear(s) => s.repeated,
             Trigger::Rotate(r) => r.repeated,
-            // you can't repeat holds, but repeated are simpler to handle
-            Trigger::Hold(_) => true,
+            // Holds fire once, either on time or when the fingers are lifted
+            Trigger::Hold(_) => false,
+            Trigger::Sequence(_) => false,
         }
     }
 
@@ -163,7 +325,237 @@ impl Trigger {
             Trigger::Pinch(p) => Some(AnyDirection::Pinch(p.direction)),
             Trigger::Shear(s) => Some(AnyDirection::Cardinal(s.direction)),
             Trigger::Rotate(r) => Some(AnyDirection::Rotate(r.direction)),
-            Trigger::Hold(_) => None,
+            Trigger::Hold(_) | Trigger::Sequence(_) => None,
+        }
+    }
+
+    /// Whether this trigger takes precedence when both match the same event.
+    /// Diagonals accept only gestures that are clearly diagonal, so they win
+    /// over cardinals that accept anything near their axis
+    pub(crate) fn overrides(&self, other: &Trigger) -> bool {
+        match (self, other) {
+            (Trigger::Swipe(a), Trigger::Swipe(b)) | (Trigger::Shear(a), Trigger::Shear(b)) => {
+                a.direction.diagonal() && !b.direction.diagonal()
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn cooldown(&self) -> u32 {
+        match self {
+            Trigger::Swipe(s) | Trigger::Shear(s) => s.cooldown,
+            Trigger::Pinch(p) => p.cooldown,
+            Trigger::Rotate(r) => r.cooldown,
+            Trigger::Hold(h) => h.cooldown,
+            Trigger::Sequence(q) => q.cooldown,
+        }
+    }
+
+    pub(crate) fn repeat(&self) -> Option<u32> {
+        match self {
+            Trigger::Swipe(s) | Trigger::Shear(s) => s.repeat,
+            Trigger::Pinch(p) => p.repeat,
+            Trigger::Rotate(r) => r.repeat,
+            Trigger::Hold(_) | Trigger::Sequence(_) => None,
+        }
+    }
+
+    pub(crate) fn fire_on(&self) -> FireOn {
+        match self {
+            Trigger::Swipe(s) | Trigger::Shear(s) => s.fire_on,
+            Trigger::Pinch(p) => p.fire_on,
+            Trigger::Rotate(r) => r.fire_on,
+            Trigger::Hold(h) => h.fire_on,
+            Trigger::Sequence(_) => FireOn::Threshold,
+        }
+    }
+
+    pub(crate) fn cancel(&self) -> Option<f64> {
+        match self {
+            Trigger::Swipe(s) | Trigger::Shear(s) => s.cancel,
+            Trigger::Pinch(p) => p.cancel,
+            Trigger::Rotate(r) => r.cancel,
+            Trigger::Hold(_) | Trigger::Sequence(_) => None,
+        }
+    }
+
+    /// Whether the gesture is of this trigger's kind with as many fingers,
+    /// which is all that is known when it begins
+    pub(crate) fn fits(&self, gesture: &Gesture) -> bool {
+        let fingers = match (gesture, self) {
+            (Gesture::Swipe(g), Trigger::Swipe(_)) => g.fingers,
+            (Gesture::Pinch(g), Trigger::Shear(_) | Trigger::Pinch(_) | Trigger::Rotate(_)) => {
+                g.fingers
+            }
+            (Gesture::Hold(g), Trigger::Hold(_)) => g.fingers,
+            _ => return false,
+        };
+        fingers == self.fingers()
+    }
+
+    /// Whether the gesture fits and went in this trigger's direction, however
+    /// far. Swipes go from `aim`, and head nowhere without one
+    pub(crate) fn heads(&self, gesture: &Gesture, aim: Option<(f64, f64)>, ctime: u32) -> bool {
+        if !self.fits(gesture) {
+            return false;
+        }
+        match (gesture, self) {
+            (Gesture::Swipe(g), Trigger::Swipe(t)) => aim.is_some_and(|(x, y)| {
+                t.aims(g.dx - x, g.dy - y) && t.paced(g, Origin::NEUTRAL, ctime)
+            }),
+            (Gesture::Pinch(g), Trigger::Shear(t)) => t.aims(g.dx, g.dy),
+            (Gesture::Pinch(g), Trigger::Pinch(t)) => match t.direction {
+                PinchDirection::In => g.scale > 1.0,
+                PinchDirection::Out => g.scale < 1.0,
+            },
+            (Gesture::Pinch(g), Trigger::Rotate(t)) => t.direction.matches(g.angle.signum()),
+            // A timed hold only counts once the fingers were down for its time
+            (Gesture::Hold(g), Trigger::Hold(t)) => ctime.saturating_sub(g.begin_time) >= t.time,
+            _ => true,
+        }
+    }
+
+    pub(crate) fn continuous(&self) -> bool {
+        match self {
+            Trigger::Swipe(s) => s.continuous,
+            Trigger::Pinch(p) => p.continuous,
+            Trigger::Shear(s) => s.continuous,
+            Trigger::Rotate(r) => r.continuous,
+            Trigger::Hold(_) | Trigger::Sequence(_) => false,
+        }
+    }
+
+    /// Why a completed gesture of this trigger's kind didn't fire it, for
+    /// debugging. `None` for gestures of other kinds
+    pub(crate) fn miss(&self, gesture: &Gesture, ctime: u32) -> Option<String> {
+        let fingers = match (gesture, self) {
+            (Gesture::Swipe(g), Trigger::Swipe(_)) => g.fingers,
+            (Gesture::Pinch(g), Trigger::Shear(_) | Trigger::Pinch(_) | Trigger::Rotate(_)) => {
+                g.fingers
+            }
+            (Gesture::Hold(g), Trigger::Hold(_)) => g.fingers,
+            _ => return None,
+        };
+        if fingers != self.fingers() {
+            return Some(format!("needs {} fingers, not {}", self.fingers(), fingers));
+        }
+        Some(match (gesture, self) {
+            (Gesture::Swipe(g), Trigger::Swipe(t))
+                if t.min_duration
+                    .is_some_and(|min| ctime.saturating_sub(g.begin_time) < min) =>
+            {
+                format!(
+                    "went on for {} ms, not {}",
+                    ctime.saturating_sub(g.begin_time),
+                    t.min_duration.unwrap_or(0)
+                )
+            }
+            // Gestures that fired nothing kept their origin
+            (Gesture::Swipe(g), Trigger::Swipe(t)) if !t.paced(g, Origin::NEUTRAL, ctime) => {
+                format!(
+                    "went {:.0} px/s, not between {} and {}",
+                    velocity(g, Origin::NEUTRAL, ctime),
+                    t.min_velocity.unwrap_or(0.0),
+                    t.max_velocity.unwrap_or(f64::INFINITY)
+                )
+            }
+            (Gesture::Swipe(g), Trigger::Swipe(t)) => format!(
+                "went {:.1} of {} {:?}",
+                t.direction.progress(g.dx, g.dy),
+                t.distance,
+                t.direction
+            ),
+            (Gesture::Pinch(g), Trigger::Shear(t)) => format!(
+                "went {:.1} of {} {:?}",
+                t.direction.progress(g.dx, g.dy),
+                t.distance,
+                t.direction
+            ),
+            (Gesture::Pinch(g), Trigger::Pinch(t)) => match t.direction {
+                PinchDirection::In => format!("scale {:.3} below {:.3}", g.scale, t.scale),
+                PinchDirection::Out => {
+                    format!("scale {:.3} above {:.3}", g.scale, 1.0 / t.scale)
+                }
+            },
+            (Gesture::Pinch(g), Trigger::Rotate(t)) => {
+                format!("rotated {:.1} of {} {:?}", g.angle, t.distance, t.direction)
+            }
+            _ => "not held long enough".to_string(),
+        })
+    }
+
+    /// Name of the kind of gesture, as in the config
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Trigger::Swipe(_) => "Swipe",
+            Trigger::Pinch(_) => "Pinch",
+            Trigger::Shear(_) => "Shear",
+            Trigger::Rotate(_) => "Rotate",
+            Trigger::Hold(_) => "Hold",
+            Trigger::Sequence(_) => "Sequence",
+        }
+    }
+
+    pub(crate) fn fingers(&self) -> i32 {
+        match self {
+            Trigger::Swipe(t) | Trigger::Shear(t) => t.fingers,
+            Trigger::Pinch(t) => t.fingers,
+            Trigger::Rotate(t) => t.fingers,
+            Trigger::Hold(t) => t.fingers,
+            Trigger::Sequence(q) => q.steps.first().map_or(0, Trigger::fingers),
+        }
+    }
+
+    /// How far the gesture went in the trigger's direction since it began.
+    /// Distance for swipes and shears, scale difference for pinches and angle
+    /// for rotations. `None` if the gesture is not of this trigger's kind
+    pub(crate) fn progress(&self, gesture: &Gesture) -> Option<f64> {
+        match (gesture, self) {
+            (Gesture::Swipe(g), Trigger::Swipe(t)) if g.fingers == t.fingers => {
+                Some(t.direction.progress(g.dx, g.dy))
+            }
+            (Gesture::Pinch(g), Trigger::Shear(t)) if g.fingers == t.fingers => {
+                Some(t.direction.progress(g.dx, g.dy))
+            }
+            (Gesture::Pinch(g), Trigger::Pinch(t)) if g.fingers == t.fingers => {
+                Some(match t.direction {
+                    PinchDirection::In => g.scale - 1.0,
+                    PinchDirection::Out => 1.0 / g.scale - 1.0,
+                })
+            }
+            (Gesture::Pinch(g), Trigger::Rotate(t)) if g.fingers == t.fingers => {
+                Some(match t.direction {
+                    RotateDirection::Clockwise => g.angle,
+                    RotateDirection::Anticlockwise => -g.angle,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// How far a swipe or shear went in the trigger's direction from the
+    /// origin, which is negative where it went the other way. `None` for
+    /// other gestures and triggers
+    pub(crate) fn progress_from(&self, gesture: &Gesture, origin: Origin) -> Option<f64> {
+        match self {
+            Trigger::Swipe(t) | Trigger::Shear(t) => self
+                .progress(gesture)
+                .map(|p| p - t.direction.progress(origin.x, origin.y)),
+            _ => None,
+        }
+    }
+
+    /// The origin moved along the trigger's direction by `turn`, as returned
+    /// by `progress_from`, to where the gesture turned its way
+    pub(crate) fn turned(&self, origin: Origin, turn: f64) -> Origin {
+        match self {
+            // Progress is along a unit vector, which it gives the parts of
+            Trigger::Swipe(t) | Trigger::Shear(t) => Origin {
+                x: origin.x + t.direction.progress(1.0, 0.0) * turn,
+                y: origin.y + t.direction.progress(0.0, 1.0) * turn,
+                ..origin
+            },
+            _ => origin,
         }
     }
 }
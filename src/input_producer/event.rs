@@ -2,10 +2,8 @@
 //! in gestures. It also provides routines for converting them and for updating
 //! the gesture state.
 
-use input::event::gesture::{
-    GestureEndEvent, GestureEventCoordinates, GestureEventTrait, GesturePinchEventTrait,
-    GesturePinchUpdateEvent,
-};
+use input::AsRaw;
+use serde::{Deserialize, Serialize};
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum Gesture {
@@ -21,6 +19,9 @@ pub struct SwipeGesture {
     pub fingers: i32,
     pub dx: f64,
     pub dy: f64,
+    /// Where the first finger touched the touchpad, in fractions of it from
+    /// its top left corner. `None` when it can't be told
+    pub start: Option<(f64, f64)>,
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -39,6 +40,23 @@ pub struct HoldGesture {
     pub fingers: i32,
 }
 
+impl std::fmt::Display for Gesture {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Gesture::None => write!(f, "no gesture"),
+            Gesture::Swipe(s) => {
+                write!(f, "{}-finger swipe by {:.1}, {:.1}", s.fingers, s.dx, s.dy)
+            }
+            Gesture::Pinch(p) => write!(
+                f,
+                "{}-finger pinch to scale {:.3}, angle {:.1}, moved by {:.1}, {:.1}",
+                p.fingers, p.scale, p.angle, p.dx, p.dy
+            ),
+            Gesture::Hold(h) => write!(f, "{}-finger hold", h.fingers),
+        }
+    }
+}
+
 #[derive(PartialEq, Debug)]
 pub enum GestureState {
     /// Arg is current event time
@@ -49,117 +67,234 @@ pub enum GestureState {
     Cancelled(Gesture, u32),
 }
 
-impl Gesture {
-    /// Update the ongoing gesture with the data from libinput
-    pub(crate) fn update(&mut self, gest: &input::event::GestureEvent) -> GestureState {
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum GestureKind {
+    Swipe,
+    Pinch,
+    Hold,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Phase {
+    Begin,
+    Update,
+    End,
+}
+
+/// A gesture event from libinput, keeping only what gestures are built from.
+/// Recordings of gestures are lines of these
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RawEvent {
+    pub time: u32,
+    pub kind: GestureKind,
+    pub phase: Phase,
+    pub fingers: i32,
+    /// Movement since the previous event. Only in updates of swipes and
+    /// pinches
+    pub dx: f64,
+    pub dy: f64,
+    /// Scale of a pinch relative to its start
+    pub scale: f64,
+    /// Rotation of a pinch since the previous event, in degrees. Only in
+    /// updates
+    pub angle: f64,
+    /// The gesture ended without being completed
+    pub cancelled: bool,
+    /// Where the first finger touched, like in `SwipeGesture`. Only in begins
+    /// of swipes, and left out of recordings without it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<(f64, f64)>,
+}
+
+impl RawEvent {
+    /// Make the event move less, as if the thresholds were higher by the
+    /// factors: the distance of swipes and shears, the scale of pinches as a
+    /// power, and the angle of rotations
+    pub fn scale_down(&mut self, distance: f64, pinch: f64, rotation: f64) {
+        self.dx /= distance;
+        self.dy /= distance;
+        self.scale = self.scale.powf(1.0 / pinch);
+        self.angle /= rotation;
+    }
+
+    /// Take the data out of a libinput event. Returns `None` for events of
+    /// libinput versions newer than this code
+    pub fn from_libinput(gest: &input::event::GestureEvent) -> Option<Self> {
         use input::event::gesture::*;
+        let mut raw = RawEvent {
+            time: gest.time(),
+            kind: GestureKind::Swipe,
+            phase: Phase::Begin,
+            fingers: gest.finger_count(),
+            dx: 0.0,
+            dy: 0.0,
+            scale: 1.0,
+            angle: 0.0,
+            cancelled: false,
+            start: None,
+        };
         match gest {
             GestureEvent::Swipe(sw) => match sw {
-                GestureSwipeEvent::Begin(_ev) => {
-                    *self = Gesture::Swipe(SwipeGesture {
-                        begin_time: gest.time(),
-                        fingers: gest.finger_count(),
-                        dx: 0.0,
-                        dy: 0.0,
-                    });
-                    GestureState::Ongoing(gest.time())
-                }
+                GestureSwipeEvent::Begin(_ev) => (),
                 GestureSwipeEvent::Update(ev) => {
-                    self.update_coords(ev);
-                    GestureState::Ongoing(gest.time())
+                    raw.phase = Phase::Update;
+                    raw.dx = ev.dx();
+                    raw.dy = ev.dy();
                 }
-                GestureSwipeEvent::End(ev) => self.end_gesture(ev),
-                _ => {
-                    log::warn!("Swipe update from the future");
-                    GestureState::Ongoing(gest.time())
-                }
-            },
-            GestureEvent::Pinch(pc) => match pc {
-                GesturePinchEvent::Begin(_ev) => {
-                    *self = Gesture::Pinch(PinchGesture {
-                        begin_time: gest.time(),
-                        fingers: gest.finger_count(),
-                        scale: pc.scale(),
-                        angle: 0.0,
-                        dx: 0.0,
-                        dy: 0.0,
-                    });
-                    GestureState::Ongoing(gest.time())
-                }
-                GesturePinchEvent::Update(ev) => {
-                    self.update_coords(ev);
-                    self.update_scale(ev);
-                    self.update_angle(ev);
-                    GestureState::Ongoing(gest.time())
-                }
-                GesturePinchEvent::End(ev) => {
-                    self.update_scale(ev);
-                    self.end_gesture(ev)
+                GestureSwipeEvent::End(ev) => {
+                    raw.phase = Phase::End;
+                    raw.cancelled = ev.cancelled();
                 }
                 _ => {
-                    log::warn!("Pinch update from the future");
-                    GestureState::Ongoing(gest.time())
+                    log::warn!("Swipe update from the future");
+                    return None;
                 }
             },
-            GestureEvent::Hold(ho) => match ho {
-                GestureHoldEvent::Begin(_ev) => {
-                    *self = Gesture::Hold(HoldGesture {
-                        begin_time: gest.time(),
-                        fingers: gest.finger_count(),
-                    });
-                    GestureState::Ongoing(gest.time())
+            GestureEvent::Pinch(pc) => {
+                raw.kind = GestureKind::Pinch;
+                raw.scale = pc.scale();
+                match pc {
+                    GesturePinchEvent::Begin(_ev) => (),
+                    GesturePinchEvent::Update(ev) => {
+                        raw.phase = Phase::Update;
+                        raw.dx = ev.dx();
+                        raw.dy = ev.dy();
+                        raw.angle = ev.angle_delta();
+                    }
+                    GesturePinchEvent::End(ev) => {
+                        raw.phase = Phase::End;
+                        raw.cancelled = ev.cancelled();
+                    }
+                    _ => {
+                        log::warn!("Pinch update from the future");
+                        return None;
+                    }
                 }
-                GestureHoldEvent::End(_ev) => {
-                    GestureState::Ended(std::mem::replace(self, Gesture::None), gest.time())
-                }
-                _ => {
-                    log::warn!("Hold update from the future");
-                    GestureState::Ongoing(gest.time())
+            }
+            GestureEvent::Hold(ho) => {
+                raw.kind = GestureKind::Hold;
+                match ho {
+                    GestureHoldEvent::Begin(_ev) => (),
+                    GestureHoldEvent::End(ev) => {
+                        // Holds end cancelled when the fingers move too far,
+                        // which the bindings don't expose for holds
+                        raw.phase = Phase::End;
+                        raw.cancelled = unsafe {
+                            input::ffi::libinput_event_gesture_get_cancelled(ev.as_raw_mut()) != 0
+                        };
+                    }
+                    _ => {
+                        log::warn!("Hold update from the future");
+                        return None;
+                    }
                 }
-            },
+            }
             _ => {
                 log::warn!("Event from the future");
-                GestureState::Ongoing(gest.time())
+                return None;
             }
         }
+        Some(raw)
     }
 
-    fn update_coords(&mut self, upd: &dyn GestureEventCoordinates) {
-        match *self {
-            Gesture::Swipe(ref mut swipe) => {
-                swipe.dx += upd.dx();
-                swipe.dy += upd.dy();
+    /// Read a recording, as written by `Engine::record`
+    pub fn read_recording(reader: impl std::io::BufRead) -> std::io::Result<Vec<Self>> {
+        let mut events = Vec::new();
+        for (n, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
             }
-            Gesture::Pinch(ref mut pinch) => {
-                pinch.dx += upd.dx();
-                pinch.dy += upd.dy();
-            }
-            _ => log::error!("Impossible coords update!"),
+            let event = ron::from_str(&line).map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("line {}: {}", n + 1, e),
+                )
+            })?;
+            events.push(event);
         }
+        Ok(events)
     }
 
-    fn update_scale(&mut self, upd: &dyn GesturePinchEventTrait) {
-        match *self {
-            Gesture::Pinch(ref mut pinch) => pinch.scale = upd.scale(),
-            _ => log::error!("Impossible scale update!"),
-        }
-    }
-
-    fn update_angle(&mut self, upd: &GesturePinchUpdateEvent) {
-        match *self {
-            Gesture::Pinch(ref mut pinch) => pinch.angle += upd.angle_delta(),
-            _ => log::error!("Impossible angle update"),
-        }
+    /// Event ending the gesture as cancelled, for when libinput won't send the
+    /// end. `None` for no gesture
+    pub fn cancel(gesture: &Gesture, time: u32) -> Option<Self> {
+        let (kind, fingers, scale) = match gesture {
+            Gesture::None => return None,
+            Gesture::Swipe(s) => (GestureKind::Swipe, s.fingers, 1.0),
+            Gesture::Pinch(p) => (GestureKind::Pinch, p.fingers, p.scale),
+            Gesture::Hold(h) => (GestureKind::Hold, h.fingers, 1.0),
+        };
+        Some(RawEvent {
+            time,
+            kind,
+            phase: Phase::End,
+            fingers,
+            dx: 0.0,
+            dy: 0.0,
+            scale,
+            angle: 0.0,
+            cancelled: true,
+            start: None,
+        })
     }
+}
 
-    fn end_gesture<T>(&mut self, upd: &T) -> GestureState
-    where
-        T: GestureEventTrait + GestureEndEvent,
-    {
-        if upd.cancelled() {
-            GestureState::Cancelled(std::mem::replace(self, Gesture::None), upd.time())
-        } else {
-            GestureState::Ended(std::mem::replace(self, Gesture::None), upd.time())
+impl Gesture {
+    /// Update the ongoing gesture with the data from libinput
+    pub(crate) fn update(&mut self, raw: &RawEvent) -> GestureState {
+        match raw.phase {
+            Phase::Begin => {
+                *self = match raw.kind {
+                    GestureKind::Swipe => Gesture::Swipe(SwipeGesture {
+                        begin_time: raw.time,
+                        fingers: raw.fingers,
+                        dx: 0.0,
+                        dy: 0.0,
+                        start: raw.start,
+                    }),
+                    GestureKind::Pinch => Gesture::Pinch(PinchGesture {
+                        begin_time: raw.time,
+                        fingers: raw.fingers,
+                        scale: raw.scale,
+                        angle: 0.0,
+                        dx: 0.0,
+                        dy: 0.0,
+                    }),
+                    GestureKind::Hold => Gesture::Hold(HoldGesture {
+                        begin_time: raw.time,
+                        fingers: raw.fingers,
+                    }),
+                };
+                GestureState::Ongoing(raw.time)
+            }
+            Phase::Update => {
+                match *self {
+                    Gesture::Swipe(ref mut swipe) => {
+                        swipe.dx += raw.dx;
+                        swipe.dy += raw.dy;
+                    }
+                    Gesture::Pinch(ref mut pinch) => {
+                        pinch.dx += raw.dx;
+                        pinch.dy += raw.dy;
+                        pinch.scale = raw.scale;
+                        pinch.angle += raw.angle;
+                    }
+                    _ => log::error!("Impossible update!"),
+                }
+                GestureState::Ongoing(raw.time)
+            }
+            Phase::End => {
+                if let Gesture::Pinch(ref mut pinch) = *self {
+                    pinch.scale = raw.scale;
+                }
+                let gesture = std::mem::replace(self, Gesture::None);
+                if raw.cancelled {
+                    GestureState::Cancelled(gesture, raw.time)
+                } else {
+                    GestureState::Ended(gesture, raw.time)
+                }
+            }
         }
     }
 }
@@ -174,6 +309,29 @@ pub enum InputEvent {
 }
 
 impl InputEvent {
+    pub fn time(&self) -> u32 {
+        match self {
+            InputEvent::Ongoing(_, t) | InputEvent::Ended(_, t) | InputEvent::Cancelled(_, t) => *t,
+        }
+    }
+
+    /// Convert an event from libinput, updating the current gesture with it.
+    /// Returns `None` for events that are not about gestures
+    pub fn from_libinput(event: &input::Event, current: &mut Gesture) -> Option<Self> {
+        match event {
+            input::Event::Gesture(gest) => {
+                Some(Self::from_raw(&RawEvent::from_libinput(gest)?, current))
+            }
+            _ => None,
+        }
+    }
+
+    /// Like `from_libinput`, for an event that was already taken apart
+    pub fn from_raw(raw: &RawEvent, current: &mut Gesture) -> Self {
+        let state = current.update(raw);
+        InputEvent::from_state(state, current)
+    }
+
     pub(crate) fn from_state(state: GestureState, current: &Gesture) -> Self {
         match state {
             GestureState::Ongoing(time) => InputEvent::Ongoing(current.clone(), time),
@@ -182,3 +340,33 @@ impl InputEvent {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{GestureKind, Phase, RawEvent};
+
+    #[test]
+    fn scale_down() {
+        let mut raw = RawEvent {
+            time: 0,
+            kind: GestureKind::Pinch,
+            phase: Phase::Update,
+            fingers: 2,
+            dx: 10.0,
+            dy: -5.0,
+            scale: 4.0,
+            angle: 30.0,
+            cancelled: false,
+            start: None,
+        };
+        raw.scale_down(2.5, 2.0, 1.5);
+        assert_eq!(
+            (raw.dx, raw.dy, raw.scale, raw.angle),
+            (4.0, -2.0, 2.0, 20.0)
+        );
+        // Pinches in shrink toward 1 too
+        raw.scale = 0.25;
+        raw.scale_down(1.0, 2.0, 1.0);
+        assert_eq!(raw.scale, 0.5);
+    }
+}
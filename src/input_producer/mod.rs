@@ -2,6 +2,7 @@
 //! gesture events
 
 pub mod event;
+pub mod touch;
 
 use event::{Gesture, InputEvent};
 
@@ -11,7 +12,7 @@ use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use std::path::Path;
 
 use input::{Libinput, LibinputInterface};
-use libc::{O_RDONLY, O_RDWR, O_WRONLY};
+use libc::{O_ACCMODE, O_RDONLY, O_RDWR, O_WRONLY};
 
 /* Libinput thing */
 
@@ -23,8 +24,8 @@ impl LibinputInterface for Interface {
     fn open_restricted(&mut self, path: &Path, flags: i32) -> Result<RawFd, i32> {
         OpenOptions::new()
             .custom_flags(flags)
-            .read((flags & O_RDONLY != 0) | (flags & O_RDWR != 0))
-            .write((flags & O_WRONLY != 0) | (flags & O_RDWR != 0))
+            .read((flags & O_ACCMODE == O_RDONLY) | (flags & O_ACCMODE == O_RDWR))
+            .write((flags & O_ACCMODE == O_WRONLY) | (flags & O_ACCMODE == O_RDWR))
             .open(path)
             .map(|file| file.into_raw_fd())
             .map_err(|err| err.raw_os_error().unwrap())
@@ -64,11 +65,85 @@ impl GestureProducer {
         }
     }
 
-    fn poll_events(&mut self) {
+    /// Read what's available from the kernel. Call this when the file
+    /// descriptor is readable, and then take the events with `next_pending`
+    pub fn dispatch(&mut self) {
+        if let Err(e) = self.input.dispatch() {
+            log::error!("Can't read input events: {}", e);
+        }
+    }
+
+    /// Next libinput event that was already dispatched, of any kind
+    pub fn next_libinput(&mut self) -> Option<input::Event> {
+        self.input.next()
+    }
+
+    /// Next gesture event that was already dispatched, without waiting
+    pub fn next_pending(&mut self) -> Option<InputEvent> {
+        for event in &mut self.input {
+            if let Some(event) = InputEvent::from_libinput(&event, &mut self.current) {
+                return Some(event);
+            }
+        }
+        None
+    }
+
+    /// Whether the file descriptor has something to read, waiting for up to
+    /// `timeout` milliseconds, or forever if it's negative
+    pub fn wait(&self, timeout: i32) -> bool {
         use nix::poll::PollFlags;
-        let pollfd = nix::poll::PollFd::new(self.input.as_raw_fd(), PollFlags::POLLIN);
-        nix::poll::poll(&mut [pollfd], -1).unwrap();
-        self.input.dispatch().unwrap();
+        let mut pollfd = [nix::poll::PollFd::new(
+            self.input.as_raw_fd(),
+            PollFlags::POLLIN,
+        )];
+        loop {
+            match nix::poll::poll(&mut pollfd, timeout) {
+                Ok(n) => break n > 0,
+                Err(nix::errno::Errno::EINTR) => continue,
+                Err(e) => panic!("Waiting for input failed: {}", e),
+            }
+        }
+    }
+
+    fn poll_events(&mut self) {
+        if self.wait(-1) {
+            self.dispatch();
+        }
+    }
+}
+
+/// Whether a device name matches a pattern of the config's `device_filter`: a
+/// glob if it has `*` or `?`, otherwise a part of the name. Case is ignored
+pub fn device_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let name = name.to_lowercase();
+    if pattern.contains(['*', '?']) {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        glob(&pattern, &name)
+    } else {
+        name.contains(&pattern)
+    }
+}
+
+fn glob(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| glob(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && glob(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && glob(rest, &name[1..]),
+    }
+}
+
+impl Default for GestureProducer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsRawFd for GestureProducer {
+    fn as_raw_fd(&self) -> RawFd {
+        self.input.as_raw_fd()
     }
 }
 
@@ -76,14 +151,26 @@ impl Iterator for GestureProducer {
     type Item = InputEvent;
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            match self.input.next() {
-                Some(input::Event::Gesture(gest)) => {
-                    let state = self.current.update(&gest);
-                    break Some(InputEvent::from_state(state, &self.current));
-                }
-                Some(_) => (),
+            match self.next_pending() {
+                Some(event) => break Some(event),
                 None => self.poll_events(),
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::device_matches;
+
+    #[test]
+    fn device_patterns() {
+        let name = "SynPS/2 Synaptics TouchPad";
+        assert!(device_matches("touchpad", name));
+        assert!(device_matches("Syn*Pad", name));
+        assert!(device_matches("synps/? *", name));
+        assert!(!device_matches("Wacom", name));
+        // Globs match the whole name
+        assert!(!device_matches("Synaptics*", name));
+    }
+}
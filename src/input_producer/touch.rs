@@ -0,0 +1,221 @@
+//! Where fingers touch the touchpad, which libinput keeps to itself for
+//! gestures. The device is read a second time through its event node, next to
+//! libinput
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const SYN_REPORT: u16 = 0;
+const SYN_DROPPED: u16 = 3;
+const BTN_TOUCH: u16 = 0x14a;
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const ABS_MT_SLOT: u16 = 0x2f;
+const KEY_MAX: usize = 0x2ff;
+
+/// Keys that tell how many fingers are down, up to five, for touchpads that
+/// count more fingers than they track
+const TOOLS: [(u16, i32); 5] = [
+    (0x145, 1), // BTN_TOOL_FINGER
+    (0x14d, 2), // BTN_TOOL_DOUBLETAP
+    (0x14e, 3), // BTN_TOOL_TRIPLETAP
+    (0x14f, 4), // BTN_TOOL_QUADTAP
+    (0x148, 5), // BTN_TOOL_QUINTTAP
+];
+
+nix::ioctl_read!(abs_x_info, b'E', 0x40 + ABS_X, libc::input_absinfo);
+nix::ioctl_read!(abs_y_info, b'E', 0x40 + ABS_Y, libc::input_absinfo);
+nix::ioctl_read!(abs_slot_info, b'E', 0x40 + ABS_MT_SLOT, libc::input_absinfo);
+nix::ioctl_read_buf!(key_bits, b'E', 0x20 + EV_KEY, u8);
+
+/// Event node of a touchpad, read without blocking whenever libinput has
+/// something from it
+pub struct Touchpad {
+    file: File,
+    x_range: (i32, i32),
+    y_range: (i32, i32),
+    max_fingers: i32,
+    touches: Touches,
+}
+
+impl Touchpad {
+    /// Open the event node of the device named by libinput, like `event5`
+    pub fn open(sysname: &str) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(std::path::Path::new("/dev/input").join(sysname))?;
+        let mut x = unsafe { std::mem::zeroed::<libc::input_absinfo>() };
+        let mut y = unsafe { std::mem::zeroed::<libc::input_absinfo>() };
+        unsafe {
+            abs_x_info(file.as_raw_fd(), &mut x)?;
+            abs_y_info(file.as_raw_fd(), &mut y)?;
+        }
+        // Touchpads without slots track one finger
+        let mut slot = unsafe { std::mem::zeroed::<libc::input_absinfo>() };
+        let slots = match unsafe { abs_slot_info(file.as_raw_fd(), &mut slot) } {
+            Ok(_) => slot.maximum - slot.minimum + 1,
+            Err(_) => 1,
+        };
+        let mut keys = [0; KEY_MAX / 8 + 1];
+        unsafe { key_bits(file.as_raw_fd(), &mut keys)? };
+        Ok(Touchpad {
+            file,
+            x_range: (x.minimum, x.maximum),
+            y_range: (y.minimum, y.maximum),
+            max_fingers: max_fingers(slots, &keys),
+            touches: Touches::default(),
+        })
+    }
+
+    /// Most fingers the touchpad can tell apart, which is as many as libinput
+    /// reports gestures of
+    pub fn max_fingers(&self) -> i32 {
+        self.max_fingers
+    }
+
+    /// Take in what the device sent since the last time
+    pub fn read(&mut self) {
+        let size = std::mem::size_of::<libc::input_event>();
+        let mut buffer = vec![0; size * 64];
+        loop {
+            let read = match self.file.read(&mut buffer) {
+                Ok(0) => return,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    log::warn!("Can't read touches: {}", e);
+                    return;
+                }
+            };
+            // The kernel only hands out whole events
+            for chunk in buffer[..read].chunks_exact(size) {
+                let event: libc::input_event =
+                    unsafe { std::ptr::read_unaligned(chunk.as_ptr().cast()) };
+                self.touches.feed(event.type_, event.code, event.value);
+            }
+        }
+    }
+
+    /// Where the first finger of the latest touch landed, in fractions of the
+    /// touchpad from its top left corner
+    pub fn start(&self) -> Option<(f64, f64)> {
+        let (x, y) = self.touches.start?;
+        Some((fraction(x, self.x_range), fraction(y, self.y_range)))
+    }
+}
+
+/// The most of the fingers tracked in slots and of the ones counted by the
+/// keys the device has, from its bits of `EV_KEY`
+fn max_fingers(slots: i32, keys: &[u8]) -> i32 {
+    let has = |code: u16| {
+        let code = usize::from(code);
+        keys.get(code / 8)
+            .is_some_and(|b| b & (1 << (code % 8)) != 0)
+    };
+    TOOLS
+        .iter()
+        .filter(|(code, _)| has(*code))
+        .map(|(_, fingers)| *fingers)
+        .fold(slots, i32::max)
+}
+
+fn fraction(value: i32, (min, max): (i32, i32)) -> f64 {
+    if max <= min {
+        return 0.5;
+    }
+    (f64::from(value - min) / f64::from(max - min)).clamp(0.0, 1.0)
+}
+
+/// The position of the first finger down, which is what `ABS_X` and `ABS_Y`
+/// follow, taken when the touchpad goes from no fingers to some
+#[derive(Default, Debug)]
+struct Touches {
+    x: i32,
+    y: i32,
+    /// `BTN_TOUCH` went down in the frame being read
+    landed: bool,
+    /// Events were lost, so the frame is skipped up to the next report
+    dropped: bool,
+    start: Option<(i32, i32)>,
+}
+
+impl Touches {
+    fn feed(&mut self, kind: u16, code: u16, value: i32) {
+        if self.dropped {
+            self.dropped = !(kind == EV_SYN && code == SYN_REPORT);
+            return;
+        }
+        match (kind, code) {
+            (EV_ABS, ABS_X) => self.x = value,
+            (EV_ABS, ABS_Y) => self.y = value,
+            (EV_KEY, BTN_TOUCH) if value != 0 => self.landed = true,
+            (EV_SYN, SYN_REPORT) if self.landed => {
+                self.landed = false;
+                self.start = Some((self.x, self.y));
+            }
+            (EV_SYN, SYN_DROPPED) => {
+                self.dropped = true;
+                self.landed = false;
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn touch_start() {
+        let mut touches = Touches::default();
+        // Moving a finger before the touch doesn't count
+        touches.feed(EV_ABS, ABS_X, 500);
+        touches.feed(EV_SYN, SYN_REPORT, 0);
+        assert_eq!(touches.start, None);
+
+        touches.feed(EV_KEY, BTN_TOUCH, 1);
+        touches.feed(EV_ABS, ABS_X, 40);
+        touches.feed(EV_ABS, ABS_Y, 300);
+        touches.feed(EV_SYN, SYN_REPORT, 0);
+        touches.feed(EV_ABS, ABS_X, 200);
+        touches.feed(EV_SYN, SYN_REPORT, 0);
+        assert_eq!(touches.start, Some((40, 300)));
+
+        // Lost events leave the start alone
+        touches.feed(EV_KEY, BTN_TOUCH, 0);
+        touches.feed(EV_SYN, SYN_REPORT, 0);
+        touches.feed(EV_SYN, SYN_DROPPED, 0);
+        touches.feed(EV_KEY, BTN_TOUCH, 1);
+        touches.feed(EV_SYN, SYN_REPORT, 0);
+        assert_eq!(touches.start, Some((40, 300)));
+
+        assert_eq!(fraction(40, (0, 1000)), 0.04);
+        assert_eq!(fraction(-5, (0, 1000)), 0.0);
+    }
+
+    #[test]
+    fn finger_count() {
+        let keys = |codes: &[u16]| {
+            let mut keys = [0; KEY_MAX / 8 + 1];
+            for code in codes {
+                keys[usize::from(code / 8)] |= 1 << (code % 8);
+            }
+            keys
+        };
+        let three = keys(&[BTN_TOUCH, 0x145, 0x14d, 0x14e]);
+        assert_eq!(max_fingers(2, &three), 3);
+        // Tracking more than the keys count
+        assert_eq!(max_fingers(5, &three), 5);
+        let five = keys(&[BTN_TOUCH, 0x145, 0x14d, 0x14e, 0x14f, 0x148]);
+        assert_eq!(max_fingers(2, &five), 5);
+        assert_eq!(max_fingers(1, &[]), 1);
+    }
+}
@@ -0,0 +1,17 @@
+//! Touchpad gesture engine. The `wzmach` binary runs it as a daemon, but the
+//! engine can also be embedded: build a [`Config`], create an [`Engine`] from
+//! it, and feed it the libinput events you already receive
+
+pub mod action;
+pub mod common;
+pub mod config;
+mod dispatch;
+mod engine;
+pub mod gesture_event;
+pub mod input_producer;
+mod stats;
+mod window;
+
+pub use action::{Action, ActionError};
+pub use config::{Config, ConfigAction, ConfigTrigger};
+pub use engine::{Engine, Match};
@@ -2,21 +2,59 @@ use std::path::{Path, PathBuf};
 
 use bpaf::construct;
 
-mod action;
-mod common;
-mod config;
-mod gesture_event;
-mod input_producer;
+mod daemon;
+mod display;
+mod selftest;
+
+use display::DisplayServer;
+
+use wzmach::{config, gesture_event, input_producer};
 
 #[derive(PartialEq, Eq, Debug, Clone)]
 enum Opts {
-    Run { config_path: Option<String> },
-    DebugConfig { path: String },
+    Run {
+        config_path: Option<String>,
+        dry_run: bool,
+        record: Option<String>,
+        replay: Option<String>,
+        log_file: Option<String>,
+        log_level: Option<log::LevelFilter>,
+        display_server: Option<DisplayServer>,
+        profile: Option<String>,
+    },
+    Validate {
+        path: Option<String>,
+    },
+    Init {
+        path: Option<String>,
+    },
+    DebugConfig {
+        path: String,
+    },
     DebugGestures,
     DebugEvents,
+    Selftest,
 }
 
 fn parse_opts() -> Opts {
+    let validate = bpaf::command(
+        "validate",
+        Some("Check a config file for errors"),
+        bpaf::Info::default()
+            .descr("Check a config file, by default the one wzmach would use. Exits with an error if the config has any")
+            .for_parser(bpaf::positional("PATH").optional()),
+    )
+    .map(|path| Opts::Validate { path });
+
+    let init = bpaf::command(
+        "init",
+        Some("Write an example config to start from"),
+        bpaf::Info::default()
+            .descr("Write an example config to PATH, or print it if no PATH is given")
+            .for_parser(bpaf::positional("PATH").optional()),
+    )
+    .map(|path| Opts::Init { path });
+
     let debug_config = bpaf::command(
         "debug-config",
         Some("Parse config file and check it for errors"),
@@ -42,15 +80,66 @@ fn parse_opts() -> Opts {
             .for_parser(bpaf::Parser::pure(Opts::DebugEvents)),
     );
 
+    let selftest = bpaf::command(
+        "selftest",
+        Some("Check that gesture devices and the uinput device work"),
+        bpaf::Info::default()
+            .descr("List the input devices, and tap Shift on the uinput device to check that its keys arrive")
+            .for_parser(bpaf::Parser::pure(Opts::Selftest)),
+    );
+
     let config_path = bpaf::long("config")
         .help("Path to a config file to use instead of default")
         .argument("PATH")
         .optional();
-    let run = construct!(Opts::Run { config_path });
+    let dry_run = bpaf::long("dry-run")
+        .help("Log the actions of triggers instead of executing them")
+        .switch();
+    let record = bpaf::long("record")
+        .help("Write the gesture events from libinput to a file, to replay them later")
+        .argument("PATH")
+        .optional();
+    let replay = bpaf::long("replay")
+        .help("Run the triggers of gestures recorded with --record, instead of reading libinput")
+        .argument("PATH")
+        .optional();
+    let log_file = bpaf::long("log-file")
+        .help("Write the log to a file, rotated as it grows, instead of to stderr")
+        .argument("PATH")
+        .optional();
+    let log_level = bpaf::long("log-level")
+        .help(
+            "Log messages up to this level: error, warn, info, debug or trace. Overrides RUST_LOG",
+        )
+        .argument("LEVEL")
+        .from_str::<log::LevelFilter>()
+        .optional();
+    let display_server = bpaf::long("display-server")
+        .help("Load the triggers for x11 or wayland, instead of telling from the environment")
+        .argument("SERVER")
+        .from_str::<DisplayServer>()
+        .optional();
+    let profile = bpaf::long("profile")
+        .help("Start with the triggers of this profile of the config")
+        .argument("NAME")
+        .optional();
+    let run = construct!(Opts::Run {
+        config_path,
+        dry_run,
+        record,
+        replay,
+        log_file,
+        log_level,
+        display_server,
+        profile
+    });
 
-    let parser = debug_config
+    let parser = validate
+        .or_else(init)
+        .or_else(debug_config)
         .or_else(debug_gestures)
         .or_else(debug_events)
+        .or_else(selftest)
         .or_else(run);
 
     bpaf::Info::default()
@@ -60,10 +149,22 @@ fn parse_opts() -> Opts {
 }
 
 fn main() {
-    env_logger::init();
+    let opts = parse_opts();
+    match &opts {
+        Opts::Run {
+            log_file,
+            log_level,
+            ..
+        } => init_logging(log_file.as_deref(), *log_level),
+        _ => init_logging(None, None),
+    }
     log::trace!("initialized logging");
 
-    match parse_opts() {
+    match opts {
+        Opts::Validate { path } => validate(path),
+
+        Opts::Init { path } => init(path),
+
         Opts::DebugConfig { path } => {
             let c = config::Config::load(path);
             println!("{:?}", c);
@@ -79,51 +180,63 @@ fn main() {
             }
         }
 
-        Opts::Run { config_path } => run(config_path),
+        Opts::Selftest => {
+            if !selftest::run() {
+                std::process::exit(1);
+            }
+        }
+
+        Opts::Run {
+            config_path,
+            dry_run,
+            record,
+            replay,
+            display_server,
+            profile,
+            ..
+        } => run(
+            config_path,
+            dry_run,
+            record,
+            replay,
+            display_server,
+            profile,
+        ),
     }
 }
 
-/// Builds a [`PathBuf`] from string literals and identifiers. Slash separators
-/// are optional (`/`) between items. A beginning slash is significant as it
-/// will push `/` to the buffer first. A trailing slash at the end of an
-/// invocation is ignored. String literals are pushed into the buffer directly
-/// as path components, and identifiers are trated as environment variable
-/// names. If any of the environment variables used in the invocation
-/// are undefined, the expression will resolve to `None`.
-macro_rules! env_path {
-    (@root $buffer:ident | / $($tail:tt)*) => {
-        $buffer.push("/");
-        env_path!(@push $buffer | $($tail)*)
-    };
-    (@root $buffer:ident | $($tail:tt)*) => {
-        env_path!(@push $buffer | $($tail)*)
-    };
-    (@push $buffer:ident | $(/)?) => {};
-    (@push $buffer:ident | $(/)? $segment:literal $($tail:tt)*) => {
-        $buffer.push($segment);
-        env_path!(@push $buffer | $($tail)*)
-    };
-    (@push $buffer:ident | $(/)? $variable:ident $($tail:tt)*) => {
-        $buffer.push(std::env::var_os(stringify!($variable))?);
-        env_path!(@push $buffer | $($tail)*)
-    };
-    ($($munch:tt)*) => {(|| {
-        use std::path::PathBuf;
-        let mut buffer = PathBuf::new();
-        env_path!(@root buffer | $($munch)*);
-        Some(buffer)
-    })()};
+/// Log to stderr, or to the file, as `RUST_LOG` says unless the level is given
+fn init_logging(log_file: Option<&str>, log_level: Option<log::LevelFilter>) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if let Some(level) = log_level {
+        builder.filter_level(level);
+    }
+    if let Some(path) = log_file {
+        use daemon::log_file::{RotatingFile, KEEP, MAX_SIZE};
+        match RotatingFile::open(Path::new(path), MAX_SIZE, KEEP) {
+            Ok(file) => {
+                builder
+                    .target(env_logger::Target::Pipe(Box::new(file)))
+                    .write_style(env_logger::WriteStyle::Never);
+            }
+            Err(e) => {
+                eprintln!("Can't log to {}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    builder.init();
 }
 
-fn run(command_config: Option<String>) {
+fn run(
+    command_config: Option<String>,
+    dry_run: bool,
+    record: Option<String>,
+    replay: Option<String>,
+    display_server: Option<DisplayServer>,
+    profile: Option<String>,
+) {
     let default_config_path: &Path = Path::new("/etc/wzmach/config.ron");
-    let config_paths = &[
-        command_config.map(PathBuf::from),
-        env_path!(XDG_CONFIG_HOME / "wzmach.ron"),
-        env_path!(XDG_CONFIG_HOME / "wzmach" / "config.ron"),
-        env_path!(HOME / ".config" / "wzmach.ron"),
-        env_path!(HOME / ".config" / "wzmach" / "config.ron"),
-    ];
 
     // let is_root = nix::unistd::getuid().is_root();
 
@@ -141,33 +254,123 @@ fn run(command_config: Option<String>) {
 
     // find config path
 
-    let config_path = config_paths.iter().flatten().find(|path| path.is_file());
+    // A path that was asked for overrides the usual places
+    let config_path = command_config
+        .map(PathBuf::from)
+        .or_else(config::Config::discover);
 
     // read config
 
     // TODO: Improve logging here, this doesn't say it's using default.
-    let config = config_path
+    let mut config: config::Config = config_path
+        .as_ref()
         .map(config::Config::load)
-        .and_then(Result::ok)
+        .and_then(|c| c.map_err(|e| log::error!("Can't load config: {}", e)).ok())
         .unwrap_or_default();
+    if let Some(profile) = profile {
+        if !config.profiles.contains_key(&profile) {
+            eprintln!("The config has no profile named {:?}", profile);
+            std::process::exit(1);
+        }
+        config.profile = Some(profile);
+    }
 
-    let is_wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let is_wayland = DisplayServer::choose(display_server).is_wayland();
 
-    // run
+    if let Some(path) = replay {
+        let events = std::fs::File::open(&path)
+            .map(std::io::BufReader::new)
+            .and_then(input_producer::event::RawEvent::read_recording);
+        match events {
+            Ok(events) => daemon::replay(config, is_wayland, dry_run, &events),
+            Err(e) => {
+                eprintln!("Can't replay {}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-    let (triggers, mut actions) = config.make_triggers(is_wayland);
+    // Written line by line, so that a recording is complete up to a crash
+    let recording = record.map(|path| match std::fs::File::create(&path) {
+        Ok(file) => {
+            log::info!("Recording gestures to {}", path);
+            std::io::LineWriter::new(file)
+        }
+        Err(e) => {
+            eprintln!("Can't record to {}: {}", path, e);
+            std::process::exit(1);
+        }
+    });
+
+    // run
 
     log::info!("Starting up");
-    let producer = input_producer::GestureProducer::new();
-    log::debug!("Created input connection");
-    let events = gesture_event::EventAdapter::new(producer, &triggers);
-    for action_inds in events {
-        for index in action_inds {
-            match actions[index].execute() {
-                Ok(()) => (),
-                Err(action::ActionError(msg)) => log::error!("{}", msg),
+    daemon::Daemon::new(config_path, config, is_wayland, dry_run, recording).run();
+}
+
+/// Load the config without touching any devices, and report what's wrong
+fn validate(path: Option<String>) {
+    // A path that was asked for is checked even if it doesn't exist
+    let path = match path.map(PathBuf::from).or_else(config::Config::discover) {
+        Some(p) => p,
+        None => {
+            eprintln!("No config file found");
+            // Like a config path that doesn't exist
+            std::process::exit(2);
+        }
+    };
+    match config::Config::load(&path) {
+        Ok(c) => {
+            println!(
+                "{} is valid, with {} triggers",
+                path.display(),
+                c.global_triggers.len()
+                    + c.x11_triggers.len()
+                    + c.wayland_triggers.len()
+                    + c.profiles.values().map(Vec::len).sum::<usize>()
+            );
+            // Counted as the triggers load in each display server
+            for server in [DisplayServer::X11, DisplayServer::Wayland] {
+                for warning in c.overlap_warnings(server.is_wayland(), c.profile.as_deref()) {
+                    println!("Warning in {}: {}", server, warning);
+                }
             }
         }
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), e);
+            // Apart from errors in the config, for scripts to tell
+            std::process::exit(if e.is_not_found() { 2 } else { 1 });
+        }
+    }
+}
+
+/// Print the example config, or write it to a new file
+fn init(path: Option<String>) {
+    let example = config::example();
+    let path = match path {
+        Some(p) => PathBuf::from(p),
+        None => {
+            print!("{}", example);
+            return;
+        }
+    };
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+        })
+        .and_then(|mut file| std::io::Write::write_all(&mut file, example.as_bytes()));
+    match written {
+        Ok(()) => println!("Wrote an example config to {}", path.display()),
+        Err(e) => {
+            eprintln!("Can't write {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
     }
 }
 
@@ -176,91 +379,198 @@ fn debug_events() {
     log::debug!("Created input connection");
     let triggers = {
         let mut ts = Vec::new();
-        use common::{Direction, PinchDirection, RotateDirection};
         use gesture_event::trigger::*;
+        use wzmach::common::{Direction, FireOn, PinchDirection, RotateDirection};
         for fingers in 2..5 {
             for repeated in [false, true] {
                 ts.push(Trigger::Swipe(CardinalTrigger {
                     fingers,
                     direction: Direction::Up,
                     distance: 100.0,
+                    angle_tolerance: 20.0,
+                    min_velocity: None,
+                    max_velocity: None,
+                    min_duration: None,
                     repeated,
+                    continuous: false,
+                    cooldown: 0,
+                    fire_on: FireOn::Threshold,
+                    cancel: None,
+                    repeat: None,
+                    edge: None,
                 }));
                 ts.push(Trigger::Swipe(CardinalTrigger {
                     fingers,
                     direction: Direction::Down,
                     distance: 100.0,
+                    angle_tolerance: 20.0,
+                    min_velocity: None,
+                    max_velocity: None,
+                    min_duration: None,
                     repeated,
+                    continuous: false,
+                    cooldown: 0,
+                    fire_on: FireOn::Threshold,
+                    cancel: None,
+                    repeat: None,
+                    edge: None,
                 }));
                 ts.push(Trigger::Swipe(CardinalTrigger {
                     fingers,
                     direction: Direction::Left,
                     distance: 100.0,
+                    angle_tolerance: 20.0,
+                    min_velocity: None,
+                    max_velocity: None,
+                    min_duration: None,
                     repeated,
+                    continuous: false,
+                    cooldown: 0,
+                    fire_on: FireOn::Threshold,
+                    cancel: None,
+                    repeat: None,
+                    edge: None,
                 }));
                 ts.push(Trigger::Swipe(CardinalTrigger {
                     fingers,
                     direction: Direction::Right,
                     distance: 100.0,
+                    angle_tolerance: 20.0,
+                    min_velocity: None,
+                    max_velocity: None,
+                    min_duration: None,
                     repeated,
+                    continuous: false,
+                    cooldown: 0,
+                    fire_on: FireOn::Threshold,
+                    cancel: None,
+                    repeat: None,
+                    edge: None,
                 }));
                 ts.push(Trigger::Pinch(PinchTrigger {
                     fingers,
                     direction: PinchDirection::In,
                     scale: 1.3,
                     repeated,
+                    continuous: false,
+                    cooldown: 0,
+                    fire_on: FireOn::Threshold,
+                    cancel: None,
+                    repeat: None,
                 }));
                 ts.push(Trigger::Pinch(PinchTrigger {
                     fingers,
                     direction: PinchDirection::Out,
                     scale: 1.3,
                     repeated,
+                    continuous: false,
+                    cooldown: 0,
+                    fire_on: FireOn::Threshold,
+                    cancel: None,
+                    repeat: None,
                 }));
                 ts.push(Trigger::Shear(CardinalTrigger {
                     fingers,
                     direction: Direction::Up,
                     distance: 100.0,
+                    angle_tolerance: 20.0,
+                    min_velocity: None,
+                    max_velocity: None,
+                    min_duration: None,
                     repeated,
+                    continuous: false,
+                    cooldown: 0,
+                    fire_on: FireOn::Threshold,
+                    cancel: None,
+                    repeat: None,
+                    edge: None,
                 }));
                 ts.push(Trigger::Shear(CardinalTrigger {
                     fingers,
                     direction: Direction::Down,
                     distance: 100.0,
+                    angle_tolerance: 20.0,
+                    min_velocity: None,
+                    max_velocity: None,
+                    min_duration: None,
                     repeated,
+                    continuous: false,
+                    cooldown: 0,
+                    fire_on: FireOn::Threshold,
+                    cancel: None,
+                    repeat: None,
+                    edge: None,
                 }));
                 ts.push(Trigger::Shear(CardinalTrigger {
                     fingers,
                     direction: Direction::Left,
                     distance: 100.0,
+                    angle_tolerance: 20.0,
+                    min_velocity: None,
+                    max_velocity: None,
+                    min_duration: None,
                     repeated,
+                    continuous: false,
+                    cooldown: 0,
+                    fire_on: FireOn::Threshold,
+                    cancel: None,
+                    repeat: None,
+                    edge: None,
                 }));
                 ts.push(Trigger::Shear(CardinalTrigger {
                     fingers,
                     direction: Direction::Right,
                     distance: 100.0,
+                    angle_tolerance: 20.0,
+                    min_velocity: None,
+                    max_velocity: None,
+                    min_duration: None,
                     repeated,
+                    continuous: false,
+                    cooldown: 0,
+                    fire_on: FireOn::Threshold,
+                    cancel: None,
+                    repeat: None,
+                    edge: None,
                 }));
                 ts.push(Trigger::Rotate(RotateTrigger {
                     fingers,
                     direction: RotateDirection::Anticlockwise,
                     distance: 45.0,
                     repeated,
+                    continuous: false,
+                    cooldown: 0,
+                    fire_on: FireOn::Threshold,
+                    cancel: None,
+                    repeat: None,
                 }));
                 ts.push(Trigger::Rotate(RotateTrigger {
                     fingers,
                     direction: RotateDirection::Clockwise,
                     distance: 45.0,
                     repeated,
+                    continuous: false,
+                    cooldown: 0,
+                    fire_on: FireOn::Threshold,
+                    cancel: None,
+                    repeat: None,
                 }));
             }
-            ts.push(Trigger::Hold(HoldTrigger { fingers, time: 50 }));
+            ts.push(Trigger::Hold(HoldTrigger {
+                fingers,
+                time: 50,
+                cooldown: 0,
+                fire_on: FireOn::Threshold,
+            }));
         }
         ts
     };
     let events = gesture_event::EventAdapter::new(producer, &triggers);
     for event in events {
-        for i in event {
-            log::debug!("triggered: {:?}", triggers[i]);
+        for e in event {
+            if let gesture_event::TriggerEvent::Fired(i) = e {
+                log::debug!("triggered: {:?}", triggers[i]);
+            }
         }
     }
 }
@@ -0,0 +1,131 @@
+//! Checks that what wzmach needs works in this session, for when gestures
+//! silently do nothing
+
+#[cfg(feature = "uinput")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "uinput")]
+use input::event::keyboard::KeyboardEventTrait;
+#[cfg(feature = "uinput")]
+use input::event::KeyboardEvent;
+use input::event::{DeviceEvent, EventTrait};
+use input::DeviceCapability;
+
+#[cfg(feature = "uinput")]
+use wzmach::common::DeviceId;
+use wzmach::input_producer::GestureProducer;
+
+/// How long to wait for libinput to see the uinput device and its keys
+#[cfg(feature = "uinput")]
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Print what was found, and whether the uinput device works. Returns whether
+/// everything does
+pub fn run() -> bool {
+    let (display, why) = crate::display::detect(|name| std::env::var(name).ok());
+    println!("Display server: {}, as {}", display, why);
+
+    let mut producer = GestureProducer::new();
+    let mut gestures = 0;
+    for (name, gesture) in devices(&mut producer) {
+        if gesture {
+            gestures += 1;
+            println!("Device: {} (gestures)", name);
+        } else {
+            println!("Device: {}", name);
+        }
+    }
+    let mut ok = true;
+    if gestures == 0 {
+        println!("No gesture devices. Is your user in the input group?");
+        ok = false;
+    }
+    uinput(&mut producer) && ok
+}
+
+/// Names of the devices libinput has, and whether each has gestures
+fn devices(producer: &mut GestureProducer) -> Vec<(String, bool)> {
+    producer.dispatch();
+    let mut devices = Vec::new();
+    while let Some(event) = producer.next_libinput() {
+        if let input::Event::Device(DeviceEvent::Added(_)) = &event {
+            let device = event.device();
+            devices.push((
+                device.name().to_string(),
+                device.has_capability(DeviceCapability::Gesture),
+            ));
+        }
+    }
+    devices
+}
+
+#[cfg(not(feature = "uinput"))]
+fn uinput(_producer: &mut GestureProducer) -> bool {
+    println!("Built without uinput, mouse actions only log, and keyboard actions too unless they go through the wayland virtual keyboard");
+    true
+}
+
+/// Create the device and tap Shift on it, which does nothing by itself, and
+/// look for the tap in libinput
+#[cfg(feature = "uinput")]
+fn uinput(producer: &mut GestureProducer) -> bool {
+    // Named like by default, whatever the config names its own
+    let id = DeviceId::default();
+    let device = match wzmach::action::KeyboardInputAction::default_device(&id) {
+        Ok(d) => d,
+        Err(e) => {
+            println!("Can't create the uinput device: {}", e);
+            println!("Is the uinput module loaded, and can your user write /dev/uinput?");
+            return false;
+        }
+    };
+    println!("Created the uinput device");
+
+    if !wait_for(producer, |event| {
+        matches!(event, input::Event::Device(DeviceEvent::Added(_)))
+            && event.device().name() == id.name
+    }) {
+        println!("libinput doesn't see the uinput device");
+        return false;
+    }
+    let key = wzmach::config::ConfigKey::from_name("LeftShift").unwrap();
+    let (shift, code) = (key.0, key.code());
+    let sent = (|| {
+        let mut device = device.borrow_mut();
+        device.press(&shift)?;
+        device.synchronize()?;
+        device.release(&shift)?;
+        device.synchronize()
+    })();
+    if let Err(e) = sent {
+        println!("Can't send a key on the uinput device: {}", e);
+        return false;
+    }
+    if !wait_for(producer, |event| {
+        matches!(event, input::Event::Keyboard(KeyboardEvent::Key(k)) if k.key() == code)
+            && event.device().name() == id.name
+    }) {
+        println!("Sent a key on the uinput device, but it didn't arrive");
+        return false;
+    }
+    println!("Sent a key on the uinput device, and it arrived");
+    true
+}
+
+/// Read libinput until an event is like that, giving up after `TIMEOUT`
+#[cfg(feature = "uinput")]
+fn wait_for(producer: &mut GestureProducer, like: impl Fn(&input::Event) -> bool) -> bool {
+    let start = Instant::now();
+    while let Some(left) = TIMEOUT.checked_sub(start.elapsed()) {
+        if !producer.wait(left.as_millis() as i32) {
+            break;
+        }
+        producer.dispatch();
+        while let Some(event) = producer.next_libinput() {
+            if like(&event) {
+                return true;
+            }
+        }
+    }
+    false
+}
@@ -0,0 +1,119 @@
+//! Counts of the triggers that ran and the gestures that were made, to tell
+//! which parts of a config are used
+
+use std::collections::BTreeMap;
+
+use crate::gesture_event::trigger::Trigger;
+use crate::input_producer::event::Gesture;
+
+#[derive(Default, Debug)]
+pub(crate) struct Stats {
+    /// Times each loaded trigger ran its action, by its index
+    runs: Vec<u64>,
+    /// Gestures made, by kind and fingers
+    gestures: BTreeMap<(&'static str, i32), u64>,
+}
+
+impl Stats {
+    pub(crate) fn ran(&mut self, index: usize) {
+        if self.runs.len() <= index {
+            self.runs.resize(index + 1, 0);
+        }
+        self.runs[index] += 1;
+    }
+
+    pub(crate) fn gesture(&mut self, gesture: &Gesture) {
+        let key = match gesture {
+            Gesture::None => return,
+            Gesture::Swipe(s) => ("swipes", s.fingers),
+            Gesture::Pinch(p) => ("pinches", p.fingers),
+            Gesture::Hold(h) => ("holds", h.fingers),
+        };
+        *self.gestures.entry(key).or_default() += 1;
+    }
+
+    /// Keep the counts of the triggers that the new config has too
+    pub(crate) fn reload(&mut self, old: &[Trigger], new: &[Trigger]) {
+        let mut taken = vec![false; old.len()];
+        self.runs = new
+            .iter()
+            .map(|trigger| {
+                let same = (0..old.len()).find(|&i| !taken[i] && old[i] == *trigger);
+                same.map_or(0, |i| {
+                    taken[i] = true;
+                    self.runs.get(i).copied().unwrap_or(0)
+                })
+            })
+            .collect();
+    }
+
+    /// Lines describing the counts, with the triggers that never ran too
+    pub(crate) fn report(&self, triggers: &[Trigger]) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .gestures
+            .iter()
+            .map(|((kind, fingers), n)| format!("{} finger {}: {}", fingers, kind, n))
+            .collect();
+        if lines.is_empty() {
+            lines.push("No gestures".to_string());
+        }
+        for (index, trigger) in triggers.iter().enumerate() {
+            let runs = self.runs.get(index).copied().unwrap_or(0);
+            let direction = match trigger.direction() {
+                Some(d) => format!(" {}", d.name()),
+                None => String::new(),
+            };
+            lines.push(format!(
+                "Trigger {}, {} finger {}{}: ran {} times",
+                index,
+                trigger.fingers(),
+                trigger.name(),
+                direction,
+                runs
+            ));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Stats;
+    use crate::common::FireOn;
+    use crate::gesture_event::trigger::{HoldTrigger, Trigger};
+    use crate::input_producer::event::{Gesture, HoldGesture};
+
+    fn hold(fingers: i32) -> Trigger {
+        Trigger::Hold(HoldTrigger {
+            fingers,
+            time: 0,
+            cooldown: 0,
+            fire_on: FireOn::Threshold,
+        })
+    }
+
+    #[test]
+    fn counts() {
+        let mut stats = Stats::default();
+        let triggers = [hold(3), hold(4)];
+        let gesture = Gesture::Hold(HoldGesture {
+            begin_time: 0,
+            fingers: 3,
+        });
+        stats.gesture(&gesture);
+        stats.gesture(&gesture);
+        stats.ran(0);
+        assert_eq!(
+            stats.report(&triggers),
+            [
+                "3 finger holds: 2",
+                "Trigger 0, 3 finger Hold: ran 1 times",
+                "Trigger 1, 4 finger Hold: ran 0 times",
+            ]
+        );
+
+        // Counts follow the triggers to their new places
+        stats.reload(&triggers, &[hold(5), hold(3)]);
+        assert_eq!(stats.runs, [0, 1]);
+    }
+}
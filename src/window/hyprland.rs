@@ -0,0 +1,177 @@
+use super::{ActiveWindow, WindowSource};
+
+use serde::Deserialize;
+
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Hyprland answers right away. This is against a socket that nothing answers
+/// on
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Window information from Hyprland, over its IPC socket
+pub struct HyprlandWindows {
+    socket: PathBuf,
+}
+
+impl HyprlandWindows {
+    /// Find the socket of the Hyprland instance wzmach runs in
+    pub fn connect() -> io::Result<Self> {
+        Ok(HyprlandWindows { socket: socket()? })
+    }
+}
+
+impl WindowSource for HyprlandWindows {
+    fn active_window(&mut self) -> Option<ActiveWindow> {
+        match request_at(&self.socket, "j/activewindow") {
+            Ok(reply) => {
+                let window = active_window(&reply);
+                log::trace!("Active window: {:?}", window);
+                window
+            }
+            Err(e) => {
+                log::warn!("Can't query active window: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Send a request, like `dispatch workspace 2`, to the running Hyprland, and
+/// return the reply
+pub fn request(message: &str) -> io::Result<String> {
+    request_at(&socket()?, message)
+}
+
+fn request_at(socket: &Path, message: &str) -> io::Result<String> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    stream.write_all(message.as_bytes())?;
+    // Hyprland closes the connection after the reply
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(reply)
+}
+
+/// The socket is in the runtime directory, or in `/tmp` before Hyprland 0.40
+fn socket() -> io::Result<PathBuf> {
+    let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE").map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "HYPRLAND_INSTANCE_SIGNATURE isn't set, is Hyprland running?",
+        )
+    })?;
+    let runtime = std::env::var_os("XDG_RUNTIME_DIR").map(|dir| {
+        Path::new(&dir)
+            .join("hypr")
+            .join(&signature)
+            .join(".socket.sock")
+    });
+    let legacy = Path::new("/tmp/hypr").join(&signature).join(".socket.sock");
+    Ok(match runtime {
+        Some(path) if path.exists() || !legacy.exists() => path,
+        _ => legacy,
+    })
+}
+
+/// The fields of the reply to `j/activewindow` that are used
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Window {
+    class: String,
+    initial_class: String,
+    /// A boolean in older versions, and since then the fullscreen mode, which
+    /// is 0 for none
+    fullscreen: serde_json::Value,
+}
+
+/// Read the reply to `j/activewindow`, which is `{}` without a focused window
+fn active_window(json: &str) -> Option<ActiveWindow> {
+    let window: Window = match serde_json::from_str(json) {
+        Ok(w) => w,
+        Err(e) => {
+            log::warn!("Unexpected reply for the active window {:?}: {}", json, e);
+            return None;
+        }
+    };
+    let mut class = Vec::new();
+    for c in [window.class, window.initial_class] {
+        if !c.is_empty() && !class.contains(&c) {
+            class.push(c);
+        }
+    }
+    if class.is_empty() {
+        return None;
+    }
+    let fullscreen = match window.fullscreen {
+        serde_json::Value::Bool(b) => b,
+        serde_json::Value::Number(n) => n.as_u64() != Some(0),
+        _ => false,
+    };
+    Some(ActiveWindow { class, fullscreen })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{active_window, request_at};
+    use crate::window::ActiveWindow;
+
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn reads_active_window() {
+        let reply = r#"{
+    "address": "0x5581c0d7e0a0",
+    "workspace": {
+        "id": 1,
+        "name": "1"
+    },
+    "fullscreen": 0,
+    "class": "org.wezfurlong.wezterm",
+    "title": "vim \"notes\"",
+    "initialClass": "org.wezfurlong.wezterm",
+    "initialTitle": "wezterm"
+}"#;
+        assert_eq!(
+            active_window(reply),
+            Some(ActiveWindow {
+                class: vec!["org.wezfurlong.wezterm".to_string()],
+                fullscreen: false,
+            })
+        );
+        let reply = r#"{"class": "Fire\"foxé", "fullscreen": true, "initialClass": "firefox"}"#;
+        assert_eq!(
+            active_window(reply),
+            Some(ActiveWindow {
+                class: vec!["Fire\"foxé".to_string(), "firefox".to_string()],
+                fullscreen: true,
+            })
+        );
+        assert_eq!(active_window("{}"), None);
+        assert_eq!(active_window("ok"), None);
+    }
+
+    #[test]
+    fn sends_request() {
+        let dir = std::env::temp_dir().join(format!("wzmach-hypr-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(".socket.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let hyprland = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = vec![0; "dispatch workspace 2".len()];
+            stream.read_exact(&mut received).unwrap();
+            stream.write_all(b"ok").unwrap();
+            received
+        });
+
+        assert_eq!(request_at(&path, "dispatch workspace 2").unwrap(), "ok");
+        assert_eq!(hyprland.join().unwrap(), b"dispatch workspace 2");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
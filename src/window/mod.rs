@@ -0,0 +1,101 @@
+//! Information about the window the user is currently working in, so that
+//! triggers can be scoped to applications
+
+mod hyprland;
+mod x11;
+
+pub(crate) use self::hyprland::request as hyprland_request;
+pub use self::hyprland::HyprlandWindows;
+pub use self::x11::X11Windows;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::common::Region;
+
+/// The focused window, as far as triggers care
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct ActiveWindow {
+    /// Class names of the window. For X11 these are both parts of `WM_CLASS`:
+    /// the instance name and the class name
+    pub class: Vec<String>,
+    pub fullscreen: bool,
+}
+
+/// Where the pointer is, in screen coordinates
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Pointer {
+    pub x: i32,
+    pub y: i32,
+    /// Index of the monitor the pointer is on
+    pub monitor: Option<usize>,
+}
+
+impl Pointer {
+    pub fn within(&self, region: &Region) -> bool {
+        match *region {
+            Region::Monitor(index) => self.monitor == Some(index),
+            Region::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                (x..x.saturating_add_unsigned(width)).contains(&self.x)
+                    && (y..y.saturating_add_unsigned(height)).contains(&self.y)
+            }
+        }
+    }
+}
+
+/// Something that can tell what window is focused right now
+pub trait WindowSource {
+    /// Returns `None` when no window is focused or it can't be determined
+    fn active_window(&mut self) -> Option<ActiveWindow>;
+
+    /// Returns `None` when the pointer position can't be determined
+    fn pointer(&mut self) -> Option<Pointer> {
+        None
+    }
+}
+
+/// Window source shared by the dispatcher and the actions that check windows
+pub type Windows = Rc<RefCell<dyn WindowSource>>;
+
+/// Create a window source for the current display server. Returns `None` when
+/// there is no way to query windows, for example on wayland other than
+/// Hyprland
+pub fn detect(is_wayland: bool) -> Option<Windows> {
+    if is_wayland {
+        if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_none() {
+            log::debug!("No window information on wayland");
+            return None;
+        }
+        return match HyprlandWindows::connect() {
+            Ok(h) => {
+                log::debug!("Using Hyprland for window information");
+                Some(Rc::new(RefCell::new(h)))
+            }
+            Err(e) => {
+                log::warn!(
+                    "Can't find Hyprland, window-scoped triggers disabled: {}",
+                    e
+                );
+                None
+            }
+        };
+    }
+    match X11Windows::connect() {
+        Ok(x) => {
+            log::debug!("Connected to X11 for window information");
+            Some(Rc::new(RefCell::new(x)))
+        }
+        Err(e) => {
+            log::warn!(
+                "Can't connect to X11, window-scoped triggers disabled: {}",
+                e
+            );
+            None
+        }
+    }
+}
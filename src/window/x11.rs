@@ -0,0 +1,274 @@
+use super::{ActiveWindow, Pointer, WindowSource};
+use crate::common::WindowTarget;
+
+use thiserror::Error;
+use x11rb::connection::Connection;
+use x11rb::errors::{ConnectError, ConnectionError, ReplyError};
+use x11rb::protocol::randr::ConnectionExt as _;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ClientMessageEvent, ConnectionExt, EventMask, Window,
+};
+use x11rb::protocol::xproto::{KeyPressEvent, KEY_PRESS_EVENT, KEY_RELEASE_EVENT};
+use x11rb::rust_connection::RustConnection;
+
+#[derive(Error, Debug)]
+pub enum X11Error {
+    #[error(transparent)]
+    Connect(#[from] ConnectError),
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+    #[error(transparent)]
+    Reply(#[from] ReplyError),
+}
+
+/// Window information from an X11 server with an EWMH-compliant window manager
+pub struct X11Windows {
+    conn: RustConnection,
+    root: Window,
+    net_active_window: Atom,
+    net_wm_state: Atom,
+    net_wm_state_fullscreen: Atom,
+    net_current_desktop: Atom,
+    net_number_of_desktops: Atom,
+}
+
+impl X11Windows {
+    /// Connect to the display in `$DISPLAY`
+    pub fn connect() -> Result<Self, X11Error> {
+        let (conn, screen) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen].root;
+        let atom = |name: &[u8]| -> Result<Atom, X11Error> {
+            Ok(conn.intern_atom(false, name)?.reply()?.atom)
+        };
+        let net_active_window = atom(b"_NET_ACTIVE_WINDOW")?;
+        let net_wm_state = atom(b"_NET_WM_STATE")?;
+        let net_wm_state_fullscreen = atom(b"_NET_WM_STATE_FULLSCREEN")?;
+        let net_current_desktop = atom(b"_NET_CURRENT_DESKTOP")?;
+        let net_number_of_desktops = atom(b"_NET_NUMBER_OF_DESKTOPS")?;
+        Ok(X11Windows {
+            conn,
+            root,
+            net_active_window,
+            net_wm_state,
+            net_wm_state_fullscreen,
+            net_current_desktop,
+            net_number_of_desktops,
+        })
+    }
+
+    fn atom(&self, name: &[u8]) -> Result<Atom, X11Error> {
+        Ok(self.conn.intern_atom(false, name)?.reply()?.atom)
+    }
+
+    /// The first of the windows the window manager lists that is like the
+    /// target
+    pub fn find(&self, target: &WindowTarget) -> Result<Option<Window>, X11Error> {
+        let client_list = self.atom(b"_NET_CLIENT_LIST")?;
+        let reply = self
+            .conn
+            .get_property(
+                false,
+                self.root,
+                client_list,
+                AtomEnum::WINDOW,
+                0,
+                u32::MAX / 4,
+            )?
+            .reply()?;
+        for window in reply.value32().into_iter().flatten() {
+            if target.matches(&self.window_class(window)?, &self.title(window)?) {
+                return Ok(Some(window));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Send a key event straight to the window, whether it has the focus or
+    /// not. The key code is X11's, and `state` has the modifiers held
+    pub fn send_key(
+        &self,
+        window: Window,
+        code: u8,
+        state: u16,
+        pressed: bool,
+    ) -> Result<(), X11Error> {
+        let event = KeyPressEvent {
+            response_type: if pressed {
+                KEY_PRESS_EVENT
+            } else {
+                KEY_RELEASE_EVENT
+            },
+            detail: code,
+            sequence: 0,
+            time: x11rb::CURRENT_TIME,
+            root: self.root,
+            event: window,
+            child: x11rb::NONE,
+            root_x: 0,
+            root_y: 0,
+            event_x: 0,
+            event_y: 0,
+            state,
+            same_screen: true,
+        };
+        let mask = if pressed {
+            EventMask::KEY_PRESS
+        } else {
+            EventMask::KEY_RELEASE
+        };
+        self.conn.send_event(true, window, mask, event)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// The current desktop and how many there are. Both are 0 when the window
+    /// manager doesn't say
+    pub fn desktops(&self) -> Result<(u32, u32), X11Error> {
+        let cardinal = |property| -> Result<u32, X11Error> {
+            let reply = self
+                .conn
+                .get_property(false, self.root, property, AtomEnum::CARDINAL, 0, 1)?
+                .reply()?;
+            Ok(reply.value32().and_then(|mut v| v.next()).unwrap_or(0))
+        };
+        Ok((
+            cardinal(self.net_current_desktop)?,
+            cardinal(self.net_number_of_desktops)?,
+        ))
+    }
+
+    /// Ask the window manager to switch to the desktop, counting from 0
+    pub fn set_desktop(&self, desktop: u32) -> Result<(), X11Error> {
+        // The timestamp is unknown, which window managers accept
+        let event = ClientMessageEvent::new(
+            32,
+            self.root,
+            self.net_current_desktop,
+            [desktop, x11rb::CURRENT_TIME, 0, 0, 0],
+        );
+        self.conn.send_event(
+            false,
+            self.root,
+            EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+            event,
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn active(&self) -> Result<Option<Window>, X11Error> {
+        let reply = self
+            .conn
+            .get_property(
+                false,
+                self.root,
+                self.net_active_window,
+                AtomEnum::WINDOW,
+                0,
+                1,
+            )?
+            .reply()?;
+        Ok(reply
+            .value32()
+            .and_then(|mut v| v.next())
+            .filter(|w| *w != x11rb::NONE))
+    }
+
+    fn window_class(&self, window: Window) -> Result<Vec<String>, X11Error> {
+        let reply = self
+            .conn
+            .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)?
+            .reply()?;
+        // WM_CLASS is two null-terminated strings: instance and class
+        Ok(reply
+            .value
+            .split(|b| *b == 0)
+            .filter(|s| !s.is_empty())
+            .map(|s| String::from_utf8_lossy(s).into_owned())
+            .collect())
+    }
+
+    /// `_NET_WM_NAME` in UTF-8, or else the older `WM_NAME`
+    fn title(&self, window: Window) -> Result<String, X11Error> {
+        for (property, kind) in [
+            (self.atom(b"_NET_WM_NAME")?, self.atom(b"UTF8_STRING")?),
+            (AtomEnum::WM_NAME.into(), AtomEnum::STRING.into()),
+        ] {
+            let reply = self
+                .conn
+                .get_property(false, window, property, kind, 0, 256)?
+                .reply()?;
+            if !reply.value.is_empty() {
+                return Ok(String::from_utf8_lossy(&reply.value).into_owned());
+            }
+        }
+        Ok(String::new())
+    }
+
+    fn fullscreen(&self, window: Window) -> Result<bool, X11Error> {
+        let reply = self
+            .conn
+            .get_property(false, window, self.net_wm_state, AtomEnum::ATOM, 0, 32)?
+            .reply()?;
+        Ok(reply
+            .value32()
+            .is_some_and(|mut states| states.any(|s| s == self.net_wm_state_fullscreen)))
+    }
+
+    /// The monitor containing the point. Without RandR the monitors are
+    /// unknown, and so is this
+    fn monitor_at(&self, x: i32, y: i32) -> Result<Option<usize>, X11Error> {
+        let reply = self.conn.randr_get_monitors(self.root, true)?.reply()?;
+        Ok(reply.monitors.iter().position(|m| {
+            (m.x.into()..i32::from(m.x) + i32::from(m.width)).contains(&x)
+                && (m.y.into()..i32::from(m.y) + i32::from(m.height)).contains(&y)
+        }))
+    }
+
+    fn window(&self, window: Window) -> Result<ActiveWindow, X11Error> {
+        Ok(ActiveWindow {
+            class: self.window_class(window)?,
+            fullscreen: self.fullscreen(window)?,
+        })
+    }
+}
+
+impl WindowSource for X11Windows {
+    fn active_window(&mut self) -> Option<ActiveWindow> {
+        let window = self
+            .active()
+            .and_then(|w| w.map(|w| self.window(w)).transpose());
+        match window {
+            Ok(window) => {
+                log::trace!("Active window: {:?}", window);
+                window
+            }
+            Err(e) => {
+                log::warn!("Can't query active window: {}", e);
+                None
+            }
+        }
+    }
+
+    fn pointer(&mut self) -> Option<Pointer> {
+        let reply = match self.conn.query_pointer(self.root) {
+            Ok(cookie) => cookie.reply().map_err(X11Error::from),
+            Err(e) => Err(e.into()),
+        };
+        let (x, y) = match reply {
+            Ok(r) => (r.root_x.into(), r.root_y.into()),
+            Err(e) => {
+                log::warn!("Can't query pointer: {}", e);
+                return None;
+            }
+        };
+        let monitor = self
+            .monitor_at(x, y)
+            .map_err(|e| log::warn!("Can't query monitors: {}", e))
+            .ok()
+            .flatten();
+        let pointer = Pointer { x, y, monitor };
+        log::trace!("Pointer: {:?}", pointer);
+        Some(pointer)
+    }
+}
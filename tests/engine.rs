@@ -0,0 +1,685 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use wzmach::action::{MockCall, VirtualDevice};
+use wzmach::common::{AnyDirection, Direction, FireOn, OnError};
+use wzmach::config::{key_code, ConfigKey, Trigger};
+use wzmach::input_producer::event::{
+    Gesture, GestureKind, InputEvent, Phase, RawEvent, SwipeGesture,
+};
+use wzmach::{Config, ConfigAction, ConfigTrigger, Engine, Match};
+
+fn swipe(dx: f64, dy: f64) -> Gesture {
+    Gesture::Swipe(SwipeGesture {
+        begin_time: 0,
+        fingers: 3,
+        dx,
+        dy,
+        start: None,
+    })
+}
+
+#[test]
+fn swipe_runs_command() {
+    let marker = std::env::temp_dir().join(format!("wzmach-engine-{}", std::process::id()));
+    let _ = std::fs::remove_file(&marker);
+
+    let swipe_up = Trigger::Swipe {
+        fingers: 3,
+        direction: Direction::Up,
+        repeated: false,
+        continuous: false,
+        distance: None,
+        min_velocity: None,
+        max_velocity: None,
+        min_duration_ms: None,
+        edge: None,
+    };
+    let config = Config {
+        global_triggers: vec![
+            ConfigTrigger {
+                trigger: swipe_up.clone(),
+                action: ConfigAction::ExecuteCommand {
+                    path: "touch".to_string(),
+                    args: vec![marker.to_string_lossy().into_owned()],
+                    cwd: None,
+                    env: None,
+                    timeout_ms: None,
+                    gesture_env: false,
+                },
+                window_class: None,
+                cooldown_ms: 0,
+                repeat_ms: None,
+                disable_on_fullscreen: false,
+                region: None,
+                fire_on: FireOn::Threshold,
+                cancel_threshold: None,
+                priority: 0,
+                require_modifier: None,
+                enabled: true,
+                on_error: OnError::Log,
+                fallback: false,
+                from_turn: false,
+                keys: None,
+            },
+            // Left out, as there's no input device to press keys on. Or only
+            // logged when built without uinput
+            ConfigTrigger {
+                trigger: swipe_up,
+                action: ConfigAction::KeyboardInput {
+                    modifiers: Vec::new(),
+                    sequence: vec![ConfigKey::from_name("T").unwrap()],
+                    keys: None,
+                    key_delay_ms: 0,
+                    repeat: 1,
+                    chord: false,
+                    mode: None,
+                    target: None,
+                },
+                window_class: None,
+                cooldown_ms: 0,
+                repeat_ms: None,
+                disable_on_fullscreen: false,
+                region: None,
+                fire_on: FireOn::Threshold,
+                cancel_threshold: None,
+                priority: 0,
+                require_modifier: None,
+                enabled: true,
+                on_error: OnError::Log,
+                fallback: false,
+                from_turn: false,
+                keys: None,
+            },
+        ],
+        ..Config::default()
+    };
+    // No window information on wayland, so nothing to connect to
+    let mut engine = Engine::new(config, true, None, false);
+
+    engine.handle(InputEvent::Ongoing(swipe(0.0, -50.0), 10));
+    engine.handle(InputEvent::Ongoing(swipe(0.0, -150.0), 20));
+    engine.handle(InputEvent::Ended(swipe(0.0, -150.0), 30));
+
+    // The command runs in the background
+    let start = Instant::now();
+    while !marker.exists() {
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "Command was not run"
+        );
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    std::fs::remove_file(&marker).unwrap();
+}
+
+#[test]
+fn paused_runs_nothing() {
+    let marker = std::env::temp_dir().join(format!("wzmach-paused-{}", std::process::id()));
+    let _ = std::fs::remove_file(&marker);
+
+    let config = Config {
+        global_triggers: vec![ConfigTrigger {
+            trigger: Trigger::Swipe {
+                fingers: 3,
+                direction: Direction::Up,
+                repeated: false,
+                continuous: false,
+                distance: None,
+                min_velocity: None,
+                max_velocity: None,
+                min_duration_ms: None,
+                edge: None,
+            },
+            action: ConfigAction::ExecuteCommand {
+                path: "touch".to_string(),
+                args: vec![marker.to_string_lossy().into_owned()],
+                cwd: None,
+                env: None,
+                timeout_ms: None,
+                gesture_env: false,
+            },
+            window_class: None,
+            cooldown_ms: 0,
+            repeat_ms: None,
+            disable_on_fullscreen: false,
+            region: None,
+            fire_on: FireOn::Threshold,
+            cancel_threshold: None,
+            priority: 0,
+            require_modifier: None,
+            enabled: true,
+            on_error: OnError::Log,
+            fallback: false,
+            from_turn: false,
+            keys: None,
+        }],
+        ..Config::default()
+    };
+    let mut engine = Engine::new(config, true, None, false);
+
+    // Paused in the middle of a gesture, which stays ignored after resuming
+    engine.handle(InputEvent::Ongoing(swipe(0.0, -50.0), 10));
+    engine.set_paused(true);
+    engine.handle(InputEvent::Ongoing(swipe(0.0, -150.0), 20));
+    engine.set_paused(false);
+    engine.handle(InputEvent::Ongoing(swipe(0.0, -200.0), 30));
+    engine.handle(InputEvent::Ended(swipe(0.0, -200.0), 40));
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(!marker.exists(), "Command ran while paused");
+
+    engine.handle(InputEvent::Ongoing(swipe(0.0, -150.0), 50));
+    engine.handle(InputEvent::Ended(swipe(0.0, -150.0), 60));
+    let start = Instant::now();
+    while !marker.exists() {
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "Command was not run after resuming"
+        );
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    std::fs::remove_file(&marker).unwrap();
+}
+
+#[test]
+fn reports_matches() {
+    let config = Config {
+        global_triggers: vec![ConfigTrigger {
+            trigger: Trigger::Swipe {
+                fingers: 3,
+                direction: Direction::Up,
+                repeated: false,
+                continuous: false,
+                distance: None,
+                min_velocity: None,
+                max_velocity: None,
+                min_duration_ms: None,
+                edge: None,
+            },
+            action: ConfigAction::ExecuteCommand {
+                path: "true".to_string(),
+                args: Vec::new(),
+                cwd: None,
+                env: None,
+                timeout_ms: None,
+                gesture_env: false,
+            },
+            window_class: None,
+            cooldown_ms: 0,
+            repeat_ms: None,
+            disable_on_fullscreen: false,
+            region: None,
+            fire_on: FireOn::Threshold,
+            cancel_threshold: None,
+            priority: 0,
+            require_modifier: None,
+            enabled: true,
+            on_error: OnError::Log,
+            fallback: false,
+            from_turn: false,
+            keys: None,
+        }],
+        ..Config::default()
+    };
+    let mut engine = Engine::new(config, true, None, false);
+
+    engine.handle(InputEvent::Ongoing(swipe(0.0, -150.0), 10));
+    assert!(engine.take_matches().is_empty(), "Matches kept unasked");
+
+    engine.set_reporting(true);
+    engine.handle(InputEvent::Ended(swipe(0.0, -150.0), 20));
+    engine.handle(InputEvent::Ongoing(swipe(0.0, -150.0), 30));
+    assert_eq!(
+        engine.take_matches(),
+        vec![Match {
+            time: 30,
+            index: 0,
+            began: false,
+            gesture: "Swipe",
+            fingers: 3,
+            direction: Some(AnyDirection::Cardinal(Direction::Up)),
+        }]
+    );
+    assert!(engine.take_matches().is_empty());
+}
+
+/// Shares what the engine writes with the test
+#[derive(Clone, Default)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+impl std::io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn records_events() {
+    let mut engine = Engine::new(Config::default(), true, None, false);
+    let buffer = Buffer::default();
+    engine.record(Box::new(buffer.clone()));
+
+    let events = [
+        (0, Phase::Begin, 0.0),
+        (10, Phase::Update, -20.5),
+        (20, Phase::End, 0.0),
+    ]
+    .map(|(time, phase, dy)| RawEvent {
+        time,
+        kind: GestureKind::Swipe,
+        phase,
+        fingers: 3,
+        dx: 0.0,
+        dy,
+        scale: 1.0,
+        angle: 0.0,
+        cancelled: false,
+        start: None,
+    });
+    for event in &events {
+        engine.handle_raw(event);
+    }
+
+    let recorded = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+    let read: Vec<RawEvent> = recorded
+        .lines()
+        .map(|line| ron::from_str(line).unwrap())
+        .collect();
+    assert_eq!(read, events);
+}
+
+#[test]
+fn replays_recording() {
+    let trigger = |trigger| ConfigTrigger {
+        trigger,
+        action: ConfigAction::ExecuteCommand {
+            path: "true".to_string(),
+            args: Vec::new(),
+            cwd: None,
+            env: None,
+            timeout_ms: None,
+            gesture_env: false,
+        },
+        window_class: None,
+        cooldown_ms: 0,
+        repeat_ms: None,
+        disable_on_fullscreen: false,
+        region: None,
+        fire_on: FireOn::Threshold,
+        cancel_threshold: None,
+        priority: 0,
+        require_modifier: None,
+        enabled: true,
+        on_error: OnError::Log,
+        fallback: false,
+        from_turn: false,
+        keys: None,
+    };
+    let config = Config {
+        global_triggers: vec![
+            trigger(Trigger::Swipe {
+                fingers: 3,
+                direction: Direction::Left,
+                repeated: false,
+                continuous: false,
+                distance: None,
+                min_velocity: None,
+                max_velocity: None,
+                min_duration_ms: None,
+                edge: None,
+            }),
+            trigger(Trigger::Hold {
+                fingers: 4,
+                duration_ms: 200,
+            }),
+        ],
+        ..Config::default()
+    };
+    let mut engine = Engine::new(config, true, None, false);
+    engine.set_reporting(true);
+
+    let recording = "\
+(time:1000,kind:Hold,phase:Begin,fingers:4,dx:0.0,dy:0.0,scale:1.0,angle:0.0,cancelled:false)
+(time:1500,kind:Hold,phase:End,fingers:4,dx:0.0,dy:0.0,scale:1.0,angle:0.0,cancelled:false)
+
+(time:2000,kind:Swipe,phase:Begin,fingers:3,dx:0.0,dy:0.0,scale:1.0,angle:0.0,cancelled:false)
+(time:2010,kind:Swipe,phase:Update,fingers:3,dx:-60.0,dy:2.0,scale:1.0,angle:0.0,cancelled:false)
+(time:2020,kind:Swipe,phase:Update,fingers:3,dx:-60.0,dy:1.0,scale:1.0,angle:0.0,cancelled:false)
+(time:2030,kind:Swipe,phase:End,fingers:3,dx:0.0,dy:0.0,scale:1.0,angle:0.0,cancelled:false)
+";
+    let events = RawEvent::read_recording(recording.as_bytes()).unwrap();
+    engine.replay(&events);
+    let fired: Vec<(u32, usize)> = engine
+        .take_matches()
+        .iter()
+        .map(|m| (m.time, m.index))
+        .collect();
+    assert_eq!(fired, vec![(1200, 1), (2020, 0)]);
+
+    let error = RawEvent::read_recording("(time:1)\n".as_bytes()).unwrap_err();
+    assert!(error.to_string().starts_with("line 1:"), "{}", error);
+}
+
+#[test]
+fn profiles_switch() {
+    let config = r#"(
+        global_triggers: [(trigger: Hold(fingers: 3, duration_ms: 200), action: ExecuteCommand(path: "true", args: []))],
+        profiles: {
+            "presentation": [(trigger: Hold(fingers: 4, duration_ms: 200), action: ExecuteCommand(path: "true", args: []))],
+            "coding": [(trigger: Hold(fingers: 5, duration_ms: 200), action: ExecuteCommand(path: "true", args: []))],
+        },
+        profile: Some("presentation"),
+    )"#;
+    let config = Config::parse(config, wzmach::config::Format::Ron).unwrap();
+    let mut engine = Engine::new(config, true, None, false);
+    engine.set_reporting(true);
+    assert_eq!(engine.profile(), Some("presentation"));
+
+    let holds = |engine: &mut Engine| {
+        let mut recording = String::new();
+        for fingers in 3..=5 {
+            let time = fingers * 1000;
+            for (time, phase) in [(time, "Begin"), (time + 500, "End")] {
+                recording += &format!(
+                    "(time:{},kind:Hold,phase:{},fingers:{},dx:0.0,dy:0.0,scale:1.0,angle:0.0,cancelled:false)\n",
+                    time, phase, fingers
+                );
+            }
+        }
+        engine.replay(&RawEvent::read_recording(recording.as_bytes()).unwrap());
+        engine
+            .take_matches()
+            .iter()
+            .map(|m| m.fingers)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(holds(&mut engine), [3, 4]);
+    engine.set_profile(Some("coding")).unwrap();
+    assert_eq!(holds(&mut engine), [3, 5]);
+    assert!(engine.set_profile(Some("gaming")).is_err());
+    assert_eq!(engine.profile(), Some("coding"));
+    engine.set_profile(None).unwrap();
+    assert_eq!(holds(&mut engine), [3]);
+}
+
+#[test]
+fn display_server_switches() {
+    let config = r#"(
+        x11_triggers: [(trigger: Hold(fingers: 3, duration_ms: 200), action: ExecuteCommand(path: "true", args: []))],
+        wayland_triggers: [(trigger: Hold(fingers: 4, duration_ms: 200), action: ExecuteCommand(path: "true", args: []))],
+    )"#;
+    let config = Config::parse(config, wzmach::config::Format::Ron).unwrap();
+    let mut engine = Engine::new(config, true, None, false);
+    engine.set_reporting(true);
+
+    let holds = |engine: &mut Engine| {
+        let mut recording = String::new();
+        for fingers in 3..=4 {
+            let time = fingers * 1000;
+            for (time, phase) in [(time, "Begin"), (time + 500, "End")] {
+                recording += &format!(
+                    "(time:{},kind:Hold,phase:{},fingers:{},dx:0.0,dy:0.0,scale:1.0,angle:0.0,cancelled:false)\n",
+                    time, phase, fingers
+                );
+            }
+        }
+        engine.replay(&RawEvent::read_recording(recording.as_bytes()).unwrap());
+        engine
+            .take_matches()
+            .iter()
+            .map(|m| m.fingers)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(holds(&mut engine), [4]);
+    engine.set_wayland(false);
+    assert!(!engine.is_wayland());
+    assert_eq!(holds(&mut engine), [3]);
+}
+
+#[test]
+fn mocked_actions() {
+    let config = r#"(
+        global_triggers: [
+            (trigger: Swipe(fingers: 3, direction: Up, repeated: false), keys: "super+1"),
+            (
+                trigger: Swipe(fingers: 3, direction: Down, repeated: false),
+                action: Sequence([TypeText(text: "hi"), Ref("scroll")]),
+            ),
+            (
+                trigger: Swipe(fingers: 3, direction: Left, repeated: false, continuous: true),
+                action: Progressive(step: 50, action: Ref("scroll")),
+            ),
+        ],
+        actions: {"scroll": MouseScroll(vertical: 2)},
+    )"#;
+    let config = Config::parse(config, wzmach::config::Format::Ron).unwrap();
+    // Keyboard and mouse actions load without an input device
+    let mut engine = Engine::new(config, true, None, false);
+    let calls = engine.mock_actions();
+
+    let mut gesture = |dx, dy| {
+        engine.handle(InputEvent::Ongoing(swipe(dx / 2.0, dy / 2.0), 10));
+        engine.handle(InputEvent::Ongoing(swipe(dx, dy), 20));
+        engine.handle(InputEvent::Ended(swipe(dx, dy), 30));
+        calls.take()
+    };
+    let up = gesture(0.0, -150.0);
+    assert!(
+        matches!(
+            &up[..],
+            [(ConfigAction::KeyboardInput { modifiers, sequence, .. }, MockCall::Execute)]
+                if modifiers.len() == 1 && sequence.len() == 1
+        ),
+        "{:?}",
+        up
+    );
+
+    let scroll = ConfigAction::MouseScroll {
+        horizontal: 0,
+        vertical: 2,
+    };
+    let down = gesture(0.0, 150.0);
+    assert_eq!(down.len(), 2, "{:?}", down);
+    assert!(matches!(
+        down[0],
+        (ConfigAction::TypeText { .. }, MockCall::Execute)
+    ));
+    assert_eq!(down[1], (scroll.clone(), MockCall::Execute));
+
+    // Every 50 of progress is a step of the progressive action, around the
+    // gesture being followed. The motion of the fingers is passed on too
+    let left = gesture(-300.0, 0.0);
+    let calls: Vec<MockCall> = left.into_iter().map(|(_, call)| call).collect();
+    let (first, rest) = calls.split_first().unwrap();
+    let (last, steps) = rest.split_last().unwrap();
+    assert_eq!((first, last), (&MockCall::Begin, &MockCall::End));
+    let (moves, steps): (Vec<_>, Vec<_>) = steps
+        .iter()
+        .partition(|c| matches!(c, MockCall::Motion(..)));
+    assert!(!moves.is_empty(), "{:?}", calls);
+    assert!(!steps.is_empty() && steps.iter().all(|c| **c == MockCall::Execute));
+}
+
+#[test]
+fn toggle_alternates() {
+    let config = r#"(
+        global_triggers: [(
+            trigger: Swipe(fingers: 3, direction: Up, repeated: false),
+            action: Toggle(first: TypeText(text: "on"), second: TypeText(text: "off")),
+        )],
+        profiles: {"coding": []},
+    )"#;
+    let config = Config::parse(config, wzmach::config::Format::Ron).unwrap();
+    let mut engine = Engine::new(config.clone(), true, None, false);
+    let calls = engine.mock_actions();
+
+    let swipe_up = |engine: &mut Engine| {
+        engine.handle(InputEvent::Ongoing(swipe(0.0, -150.0), 10));
+        engine.handle(InputEvent::Ended(swipe(0.0, -150.0), 20));
+        match &calls.take()[..] {
+            [(ConfigAction::TypeText { text }, MockCall::Execute)] => text.clone(),
+            other => panic!("{:?}", other),
+        }
+    };
+    assert_eq!(swipe_up(&mut engine), "on");
+    assert_eq!(swipe_up(&mut engine), "off");
+    assert_eq!(swipe_up(&mut engine), "on");
+    // Switching profiles leaves the global triggers as they were
+    engine.set_profile(Some("coding")).unwrap();
+    assert_eq!(swipe_up(&mut engine), "off");
+    engine.set_profile(None).unwrap();
+    assert_eq!(swipe_up(&mut engine), "on");
+    // A reloaded config starts over
+    engine.set_config(config);
+    assert_eq!(swipe_up(&mut engine), "on");
+}
+
+#[test]
+fn command_gets_gesture() {
+    let out = std::env::temp_dir().join(format!("wzmach-context-{}", std::process::id()));
+    let _ = std::fs::remove_file(&out);
+    let config = format!(
+        r#"(
+            global_triggers: [(
+                trigger: Swipe(fingers: 3, direction: Up, repeated: false),
+                action: ExecuteCommand(
+                    path: "sh",
+                    // Not $WZMACH_FINGERS, which is expanded as the config loads
+                    args: ["-c", "printenv WZMACH_FINGERS WZMACH_DIRECTION > {}"],
+                    gesture_env: true,
+                ),
+            )],
+        )"#,
+        out.display()
+    );
+    let config = Config::parse(&config, wzmach::config::Format::Ron).unwrap();
+    let mut engine = Engine::new(config, true, None, false);
+    engine.handle(InputEvent::Ongoing(swipe(0.0, -150.0), 10));
+    engine.handle(InputEvent::Ended(swipe(0.0, -150.0), 20));
+
+    let start = Instant::now();
+    while std::fs::read_to_string(&out).map_or(true, |s| s.lines().count() < 2) {
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "Command didn't run"
+        );
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), "3\nUp\n");
+    std::fs::remove_file(&out).unwrap();
+}
+
+#[test]
+fn latched_keys() {
+    let config = r#"(
+        global_triggers: [(
+            trigger: Swipe(fingers: 3, direction: Up, repeated: false),
+            action: KeyboardInput(modifiers: ["LeftShift"], sequence: ["A"], mode: Hold),
+        )],
+    )"#;
+    let config = Config::parse(config, wzmach::config::Format::Ron).unwrap();
+    let mut device = VirtualDevice::new("test".to_string());
+    let keys = device.mock_keys();
+    let mut engine = Engine::new(config, true, Some(Rc::new(RefCell::new(device))), false);
+    let shift = key_code(&ConfigKey::from_name("LeftShift").unwrap().0);
+    let a = key_code(&ConfigKey::from_name("A").unwrap().0);
+
+    engine.handle(InputEvent::Ongoing(swipe(0.0, -150.0), 10));
+    engine.handle(InputEvent::Ended(swipe(0.0, -150.0), 20));
+    assert_eq!(keys.take(), [(shift, true), (a, true)]);
+    // Down until the next gesture begins, which releases them first
+    engine.handle(InputEvent::Ongoing(swipe(0.0, -10.0), 30));
+    assert_eq!(keys.take(), [(a, false), (shift, false)]);
+    engine.handle(InputEvent::Ongoing(swipe(0.0, -150.0), 40));
+    engine.handle(InputEvent::Ended(swipe(0.0, -150.0), 50));
+    assert_eq!(keys.take(), [(shift, true), (a, true)]);
+}
+
+#[test]
+fn replay_ends_holds() {
+    let config = r#"(
+        global_triggers: [(
+            trigger: Swipe(fingers: 3, direction: Left, repeated: false),
+            action: KeyHold(key: "A", hold_ms: 100),
+        )],
+    )"#;
+    let config = Config::parse(config, wzmach::config::Format::Ron).unwrap();
+    let mut device = VirtualDevice::new("test".to_string());
+    let keys = device.mock_keys();
+    let mut engine = Engine::new(config, true, Some(Rc::new(RefCell::new(device))), false);
+    let a = key_code(&ConfigKey::from_name("A").unwrap().0);
+
+    let recording = "\
+(time:2000,kind:Swipe,phase:Begin,fingers:3,dx:0.0,dy:0.0,scale:1.0,angle:0.0,cancelled:false)
+(time:2010,kind:Swipe,phase:Update,fingers:3,dx:-120.0,dy:0.0,scale:1.0,angle:0.0,cancelled:false)
+(time:2020,kind:Swipe,phase:End,fingers:3,dx:0.0,dy:0.0,scale:1.0,angle:0.0,cancelled:false)
+";
+    let events = RawEvent::read_recording(recording.as_bytes()).unwrap();
+    let start = Instant::now();
+    engine.replay(&events);
+    // Released by the replay once the hold is over, not when the device goes
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert_eq!(keys.take(), [(a, true), (a, false)]);
+    assert_eq!(engine.release_wait(), None);
+}
+
+#[test]
+fn dry_run_without_device() {
+    let config = r#"(
+        global_triggers: [
+            (trigger: Swipe(fingers: 3, direction: Up, repeated: false), keys: "super+1"),
+            (trigger: Swipe(fingers: 3, direction: Down, repeated: false), action: MouseScroll(vertical: 2)),
+        ],
+    )"#;
+    let config = Config::parse(config, wzmach::config::Format::Ron).unwrap();
+    // The keyboard and mouse triggers are there, though no device was made
+    let mut engine = Engine::new(config, true, None, true);
+    engine.set_reporting(true);
+    for (index, dy) in [(0, -150.0), (1, 150.0)] {
+        engine.handle(InputEvent::Ongoing(swipe(0.0, dy), 10));
+        engine.handle(InputEvent::Ended(swipe(0.0, dy), 20));
+        let fired: Vec<usize> = engine.take_matches().iter().map(|m| m.index).collect();
+        assert_eq!(fired, [index]);
+    }
+}
+
+#[test]
+fn removed_device_cancels() {
+    let config = r#"(
+        global_triggers: [(
+            trigger: Swipe(fingers: 3, direction: Left, repeated: false, continuous: true),
+            action: Progressive(step: 50, action: TypeText(text: "a")),
+        )],
+    )"#;
+    let config = Config::parse(config, wzmach::config::Format::Ron).unwrap();
+    let mut engine = Engine::new(config, true, None, false);
+    let calls = engine.mock_actions();
+    let raw = |time, phase, dx| RawEvent {
+        time,
+        kind: GestureKind::Swipe,
+        phase,
+        fingers: 3,
+        dx,
+        dy: 0.0,
+        scale: 1.0,
+        angle: 0.0,
+        cancelled: false,
+        start: None,
+    };
+    engine.handle_raw_from("event5", &raw(0, Phase::Begin, 0.0));
+    engine.handle_raw_from("event5", &raw(10, Phase::Update, -150.0));
+    // Another device going away leaves the gesture going
+    engine.remove_device("event7");
+    engine.handle_raw_from("event5", &raw(20, Phase::Update, -10.0));
+    let going: Vec<MockCall> = calls.take().into_iter().map(|(_, c)| c).collect();
+    assert_eq!(going.first(), Some(&MockCall::Begin));
+    assert!(!going.contains(&MockCall::End), "{:?}", going);
+
+    engine.remove_device("event5");
+    let ended: Vec<MockCall> = calls.take().into_iter().map(|(_, c)| c).collect();
+    assert_eq!(ended.last(), Some(&MockCall::End), "{:?}", ended);
+}